@@ -0,0 +1,106 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc,
+    },
+    thread,
+    time::Duration,
+};
+
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server,
+};
+use zerg::Backend;
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn null_server() -> SocketAddr {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async move {
+                let make_service = make_service_fn(|_| async {
+                    Ok::<_, Infallible>(service_fn(|_: Request<Body>| async {
+                        Ok::<_, Infallible>(Response::new(Body::empty()))
+                    }))
+                });
+                let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+                tx.send(server.local_addr()).unwrap();
+                server.await.unwrap();
+            });
+    });
+    rx.recv().unwrap()
+}
+
+fn cpu_time() -> Duration {
+    let mut usage = unsafe { std::mem::zeroed::<libc::rusage>() };
+    unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) };
+    let time = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+    time(usage.ru_utime) + time(usage.ru_stime)
+}
+
+fn main() {
+    let addr = null_server();
+    let duration = Duration::from_secs(3);
+
+    println!("client + in-process null server, 1 thread, {duration:?} per run");
+    let backends = [
+        Backend::Tokio,
+        #[cfg(feature = "io-uring")]
+        Backend::IoUring,
+    ];
+    for (backend, concurrency) in backends
+        .into_iter()
+        .flat_map(|backend| [1, 10, 100].map(|concurrency| (backend, concurrency)))
+    {
+        let cpu = cpu_time();
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+
+        let result = zerg::swarm(format!("http://{addr}"))
+            .backend(backend)
+            .duration(duration)
+            .threads(1)
+            .concurrency(concurrency)
+            .zerg()
+            .unwrap();
+
+        let cpu = cpu_time() - cpu;
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+        let requests = result.total_request_count().max(1) as f64;
+
+        println!(
+            "{backend:?} concurrency {concurrency:>3}: {:>9.0} req/s, {:>7.0} cpu ns/req, {:>5.1} allocs/req",
+            result.requests_per_second(),
+            cpu.as_nanos() as f64 / requests,
+            allocations as f64 / requests,
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use std::{collections::BTreeSet, sync::Mutex, time::Duration};
+
+use rand::seq::index;
+
+use crate::result::Percentiles;
+
+const QUANTILES: &[f64] = &[0.5, 0.9, 0.99, 0.999];
+
+#[derive(Debug)]
+pub(crate) struct ExactSamples {
+    workers: BTreeSet<usize>,
+    samples: Mutex<Vec<Duration>>,
+}
+
+impl ExactSamples {
+    pub(crate) fn new(threads: usize, workers: usize) -> Self {
+        Self {
+            workers: index::sample(&mut rand::thread_rng(), threads, workers.min(threads))
+                .into_iter()
+                .collect(),
+            samples: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn retains(&self, thread: usize) -> bool {
+        self.workers.contains(&thread)
+    }
+
+    pub(crate) fn extend(&self, samples: &[Duration]) {
+        self.samples.lock().unwrap().extend_from_slice(samples);
+    }
+
+    pub(crate) fn accuracy(&self) -> Option<PercentileAccuracy> {
+        let mut samples = self.samples.lock().unwrap();
+        if samples.is_empty() {
+            return None;
+        }
+        let sketch = Percentiles::new(&samples);
+        samples.sort_unstable();
+        let quantiles = QUANTILES
+            .iter()
+            .map(|&quantile| {
+                let rank = (quantile * samples.len() as f64).ceil() as usize;
+                let exact = samples[rank.clamp(1, samples.len()) - 1];
+                (quantile, exact, sketch.percentile(quantile))
+            })
+            .collect();
+        Some(PercentileAccuracy {
+            workers: self.workers.len(),
+            samples: samples.len(),
+            quantiles,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PercentileAccuracy {
+    pub(crate) workers: usize,
+    pub(crate) samples: usize,
+    pub(crate) quantiles: Vec<(f64, Duration, Duration)>,
+}
+
+impl PercentileAccuracy {
+    pub fn worker_count(&self) -> usize {
+        self.workers
+    }
+
+    pub fn sample_count(&self) -> usize {
+        self.samples
+    }
+
+    pub fn quantiles(&self) -> impl Iterator<Item = (f64, Duration, Duration)> + '_ {
+        self.quantiles.iter().copied()
+    }
+
+    pub fn error(&self, quantile: f64) -> Option<f64> {
+        self.quantiles
+            .iter()
+            .find(|(q, _, _)| *q == quantile)
+            .map(|(_, exact, estimate)| relative_error(*exact, *estimate))
+    }
+
+    pub fn max_error(&self) -> f64 {
+        self.quantiles
+            .iter()
+            .map(|(_, exact, estimate)| relative_error(*exact, *estimate))
+            .fold(0.0, f64::max)
+    }
+}
+
+fn relative_error(exact: Duration, estimate: Duration) -> f64 {
+    match exact.is_zero() {
+        true if estimate.is_zero() => 0.0,
+        true => f64::INFINITY,
+        false => (estimate.as_secs_f64() - exact.as_secs_f64()).abs() / exact.as_secs_f64(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_quantiles_come_from_the_retained_workers() {
+        let samples = ExactSamples::new(4, 2);
+        assert_eq!((0..4).filter(|&thread| samples.retains(thread)).count(), 2);
+        assert_eq!(samples.accuracy(), None);
+
+        samples.extend(&(1..=100).map(Duration::from_millis).collect::<Vec<_>>());
+        let accuracy = samples.accuracy().unwrap();
+        assert_eq!((accuracy.worker_count(), accuracy.sample_count()), (2, 100));
+        let exact: Vec<_> = accuracy.quantiles().map(|(_, exact, _)| exact).collect();
+        assert_eq!(exact, [50, 90, 99, 100].map(Duration::from_millis));
+        assert!(accuracy.error(0.5).unwrap() < 0.05);
+        assert_eq!(accuracy.error(0.25), None);
+    }
+
+    #[test]
+    fn relative_error_handles_zero_exact_values() {
+        let ms = Duration::from_millis;
+        assert!((relative_error(ms(100), ms(110)) - 0.1).abs() < 1e-9);
+        assert!((relative_error(ms(100), ms(90)) - 0.1).abs() < 1e-9);
+        assert_eq!(relative_error(Duration::ZERO, Duration::ZERO), 0.0);
+        assert_eq!(relative_error(Duration::ZERO, ms(1)), f64::INFINITY);
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    fmt::Display,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    offset: Duration,
+    text: String,
+}
+
+impl Annotation {
+    pub fn offset(&self) -> Duration {
+        self.offset
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub(crate) fn new(offset: Duration, text: String) -> Self {
+        Self { offset, text }
+    }
+
+    pub(crate) fn shift(&mut self, by: Duration) {
+        self.offset += by;
+    }
+}
+
+impl Display for Annotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "t={:.1}s {}", self.offset.as_secs_f64(), self.text)
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    epoch: Option<Instant>,
+    annotations: Vec<Annotation>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Annotator(Arc<Mutex<State>>);
+
+impl Annotator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn annotate(&self, text: impl Into<String>) {
+        let mut state = self.0.lock().unwrap();
+        let offset = state
+            .epoch
+            .map(|epoch| epoch.elapsed())
+            .unwrap_or(Duration::ZERO);
+        state.annotations.push(Annotation {
+            offset,
+            text: text.into(),
+        });
+    }
+
+    pub(crate) fn start(&self, epoch: Instant) {
+        self.0.lock().unwrap().epoch = Some(epoch);
+    }
+
+    pub(crate) fn annotations(&self) -> Vec<Annotation> {
+        self.0.lock().unwrap().annotations.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn annotations_before_the_run_sit_at_zero_and_shift_with_merges() {
+        let annotator = Annotator::new();
+        annotator.annotate("deploy");
+        let mut annotation = annotator.annotations().pop().unwrap();
+        assert_eq!(annotation.offset(), Duration::ZERO);
+
+        annotation.shift(Duration::from_millis(1500));
+        assert_eq!(annotation.to_string(), "t=1.5s deploy");
+    }
+
+    #[test]
+    fn annotations_are_offset_from_the_run_epoch() {
+        let annotator = Annotator::new();
+        annotator.start(Instant::now() - Duration::from_secs(2));
+        annotator.annotate("spike");
+        assert!(annotator.annotations()[0].offset() >= Duration::from_secs(2));
+    }
+}
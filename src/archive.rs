@@ -0,0 +1,529 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    accuracy::PercentileAccuracy,
+    annotation::Annotation,
+    breakdown::TimeBreakdown,
+    cache::CacheStatus,
+    clock::ClockSource,
+    config::RunConfig,
+    fairness::UserStats,
+    operation::Mode,
+    result::{BenchmarkResult, TcpFailure},
+    seed::CleanupReport,
+    session::TlsSession,
+    timeline::{Bucket, Timeline},
+};
+
+const SCHEMA_VERSION: u64 = 1;
+
+type QuantileV1 = (f64, u64, u64);
+
+#[derive(Serialize)]
+struct Archive<'a> {
+    version: u64,
+    #[serde(flatten)]
+    result: &'a ResultV1,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ResultV1 {
+    success: usize,
+    http_error: usize,
+    tcp_error: usize,
+    connect_errors: usize,
+    counted_failures: BTreeSet<TcpFailure>,
+    time_breakdown_us: [u64; 5],
+    elapsed_us: u64,
+    started_at_us: Option<u64>,
+    min_time_us: Option<u64>,
+    max_time_us: u64,
+    timings_us: Vec<u64>,
+    timings_seen: usize,
+    timeline: Vec<[u64; 4]>,
+    annotations: Vec<(u64, String)>,
+    labels: BTreeMap<String, ResultV1>,
+    latency_budget_us: Option<u64>,
+    budget_violations: usize,
+    phases: BTreeMap<String, ResultV1>,
+    phase_spans: BTreeMap<String, (u64, u64)>,
+    tenants: BTreeMap<String, ResultV1>,
+    users: BTreeMap<usize, (usize, u64)>,
+    cache: Vec<(CacheStatus, ResultV1)>,
+    regions: BTreeMap<String, ResultV1>,
+    counters: BTreeMap<String, u64>,
+    expectations: BTreeMap<String, usize>,
+    opened_connections: usize,
+    dropped_connections: usize,
+    idle_connections: usize,
+    pushed_messages: usize,
+    grpc_statuses: BTreeMap<String, usize>,
+    grpc_messages: usize,
+    uploaded_bytes: u64,
+    downloaded_bytes: u64,
+    doh_lookups: usize,
+    doh_failures: usize,
+    doh_time_us: u64,
+    compressed_responses: usize,
+    wire_bytes: u64,
+    decoded_bytes: u64,
+    redirects_followed: usize,
+    redirect_loops: usize,
+    script_errors: usize,
+    continue_responses: usize,
+    continue_time_us: u64,
+    continue_rejections: usize,
+    continue_timeouts: usize,
+    connection_cap: Option<usize>,
+    peak_connections: usize,
+    queued_connects: usize,
+    connect_queue_time_us: u64,
+    client_cpu: Option<f64>,
+    percentile_accuracy: Option<(usize, usize, Vec<QuantileV1>)>,
+    echo_checked: usize,
+    echo_duplicates: usize,
+    echo_mismatches: usize,
+    chaos_dropped: usize,
+    chaos_aborted: usize,
+    gauges: BTreeMap<String, i64>,
+    max_queue_depth: u64,
+    late: usize,
+    schedule_lag_us: u64,
+    clock: Option<(ClockSource, u64)>,
+    pipeline: Option<usize>,
+    mode: Mode,
+    tls_sessions: Vec<(TlsSession, usize)>,
+    tls_resumed: usize,
+    negotiated_protocols: BTreeMap<String, usize>,
+    requested_protocol: Option<String>,
+    tls_handshake_errors: usize,
+    fd_exhausted: usize,
+    ports_exhausted: usize,
+    quic_handshake_errors: usize,
+    fd_limit: Option<u64>,
+    seed: Option<(usize, usize, u64)>,
+    cleanup: Option<CleanupReport>,
+    worker_panics: usize,
+    first_panic: Option<String>,
+    config: Option<RunConfig>,
+}
+
+impl From<&BenchmarkResult> for ResultV1 {
+    fn from(result: &BenchmarkResult) -> Self {
+        let results = |map: &BTreeMap<String, BenchmarkResult>| {
+            map.iter()
+                .map(|(key, result)| (key.clone(), ResultV1::from(result)))
+                .collect()
+        };
+        Self {
+            success: result.success,
+            http_error: result.http_error,
+            tcp_error: result.tcp_error,
+            connect_errors: result.connect_errors,
+            counted_failures: result.counted_failures.clone(),
+            time_breakdown_us: [
+                micros(result.time_breakdown.rate_limited),
+                micros(result.time_breakdown.connecting),
+                micros(result.time_breakdown.waiting),
+                micros(result.time_breakdown.reading),
+                micros(result.time_breakdown.total),
+            ],
+            elapsed_us: micros(result.elapsed),
+            started_at_us: result
+                .started_at
+                .and_then(|started_at| started_at.duration_since(UNIX_EPOCH).ok())
+                .map(micros),
+            min_time_us: (result.min_time != Duration::MAX).then(|| micros(result.min_time)),
+            max_time_us: micros(result.max_time),
+            timings_us: result.timings.iter().copied().map(micros).collect(),
+            timings_seen: result.timings_seen,
+            timeline: result
+                .timeline
+                .buckets()
+                .map(|(_, bucket)| {
+                    [
+                        bucket.success as u64,
+                        bucket.http_error as u64,
+                        bucket.tcp_error as u64,
+                        bucket.queue_depth,
+                    ]
+                })
+                .collect(),
+            annotations: result
+                .annotations
+                .iter()
+                .map(|annotation| (micros(annotation.offset()), annotation.text().to_string()))
+                .collect(),
+            labels: results(&result.labels),
+            latency_budget_us: result.latency_budget.map(micros),
+            budget_violations: result.budget_violations,
+            phases: results(&result.phases),
+            phase_spans: result
+                .phase_spans
+                .iter()
+                .map(|(phase, (start, end))| (phase.clone(), (micros(*start), micros(*end))))
+                .collect(),
+            tenants: results(&result.tenants),
+            users: result
+                .users
+                .iter()
+                .map(|(id, stats)| (*id, (stats.requests, micros(stats.total_time))))
+                .collect(),
+            cache: result
+                .cache
+                .iter()
+                .map(|(status, result)| (*status, ResultV1::from(result)))
+                .collect(),
+            regions: results(&result.regions),
+            counters: result.counters.clone(),
+            expectations: result.expectations.clone(),
+            opened_connections: result.opened_connections,
+            dropped_connections: result.dropped_connections,
+            idle_connections: result.idle_connections,
+            pushed_messages: result.pushed_messages,
+            grpc_statuses: result.grpc_statuses.clone(),
+            grpc_messages: result.grpc_messages,
+            uploaded_bytes: result.uploaded_bytes,
+            downloaded_bytes: result.downloaded_bytes,
+            doh_lookups: result.doh_lookups,
+            doh_failures: result.doh_failures,
+            doh_time_us: micros(result.doh_time),
+            compressed_responses: result.compressed_responses,
+            wire_bytes: result.wire_bytes,
+            decoded_bytes: result.decoded_bytes,
+            redirects_followed: result.redirects_followed,
+            redirect_loops: result.redirect_loops,
+            script_errors: result.script_errors,
+            continue_responses: result.continue_responses,
+            continue_time_us: micros(result.continue_time),
+            continue_rejections: result.continue_rejections,
+            continue_timeouts: result.continue_timeouts,
+            connection_cap: result.connection_cap,
+            peak_connections: result.peak_connections,
+            queued_connects: result.queued_connects,
+            connect_queue_time_us: micros(result.connect_queue_time),
+            client_cpu: result.client_cpu,
+            percentile_accuracy: result.percentile_accuracy.as_ref().map(|accuracy| {
+                (
+                    accuracy.workers,
+                    accuracy.samples,
+                    accuracy
+                        .quantiles()
+                        .map(|(quantile, exact, estimate)| {
+                            (quantile, micros(exact), micros(estimate))
+                        })
+                        .collect(),
+                )
+            }),
+            echo_checked: result.echo_checked,
+            echo_duplicates: result.echo_duplicates,
+            echo_mismatches: result.echo_mismatches,
+            chaos_dropped: result.chaos_dropped,
+            chaos_aborted: result.chaos_aborted,
+            gauges: result.gauges.clone(),
+            max_queue_depth: result.max_queue_depth,
+            late: result.late,
+            schedule_lag_us: micros(result.schedule_lag),
+            clock: result
+                .clock
+                .map(|(source, overhead)| (source, micros(overhead))),
+            pipeline: result.pipeline,
+            mode: result.mode.clone(),
+            tls_sessions: result
+                .tls_sessions
+                .iter()
+                .map(|(session, handshakes)| (session.clone(), *handshakes))
+                .collect(),
+            tls_resumed: result.tls_resumed,
+            negotiated_protocols: result.negotiated_protocols.clone(),
+            requested_protocol: result.requested_protocol.clone(),
+            tls_handshake_errors: result.tls_handshake_errors,
+            fd_exhausted: result.fd_exhausted,
+            ports_exhausted: result.ports_exhausted,
+            quic_handshake_errors: result.quic_handshake_errors,
+            fd_limit: result.fd_limit,
+            seed: result
+                .seed
+                .map(|(created, failed, elapsed)| (created, failed, micros(elapsed))),
+            cleanup: result.cleanup.clone(),
+            worker_panics: result.worker_panics,
+            first_panic: result.first_panic.clone(),
+            config: result.config.clone(),
+        }
+    }
+}
+
+impl From<ResultV1> for BenchmarkResult {
+    fn from(archived: ResultV1) -> Self {
+        let results = |map: BTreeMap<String, ResultV1>| {
+            map.into_iter()
+                .map(|(key, result)| (key, BenchmarkResult::from(result)))
+                .collect()
+        };
+        let timings = archived
+            .timings_us
+            .into_iter()
+            .map(Duration::from_micros)
+            .collect::<Vec<_>>();
+        let timings_seen = archived.timings_seen.max(timings.len());
+        Self {
+            success: archived.success,
+            http_error: archived.http_error,
+            tcp_error: archived.tcp_error,
+            connect_errors: archived.connect_errors,
+            counted_failures: archived.counted_failures,
+            time_breakdown: {
+                let [rate_limited, connecting, waiting, reading, total] =
+                    archived.time_breakdown_us.map(Duration::from_micros);
+                TimeBreakdown {
+                    rate_limited,
+                    connecting,
+                    waiting,
+                    reading,
+                    total,
+                }
+            },
+            elapsed: Duration::from_micros(archived.elapsed_us),
+            started_at: archived
+                .started_at_us
+                .map(|us| UNIX_EPOCH + Duration::from_micros(us)),
+            min_time: archived
+                .min_time_us
+                .map(Duration::from_micros)
+                .unwrap_or(Duration::MAX),
+            max_time: Duration::from_micros(archived.max_time_us),
+            timings,
+            timings_seen,
+            timeline: Timeline::from_buckets(
+                archived
+                    .timeline
+                    .into_iter()
+                    .map(|[success, http_error, tcp_error, queue_depth]| Bucket {
+                        success: success as usize,
+                        http_error: http_error as usize,
+                        tcp_error: tcp_error as usize,
+                        queue_depth,
+                    })
+                    .collect(),
+            ),
+            annotations: archived
+                .annotations
+                .into_iter()
+                .map(|(offset, text)| Annotation::new(Duration::from_micros(offset), text))
+                .collect(),
+            labels: results(archived.labels),
+            latency_budget: archived.latency_budget_us.map(Duration::from_micros),
+            budget_violations: archived.budget_violations,
+            phases: results(archived.phases),
+            phase_spans: archived
+                .phase_spans
+                .into_iter()
+                .map(|(phase, (start, end))| {
+                    let span = (Duration::from_micros(start), Duration::from_micros(end));
+                    (phase, span)
+                })
+                .collect(),
+            tenants: results(archived.tenants),
+            users: archived
+                .users
+                .into_iter()
+                .map(|(id, (requests, total_us))| {
+                    let stats = UserStats {
+                        requests,
+                        total_time: Duration::from_micros(total_us),
+                    };
+                    (id, stats)
+                })
+                .collect(),
+            cache: archived
+                .cache
+                .into_iter()
+                .map(|(status, result)| (status, BenchmarkResult::from(result)))
+                .collect(),
+            regions: results(archived.regions),
+            counters: archived.counters,
+            expectations: archived.expectations,
+            opened_connections: archived.opened_connections,
+            dropped_connections: archived.dropped_connections,
+            idle_connections: archived.idle_connections,
+            pushed_messages: archived.pushed_messages,
+            grpc_statuses: archived.grpc_statuses,
+            grpc_messages: archived.grpc_messages,
+            uploaded_bytes: archived.uploaded_bytes,
+            downloaded_bytes: archived.downloaded_bytes,
+            doh_lookups: archived.doh_lookups,
+            doh_failures: archived.doh_failures,
+            doh_time: Duration::from_micros(archived.doh_time_us),
+            compressed_responses: archived.compressed_responses,
+            wire_bytes: archived.wire_bytes,
+            decoded_bytes: archived.decoded_bytes,
+            redirects_followed: archived.redirects_followed,
+            redirect_loops: archived.redirect_loops,
+            script_errors: archived.script_errors,
+            continue_responses: archived.continue_responses,
+            continue_time: Duration::from_micros(archived.continue_time_us),
+            continue_rejections: archived.continue_rejections,
+            continue_timeouts: archived.continue_timeouts,
+            connection_cap: archived.connection_cap,
+            peak_connections: archived.peak_connections,
+            queued_connects: archived.queued_connects,
+            connect_queue_time: Duration::from_micros(archived.connect_queue_time_us),
+            client_cpu: archived.client_cpu,
+            percentile_accuracy: archived.percentile_accuracy.map(
+                |(workers, samples, quantiles)| PercentileAccuracy {
+                    workers,
+                    samples,
+                    quantiles: quantiles
+                        .into_iter()
+                        .map(|(quantile, exact, estimate)| {
+                            (
+                                quantile,
+                                Duration::from_micros(exact),
+                                Duration::from_micros(estimate),
+                            )
+                        })
+                        .collect(),
+                },
+            ),
+            echo_checked: archived.echo_checked,
+            echo_duplicates: archived.echo_duplicates,
+            echo_mismatches: archived.echo_mismatches,
+            chaos_dropped: archived.chaos_dropped,
+            chaos_aborted: archived.chaos_aborted,
+            gauges: archived.gauges,
+            max_queue_depth: archived.max_queue_depth,
+            late: archived.late,
+            schedule_lag: Duration::from_micros(archived.schedule_lag_us),
+            clock: archived
+                .clock
+                .map(|(source, overhead)| (source, Duration::from_micros(overhead))),
+            pipeline: archived.pipeline,
+            mode: archived.mode,
+            tls_sessions: archived.tls_sessions.into_iter().collect(),
+            tls_resumed: archived.tls_resumed,
+            negotiated_protocols: archived.negotiated_protocols,
+            requested_protocol: archived.requested_protocol,
+            tls_handshake_errors: archived.tls_handshake_errors,
+            fd_exhausted: archived.fd_exhausted,
+            ports_exhausted: archived.ports_exhausted,
+            quic_handshake_errors: archived.quic_handshake_errors,
+            fd_limit: archived.fd_limit,
+            seed: archived.seed.map(|(created, failed, elapsed)| {
+                (created, failed, Duration::from_micros(elapsed))
+            }),
+            cleanup: archived.cleanup,
+            worker_panics: archived.worker_panics,
+            first_panic: archived.first_panic,
+            config: archived.config,
+        }
+    }
+}
+
+fn migrate(version: u64, value: Value) -> Result<ResultV1, Box<dyn Error + Send + Sync>> {
+    match version {
+        1 => Ok(serde_json::from_value(value)?),
+        version if version > SCHEMA_VERSION => Err(format!(
+            "result schema version {version} is newer than the supported version {SCHEMA_VERSION}"
+        )
+        .into()),
+        version => Err(format!("unknown result schema version {version}").into()),
+    }
+}
+
+fn micros(duration: Duration) -> u64 {
+    duration.as_micros() as u64
+}
+
+impl BenchmarkResult {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Self::from_reader(File::open(path)?)
+    }
+
+    pub fn from_reader(reader: impl Read) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut value: Value = serde_json::from_reader(BufReader::new(reader))?;
+        let version = value
+            .as_object_mut()
+            .and_then(|object| object.remove("version"))
+            .and_then(|version| version.as_u64())
+            .ok_or("missing result schema version")?;
+        Ok(migrate(version, value)?.into())
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.to_writer(File::create(path)?)
+    }
+
+    pub fn to_writer(&self, writer: impl Write) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut writer = BufWriter::new(writer);
+        let archive = Archive {
+            version: SCHEMA_VERSION,
+            result: &ResultV1::from(self),
+        };
+        serde_json::to_writer(&mut writer, &archive)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn schema_version() -> u64 {
+        SCHEMA_VERSION
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::result::Outcome;
+
+    #[test]
+    fn results_round_trip_through_the_archive() {
+        let mut result = BenchmarkResult::default();
+        result.record(Duration::ZERO, Duration::from_millis(12), Outcome::Success);
+        result.record_labeled(
+            "login",
+            Duration::from_secs(1),
+            Duration::from_millis(40),
+            Outcome::HttpError,
+        );
+        result.set_elapsed(Duration::from_secs(2));
+
+        let mut archive = Vec::new();
+        result.to_writer(&mut archive).unwrap();
+        let loaded = BenchmarkResult::from_reader(archive.as_slice()).unwrap();
+
+        assert_eq!(loaded.success_count(), 1);
+        assert_eq!(loaded.http_error_count(), 1);
+        assert_eq!(loaded.total_time(), Duration::from_secs(2));
+        assert_eq!(loaded.timings, result.timings);
+        assert_eq!(loaded.label("login").unwrap().http_error_count(), 1);
+        assert_eq!(loaded.timeline().buckets().count(), 2);
+    }
+
+    #[test]
+    fn archives_carry_their_schema_version() {
+        let mut archive = Vec::new();
+        BenchmarkResult::default().to_writer(&mut archive).unwrap();
+        let value: Value = serde_json::from_slice(&archive).unwrap();
+        assert_eq!(value["version"], BenchmarkResult::schema_version());
+    }
+
+    #[test]
+    fn unversioned_and_future_archives_are_rejected() {
+        let missing = BenchmarkResult::from_reader(&b"{\"success\": 1}"[..]).unwrap_err();
+        assert_eq!(missing.to_string(), "missing result schema version");
+
+        let newer = BenchmarkResult::from_reader(&b"{\"version\": 99}"[..]).unwrap_err();
+        assert!(newer.to_string().contains("newer"), "{newer}");
+        assert!(migrate(0, Value::Null).is_err());
+    }
+}
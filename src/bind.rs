@@ -0,0 +1,84 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use hyper::client::HttpConnector;
+use tokio::net::{TcpSocket, TcpStream};
+
+#[derive(Debug)]
+pub(crate) struct BindPool {
+    v4: Vec<Ipv4Addr>,
+    v6: Vec<Ipv6Addr>,
+    next: AtomicUsize,
+}
+
+impl BindPool {
+    pub(crate) fn new(addrs: &[IpAddr]) -> Self {
+        let mut pool = Self {
+            v4: Vec::new(),
+            v6: Vec::new(),
+            next: AtomicUsize::new(0),
+        };
+        for addr in addrs {
+            match addr {
+                IpAddr::V4(addr) => pool.v4.push(*addr),
+                IpAddr::V6(addr) => pool.v6.push(*addr),
+            }
+        }
+        pool
+    }
+
+    pub(crate) fn apply<R>(&self, http: &mut HttpConnector<R>) {
+        let n = self.next.fetch_add(1, Ordering::Relaxed);
+        match (pick(&self.v4, n), pick(&self.v6, n)) {
+            (Some(v4), Some(v6)) => http.set_local_addresses(v4, v6),
+            (v4, v6) => http.set_local_address(v4.map(IpAddr::V4).or(v6.map(IpAddr::V6))),
+        }
+    }
+
+    pub(crate) async fn connect(&self, addr: SocketAddr) -> io::Result<TcpStream> {
+        let n = self.next.fetch_add(1, Ordering::Relaxed);
+        let (socket, local) = match addr {
+            SocketAddr::V4(_) => (TcpSocket::new_v4()?, pick(&self.v4, n).map(IpAddr::V4)),
+            SocketAddr::V6(_) => (TcpSocket::new_v6()?, pick(&self.v6, n).map(IpAddr::V6)),
+        };
+        if let Some(local) = local {
+            socket.bind(SocketAddr::new(local, 0))?;
+        }
+        socket.connect(addr).await
+    }
+}
+
+fn pick<T: Copy>(addrs: &[T], n: usize) -> Option<T> {
+    match addrs.len() {
+        0 => None,
+        len => Some(addrs[n % len]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_rotate_per_connection_within_each_family() {
+        assert_eq!(pick::<u8>(&[], 3), None);
+        assert_eq!(pick(&[1, 2, 3], 4), Some(2));
+
+        let pool = BindPool::new(&[
+            "10.0.0.1".parse().unwrap(),
+            "::1".parse().unwrap(),
+            "10.0.0.2".parse().unwrap(),
+        ]);
+        assert_eq!(
+            pool.v4,
+            [
+                "10.0.0.1".parse::<Ipv4Addr>().unwrap(),
+                "10.0.0.2".parse().unwrap()
+            ]
+        );
+        assert_eq!(pool.v6, [Ipv6Addr::LOCALHOST]);
+    }
+}
@@ -0,0 +1,153 @@
+use std::{cell::Cell, fmt::Display, future::Future, ops::AddAssign, time::Duration};
+
+tokio::task_local! {
+    static CONNECTING: Cell<Connects>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Connects {
+    pub(crate) opened: usize,
+    pub(crate) elapsed: Duration,
+}
+
+pub(crate) fn record_connecting(elapsed: Duration, opened: bool) {
+    let _ = CONNECTING.try_with(|connecting| {
+        let connects = connecting.get();
+        connecting.set(Connects {
+            opened: connects.opened + opened as usize,
+            elapsed: connects.elapsed + elapsed,
+        })
+    });
+}
+
+pub(crate) async fn measure_connecting<F: Future>(future: F) -> (F::Output, Connects) {
+    CONNECTING
+        .scope(Cell::new(Connects::default()), async {
+            let output = future.await;
+            (output, CONNECTING.with(Cell::get))
+        })
+        .await
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeBreakdown {
+    pub(crate) rate_limited: Duration,
+    pub(crate) connecting: Duration,
+    pub(crate) waiting: Duration,
+    pub(crate) reading: Duration,
+    pub(crate) total: Duration,
+}
+
+impl TimeBreakdown {
+    pub fn rate_limited(&self) -> Duration {
+        self.rate_limited
+    }
+
+    pub fn connecting(&self) -> Duration {
+        self.connecting
+    }
+
+    pub fn waiting_for_response(&self) -> Duration {
+        self.waiting
+    }
+
+    pub fn reading_response(&self) -> Duration {
+        self.reading
+    }
+
+    pub fn other(&self) -> Duration {
+        self.total
+            .saturating_sub(self.rate_limited + self.connecting + self.waiting + self.reading)
+    }
+
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    pub fn share(&self, time: Duration) -> f64 {
+        match self.total.is_zero() {
+            true => 0.0,
+            false => (time.as_secs_f64() / self.total.as_secs_f64() * 100.0).min(100.0),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.rate_limited.is_zero()
+            && self.connecting.is_zero()
+            && self.waiting.is_zero()
+            && self.reading.is_zero()
+    }
+}
+
+impl AddAssign<TimeBreakdown> for TimeBreakdown {
+    fn add_assign(&mut self, rhs: TimeBreakdown) {
+        self.rate_limited += rhs.rate_limited;
+        self.connecting += rhs.connecting;
+        self.waiting += rhs.waiting;
+        self.reading += rhs.reading;
+        self.total += rhs.total;
+    }
+}
+
+impl Display for TimeBreakdown {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.1}% rate limited, {:.1}% connecting, {:.1}% waiting for response, {:.1}% reading response, {:.1}% other",
+            self.share(self.rate_limited),
+            self.share(self.connecting),
+            self.share(self.waiting),
+            self.share(self.reading),
+            self.share(self.other()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn connect_time_is_collected_per_measured_future() {
+        let (output, connects) = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(measure_connecting(async {
+                record_connecting(ms(3), true);
+                record_connecting(ms(2), false);
+                7
+            }));
+        assert_eq!(output, 7);
+        assert_eq!((connects.opened, connects.elapsed), (1, ms(5)));
+
+        // Outside a measured future there is nowhere to record, which must not panic.
+        record_connecting(ms(1), true);
+    }
+
+    #[test]
+    fn shares_are_percentages_of_the_total() {
+        let mut breakdown = TimeBreakdown {
+            rate_limited: ms(10),
+            connecting: ms(20),
+            waiting: ms(30),
+            reading: ms(20),
+            total: ms(100),
+        };
+        assert_eq!(breakdown.other(), ms(20));
+        assert_eq!(breakdown.share(ms(30)), 30.0);
+        assert_eq!(
+            breakdown.to_string(),
+            "10.0% rate limited, 20.0% connecting, 30.0% waiting for response, \
+             20.0% reading response, 20.0% other"
+        );
+
+        breakdown += breakdown;
+        assert_eq!((breakdown.waiting, breakdown.total), (ms(60), ms(200)));
+        assert_eq!(TimeBreakdown::default().share(ms(1)), 0.0);
+        assert!(TimeBreakdown::default().is_empty());
+    }
+}
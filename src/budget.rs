@@ -0,0 +1,50 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug)]
+pub(crate) struct RequestBudget {
+    requests: u64,
+    remaining: AtomicU64,
+}
+
+impl RequestBudget {
+    pub(crate) fn new(requests: u64) -> Self {
+        Self {
+            requests,
+            remaining: AtomicU64::new(requests),
+        }
+    }
+
+    pub(crate) fn take(&self) -> bool {
+        self.remaining
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            .is_ok()
+    }
+
+    pub(crate) fn refund(&self) {
+        self.remaining.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn progress(&self) -> f64 {
+        let remaining = self.remaining.load(Ordering::Relaxed);
+        1.0 - remaining as f64 / self.requests as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn budgets_run_out_and_take_refunds_back() {
+        let budget = RequestBudget::new(2);
+        assert!(budget.take());
+        assert_eq!(budget.progress(), 0.5);
+        assert!(budget.take());
+        assert!(!budget.take());
+        assert_eq!(budget.progress(), 1.0);
+
+        budget.refund();
+        assert!(budget.take());
+        assert!(!budget.take());
+    }
+}
@@ -0,0 +1,101 @@
+use std::fmt::Display;
+
+use hyper::HeaderMap;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum CacheStatus {
+    Hit,
+    Miss,
+    Bypass,
+}
+
+impl CacheStatus {
+    pub(crate) fn of(headers: &HeaderMap) -> Option<Self> {
+        for name in ["cf-cache-status", "x-cache", "x-cache-status"] {
+            let status = headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(Self::parse);
+            if status.is_some() {
+                return status;
+            }
+        }
+
+        let age = headers
+            .get("age")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok())?;
+        match age {
+            0 => Some(Self::Miss),
+            _ => Some(Self::Hit),
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.to_ascii_uppercase();
+        if value.contains("MISS") || value.contains("EXPIRED") {
+            Some(Self::Miss)
+        } else if value.contains("HIT") || value.contains("STALE") || value.contains("REVALIDATED")
+        {
+            Some(Self::Hit)
+        } else if value.contains("BYPASS") || value.contains("DYNAMIC") || value.contains("PASS") {
+            Some(Self::Bypass)
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for CacheStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Hit => write!(f, "hit"),
+            Self::Miss => write!(f, "miss"),
+            Self::Bypass => write!(f, "bypass"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn of(headers: &[(&'static str, &'static str)]) -> Option<CacheStatus> {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.insert(*name, value.parse().unwrap());
+        }
+        CacheStatus::of(&map)
+    }
+
+    #[test]
+    fn cache_headers_are_read_in_priority_order() {
+        assert_eq!(of(&[("cf-cache-status", "HIT")]), Some(CacheStatus::Hit));
+        assert_eq!(
+            of(&[("x-cache", "TCP_MISS from edge")]),
+            Some(CacheStatus::Miss)
+        );
+        assert_eq!(
+            of(&[("x-cache-status", "EXPIRED")]),
+            Some(CacheStatus::Miss)
+        );
+        assert_eq!(
+            of(&[("cf-cache-status", "DYNAMIC")]),
+            Some(CacheStatus::Bypass)
+        );
+        assert_eq!(
+            of(&[("x-cache", "unknown"), ("x-cache-status", "stale")]),
+            Some(CacheStatus::Hit)
+        );
+    }
+
+    #[test]
+    fn age_is_the_fallback() {
+        assert_eq!(of(&[("age", "0")]), Some(CacheStatus::Miss));
+        assert_eq!(of(&[("age", " 42 ")]), Some(CacheStatus::Hit));
+        assert_eq!(of(&[("age", "soon")]), None);
+        assert_eq!(of(&[]), None);
+        assert_eq!(CacheStatus::Bypass.to_string(), "bypass");
+    }
+}
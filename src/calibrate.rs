@@ -0,0 +1,157 @@
+use std::{
+    convert::Infallible, error::Error, fmt::Display, net::SocketAddr, sync::mpsc, thread,
+    time::Duration,
+};
+
+use futures::channel::oneshot;
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server,
+};
+
+use crate::result::BenchmarkResult;
+
+pub(crate) struct NullServer {
+    local_addr: SocketAddr,
+    shutdown: oneshot::Sender<()>,
+    handle: thread::JoinHandle<()>,
+}
+
+impl NullServer {
+    pub(crate) fn start() -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+        let (bound_tx, bound_rx) = mpsc::channel();
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+
+        let handle = thread::spawn(move || {
+            runtime.block_on(async move {
+                let server = match Server::try_bind(&([127, 0, 0, 1], 0).into()) {
+                    Ok(server) => server,
+                    Err(err) => {
+                        bound_tx.send(Err(err.to_string())).ok();
+                        return;
+                    }
+                };
+                let make_service = make_service_fn(|_| async {
+                    Ok::<_, Infallible>(service_fn(|_: Request<Body>| async {
+                        Ok::<_, Infallible>(Response::new(Body::empty()))
+                    }))
+                });
+
+                let server = server.serve(make_service);
+                bound_tx.send(Ok(server.local_addr())).ok();
+                server
+                    .with_graceful_shutdown(async move {
+                        shutdown_rx.await.ok();
+                    })
+                    .await
+                    .ok();
+            })
+        });
+
+        let local_addr = bound_rx.recv()??;
+        Ok(Self {
+            local_addr,
+            shutdown,
+            handle,
+        })
+    }
+
+    pub(crate) fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub(crate) fn stop(self) {
+        self.shutdown.send(()).ok();
+        self.handle.join().ok();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    result: BenchmarkResult,
+}
+
+impl Calibration {
+    pub(crate) fn new(result: BenchmarkResult) -> Self {
+        Self { result }
+    }
+
+    pub fn requests_per_second(&self) -> f64 {
+        self.result.requests_per_second()
+    }
+
+    pub fn overhead(&self) -> Option<Duration> {
+        self.result.average_time()
+    }
+
+    pub fn result(&self) -> &BenchmarkResult {
+        &self.result
+    }
+}
+
+impl Display for Calibration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Ceiling:   {:.2} reqs/sec against an in-process null server",
+            self.requests_per_second()
+        )?;
+        if let Some(overhead) = self.overhead() {
+            writeln!(
+                f,
+                "Overhead:  {:.2}us per request, P99 {:.2}us",
+                overhead.as_secs_f64() * 1e6,
+                self.result.percentiles().percentile(0.99).as_secs_f64() * 1e6,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+    };
+
+    use super::*;
+    use crate::result::Outcome;
+
+    #[test]
+    fn null_server_answers_with_an_empty_200() {
+        let server = NullServer::start().unwrap();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: x\r\nconnection: close\r\n\r\n")
+            .unwrap();
+        let mut res = String::new();
+        stream.read_to_string(&mut res).unwrap();
+        server.stop();
+
+        assert!(res.starts_with("HTTP/1.1 200 OK\r\n"), "{res}");
+        assert!(res.contains("content-length: 0\r\n"), "{res}");
+    }
+
+    #[test]
+    fn calibration_reports_the_ceiling_and_overhead() {
+        let mut result = BenchmarkResult::default();
+        for _ in 0..4 {
+            result.record(Duration::ZERO, Duration::from_micros(50), Outcome::Success);
+        }
+        result.set_elapsed(Duration::from_secs(2));
+        let calibration = Calibration::new(result);
+
+        assert_eq!(calibration.requests_per_second(), 2.0);
+        assert_eq!(calibration.overhead(), Some(Duration::from_micros(50)));
+        let report = calibration.to_string();
+        assert!(report.contains("Ceiling:   2.00 reqs/sec"), "{report}");
+        assert!(
+            report.contains("Overhead:  50.00us per request"),
+            "{report}"
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CapClosed;
+
+impl Display for CapClosed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "connection cap closed")
+    }
+}
+
+impl Error for CapClosed {}
+
+impl CapClosed {
+    pub(crate) fn caused(err: &(dyn Error + 'static)) -> bool {
+        let mut source = Some(err);
+        while let Some(err) = source {
+            if err.is::<Self>() {
+                return true;
+            }
+            source = err.source();
+        }
+        false
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct ConnectionCap {
+    max: usize,
+    permits: Arc<Semaphore>,
+    queued: AtomicUsize,
+    micros: AtomicU64,
+    peak: AtomicUsize,
+}
+
+impl ConnectionCap {
+    pub(crate) fn new(max: usize) -> Self {
+        Self {
+            max,
+            permits: Arc::new(Semaphore::new(max)),
+            queued: AtomicUsize::new(0),
+            micros: AtomicU64::new(0),
+            peak: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) async fn acquire(&self) -> Result<OwnedSemaphorePermit, CapClosed> {
+        let permit = match self.permits.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                let started = Instant::now();
+                let permit = self.permits.clone().acquire_owned().await;
+                self.queued.fetch_add(1, Ordering::Relaxed);
+                self.micros
+                    .fetch_add(started.elapsed().as_micros() as u64, Ordering::Relaxed);
+                permit.map_err(|_| CapClosed)?
+            }
+        };
+        let open = self.max - self.permits.available_permits();
+        self.peak.fetch_max(open, Ordering::Relaxed);
+        Ok(permit)
+    }
+
+    pub(crate) fn close(&self) {
+        self.permits.close();
+    }
+
+    pub(crate) fn max(&self) -> usize {
+        self.max
+    }
+
+    pub(crate) fn counts(&self) -> (usize, usize, Duration) {
+        (
+            self.peak.load(Ordering::Relaxed),
+            self.queued.load(Ordering::Relaxed),
+            Duration::from_micros(self.micros.load(Ordering::Relaxed)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Wrapped(CapClosed);
+
+    impl Display for Wrapped {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "connect failed")
+        }
+    }
+
+    impl Error for Wrapped {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    #[test]
+    fn closed_caps_are_found_through_error_sources() {
+        assert!(CapClosed::caused(&Wrapped(CapClosed)));
+        assert!(!CapClosed::caused(&std::fmt::Error));
+    }
+
+    #[test]
+    fn caps_track_their_peak_and_fail_once_closed() {
+        let cap = ConnectionCap::new(2);
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let first = cap.acquire().await.unwrap();
+                let _second = cap.acquire().await.unwrap();
+                drop(first);
+                let _third = cap.acquire().await.unwrap();
+                assert_eq!(cap.counts(), (2, 0, Duration::ZERO));
+
+                cap.close();
+                assert_eq!(cap.acquire().await.err(), Some(CapClosed));
+            });
+        assert_eq!(cap.max(), 2);
+    }
+}
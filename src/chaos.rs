@@ -0,0 +1,151 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use futures::future;
+use hyper::{body, header::CONTENT_LENGTH, Body, Request};
+use rand::Rng;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Chaos {
+    drop: f64,
+    abort: f64,
+}
+
+impl Chaos {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn drop_requests(self, fraction: f64) -> Self {
+        Self {
+            drop: fraction.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    pub fn abort_connections(self, fraction: f64) -> Self {
+        Self {
+            abort: fraction.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    pub(crate) fn is_noop(&self) -> bool {
+        self.drop == 0.0 && self.abort == 0.0
+    }
+
+    pub(crate) fn drops_all(&self) -> bool {
+        self.drop >= 1.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Fault {
+    Drop,
+    Abort,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Faults {
+    chaos: Chaos,
+    dropped: AtomicUsize,
+    aborted: AtomicUsize,
+}
+
+impl Faults {
+    pub(crate) fn new(chaos: Chaos) -> Self {
+        Self {
+            chaos,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn roll(&self) -> Option<Fault> {
+        let roll = rand::thread_rng().gen::<f64>();
+        if roll < self.chaos.drop {
+            Some(Fault::Drop)
+        } else if roll < self.chaos.drop + self.chaos.abort {
+            Some(Fault::Abort)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn record(&self, fault: Fault) {
+        let counter = match fault {
+            Fault::Drop => &self.dropped,
+            Fault::Abort => &self.aborted,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn counts(&self) -> (usize, usize) {
+        (
+            self.dropped.load(Ordering::Relaxed),
+            self.aborted.load(Ordering::Relaxed),
+        )
+    }
+}
+
+pub(crate) async fn truncate(req: Request<Body>) -> Request<Body> {
+    let (mut parts, body) = req.into_parts();
+    let bytes = body::to_bytes(body).await.unwrap_or_default();
+    let length = bytes.len().max(2);
+    parts.headers.insert(CONTENT_LENGTH, length.into());
+
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        if sender
+            .send_data(bytes.slice(..bytes.len() / 2))
+            .await
+            .is_ok()
+        {
+            let _ = future::poll_fn(|cx| sender.poll_ready(cx)).await;
+        }
+        sender.abort();
+    });
+    Request::from_parts(parts, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fractions_are_clamped() {
+        let chaos = Chaos::new().drop_requests(1.5).abort_connections(-1.0);
+        assert!(chaos.drops_all());
+        assert_eq!(chaos.abort, 0.0);
+        assert!(Chaos::new().is_noop());
+        assert!(!Chaos::new().abort_connections(0.1).is_noop());
+    }
+
+    #[test]
+    fn rolls_follow_the_configured_fractions() {
+        let drops = Faults::new(Chaos::new().drop_requests(1.0));
+        assert_eq!(drops.roll(), Some(Fault::Drop));
+        let aborts = Faults::new(Chaos::new().abort_connections(1.0));
+        assert_eq!(aborts.roll(), Some(Fault::Abort));
+        assert_eq!(Faults::new(Chaos::new()).roll(), None);
+
+        aborts.record(Fault::Abort);
+        aborts.record(Fault::Drop);
+        aborts.record(Fault::Abort);
+        assert_eq!(aborts.counts(), (1, 2));
+    }
+
+    #[test]
+    fn truncated_requests_promise_more_than_they_send() {
+        let req = Request::post("http://localhost/")
+            .body(Body::from("abcdef"))
+            .unwrap();
+        let sent = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let req = truncate(req).await;
+                assert_eq!(req.headers()[CONTENT_LENGTH], "6");
+                body::to_bytes(req.into_body()).await
+            });
+        assert!(sent.is_err());
+    }
+}
@@ -0,0 +1,52 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+pub(crate) const LABEL: &str = "after churn";
+
+pub(crate) struct Churn {
+    period: u64,
+    next: AtomicU64,
+}
+
+impl Churn {
+    pub(crate) fn new(closes_per_second: f64) -> Self {
+        let period = Duration::from_secs_f64(1.0 / closes_per_second).as_nanos() as u64;
+        Self {
+            period: period.max(1),
+            next: AtomicU64::new(period),
+        }
+    }
+
+    pub(crate) fn due(&self, elapsed: Duration) -> bool {
+        let now = elapsed.as_nanos() as u64;
+        let next = self.next.load(Ordering::Relaxed);
+        if now < next {
+            return false;
+        }
+        self.next
+            .compare_exchange(
+                next,
+                next + self.period,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_close_is_due_per_period() {
+        let churn = Churn::new(10.0);
+        assert!(!churn.due(Duration::from_millis(50)));
+        assert!(churn.due(Duration::from_millis(100)));
+        assert!(!churn.due(Duration::from_millis(150)));
+        assert!(churn.due(Duration::from_millis(250)));
+        assert!(churn.due(Duration::from_millis(300)));
+    }
+}
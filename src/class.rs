@@ -0,0 +1,134 @@
+use std::{
+    ops::Range,
+    sync::{atomic::AtomicU64, Arc},
+};
+
+use hyper::{Body, Request, Uri};
+
+use crate::{schedule::Schedule, user::UserContext, MakeRequest};
+
+#[derive(Clone)]
+pub struct TrafficClass {
+    name: Arc<str>,
+    make_request: MakeRequest,
+    rate: Option<f64>,
+    concurrency: usize,
+}
+
+impl TrafficClass {
+    pub fn new(
+        name: impl Into<Arc<str>>,
+        f: impl Fn(&Uri, &UserContext) -> Request<Body> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            make_request: Arc::new(f),
+            rate: None,
+            concurrency: 1,
+        }
+    }
+
+    pub fn rate(self, requests_per_second: f64) -> Self {
+        Self {
+            rate: Some(requests_per_second),
+            ..self
+        }
+    }
+
+    pub fn concurrency(self, concurrency: usize) -> Self {
+        Self {
+            concurrency: concurrency.max(1),
+            ..self
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Class {
+    pub(crate) name: Arc<str>,
+    pub(crate) users: Range<usize>,
+    pub(crate) schedule: Option<Schedule>,
+    pub(crate) sequence: Arc<AtomicU64>,
+}
+
+pub(crate) fn plan(classes: &[TrafficClass]) -> (Arc<[Class]>, MakeRequest) {
+    let mut first = 0;
+    let planned = classes
+        .iter()
+        .map(|class| {
+            let users = first..first + class.concurrency;
+            first = users.end;
+            Class {
+                name: class.name.clone(),
+                users,
+                schedule: class
+                    .rate
+                    .map(|rate| Schedule::constant(rate).for_users(class.concurrency)),
+                sequence: Arc::default(),
+            }
+        })
+        .collect::<Arc<[_]>>();
+
+    let requests = classes
+        .iter()
+        .map(|class| (class.name.clone(), class.make_request.clone()))
+        .collect::<Vec<_>>();
+    let make_request: MakeRequest = Arc::new(move |uri, ctx| {
+        let class = ctx
+            .class
+            .as_ref()
+            .expect("traffic class users carry their class");
+        let (_, make_request) = requests
+            .iter()
+            .find(|(name, _)| Arc::ptr_eq(name, class))
+            .expect("unknown traffic class");
+        make_request(uri, ctx)
+    });
+    (planned, make_request)
+}
+
+pub(crate) fn concurrency(classes: &[TrafficClass]) -> usize {
+    classes.iter().map(|class| class.concurrency).sum()
+}
+
+pub(crate) fn rates(classes: &[TrafficClass]) -> impl Iterator<Item = f64> + '_ {
+    classes.iter().filter_map(|class| class.rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::shared::Shared;
+
+    fn class(name: &'static str) -> TrafficClass {
+        TrafficClass::new(name, move |uri, _| {
+            Request::get(uri)
+                .header("x-class", name)
+                .body(Body::empty())
+                .unwrap()
+        })
+    }
+
+    #[test]
+    fn classes_get_consecutive_users_and_their_own_requests() {
+        let classes = [class("read").concurrency(3).rate(50.0), class("write")];
+        assert_eq!(concurrency(&classes), 4);
+        assert_eq!(rates(&classes).collect::<Vec<_>>(), [50.0]);
+
+        let (planned, make_request) = plan(&classes);
+        assert_eq!(planned[0].users, 0..3);
+        assert_eq!(planned[1].users, 3..4);
+        assert!(planned[0].schedule.is_some() && planned[1].schedule.is_none());
+
+        let mut ctx = UserContext::new(3, Instant::now(), None, None, Shared::default());
+        ctx.class = Some(planned[1].name.clone());
+        let req = make_request(&"http://localhost/".parse().unwrap(), &ctx);
+        assert_eq!(req.headers()["x-class"], "write");
+    }
+}
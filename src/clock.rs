@@ -0,0 +1,125 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+const CALIBRATION_SAMPLES: usize = 1001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ClockSource {
+    #[default]
+    Std,
+    Coarse,
+    #[cfg(feature = "quanta")]
+    Quanta,
+}
+
+#[derive(Clone)]
+pub(crate) struct Clock {
+    source: ClockSource,
+    base: Instant,
+    overhead: Duration,
+    #[cfg(feature = "quanta")]
+    quanta: quanta::Clock,
+}
+
+impl Clock {
+    pub(crate) fn new(source: ClockSource, calibrate: bool) -> Self {
+        let mut clock = Self {
+            source,
+            base: Instant::now(),
+            overhead: Duration::ZERO,
+            #[cfg(feature = "quanta")]
+            quanta: quanta::Clock::new(),
+        };
+        if calibrate {
+            clock.overhead = clock.measure_overhead();
+        }
+        clock
+    }
+
+    pub(crate) fn source(&self) -> ClockSource {
+        self.source
+    }
+
+    pub(crate) fn overhead(&self) -> Duration {
+        self.overhead
+    }
+
+    pub(crate) fn now(&self) -> u64 {
+        match self.source {
+            ClockSource::Std => self.base.elapsed().as_nanos() as u64,
+            ClockSource::Coarse => coarse_now(self.base),
+            #[cfg(feature = "quanta")]
+            ClockSource::Quanta => self.quanta.raw(),
+        }
+    }
+
+    pub(crate) fn elapsed(&self, start: u64) -> Duration {
+        self.between(start, self.now())
+    }
+
+    fn between(&self, start: u64, end: u64) -> Duration {
+        let nanos = match self.source {
+            #[cfg(feature = "quanta")]
+            ClockSource::Quanta => self.quanta.delta_as_nanos(start, end),
+            _ => end.saturating_sub(start),
+        };
+        Duration::from_nanos(nanos).saturating_sub(self.overhead)
+    }
+
+    fn measure_overhead(&self) -> Duration {
+        let mut samples = (0..CALIBRATION_SAMPLES)
+            .map(|_| {
+                let start = self.now();
+                let end = self.now();
+                self.between(start, end)
+            })
+            .collect::<Vec<_>>();
+        samples.sort();
+        samples[samples.len() / 2]
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn coarse_now(_base: Instant) -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC_COARSE, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+#[cfg(not(target_os = "linux"))]
+fn coarse_now(base: Instant) -> u64 {
+    base.elapsed().as_nanos() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elapsed_time_is_monotonic_and_net_of_overhead() {
+        let clock = Clock::new(ClockSource::Std, false);
+        let start = clock.now();
+        std::thread::sleep(Duration::from_millis(2));
+        assert!(clock.elapsed(start) >= Duration::from_millis(2));
+        assert_eq!(clock.between(10, 5), Duration::ZERO);
+
+        let clock = Clock {
+            overhead: Duration::from_nanos(100),
+            ..clock
+        };
+        assert_eq!(clock.between(0, 250), Duration::from_nanos(150));
+        assert_eq!(clock.between(0, 50), Duration::ZERO);
+    }
+
+    #[test]
+    fn calibration_measures_a_small_overhead() {
+        let clock = Clock::new(ClockSource::Coarse, true);
+        assert_eq!(clock.source(), ClockSource::Coarse);
+        assert!(clock.overhead() < Duration::from_millis(10));
+        assert!(clock.now() > 0);
+    }
+}
@@ -0,0 +1,177 @@
+use std::{fmt::Display, time::Duration};
+
+use hyper::{http::uri::Authority, Request, Uri};
+use serde::{Deserialize, Serialize};
+
+use crate::redact::{Redactor, REDACTED};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunConfig {
+    pub(crate) version: String,
+    pub(crate) target: String,
+    pub(crate) method: Option<String>,
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) mode: String,
+    pub(crate) protocol: String,
+    pub(crate) backend: String,
+    pub(crate) threads: usize,
+    pub(crate) concurrency: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) duration_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) requests: Option<u64>,
+    pub(crate) profile: String,
+    pub(crate) pipeline: Option<usize>,
+    pub(crate) streams_per_connection: usize,
+    pub(crate) region: Option<String>,
+}
+
+impl RunConfig {
+    pub(crate) fn target(uri: &Uri, redactor: &Redactor) -> String {
+        let target = match uri.authority() {
+            Some(authority) if authority.as_str().contains('@') => {
+                let host = authority.as_str().rsplit('@').next().unwrap_or_default();
+                let mut parts = uri.clone().into_parts();
+                parts.authority = host.parse::<Authority>().ok();
+                Uri::from_parts(parts)
+                    .map(|uri| uri.to_string())
+                    .unwrap_or_else(|_| REDACTED.to_string())
+            }
+            _ => uri.to_string(),
+        };
+        redactor.text(&target)
+    }
+
+    pub(crate) fn with_request<B>(self, req: &Request<B>, redactor: &Redactor) -> Self {
+        let headers = req
+            .headers()
+            .iter()
+            .map(|(name, value)| (name.to_string(), redactor.header_value(name, value)))
+            .collect();
+        Self {
+            method: Some(req.method().to_string()),
+            headers,
+            ..self
+        }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration_ms.map(Duration::from_millis)
+    }
+
+    pub fn requests(&self) -> Option<u64> {
+        self.requests
+    }
+
+    pub fn target_uri(&self) -> &str {
+        &self.target
+    }
+
+    pub fn method(&self) -> Option<&str> {
+        self.method.as_deref()
+    }
+
+    pub fn headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    pub fn fingerprint(&self) -> String {
+        let bytes = serde_json::to_vec(self).unwrap_or_default();
+        let hash = bytes.iter().fold(0xcbf29ce484222325_u64, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        });
+        format!("{hash:016x}")
+    }
+}
+
+impl Display for RunConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let budget = match self.requests {
+            Some(requests) => format!(", {requests} requests"),
+            None => String::new(),
+        };
+        write!(
+            f,
+            "{} {} threads x {} users, {}{budget}, {} {} ({})",
+            self.target,
+            self.threads,
+            self.concurrency,
+            self.profile,
+            self.protocol,
+            self.backend,
+            self.fingerprint()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> RunConfig {
+        RunConfig {
+            version: "0.0.0".into(),
+            target: "http://localhost/".into(),
+            method: None,
+            headers: Vec::new(),
+            mode: "http".into(),
+            protocol: "http1".into(),
+            backend: "hyper".into(),
+            threads: 2,
+            concurrency: 8,
+            duration_ms: Some(1000),
+            requests: Some(100),
+            profile: "closed".into(),
+            pipeline: None,
+            streams_per_connection: 1,
+            region: None,
+        }
+    }
+
+    #[test]
+    fn targets_drop_userinfo_and_secrets() {
+        let mut redactor = Redactor::default();
+        redactor.value("t0ken".into());
+        let uri = "http://user:pw@example.com/a?key=t0ken".parse().unwrap();
+        assert_eq!(
+            RunConfig::target(&uri, &redactor),
+            "http://example.com/a?key=[redacted]"
+        );
+    }
+
+    #[test]
+    fn fingerprints_change_with_the_config() {
+        let config = config();
+        assert_eq!(config.fingerprint(), config.clone().fingerprint());
+        assert_ne!(
+            config.fingerprint(),
+            RunConfig {
+                threads: 3,
+                ..config.clone()
+            }
+            .fingerprint()
+        );
+        assert_eq!(config.duration(), Some(Duration::from_secs(1)));
+        assert_eq!(
+            config.to_string(),
+            format!(
+                "http://localhost/ 2 threads x 8 users, closed, 100 requests, http1 hyper ({})",
+                config.fingerprint()
+            )
+        );
+    }
+}
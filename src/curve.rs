@@ -0,0 +1,114 @@
+use std::{fs, io, path::Path, time::Duration};
+
+#[derive(Debug, Clone)]
+pub struct RateCurve {
+    points: Vec<(Duration, f64)>,
+}
+
+impl RateCurve {
+    pub fn new(points: impl IntoIterator<Item = (Duration, f64)>) -> io::Result<Self> {
+        let mut points = points.into_iter().collect::<Vec<_>>();
+        if points.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "empty rate curve",
+            ));
+        }
+        if points.iter().any(|(_, rate)| !rate.is_finite()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "rate curve rates must be finite",
+            ));
+        }
+        points.sort_by_key(|(offset, _)| *offset);
+        let start = points[0].0;
+        for (offset, rate) in points.iter_mut() {
+            *offset -= start;
+            *rate = rate.max(0.0);
+        }
+        Ok(Self { points })
+    }
+
+    pub fn from_csv(path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let mut points = Vec::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split(',').map(str::trim);
+            let (Some(timestamp), Some(rate)) = (fields.next(), fields.next()) else {
+                return Err(invalid(number, "expected timestamp,rate"));
+            };
+            match (timestamp.parse::<f64>(), rate.parse::<f64>()) {
+                (Ok(timestamp), Ok(rate))
+                    if timestamp >= 0.0 && timestamp.is_finite() && rate.is_finite() =>
+                {
+                    points.push((Duration::from_secs_f64(timestamp), rate))
+                }
+                _ if points.is_empty() => continue,
+                _ => return Err(invalid(number, "invalid timestamp or rate")),
+            }
+        }
+        Self::new(points)
+    }
+
+    pub fn points(&self) -> &[(Duration, f64)] {
+        &self.points
+    }
+
+    pub fn span(&self) -> Duration {
+        self.points
+            .last()
+            .map(|(offset, _)| *offset)
+            .unwrap_or_default()
+    }
+}
+
+fn invalid(number: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {}: {message}", number + 1),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curves_are_sorted_and_start_at_zero() {
+        let curve = RateCurve::new([
+            (Duration::from_secs(12), 5.0),
+            (Duration::from_secs(10), -3.0),
+        ])
+        .unwrap();
+        assert_eq!(
+            curve.points(),
+            [(Duration::ZERO, 0.0), (Duration::from_secs(2), 5.0)]
+        );
+        assert_eq!(curve.span(), Duration::from_secs(2));
+
+        assert!(RateCurve::new([]).is_err());
+        assert!(RateCurve::new([(Duration::ZERO, f64::NAN)]).is_err());
+    }
+
+    #[test]
+    fn csv_curves_skip_comments_and_a_header() {
+        let path = std::env::temp_dir().join(format!("zerg-curve-{}.csv", std::process::id()));
+        fs::write(&path, "seconds,rps\n# warmup\n0,10\n\n1.5, 20\n").unwrap();
+        let curve = RateCurve::from_csv(&path).unwrap();
+        assert_eq!(
+            curve.points(),
+            [(Duration::ZERO, 10.0), (Duration::from_millis(1500), 20.0)]
+        );
+
+        fs::write(&path, "0,10\n1,fast\n").unwrap();
+        let err = RateCurve::from_csv(&path).unwrap_err();
+        assert_eq!(err.to_string(), "line 2: invalid timestamp or rate");
+        fs::write(&path, "0\n").unwrap();
+        assert!(RateCurve::from_csv(&path).is_err());
+        fs::remove_file(path).ok();
+    }
+}
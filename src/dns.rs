@@ -0,0 +1,187 @@
+use std::{error::Error, fmt::Display, net::SocketAddr, sync::Arc, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use tokio::{net::UdpSocket, time::timeout};
+
+use crate::{operation::Operation, result::Outcome};
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+const MAX_RESPONSE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RecordType {
+    #[default]
+    A,
+    Aaaa,
+    Cname,
+    Mx,
+    Ns,
+    Txt,
+    Srv,
+}
+
+impl RecordType {
+    fn code(self) -> u16 {
+        match self {
+            Self::A => 1,
+            Self::Ns => 2,
+            Self::Cname => 5,
+            Self::Mx => 15,
+            Self::Txt => 16,
+            Self::Aaaa => 28,
+            Self::Srv => 33,
+        }
+    }
+}
+
+impl Display for RecordType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::A => "A",
+            Self::Aaaa => "AAAA",
+            Self::Cname => "CNAME",
+            Self::Mx => "MX",
+            Self::Ns => "NS",
+            Self::Txt => "TXT",
+            Self::Srv => "SRV",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DnsQuery {
+    name: String,
+    record_type: RecordType,
+}
+
+impl DnsQuery {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            record_type: RecordType::default(),
+        }
+    }
+
+    pub fn record_type(self, record_type: RecordType) -> Self {
+        Self {
+            record_type,
+            ..self
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn encode(&self, id: u16) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        let mut packet = Vec::with_capacity(512);
+        packet.extend_from_slice(&id.to_be_bytes());
+        packet.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+
+        for label in self.name.trim_end_matches('.').split('.') {
+            if label.is_empty() || label.len() > 63 {
+                return Err(format!("invalid dns name {:?}", self.name).into());
+            }
+            packet.push(label.len() as u8);
+            packet.extend_from_slice(label.as_bytes());
+        }
+        packet.push(0);
+
+        packet.extend_from_slice(&self.record_type.code().to_be_bytes());
+        packet.extend_from_slice(&1u16.to_be_bytes());
+        Ok(packet)
+    }
+}
+
+impl Display for DnsQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.record_type, self.name)
+    }
+}
+
+pub(crate) fn response_code(id: u16, packet: &[u8]) -> Option<u8> {
+    if packet.len() < 12 || packet[..2] != id.to_be_bytes() || packet[2] & 0x80 == 0 {
+        return None;
+    }
+    Some(packet[3] & 0x0f)
+}
+
+pub(crate) fn lookup(
+    addr: SocketAddr,
+    query: &DnsQuery,
+) -> Result<Operation, Box<dyn Error + Send + Sync>> {
+    let template: Arc<[u8]> = query.encode(0)?.into();
+    let bind: SocketAddr = match addr {
+        SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+        SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+    };
+
+    Ok(Arc::new(move |ctx| {
+        let id = ctx.sequence() as u16;
+        let template = template.clone();
+        Box::pin(async move {
+            let mut packet = template.to_vec();
+            packet[..2].copy_from_slice(&id.to_be_bytes());
+
+            let socket = match UdpSocket::bind(bind).await {
+                Ok(socket) => socket,
+                Err(err) => return Outcome::from_error(&err),
+            };
+            if socket.connect(addr).await.is_err() || socket.send(&packet).await.is_err() {
+                return Outcome::TcpError;
+            }
+
+            let response = timeout(TIMEOUT, async {
+                let mut buf = [0; MAX_RESPONSE];
+                loop {
+                    let len = socket.recv(&mut buf).await?;
+                    if let Some(code) = response_code(id, &buf[..len]) {
+                        return Ok::<_, std::io::Error>(code);
+                    }
+                }
+            });
+
+            match response.await {
+                Ok(Ok(0)) => Outcome::Success,
+                Ok(Ok(_)) => Outcome::HttpError,
+                _ => Outcome::TcpError,
+            }
+        })
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_encode_labels_type_and_class() {
+        let query = DnsQuery::new("example.com.").record_type(RecordType::Aaaa);
+        assert_eq!(query.to_string(), "AAAA example.com.");
+
+        let mut expected = vec![0x12, 0x34, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 7];
+        expected.extend(b"example");
+        expected.push(3);
+        expected.extend(b"com");
+        expected.extend([0, 0, 28, 0, 1]);
+        assert_eq!(query.encode(0x1234).unwrap(), expected);
+
+        assert!(DnsQuery::new("a..b").encode(0).is_err());
+        assert!(DnsQuery::new("x".repeat(64)).encode(0).is_err());
+    }
+
+    #[test]
+    fn only_answers_to_our_id_carry_a_response_code() {
+        let mut answer = vec![0; 12];
+        answer[..2].copy_from_slice(&7u16.to_be_bytes());
+        answer[2] = 0x81;
+        answer[3] = 0x83;
+        assert_eq!(response_code(7, &answer), Some(3));
+        assert_eq!(response_code(8, &answer), None);
+        assert_eq!(response_code(7, &answer[..11]), None);
+
+        answer[2] = 0x01;
+        assert_eq!(response_code(7, &answer), None);
+    }
+}
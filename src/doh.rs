@@ -0,0 +1,263 @@
+use std::{
+    error::Error,
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use hyper::{
+    body,
+    header::{ACCEPT, CONTENT_TYPE},
+    Body, Request, Uri,
+};
+use tokio::time::timeout;
+
+use crate::{
+    dns::{DnsQuery, RecordType},
+    resolve::{
+        http_client, lookup, AddressFamily, Https, KeepAlive, Resolve, Resolver, Sockets,
+        SystemResolver,
+    },
+    session::{TlsOptions, TlsStats},
+    transport::{Protocol, TlsBackend},
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+const DNS_MESSAGE: &str = "application/dns-message";
+
+#[derive(Debug, Default)]
+pub(crate) struct DohStats {
+    lookups: AtomicUsize,
+    failures: AtomicUsize,
+    micros: AtomicU64,
+}
+
+impl DohStats {
+    fn record(&self, elapsed: Duration, ok: bool) {
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+        if !ok {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+        }
+        self.micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn counts(&self) -> (usize, usize, Duration) {
+        (
+            self.lookups.load(Ordering::Relaxed),
+            self.failures.load(Ordering::Relaxed),
+            Duration::from_micros(self.micros.load(Ordering::Relaxed)),
+        )
+    }
+}
+
+pub(crate) struct DohResolver {
+    endpoint: Uri,
+    tls: TlsOptions,
+    stats: Arc<DohStats>,
+}
+
+impl DohResolver {
+    pub(crate) fn new(
+        endpoint: Uri,
+        tls: TlsOptions,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        if !matches!(endpoint.scheme_str(), Some("http" | "https")) || endpoint.host().is_none() {
+            return Err("DNS-over-HTTPS endpoints must be http:// or https:// URLs".into());
+        }
+        Ok(Self {
+            endpoint,
+            tls,
+            stats: Arc::default(),
+        })
+    }
+
+    pub(crate) fn stats(&self) -> Arc<DohStats> {
+        self.stats.clone()
+    }
+
+    fn query(&self, host: &str) -> Result<Vec<IpAddr>, Box<dyn Error + Send + Sync>> {
+        thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()?
+                        .block_on(self.fetch(host))
+                })
+                .join()
+                .unwrap_or_else(|_| Err("DNS-over-HTTPS lookup panicked".into()))
+        })
+    }
+
+    async fn fetch(&self, host: &str) -> Result<Vec<IpAddr>, Box<dyn Error + Send + Sync>> {
+        let https = match self.endpoint.scheme_str() {
+            Some("https") => Some(Https::new(
+                &self.endpoint,
+                &self.tls,
+                TlsBackend::Rustls,
+                Protocol::Http1,
+                TlsStats::default(),
+            )?),
+            _ => None,
+        };
+        let server = self.endpoint.host().unwrap_or_default();
+        let server = server.trim_start_matches('[').trim_end_matches(']');
+        let port = self.endpoint.port_u16().unwrap_or(match https {
+            Some(_) => 443,
+            None => 80,
+        });
+        let addrs = lookup(&SystemResolver, server, port, AddressFamily::Any)?;
+        let client = http_client(
+            Resolver::Static(addrs.into()),
+            Protocol::Http1,
+            None,
+            None,
+            KeepAlive::default(),
+            https,
+            Sockets::default(),
+        );
+
+        let mut ips = Vec::new();
+        for record_type in [RecordType::A, RecordType::Aaaa] {
+            let query = DnsQuery::new(host).record_type(record_type).encode(0)?;
+            let req = Request::post(self.endpoint.clone())
+                .header(CONTENT_TYPE, DNS_MESSAGE)
+                .header(ACCEPT, DNS_MESSAGE)
+                .body(Body::from(query))?;
+            let res = timeout(TIMEOUT, client.request(req)).await??;
+            if !res.status().is_success() {
+                return Err(format!("DNS-over-HTTPS server answered {}", res.status()).into());
+            }
+            let packet = timeout(TIMEOUT, body::to_bytes(res.into_body())).await??;
+            ips.extend(addresses(&packet).ok_or("malformed DNS-over-HTTPS response")?);
+        }
+        Ok(ips)
+    }
+}
+
+impl Resolve for DohResolver {
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Ok(vec![SocketAddr::new(ip, port)]);
+        }
+        let started = Instant::now();
+        let ips = self.query(host);
+        self.stats.record(started.elapsed(), ips.is_ok());
+        match ips {
+            Ok(ips) => Ok(ips
+                .into_iter()
+                .map(|ip| SocketAddr::new(ip, port))
+                .collect()),
+            Err(err) => Err(io::Error::other(err.to_string())),
+        }
+    }
+}
+
+fn addresses(packet: &[u8]) -> Option<Vec<IpAddr>> {
+    if packet.len() < 12 || packet[2] & 0x80 == 0 {
+        return None;
+    }
+    match packet[3] & 0x0f {
+        0 => {}
+        3 => return Some(Vec::new()),
+        _ => return None,
+    }
+    let questions = u16::from_be_bytes([packet[4], packet[5]]);
+    let answers = u16::from_be_bytes([packet[6], packet[7]]);
+    let mut at = 12;
+    for _ in 0..questions {
+        at = skip_name(packet, at)? + 4;
+    }
+    let mut ips = Vec::new();
+    for _ in 0..answers {
+        at = skip_name(packet, at)?;
+        let record = packet.get(at..at + 10)?;
+        let kind = u16::from_be_bytes([record[0], record[1]]);
+        let len = u16::from_be_bytes([record[8], record[9]]) as usize;
+        let data = packet.get(at + 10..at + 10 + len)?;
+        match kind {
+            1 => ips.push(IpAddr::from(<[u8; 4]>::try_from(data).ok()?)),
+            28 => ips.push(IpAddr::from(<[u8; 16]>::try_from(data).ok()?)),
+            _ => {}
+        }
+        at += 10 + len;
+    }
+    Some(ips)
+}
+
+fn skip_name(packet: &[u8], mut at: usize) -> Option<usize> {
+    loop {
+        match *packet.get(at)? {
+            0 => return Some(at + 1),
+            len if len & 0xc0 == 0xc0 => return Some(at + 2),
+            len => at += 1 + len as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answer(code: u8, records: &[(u16, &[u8])]) -> Vec<u8> {
+        let mut packet = vec![0, 0, 0x81, 0x80 | code, 0, 1];
+        packet.extend((records.len() as u16).to_be_bytes());
+        packet.extend([0, 0, 0, 0]);
+        packet.extend(b"\x07example\x03com\x00\x00\x01\x00\x01");
+        for (kind, data) in records {
+            packet.extend([0xc0, 12]);
+            packet.extend(kind.to_be_bytes());
+            packet.extend([0, 1, 0, 0, 0, 60]);
+            packet.extend((data.len() as u16).to_be_bytes());
+            packet.extend(*data);
+        }
+        packet
+    }
+
+    #[test]
+    fn answers_yield_their_addresses_and_skip_other_records() {
+        let v6 = std::net::Ipv6Addr::LOCALHOST.octets();
+        let packet = answer(0, &[(5, b"\xc0\x0c"), (1, &[10, 0, 0, 1]), (28, &v6)]);
+        assert_eq!(
+            addresses(&packet).unwrap(),
+            [
+                "10.0.0.1".parse::<IpAddr>().unwrap(),
+                "::1".parse().unwrap()
+            ]
+        );
+
+        assert_eq!(addresses(&answer(3, &[])), Some(Vec::new()));
+        assert_eq!(addresses(&answer(2, &[])), None);
+        assert_eq!(addresses(&answer(0, &[(1, &[10, 0])])), None);
+        let truncated = answer(0, &[(1, &[10, 0, 0, 1])]);
+        assert_eq!(addresses(&truncated[..truncated.len() - 2]), None);
+    }
+
+    #[test]
+    fn endpoints_are_checked_and_ip_literals_need_no_lookup() {
+        let tls = TlsOptions::default();
+        assert!(DohResolver::new("udp://1.1.1.1".parse().unwrap(), tls.clone()).is_err());
+
+        let resolver =
+            DohResolver::new("http://127.0.0.1:9/dns-query".parse().unwrap(), tls).unwrap();
+        assert_eq!(
+            resolver.resolve("10.0.0.7", 443).unwrap(),
+            ["10.0.0.7:443".parse().unwrap()]
+        );
+        assert_eq!(resolver.stats().counts().0, 0);
+    }
+
+    #[test]
+    fn stats_count_lookups_failures_and_time() {
+        let stats = DohStats::default();
+        stats.record(Duration::from_millis(2), true);
+        stats.record(Duration::from_millis(3), false);
+        assert_eq!(stats.counts(), (2, 1, Duration::from_millis(5)));
+    }
+}
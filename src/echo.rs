@@ -0,0 +1,140 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use hyper::{header::HeaderName, Body, Request, Response, Uri};
+
+use crate::user::{UserContext, VirtualUser};
+
+const WINDOW: usize = 1 << 14;
+
+#[derive(Default)]
+struct Answered {
+    base: u64,
+    words: VecDeque<u64>,
+}
+
+impl Answered {
+    fn insert(&mut self, n: u64) -> bool {
+        let Some(offset) = n.checked_sub(self.base) else {
+            return true;
+        };
+        let mut word = offset / 64;
+        if word >= WINDOW as u64 {
+            let excess = word + 1 - WINDOW as u64;
+            self.words.drain(..(excess as usize).min(self.words.len()));
+            self.base += excess * 64;
+            word = WINDOW as u64 - 1;
+        }
+        let word = word as usize;
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        let bit = 1 << (offset % 64);
+        let fresh = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        fresh
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct EchoTracker {
+    answered: Mutex<Answered>,
+    checked: AtomicUsize,
+    duplicates: AtomicUsize,
+    mismatches: AtomicUsize,
+}
+
+impl EchoTracker {
+    fn check(&self, expected: u64, echoed: Option<u64>) -> bool {
+        self.checked.fetch_add(1, Ordering::Relaxed);
+        let Some(echoed) = echoed else {
+            self.mismatches.fetch_add(1, Ordering::Relaxed);
+            return false;
+        };
+        if !self.answered.lock().unwrap().insert(echoed) {
+            self.duplicates.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        if echoed != expected {
+            self.mismatches.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    pub(crate) fn counts(&self) -> (usize, usize, usize) {
+        (
+            self.checked.load(Ordering::Relaxed),
+            self.duplicates.load(Ordering::Relaxed),
+            self.mismatches.load(Ordering::Relaxed),
+        )
+    }
+}
+
+pub(crate) struct EchoUser {
+    pub(crate) inner: Box<dyn VirtualUser>,
+    pub(crate) header: HeaderName,
+    pub(crate) tracker: Arc<EchoTracker>,
+}
+
+impl VirtualUser for EchoUser {
+    fn on_start(&mut self, ctx: &UserContext) {
+        self.inner.on_start(ctx)
+    }
+
+    fn next_request(&mut self, uri: &Uri, ctx: &UserContext) -> Option<Request<Body>> {
+        let mut req = self.inner.next_request(uri, ctx)?;
+        req.headers_mut()
+            .insert(self.header.clone(), ctx.sequence.into());
+        Some(req)
+    }
+
+    fn on_response(&mut self, res: Response<Body>, ctx: &UserContext) -> bool {
+        let echoed = res
+            .headers()
+            .get(&self.header)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok());
+        let answered = self.tracker.check(ctx.sequence, echoed);
+        self.inner.on_response(res, ctx) && answered
+    }
+
+    fn on_stop(&mut self, ctx: &UserContext) {
+        self.inner.on_stop(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answered_sequences_are_fresh_once() {
+        let mut answered = Answered::default();
+        assert!(answered.insert(3));
+        assert!(!answered.insert(3));
+        assert!(answered.insert(200));
+
+        // Sequences far ahead slide the window; anything that falls out of it is forgotten.
+        let far = WINDOW as u64 * 64 + 10;
+        assert!(answered.insert(far));
+        assert!(answered.base > 3);
+        assert!(answered.insert(3));
+        assert!(!answered.insert(far));
+    }
+
+    #[test]
+    fn echoes_count_duplicates_and_mismatches() {
+        let tracker = EchoTracker::default();
+        assert!(tracker.check(1, Some(1)));
+        assert!(!tracker.check(2, Some(1)));
+        assert!(!tracker.check(3, Some(4)));
+        assert!(!tracker.check(5, None));
+        assert_eq!(tracker.counts(), (4, 1, 2));
+    }
+}
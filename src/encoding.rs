@@ -0,0 +1,151 @@
+use std::{error::Error, fmt::Display, io::Read};
+
+use brotli_decompressor::Decompressor;
+use flate2::read::{MultiGzDecoder, ZlibDecoder};
+use hyper::{
+    body,
+    header::{HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH},
+    Body, Request, Response,
+};
+
+const ACCEPTED: &str = "gzip, br";
+const MAX_DECODED: u64 = 256 * 1024 * 1024;
+const BROTLI_BUFFER: usize = 4096;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Decoded {
+    pub(crate) wire: u64,
+    pub(crate) decoded: u64,
+}
+
+#[derive(Debug)]
+pub(crate) struct CorruptBody(String);
+
+impl Display for CorruptBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cannot decode {} response body", self.0)
+    }
+}
+
+impl Error for CorruptBody {}
+
+pub(crate) fn accept(req: &mut Request<Body>) {
+    req.headers_mut()
+        .entry(ACCEPT_ENCODING)
+        .or_insert(HeaderValue::from_static(ACCEPTED));
+}
+
+pub(crate) async fn decode(
+    res: Response<Body>,
+) -> Result<(Response<Body>, Option<Decoded>), Box<dyn Error + Send + Sync>> {
+    let encoding = res
+        .headers()
+        .get(CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_ascii_lowercase());
+    let Some(encoding) = encoding
+        .filter(|encoding| matches!(encoding.as_str(), "gzip" | "x-gzip" | "deflate" | "br"))
+    else {
+        return Ok((res, None));
+    };
+
+    let (mut parts, body) = res.into_parts();
+    let wire = body::to_bytes(body).await?;
+    let mut decoded = Vec::new();
+    let read = match encoding.as_str() {
+        "br" => Decompressor::new(&wire[..], BROTLI_BUFFER)
+            .take(MAX_DECODED)
+            .read_to_end(&mut decoded),
+        "deflate" => ZlibDecoder::new(&wire[..])
+            .take(MAX_DECODED)
+            .read_to_end(&mut decoded),
+        _ => MultiGzDecoder::new(&wire[..])
+            .take(MAX_DECODED)
+            .read_to_end(&mut decoded),
+    };
+    if read.is_err() {
+        return Err(Box::new(CorruptBody(encoding)));
+    }
+
+    let sizes = Decoded {
+        wire: wire.len() as u64,
+        decoded: decoded.len() as u64,
+    };
+    parts.headers.remove(CONTENT_ENCODING);
+    parts.headers.insert(CONTENT_LENGTH, decoded.len().into());
+    Ok((
+        Response::from_parts(parts, Body::from(decoded)),
+        Some(sizes),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{
+        write::{GzEncoder, ZlibEncoder},
+        Compression,
+    };
+
+    use super::*;
+
+    fn decoded(encoding: &str, wire: Vec<u8>) -> Result<(Vec<u8>, Option<Decoded>), String> {
+        let res = Response::builder()
+            .header(CONTENT_ENCODING, encoding)
+            .body(Body::from(wire))
+            .unwrap();
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let (res, sizes) = decode(res).await.map_err(|err| err.to_string())?;
+                assert!(res.headers().get(CONTENT_ENCODING).is_none() || sizes.is_none());
+                let body = body::to_bytes(res.into_body()).await.unwrap();
+                Ok((body.to_vec(), sizes))
+            })
+    }
+
+    #[test]
+    fn gzip_and_deflate_bodies_are_decoded_and_sized() {
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(&[b'a'; 1000]).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let wire = gzip.len() as u64;
+        let (body, sizes) = decoded("GZip", gzip).unwrap();
+        assert_eq!(body, [b'a'; 1000]);
+        let sizes = sizes.unwrap();
+        assert_eq!((sizes.wire, sizes.decoded), (wire, 1000));
+
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(b"deflated").unwrap();
+        assert_eq!(
+            decoded("deflate", zlib.finish().unwrap()).unwrap().0,
+            b"deflated"
+        );
+    }
+
+    #[test]
+    fn unknown_encodings_pass_through_and_corrupt_ones_fail() {
+        let (body, sizes) = decoded("zstd", b"raw".to_vec()).unwrap();
+        assert_eq!((body.as_slice(), sizes.is_none()), (&b"raw"[..], true));
+        assert_eq!(
+            decoded("br", b"not brotli".to_vec()).unwrap_err(),
+            "cannot decode br response body"
+        );
+    }
+
+    #[test]
+    fn accept_encoding_is_only_added_when_missing() {
+        let mut req = Request::new(Body::empty());
+        accept(&mut req);
+        assert_eq!(req.headers()[ACCEPT_ENCODING], ACCEPTED);
+
+        let mut req = Request::builder()
+            .header(ACCEPT_ENCODING, "identity")
+            .body(Body::empty())
+            .unwrap();
+        accept(&mut req);
+        assert_eq!(req.headers()[ACCEPT_ENCODING], "identity");
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    error::Error,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use hyper::{
+    body,
+    header::{HeaderValue, EXPECT},
+    Body, Method, Request, Response, StatusCode,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout_at,
+};
+
+use crate::wire;
+
+const READ_SIZE: usize = 16 * 1024;
+const CONTINUE_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Continue {
+    Interim(Duration),
+    Rejected,
+    TimedOut,
+}
+
+pub(crate) struct ContinueClient {
+    addr: SocketAddr,
+    min_body: usize,
+    stream: Option<TcpStream>,
+    write: Vec<u8>,
+    read: Vec<u8>,
+}
+
+impl ContinueClient {
+    pub(crate) fn new(addr: SocketAddr, min_body: usize) -> Self {
+        Self {
+            addr,
+            min_body,
+            stream: None,
+            write: Vec::new(),
+            read: Vec::new(),
+        }
+    }
+
+    pub(crate) async fn request(
+        &mut self,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let head_only = req.method() == Method::HEAD;
+        let (mut parts, body) = req.into_parts();
+        let body = body::to_bytes(body).await?;
+        let expect = !body.is_empty() && body.len() >= self.min_body;
+        if expect {
+            parts
+                .headers
+                .insert(EXPECT, HeaderValue::from_static("100-continue"));
+        }
+
+        self.write.clear();
+        wire::encode_request(&parts, &body, &mut self.write);
+        let head = self.write.len() - body.len();
+
+        let mut stream = match self.stream.take() {
+            Some(stream) => stream,
+            None => {
+                let stream = TcpStream::connect(self.addr).await?;
+                stream.set_nodelay(true)?;
+                stream
+            }
+        };
+        self.read.clear();
+
+        if !expect {
+            stream.write_all(&self.write).await?;
+            return self.response(stream, head_only).await;
+        }
+
+        let sent = Instant::now();
+        stream.write_all(&self.write[..head]).await?;
+        let deadline = sent + CONTINUE_TIMEOUT;
+        let mut eof = false;
+        let interim = loop {
+            match wire::parse_response(&self.read, head_only, eof)? {
+                Some((res, len)) if res.status() == StatusCode::CONTINUE => {
+                    self.read.drain(..len);
+                    break Continue::Interim(sent.elapsed());
+                }
+                Some((res, len)) if res.status().is_informational() => {
+                    self.read.drain(..len);
+                    continue;
+                }
+                Some((mut res, _)) => {
+                    res.extensions_mut().insert(Continue::Rejected);
+                    return Ok(res);
+                }
+                None if eof => return Err("connection closed".into()),
+                None => {}
+            }
+
+            self.read.reserve(READ_SIZE);
+            match timeout_at(deadline.into(), stream.read_buf(&mut self.read)).await {
+                Ok(Ok(0)) => eof = true,
+                Ok(read) => {
+                    read?;
+                }
+                Err(_) => break Continue::TimedOut,
+            }
+        };
+
+        stream.write_all(&self.write[head..]).await?;
+        let mut res = self.response(stream, head_only).await?;
+        res.extensions_mut().insert(interim);
+        Ok(res)
+    }
+
+    async fn response(
+        &mut self,
+        mut stream: TcpStream,
+        head_only: bool,
+    ) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let mut eof = false;
+        loop {
+            match wire::parse_response(&self.read, head_only, eof)? {
+                Some((res, len)) if res.status().is_informational() => {
+                    self.read.drain(..len);
+                    continue;
+                }
+                Some((res, _)) => {
+                    if wire::keep_alive(&res) {
+                        self.stream = Some(stream);
+                    }
+                    return Ok(res);
+                }
+                None if eof => return Err("connection closed".into()),
+                None => {}
+            }
+
+            self.read.reserve(READ_SIZE);
+            eof = stream.read_buf(&mut self.read).await? == 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+
+    /// Serves one connection: reads a head, sends `interim` (if any) and then, after reading a
+    /// body of `body` bytes, an empty 200.
+    fn server(interim: Option<&'static str>, body: usize) -> (SocketAddr, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 && !line.ends_with("\r\n\r\n") {}
+            let mut stream = stream;
+            if let Some(interim) = interim {
+                stream.write_all(interim.as_bytes()).unwrap();
+                if !interim.starts_with("HTTP/1.1 1") {
+                    return;
+                }
+            }
+            reader.read_exact(&mut vec![0; body]).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                .unwrap();
+        });
+        (addr, handle)
+    }
+
+    fn send(client: &mut ContinueClient, body: &'static str) -> Response<Body> {
+        let req = Request::post("http://localhost/upload")
+            .body(Body::from(body))
+            .unwrap();
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(client.request(req))
+            .unwrap()
+    }
+
+    #[test]
+    fn bodies_wait_for_100_continue() {
+        let (addr, handle) = server(Some("HTTP/1.1 100 Continue\r\n\r\n"), 5);
+        let res = send(&mut ContinueClient::new(addr, 1), "hello");
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(matches!(
+            res.extensions().get::<Continue>(),
+            Some(Continue::Interim(_))
+        ));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn final_answers_before_the_body_are_rejections() {
+        let (addr, handle) = server(
+            Some("HTTP/1.1 417 Expectation Failed\r\ncontent-length: 0\r\n\r\n"),
+            0,
+        );
+        let res = send(&mut ContinueClient::new(addr, 1), "hello");
+        assert_eq!(res.status(), StatusCode::EXPECTATION_FAILED);
+        assert_eq!(
+            res.extensions().get::<Continue>(),
+            Some(&Continue::Rejected)
+        );
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn small_bodies_are_sent_without_expecting() {
+        let (addr, handle) = server(None, 2);
+        let res = send(&mut ContinueClient::new(addr, 1024), "hi");
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.extensions().get::<Continue>(), None);
+        handle.join().unwrap();
+    }
+}
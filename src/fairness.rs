@@ -0,0 +1,161 @@
+use std::{collections::BTreeMap, fmt::Display, ops::AddAssign, time::Duration};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UserStats {
+    pub(crate) requests: usize,
+    pub(crate) total_time: Duration,
+}
+
+impl UserStats {
+    pub fn request_count(&self) -> usize {
+        self.requests
+    }
+
+    pub fn average_time(&self) -> Option<Duration> {
+        match self.requests {
+            0 => None,
+            n => Some(self.total_time / n as u32),
+        }
+    }
+}
+
+impl AddAssign<UserStats> for UserStats {
+    fn add_assign(&mut self, rhs: UserStats) {
+        self.requests += rhs.requests;
+        self.total_time += rhs.total_time;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Fairness {
+    users: usize,
+    min_requests: usize,
+    max_requests: usize,
+    request_cv: f64,
+    latency_cv: f64,
+    starved: Vec<usize>,
+}
+
+impl Fairness {
+    pub(crate) fn new(users: &BTreeMap<usize, UserStats>, threshold: f64) -> Option<Self> {
+        if users.is_empty() {
+            return None;
+        }
+
+        let requests = users
+            .values()
+            .map(|user| user.requests as f64)
+            .collect::<Vec<_>>();
+        let latencies = users
+            .values()
+            .filter_map(UserStats::average_time)
+            .map(|time| time.as_secs_f64())
+            .collect::<Vec<_>>();
+        let mean = requests.iter().sum::<f64>() / requests.len() as f64;
+
+        Some(Self {
+            users: users.len(),
+            min_requests: users.values().map(|user| user.requests).min()?,
+            max_requests: users.values().map(|user| user.requests).max()?,
+            request_cv: coefficient_of_variation(&requests),
+            latency_cv: coefficient_of_variation(&latencies),
+            starved: users
+                .iter()
+                .filter(|(_, user)| (user.requests as f64) < mean * threshold)
+                .map(|(id, _)| *id)
+                .collect(),
+        })
+    }
+
+    pub fn user_count(&self) -> usize {
+        self.users
+    }
+
+    pub fn min_requests(&self) -> usize {
+        self.min_requests
+    }
+
+    pub fn max_requests(&self) -> usize {
+        self.max_requests
+    }
+
+    pub fn request_cv(&self) -> f64 {
+        self.request_cv
+    }
+
+    pub fn latency_cv(&self) -> f64 {
+        self.latency_cv
+    }
+
+    pub fn starved_users(&self) -> &[usize] {
+        &self.starved
+    }
+}
+
+impl Display for Fairness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} users, {}-{} reqs each (CV {:.2}), latency CV {:.2}",
+            self.users, self.min_requests, self.max_requests, self.request_cv, self.latency_cv
+        )
+    }
+}
+
+fn coefficient_of_variation(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    if mean == 0.0 {
+        return 0.0;
+    }
+    let var = values
+        .iter()
+        .map(|value| mean - value)
+        .map(|val| val * val)
+        .sum::<f64>()
+        / values.len() as f64;
+    var.sqrt() / mean
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(requests: usize, ms: u64) -> UserStats {
+        UserStats {
+            requests,
+            total_time: Duration::from_millis(ms),
+        }
+    }
+
+    #[test]
+    fn starved_users_fall_below_the_threshold_of_the_mean() {
+        let users = BTreeMap::from([(0, user(10, 100)), (1, user(10, 100)), (2, user(1, 10))]);
+        let fairness = Fairness::new(&users, 0.5).unwrap();
+        assert_eq!((fairness.min_requests(), fairness.max_requests()), (1, 10));
+        assert_eq!(fairness.starved_users(), [2]);
+        assert_eq!(fairness.latency_cv(), 0.0);
+        assert!(fairness.request_cv() > 0.5);
+        assert_eq!(
+            fairness.to_string(),
+            format!(
+                "3 users, 1-10 reqs each (CV {:.2}), latency CV 0.00",
+                fairness.request_cv()
+            )
+        );
+        assert!(Fairness::new(&BTreeMap::new(), 0.5).is_none());
+    }
+
+    #[test]
+    fn user_stats_average_and_add_up() {
+        let mut stats = user(2, 30);
+        stats += user(1, 0);
+        assert_eq!(stats.request_count(), 3);
+        assert_eq!(stats.average_time(), Some(Duration::from_millis(10)));
+        assert_eq!(UserStats::default().average_time(), None);
+        assert_eq!(coefficient_of_variation(&[0.0, 0.0]), 0.0);
+        assert_eq!(coefficient_of_variation(&[1.0, 3.0]), 0.5);
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    fs, io,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use hyper::{Body, Request, Response, Uri};
+
+use crate::user::{UserContext, VirtualUser};
+
+#[derive(Debug, Clone)]
+pub struct Feed {
+    values: Arc<[Arc<str>]>,
+    next: Arc<AtomicUsize>,
+    unique: bool,
+}
+
+impl Feed {
+    pub fn new<I, V>(values: I) -> Self
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<Arc<str>>,
+    {
+        let values: Arc<[Arc<str>]> = values.into_iter().map(Into::into).collect();
+        assert!(!values.is_empty(), "cannot create an empty feed");
+        Self {
+            values,
+            next: Arc::new(AtomicUsize::new(0)),
+            unique: false,
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let values = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(Arc::from)
+            .collect::<Vec<Arc<str>>>();
+        if values.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "empty feed"));
+        }
+        Ok(Self::new(values))
+    }
+
+    pub fn unique(self) -> Self {
+        Self {
+            unique: true,
+            ..self
+        }
+    }
+
+    pub fn is_unique(&self) -> bool {
+        self.unique
+    }
+
+    pub fn remaining(&self) -> Option<usize> {
+        match self.unique {
+            true => Some(
+                self.values
+                    .len()
+                    .saturating_sub(self.next.load(Ordering::Relaxed)),
+            ),
+            false => None,
+        }
+    }
+
+    pub(crate) fn values(&self) -> &[Arc<str>] {
+        &self.values
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn next(&self) -> Option<Arc<str>> {
+        let n = self.next.fetch_add(1, Ordering::Relaxed);
+        match self.unique {
+            true => self.values.get(n).cloned(),
+            false => Some(self.values[n % self.values.len()].clone()),
+        }
+    }
+}
+
+pub(crate) struct FeedUser {
+    pub(crate) inner: Box<dyn VirtualUser>,
+    pub(crate) feed: Feed,
+}
+
+impl VirtualUser for FeedUser {
+    fn on_start(&mut self, ctx: &UserContext) {
+        self.inner.on_start(ctx)
+    }
+
+    fn next_request(&mut self, uri: &Uri, ctx: &UserContext) -> Option<Request<Body>> {
+        let mut ctx = ctx.clone();
+        ctx.feed_value = Some(self.feed.next()?);
+        self.inner.next_request(uri, &ctx)
+    }
+
+    fn on_response(&mut self, res: Response<Body>, ctx: &UserContext) -> bool {
+        self.inner.on_response(res, ctx)
+    }
+
+    fn on_stop(&mut self, ctx: &UserContext) {
+        self.inner.on_stop(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn feeds_cycle_unless_unique() {
+        let feed = Feed::new(["a", "b"]);
+        let values: Vec<_> = (0..3).map(|_| feed.next().unwrap()).collect();
+        assert_eq!(values, ["a", "b", "a"].map(Arc::from));
+        assert_eq!(feed.remaining(), None);
+
+        let unique = Feed::new(["a", "b"]).unique();
+        assert_eq!(unique.remaining(), Some(2));
+        assert_eq!(unique.next().as_deref(), Some("a"));
+        assert_eq!(unique.next().as_deref(), Some("b"));
+        assert_eq!(unique.next(), None);
+        assert_eq!(unique.remaining(), Some(0));
+    }
+
+    struct Echo;
+
+    impl VirtualUser for Echo {
+        fn next_request(&mut self, uri: &Uri, ctx: &UserContext) -> Option<Request<Body>> {
+            let path = format!("/{}", ctx.feed_value()?);
+            Some(
+                Request::get(crate::uri::rebase(uri, &path))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        }
+    }
+
+    #[test]
+    fn feed_users_stop_once_a_unique_feed_runs_dry() {
+        let mut user = FeedUser {
+            inner: Box::new(Echo),
+            feed: Feed::new(["a"]).unique(),
+        };
+        let ctx = UserContext::new(
+            0,
+            std::time::Instant::now(),
+            None,
+            None,
+            crate::shared::Shared::default(),
+        );
+        let uri = "http://localhost/".parse().unwrap();
+        assert_eq!(user.next_request(&uri, &ctx).unwrap().uri().path(), "/a");
+        assert!(user.next_request(&uri, &ctx).is_none());
+    }
+
+    #[test]
+    fn feed_files_skip_blank_lines() {
+        let path = std::env::temp_dir().join(format!("zerg-feed-{}.txt", std::process::id()));
+        fs::write(&path, " alice \n\nbob\n").unwrap();
+        assert_eq!(
+            Feed::from_file(&path).unwrap().values(),
+            ["alice", "bob"].map(Arc::from)
+        );
+        fs::write(&path, "\n  \n").unwrap();
+        assert!(Feed::from_file(&path).is_err());
+        fs::remove_file(path).ok();
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fmt::Display,
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc, Barrier},
+    thread,
+};
+
+use crate::{result::BenchmarkResult, Swarm, SwarmBuilder};
+
+#[derive(Default)]
+pub struct Fleet {
+    swarms: Vec<(String, SwarmBuilder)>,
+    dependencies: Vec<(String, String)>,
+}
+
+impl Fleet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn swarm(mut self, name: impl Into<String>, swarm: SwarmBuilder) -> Self {
+        self.swarms.push((name.into(), swarm));
+        self
+    }
+
+    pub fn after(mut self, name: impl Into<String>, dependency: impl Into<String>) -> Self {
+        self.dependencies.push((name.into(), dependency.into()));
+        self
+    }
+
+    pub fn zerg(self) -> Result<FleetResult, Box<dyn Error + Send + Sync>> {
+        if self.swarms.is_empty() {
+            return Err("no swarms configured".into());
+        }
+        let mut swarms = BTreeMap::new();
+        for (name, swarm) in self.swarms {
+            if swarms.contains_key(&name) {
+                return Err(format!("duplicate swarm name: {name}").into());
+            }
+            let swarm = swarm
+                .build()
+                .map_err(|err| format!("swarm {name}: {err}"))?;
+            swarms.insert(name, swarm);
+        }
+
+        let mut waiting = swarms
+            .keys()
+            .map(|name| (name.clone(), BTreeSet::new()))
+            .collect::<BTreeMap<_, _>>();
+        for (name, dependency) in self.dependencies {
+            if !swarms.contains_key(&dependency) {
+                return Err(format!("swarm {name} depends on unknown swarm {dependency}").into());
+            }
+            waiting
+                .get_mut(&name)
+                .ok_or_else(|| format!("unknown swarm: {name}"))?
+                .insert(dependency);
+        }
+        check_acyclic(&waiting)?;
+
+        let (done, finished) = mpsc::channel();
+        let ready = take_ready(&mut waiting);
+        let start = Arc::new(Barrier::new(ready.len()));
+        let mut running = 0;
+        for name in ready {
+            let swarm = swarms.remove(&name).unwrap();
+            launch(name, swarm, Some(start.clone()), done.clone());
+            running += 1;
+        }
+
+        let mut results = BTreeMap::new();
+        while running > 0 {
+            let (name, result) = finished.recv()?;
+            running -= 1;
+            let result = result.ok_or_else(|| format!("swarm {name} panicked"))?;
+            for dependencies in waiting.values_mut() {
+                dependencies.remove(&name);
+            }
+            results.insert(name, result);
+            for name in take_ready(&mut waiting) {
+                let swarm = swarms.remove(&name).unwrap();
+                launch(name, swarm, None, done.clone());
+                running += 1;
+            }
+        }
+        Ok(FleetResult { results })
+    }
+}
+
+type Finished = (String, Option<BenchmarkResult>);
+
+fn launch(name: String, swarm: Swarm, start: Option<Arc<Barrier>>, done: mpsc::Sender<Finished>) {
+    thread::spawn(move || {
+        if let Some(start) = start {
+            start.wait();
+        }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| swarm.zerg())).ok();
+        done.send((name, result)).ok();
+    });
+}
+
+fn take_ready(waiting: &mut BTreeMap<String, BTreeSet<String>>) -> Vec<String> {
+    let ready = waiting
+        .iter()
+        .filter(|(_, dependencies)| dependencies.is_empty())
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    for name in &ready {
+        waiting.remove(name);
+    }
+    ready
+}
+
+fn check_acyclic(
+    waiting: &BTreeMap<String, BTreeSet<String>>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut waiting = waiting.clone();
+    while !waiting.is_empty() {
+        let ready = take_ready(&mut waiting);
+        if ready.is_empty() {
+            let names = waiting.keys().cloned().collect::<Vec<_>>();
+            return Err(format!("swarm dependencies form a cycle: {}", names.join(", ")).into());
+        }
+        for dependencies in waiting.values_mut() {
+            for name in &ready {
+                dependencies.remove(name);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct FleetResult {
+    results: BTreeMap<String, BenchmarkResult>,
+}
+
+impl FleetResult {
+    pub fn swarm(&self, name: &str) -> Option<&BenchmarkResult> {
+        self.results.get(name)
+    }
+
+    pub fn swarms(&self) -> impl Iterator<Item = (&str, &BenchmarkResult)> {
+        self.results
+            .iter()
+            .map(|(name, result)| (name.as_str(), result))
+    }
+
+    pub fn combined(&self) -> BenchmarkResult {
+        let mut combined = BenchmarkResult::merge_aligned(self.results.values().cloned());
+        combined.config = None;
+        combined
+    }
+}
+
+impl Display for FleetResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.combined())?;
+        for (name, result) in &self.results {
+            let percentiles = result.percentiles();
+            writeln!(
+                f,
+                "Swarm {name}: {} reqs in {:.2}s, {} errors, P50 {:.2}ms, P99 {:.2}ms",
+                result.total_request_count(),
+                result.elapsed.as_secs_f64(),
+                result.http_error + result.tcp_error,
+                percentiles.percentile(0.50).as_millis() as f64,
+                percentiles.percentile(0.99).as_millis() as f64,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::swarm;
+
+    fn waiting(edges: &[(&str, &[&str])]) -> BTreeMap<String, BTreeSet<String>> {
+        edges
+            .iter()
+            .map(|(name, deps)| {
+                (
+                    name.to_string(),
+                    deps.iter().map(|d| d.to_string()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn swarms_without_dependencies_are_ready() {
+        let mut graph = waiting(&[("a", &[]), ("b", &["a"]), ("c", &[])]);
+        assert_eq!(take_ready(&mut graph), ["a", "c"]);
+        assert_eq!(graph.keys().collect::<Vec<_>>(), ["b"]);
+
+        assert!(check_acyclic(&waiting(&[("a", &[]), ("b", &["a"]), ("c", &["b"])])).is_ok());
+        assert_eq!(
+            check_acyclic(&waiting(&[("a", &[]), ("b", &["c"]), ("c", &["b"])]))
+                .unwrap_err()
+                .to_string(),
+            "swarm dependencies form a cycle: b, c"
+        );
+    }
+
+    #[test]
+    fn fleets_are_checked_before_anything_runs() {
+        let error = |fleet: Fleet| fleet.zerg().err().unwrap().to_string();
+        let target = || swarm("http://127.0.0.1:9/");
+
+        assert_eq!(error(Fleet::new()), "no swarms configured");
+        assert_eq!(
+            error(Fleet::new().swarm("a", target()).swarm("a", target())),
+            "duplicate swarm name: a"
+        );
+        assert_eq!(
+            error(Fleet::new().swarm("a", target()).after("a", "b")),
+            "swarm a depends on unknown swarm b"
+        );
+        assert!(error(
+            Fleet::new()
+                .swarm("a", target())
+                .swarm("b", target())
+                .after("a", "b")
+                .after("b", "a")
+        )
+        .contains("cycle"));
+    }
+}
@@ -0,0 +1,95 @@
+use futures::FutureExt;
+use hyper::{
+    body,
+    header::{ACCEPT, CONTENT_TYPE},
+    Body, Request, Response, Uri,
+};
+use serde_json::{json, Value};
+
+use crate::user::{UserContext, VirtualUser};
+
+pub fn graphql_query(uri: &Uri, query: &str, variables: Value) -> Request<Body> {
+    let body = json!({ "query": query, "variables": variables });
+    Request::post(uri.clone())
+        .header(CONTENT_TYPE, "application/json")
+        .header(
+            ACCEPT,
+            "application/graphql-response+json, application/json",
+        )
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+pub fn graphql_errors(body: &[u8]) -> bool {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(body) => match body.get("errors") {
+            None | Some(Value::Null) => false,
+            Some(Value::Array(errors)) => !errors.is_empty(),
+            Some(_) => true,
+        },
+        Err(_) => true,
+    }
+}
+
+pub(crate) struct GraphqlUser {
+    pub(crate) inner: Box<dyn VirtualUser>,
+}
+
+impl VirtualUser for GraphqlUser {
+    fn on_start(&mut self, ctx: &UserContext) {
+        self.inner.on_start(ctx)
+    }
+
+    fn next_request(&mut self, uri: &Uri, ctx: &UserContext) -> Option<Request<Body>> {
+        self.inner.next_request(uri, ctx)
+    }
+
+    fn on_response(&mut self, res: Response<Body>, ctx: &UserContext) -> bool {
+        let (parts, body) = res.into_parts();
+        let Some(Ok(body)) = body::to_bytes(body).now_or_never() else {
+            return false;
+        };
+        let failed = graphql_errors(&body);
+        self.inner
+            .on_response(Response::from_parts(parts, Body::from(body)), ctx)
+            && !failed
+    }
+
+    fn on_stop(&mut self, ctx: &UserContext) {
+        self.inner.on_stop(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_are_json_posts() {
+        let req = graphql_query(
+            &"http://localhost/graphql".parse().unwrap(),
+            "{ me { id } }",
+            json!({ "first": 2 }),
+        );
+        assert_eq!(req.method(), "POST");
+        assert_eq!(req.headers()[CONTENT_TYPE], "application/json");
+        let body = body::to_bytes(req.into_body())
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&body).unwrap(),
+            json!({ "query": "{ me { id } }", "variables": { "first": 2 } })
+        );
+    }
+
+    #[test]
+    fn errors_are_non_empty_error_fields_or_bad_json() {
+        assert!(!graphql_errors(br#"{"data":{}}"#));
+        assert!(!graphql_errors(br#"{"data":{},"errors":[]}"#));
+        assert!(!graphql_errors(br#"{"errors":null}"#));
+        assert!(graphql_errors(br#"{"errors":[{"message":"no"}]}"#));
+        assert!(graphql_errors(br#"{"errors":"no"}"#));
+        assert!(graphql_errors(b"<html>"));
+    }
+}
@@ -0,0 +1,362 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use hyper::{
+    body::{self, HttpBody},
+    header::{HeaderMap, CONTENT_TYPE, TE},
+    Body, Request, Response, StatusCode, Uri,
+};
+use prost::Message;
+use tokio::time::timeout_at;
+
+use crate::{
+    clock::Clock,
+    metrics::Recorder,
+    resolve::HttpClient,
+    result::Outcome,
+    uri::rebase,
+    user::{UserContext, VirtualUser},
+};
+
+const CODES: [&str; 17] = [
+    "OK",
+    "CANCELLED",
+    "UNKNOWN",
+    "INVALID_ARGUMENT",
+    "DEADLINE_EXCEEDED",
+    "NOT_FOUND",
+    "ALREADY_EXISTS",
+    "PERMISSION_DENIED",
+    "RESOURCE_EXHAUSTED",
+    "FAILED_PRECONDITION",
+    "ABORTED",
+    "OUT_OF_RANGE",
+    "UNIMPLEMENTED",
+    "INTERNAL",
+    "UNAVAILABLE",
+    "DATA_LOSS",
+    "UNAUTHENTICATED",
+];
+const UNKNOWN: usize = 2;
+const FIRST_MESSAGE_LABEL: &str = "grpc first message";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrpcStreaming {
+    Server,
+    Bidi { messages: usize },
+}
+
+pub fn grpc_request(uri: &Uri, method: &str, message: &impl Message) -> Request<Body> {
+    let len = message.encoded_len();
+    let mut body = Vec::with_capacity(5 + len);
+    body.push(0);
+    body.extend_from_slice(&(len as u32).to_be_bytes());
+    message.encode_raw(&mut body);
+
+    Request::post(rebase(uri, method))
+        .header(CONTENT_TYPE, "application/grpc")
+        .header(TE, "trailers")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+fn code(res: &Response<Body>) -> usize {
+    if let Some(code) = trailer_code(res.headers()) {
+        return code;
+    }
+    match res.status() {
+        StatusCode::BAD_REQUEST => 13,
+        StatusCode::UNAUTHORIZED => 16,
+        StatusCode::FORBIDDEN => 7,
+        StatusCode::NOT_FOUND => 12,
+        StatusCode::TOO_MANY_REQUESTS
+        | StatusCode::BAD_GATEWAY
+        | StatusCode::SERVICE_UNAVAILABLE
+        | StatusCode::GATEWAY_TIMEOUT => 14,
+        _ => UNKNOWN,
+    }
+}
+
+fn trailer_code(headers: &HeaderMap) -> Option<usize> {
+    let code = headers.get("grpc-status")?;
+    Some(
+        code.to_str()
+            .ok()
+            .and_then(|code| code.trim().parse().ok())
+            .filter(|code| *code < CODES.len())
+            .unwrap_or(UNKNOWN),
+    )
+}
+
+#[derive(Default)]
+pub(crate) struct GrpcStats {
+    codes: [AtomicUsize; CODES.len()],
+    messages: AtomicUsize,
+}
+
+impl GrpcStats {
+    pub(crate) fn counts(&self) -> impl Iterator<Item = (String, usize)> + '_ {
+        CODES
+            .iter()
+            .zip(&self.codes)
+            .map(|(name, count)| (name.to_string(), count.load(Ordering::Relaxed)))
+            .filter(|(_, count)| *count > 0)
+    }
+
+    pub(crate) fn messages(&self) -> usize {
+        self.messages.load(Ordering::Relaxed)
+    }
+
+    fn record(&self, code: usize) {
+        self.codes[code].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub(crate) struct GrpcUser {
+    pub(crate) inner: Box<dyn VirtualUser>,
+    pub(crate) stats: Arc<GrpcStats>,
+}
+
+impl VirtualUser for GrpcUser {
+    fn on_start(&mut self, ctx: &UserContext) {
+        self.inner.on_start(ctx)
+    }
+
+    fn next_request(&mut self, uri: &Uri, ctx: &UserContext) -> Option<Request<Body>> {
+        self.inner.next_request(uri, ctx)
+    }
+
+    fn on_response(&mut self, res: Response<Body>, ctx: &UserContext) -> bool {
+        let code = code(&res);
+        self.stats.record(code);
+        self.inner.on_response(res, ctx) && code == 0
+    }
+
+    fn on_stop(&mut self, ctx: &UserContext) {
+        self.inner.on_stop(ctx)
+    }
+}
+
+pub(crate) struct Stream {
+    pub(crate) http: HttpClient,
+    pub(crate) streaming: GrpcStreaming,
+    pub(crate) stats: Arc<GrpcStats>,
+    pub(crate) epoch: Instant,
+    pub(crate) deadline: Instant,
+    pub(crate) clock: Clock,
+    pub(crate) recorder: Recorder,
+}
+
+impl Stream {
+    pub(crate) async fn run(
+        &self,
+        uri: &Uri,
+        user: &mut dyn VirtualUser,
+        ctx: &mut UserContext,
+        running: &AtomicBool,
+        sequence: &AtomicU64,
+    ) {
+        while running.load(Ordering::Relaxed) {
+            ctx.sequence = sequence.fetch_add(1, Ordering::Relaxed);
+            let Some(req) = user.next_request(uri, ctx) else {
+                break;
+            };
+            let (parts, message) = req.into_parts();
+            let message = body::to_bytes(message).await.unwrap_or_default();
+            let (mut sender, body, mut remaining) = match self.streaming {
+                GrpcStreaming::Server => (None, Body::from(message.clone()), 0),
+                GrpcStreaming::Bidi { messages } => {
+                    let (mut sender, body) = Body::channel();
+                    sender.try_send_data(message.clone()).ok();
+                    (Some(sender), body, messages - 1)
+                }
+            };
+
+            let start = Instant::now();
+            let timer = self.clock.now();
+            let req = Request::from_parts(parts, body);
+            let res = match timeout_at(self.deadline.into(), self.http.request(req)).await {
+                Ok(Ok(res)) if res.status().is_success() => res,
+                Ok(Ok(res)) => {
+                    self.stats.record(code(&res));
+                    self.first_message(start, timer, Outcome::HttpError);
+                    continue;
+                }
+                Ok(Err(err)) => {
+                    self.first_message(start, timer, Outcome::from_error(&err));
+                    continue;
+                }
+                Err(_) => break,
+            };
+            let headers_code = trailer_code(res.headers());
+
+            let mut body = res.into_body();
+            let mut frames = Frames::default();
+            let mut received = 0;
+            let mut last = (start, timer);
+            let finished = loop {
+                let chunk = match timeout_at(self.deadline.into(), body.data()).await {
+                    Ok(Some(Ok(chunk))) => chunk,
+                    Ok(None) => break true,
+                    Ok(Some(Err(_))) => {
+                        if received == 0 {
+                            self.first_message(start, timer, Outcome::TcpError);
+                        }
+                        self.recorder
+                            .record_drop(Instant::now().saturating_duration_since(self.epoch));
+                        break false;
+                    }
+                    Err(_) => break false,
+                };
+                for _ in 0..frames.feed(&chunk) {
+                    let (at, timer) = last;
+                    match received {
+                        0 => self.first_message(at, timer, Outcome::Success),
+                        _ => self.recorder.record(
+                            at.saturating_duration_since(self.epoch),
+                            self.clock.elapsed(timer),
+                            Outcome::Success,
+                        ),
+                    }
+                    received += 1;
+                    self.stats.messages.fetch_add(1, Ordering::Relaxed);
+                    last = (Instant::now(), self.clock.now());
+                    if remaining == 0 {
+                        sender = None;
+                    } else if let Some(sender) = &mut sender {
+                        remaining -= 1;
+                        sender.try_send_data(message.clone()).ok();
+                    }
+                }
+                if !running.load(Ordering::Relaxed) {
+                    break false;
+                }
+            };
+
+            if finished {
+                let code = match timeout_at(self.deadline.into(), body.trailers()).await {
+                    Ok(Ok(Some(trailers))) => trailer_code(&trailers),
+                    _ => None,
+                }
+                .or(headers_code)
+                .unwrap_or(UNKNOWN);
+                self.stats.record(code);
+                match (code, received) {
+                    (0, _) => {}
+                    (_, 0) => self.first_message(start, timer, Outcome::HttpError),
+                    _ => self.recorder.record(
+                        last.0.saturating_duration_since(self.epoch),
+                        self.clock.elapsed(last.1),
+                        Outcome::HttpError,
+                    ),
+                }
+            }
+            ctx.iteration += 1;
+        }
+    }
+
+    fn first_message(&self, start: Instant, timer: u64, outcome: Outcome) {
+        let offset = start.saturating_duration_since(self.epoch);
+        let elapsed = self.clock.elapsed(timer);
+        self.recorder.record(offset, elapsed, outcome);
+        self.recorder
+            .record_connect(FIRST_MESSAGE_LABEL, offset, elapsed, outcome);
+    }
+}
+
+#[derive(Default)]
+struct Frames {
+    buf: Vec<u8>,
+}
+
+impl Frames {
+    fn feed(&mut self, chunk: &[u8]) -> usize {
+        self.buf.extend_from_slice(chunk);
+        let mut messages = 0;
+        let mut at = 0;
+        while let Some(header) = self.buf.get(at..at + 5) {
+            let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+            if self.buf.len() < at + 5 + len {
+                break;
+            }
+            at += 5 + len;
+            messages += 1;
+        }
+        self.buf.drain(..at);
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(status: StatusCode, grpc_status: Option<&'static str>) -> Response<Body> {
+        let mut res = Response::new(Body::empty());
+        *res.status_mut() = status;
+        if let Some(grpc_status) = grpc_status {
+            res.headers_mut()
+                .insert("grpc-status", grpc_status.parse().unwrap());
+        }
+        res
+    }
+
+    #[test]
+    fn requests_are_length_prefixed_grpc_posts() {
+        let uri = Uri::from_static("http://localhost:50051/");
+        let req = grpc_request(&uri, "/echo.Echo/Say", &String::from("hi"));
+        assert_eq!(req.method(), "POST");
+        assert_eq!(req.uri(), "http://localhost:50051/echo.Echo/Say");
+        assert_eq!(req.headers()[CONTENT_TYPE], "application/grpc");
+        assert_eq!(req.headers()[TE], "trailers");
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let body = runtime.block_on(body::to_bytes(req.into_body())).unwrap();
+        assert_eq!(&body[..], [0, 0, 0, 0, 4, 0x0a, 2, b'h', b'i']);
+    }
+
+    #[test]
+    fn status_codes_come_from_trailers_or_map_from_http() {
+        assert_eq!(code(&response(StatusCode::OK, Some("5"))), 5);
+        assert_eq!(code(&response(StatusCode::OK, Some(" 0 "))), 0);
+        assert_eq!(code(&response(StatusCode::OK, Some("99"))), UNKNOWN);
+        assert_eq!(code(&response(StatusCode::OK, Some("x"))), UNKNOWN);
+        assert_eq!(code(&response(StatusCode::SERVICE_UNAVAILABLE, None)), 14);
+        assert_eq!(code(&response(StatusCode::UNAUTHORIZED, None)), 16);
+        assert_eq!(code(&response(StatusCode::OK, None)), UNKNOWN);
+    }
+
+    #[test]
+    fn stats_count_only_the_codes_seen() {
+        let stats = GrpcStats::default();
+        stats.record(0);
+        stats.record(0);
+        stats.record(14);
+        let counts: Vec<_> = stats.counts().collect();
+        assert_eq!(
+            counts,
+            [("OK".to_string(), 2), ("UNAVAILABLE".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn frames_are_counted_across_chunk_boundaries() {
+        let mut frames = Frames::default();
+        let message = [0, 0, 0, 0, 2, b'h', b'i'];
+        assert_eq!(frames.feed(&message[..3]), 0);
+        assert_eq!(frames.feed(&message[3..]), 1);
+
+        let mut two = message.repeat(2);
+        two.extend_from_slice(&message[..6]);
+        assert_eq!(frames.feed(&two), 2);
+        assert_eq!(frames.feed(&message[6..]), 1);
+        assert!(frames.buf.is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+use std::{error::Error, sync::Arc};
+
+use hyper::{
+    header::{HeaderName, HeaderValue, USER_AGENT},
+    Body, Request, Response, Uri,
+};
+
+use crate::user::{UserContext, VirtualUser};
+
+const CHROME_SUITES: &[&str] = &[
+    "TLS13_AES_128_GCM_SHA256",
+    "TLS13_AES_256_GCM_SHA384",
+    "TLS13_CHACHA20_POLY1305_SHA256",
+    "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256",
+    "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256",
+    "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384",
+    "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384",
+    "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256",
+    "TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256",
+];
+const FIREFOX_SUITES: &[&str] = &[
+    "TLS13_AES_128_GCM_SHA256",
+    "TLS13_CHACHA20_POLY1305_SHA256",
+    "TLS13_AES_256_GCM_SHA384",
+    "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256",
+    "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256",
+    "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256",
+    "TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256",
+    "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384",
+    "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384",
+];
+const BROWSER_GROUPS: &[&str] = &["X25519", "secp256r1", "secp384r1"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct TlsFingerprint {
+    pub(crate) cipher_suites: Vec<String>,
+    pub(crate) key_exchange_groups: Vec<String>,
+    pub(crate) tls13_only: bool,
+}
+
+impl TlsFingerprint {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IdentityProfile {
+    user_agent: Option<String>,
+    headers: Vec<(String, String)>,
+    tls: TlsFingerprint,
+}
+
+impl IdentityProfile {
+    pub fn new(user_agent: impl Into<String>) -> Self {
+        Self {
+            user_agent: Some(user_agent.into()),
+            ..Self::default()
+        }
+    }
+
+    pub fn chrome() -> Self {
+        Self::new(
+            "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36",
+        )
+        .header(
+            "accept",
+            "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8",
+        )
+        .header("accept-language", "en-US,en;q=0.9")
+        .header("sec-ch-ua-platform", "\"Linux\"")
+        .cipher_suites(CHROME_SUITES)
+        .key_exchange_groups(BROWSER_GROUPS)
+    }
+
+    pub fn firefox() -> Self {
+        Self::new("Mozilla/5.0 (X11; Linux x86_64; rv:125.0) Gecko/20100101 Firefox/125.0")
+            .header(
+                "accept",
+                "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+            )
+            .header("accept-language", "en-US,en;q=0.5")
+            .cipher_suites(FIREFOX_SUITES)
+            .key_exchange_groups(BROWSER_GROUPS)
+    }
+
+    pub fn curl() -> Self {
+        Self::new("curl/8.7.1").header("accept", "*/*")
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn cipher_suites<I, S>(mut self, suites: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.tls.cipher_suites = suites
+            .into_iter()
+            .map(|suite| suite.as_ref().to_string())
+            .collect();
+        self
+    }
+
+    pub fn key_exchange_groups<I, S>(mut self, groups: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.tls.key_exchange_groups = groups
+            .into_iter()
+            .map(|group| group.as_ref().to_string())
+            .collect();
+        self
+    }
+
+    pub fn tls13_only(mut self) -> Self {
+        self.tls.tls13_only = true;
+        self
+    }
+
+    pub fn user_agent(&self) -> Option<&str> {
+        self.user_agent.as_deref()
+    }
+
+    pub(crate) fn fingerprint(&self) -> Option<TlsFingerprint> {
+        match self.tls.is_empty() {
+            true => None,
+            false => Some(self.tls.clone()),
+        }
+    }
+
+    pub(crate) fn headers(
+        &self,
+    ) -> Result<Vec<(HeaderName, HeaderValue)>, Box<dyn Error + Send + Sync>> {
+        let mut headers = self
+            .headers
+            .iter()
+            .map(|(name, value)| Ok((name.parse()?, value.parse()?)))
+            .collect::<Result<Vec<_>, Box<dyn Error + Send + Sync>>>()?;
+        if let Some(user_agent) = &self.user_agent {
+            headers.insert(0, (USER_AGENT, user_agent.parse()?));
+        }
+        Ok(headers)
+    }
+}
+
+pub(crate) struct IdentifiedUser {
+    pub(crate) inner: Box<dyn VirtualUser>,
+    pub(crate) headers: Arc<Vec<(HeaderName, HeaderValue)>>,
+}
+
+impl VirtualUser for IdentifiedUser {
+    fn on_start(&mut self, ctx: &UserContext) {
+        self.inner.on_start(ctx)
+    }
+
+    fn next_request(&mut self, uri: &Uri, ctx: &UserContext) -> Option<Request<Body>> {
+        let mut req = self.inner.next_request(uri, ctx)?;
+        for (name, value) in self.headers.iter() {
+            req.headers_mut()
+                .entry(name.clone())
+                .or_insert_with(|| value.clone());
+        }
+        Some(req)
+    }
+
+    fn on_response(&mut self, res: Response<Body>, ctx: &UserContext) -> bool {
+        self.inner.on_response(res, ctx)
+    }
+
+    fn on_stop(&mut self, ctx: &UserContext) {
+        self.inner.on_stop(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::shared::Shared;
+
+    struct Plain;
+
+    impl VirtualUser for Plain {
+        fn next_request(&mut self, uri: &Uri, _ctx: &UserContext) -> Option<Request<Body>> {
+            Some(
+                Request::get(uri)
+                    .header("accept", "application/json")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        }
+    }
+
+    #[test]
+    fn profiles_fill_in_headers_the_request_left_out() {
+        let profile = IdentityProfile::curl().header("x-tenant", "blue");
+        let mut user = IdentifiedUser {
+            inner: Box::new(Plain),
+            headers: Arc::new(profile.headers().unwrap()),
+        };
+        let ctx = UserContext::new(0, Instant::now(), None, None, Shared::default());
+        let req = user
+            .next_request(&"http://localhost/".parse().unwrap(), &ctx)
+            .unwrap();
+        assert_eq!(req.headers()[USER_AGENT], "curl/8.7.1");
+        assert_eq!(req.headers()["accept"], "application/json");
+        assert_eq!(req.headers()["x-tenant"], "blue");
+    }
+
+    #[test]
+    fn only_browser_profiles_carry_a_tls_fingerprint() {
+        assert_eq!(IdentityProfile::curl().fingerprint(), None);
+        let chrome = IdentityProfile::chrome().fingerprint().unwrap();
+        assert_eq!(chrome.cipher_suites.len(), CHROME_SUITES.len());
+        assert_eq!(chrome.key_exchange_groups, BROWSER_GROUPS);
+        assert!(IdentityProfile::default()
+            .tls13_only()
+            .fingerprint()
+            .is_some_and(|tls| tls.tls13_only));
+
+        let headers = IdentityProfile::firefox().headers().unwrap();
+        assert_eq!(headers[0].0, USER_AGENT);
+        assert!(IdentityProfile::new("ua")
+            .header("bad header", "x")
+            .headers()
+            .is_err());
+    }
+}
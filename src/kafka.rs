@@ -0,0 +1,856 @@
+use std::{
+    io,
+    net::SocketAddr,
+    str,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use futures::future;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{lookup_host, TcpStream},
+    time::{sleep_until, timeout_at},
+};
+
+use crate::{
+    clock::Clock,
+    metrics::Recorder,
+    result::Outcome,
+    schedule::{Schedule, Slot},
+    user::UserContext,
+};
+
+const CONNECT_LABEL: &str = "kafka connect";
+const DELIVERY_LABEL: &str = "kafka end-to-end";
+const CLIENT_ID: &str = "zerg";
+const MAX_RESPONSE: usize = 64 * 1024 * 1024;
+const FETCH_MAX_BYTES: i32 = 4 * 1024 * 1024;
+const FETCH_MAX_WAIT_MS: i32 = 100;
+const REQUEST_TIMEOUT_MS: i32 = 30_000;
+const STAMP: usize = 16;
+const RECONNECT_DELAY: Duration = Duration::from_millis(100);
+
+const PRODUCE: i16 = 0;
+const FETCH: i16 = 1;
+const LIST_OFFSETS: i16 = 2;
+const METADATA: i16 = 3;
+const NOT_LEADER: i16 = 6;
+
+type MakePayload = Arc<dyn Fn(&UserContext) -> Vec<u8> + Send + Sync + 'static>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Acks {
+    None,
+    Leader,
+    #[default]
+    All,
+}
+
+impl Acks {
+    fn code(self) -> i16 {
+        match self {
+            Self::None => 0,
+            Self::Leader => 1,
+            Self::All => -1,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Kafka {
+    topic: String,
+    partitions: usize,
+    payload: MakePayload,
+    acks: Acks,
+    consume: bool,
+}
+
+impl Kafka {
+    pub fn new(topic: impl Into<String>) -> Self {
+        Self {
+            topic: topic.into(),
+            partitions: 1,
+            payload: Arc::new(|_| Vec::new()),
+            acks: Acks::default(),
+            consume: false,
+        }
+    }
+
+    pub fn partitions(self, partitions: usize) -> Self {
+        Self {
+            partitions: partitions.max(1),
+            ..self
+        }
+    }
+
+    pub fn payload(
+        self,
+        payload: impl Fn(&UserContext) -> Vec<u8> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            payload: Arc::new(payload),
+            ..self
+        }
+    }
+
+    pub fn acks(self, acks: Acks) -> Self {
+        Self { acks, ..self }
+    }
+
+    pub fn consume(self) -> Self {
+        Self {
+            consume: true,
+            ..self
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), &'static str> {
+        if self.topic.is_empty() {
+            return Err("Kafka topic cannot be empty");
+        }
+        if self.topic.len() > 249 {
+            return Err("Kafka topic names are limited to 249 characters");
+        }
+        if self.partitions > i32::MAX as usize {
+            return Err("too many Kafka partitions");
+        }
+        Ok(())
+    }
+
+    fn partition(&self, user: usize) -> i32 {
+        (user % self.partitions) as i32
+    }
+}
+
+pub(crate) struct Session {
+    pub(crate) addr: SocketAddr,
+    pub(crate) epoch: Instant,
+    pub(crate) deadline: Instant,
+    pub(crate) clock: Clock,
+    pub(crate) recorder: Recorder,
+    pub(crate) schedule: Option<Schedule>,
+}
+
+impl Session {
+    pub(crate) async fn run(
+        &self,
+        kafka: &Kafka,
+        ctx: &mut UserContext,
+        running: &AtomicBool,
+        sequence: &AtomicU64,
+    ) {
+        let partition = kafka.partition(ctx.id());
+        let leader = self.leader(kafka, partition).await;
+        let consumer = match kafka.consume && ctx.id() < kafka.partitions {
+            true => self.subscribe(kafka, partition, leader).await,
+            false => None,
+        };
+        match consumer {
+            Some(consumer) => {
+                future::join(
+                    self.produce(kafka, partition, leader, ctx, running, sequence),
+                    self.consume(kafka, partition, consumer),
+                )
+                .await;
+            }
+            None => {
+                self.produce(kafka, partition, leader, ctx, running, sequence)
+                    .await
+            }
+        }
+    }
+
+    async fn leader(&self, kafka: &Kafka, partition: i32) -> SocketAddr {
+        self.lookup_leader(kafka, partition)
+            .await
+            .unwrap_or(self.addr)
+    }
+
+    async fn lookup_leader(&self, kafka: &Kafka, partition: i32) -> Option<SocketAddr> {
+        let mut stream = timeout_at(self.deadline.into(), TcpStream::connect(self.addr))
+            .await
+            .ok()?
+            .ok()?;
+        let request = metadata(1, &kafka.topic);
+        let response = timeout_at(self.deadline.into(), send(&mut stream, &request, 1, true))
+            .await
+            .ok()?
+            .ok()??;
+        let (host, port) = partition_leader(&response, partition)?;
+        let mut addrs = lookup_host((host.as_str(), port)).await.ok()?;
+        addrs.next()
+    }
+
+    async fn produce(
+        &self,
+        kafka: &Kafka,
+        partition: i32,
+        mut leader: SocketAddr,
+        ctx: &mut UserContext,
+        running: &AtomicBool,
+        sequence: &AtomicU64,
+    ) {
+        let mut connection = None;
+        let mut correlation = 0;
+        while running.load(Ordering::Relaxed) {
+            let ticket = self
+                .schedule
+                .as_ref()
+                .map(|schedule| schedule.claim(sequence, self.epoch));
+            ctx.sequence = match &ticket {
+                Some(ticket) => ticket.n,
+                None => sequence.fetch_add(1, Ordering::Relaxed),
+            };
+            let mut late = Duration::ZERO;
+            if let (Some(schedule), Some(ticket)) = (&self.schedule, &ticket) {
+                match schedule
+                    .wait(ticket, self.epoch, Some(self.deadline), &self.recorder)
+                    .await
+                {
+                    Slot::Due(lateness) => late = lateness,
+                    Slot::Taken => continue,
+                    Slot::Closed => break,
+                }
+                if !running.load(Ordering::Relaxed) {
+                    break;
+                }
+            }
+
+            let stream = match &mut connection {
+                Some(stream) => stream,
+                None => match self.connect(leader).await {
+                    Some(stream) => connection.insert(stream),
+                    None => {
+                        sleep_until((Instant::now() + RECONNECT_DELAY).min(self.deadline).into())
+                            .await;
+                        continue;
+                    }
+                },
+            };
+
+            correlation += 1;
+            let start = Instant::now();
+            let timer = self.clock.now();
+            let mut value = Vec::with_capacity(STAMP);
+            value.extend_from_slice(&timer.to_be_bytes());
+            value.extend_from_slice(&(ctx.id() as u64).to_be_bytes());
+            value.extend_from_slice(&(kafka.payload)(ctx));
+            let request = produce(correlation, &kafka.topic, partition, kafka.acks, &value);
+            let sent = timeout_at(
+                self.deadline.into(),
+                send(stream, &request, correlation, kafka.acks != Acks::None),
+            )
+            .await;
+            let mut moved = false;
+            let outcome = match sent {
+                Ok(Ok(Some(response))) => match produced(&response) {
+                    Some(0) => Outcome::Success,
+                    Some(NOT_LEADER) => {
+                        moved = true;
+                        Outcome::HttpError
+                    }
+                    _ => Outcome::HttpError,
+                },
+                Ok(Ok(None)) => Outcome::Success,
+                Ok(Err(_)) => Outcome::TcpError,
+                Err(_) => break,
+            };
+            self.recorder.record(
+                start.saturating_duration_since(self.epoch),
+                self.clock.elapsed(timer) + late,
+                outcome,
+            );
+            if outcome == Outcome::TcpError {
+                self.recorder
+                    .record_drop(Instant::now().saturating_duration_since(self.epoch));
+                connection = None;
+            }
+            if moved {
+                leader = self.leader(kafka, partition).await;
+                connection = None;
+            }
+            ctx.iteration += 1;
+        }
+    }
+
+    async fn subscribe(
+        &self,
+        kafka: &Kafka,
+        partition: i32,
+        leader: SocketAddr,
+    ) -> Option<(TcpStream, i64)> {
+        let mut stream = self.connect(leader).await?;
+        let request = list_offsets(1, &kafka.topic, partition);
+        let response = timeout_at(self.deadline.into(), send(&mut stream, &request, 1, true))
+            .await
+            .ok()?
+            .ok()??;
+        let offset = latest_offset(&response)?;
+        Some((stream, offset))
+    }
+
+    async fn consume(&self, kafka: &Kafka, partition: i32, consumer: (TcpStream, i64)) {
+        let (mut stream, mut offset) = consumer;
+        let mut correlation = 1;
+        loop {
+            correlation += 1;
+            let request = fetch(correlation, &kafka.topic, partition, offset);
+            let response = match timeout_at(
+                self.deadline.into(),
+                send(&mut stream, &request, correlation, true),
+            )
+            .await
+            {
+                Ok(Ok(Some(response))) => response,
+                Ok(_) => {
+                    self.recorder
+                        .record_drop(Instant::now().saturating_duration_since(self.epoch));
+                    return;
+                }
+                Err(_) => return,
+            };
+            let Some(records) = fetched(&response) else {
+                return;
+            };
+            offset = read_batches(records, offset, |value| {
+                if value.len() < STAMP {
+                    return;
+                }
+                let timer = u64::from_be_bytes(value[..8].try_into().unwrap());
+                let elapsed = self.clock.elapsed(timer);
+                let offset = Instant::now()
+                    .saturating_duration_since(self.epoch)
+                    .saturating_sub(elapsed);
+                self.recorder
+                    .record_connect(DELIVERY_LABEL, offset, elapsed, Outcome::Success);
+            });
+        }
+    }
+
+    async fn connect(&self, addr: SocketAddr) -> Option<TcpStream> {
+        let start = Instant::now();
+        let timer = self.clock.now();
+        let connected = timeout_at(self.deadline.into(), TcpStream::connect(addr)).await;
+        let offset = start.saturating_duration_since(self.epoch);
+        let elapsed = self.clock.elapsed(timer);
+        match connected {
+            Ok(Ok(stream)) => {
+                stream.set_nodelay(true).ok();
+                self.recorder
+                    .record_connect(CONNECT_LABEL, offset, elapsed, Outcome::Success);
+                Some(stream)
+            }
+            Ok(Err(err)) => {
+                let outcome = Outcome::from_error(&err);
+                self.recorder
+                    .record_connect(CONNECT_LABEL, offset, elapsed, outcome);
+                self.recorder.record(offset, elapsed, outcome);
+                None
+            }
+            Err(_) => None,
+        }
+    }
+}
+
+async fn send(
+    stream: &mut TcpStream,
+    request: &[u8],
+    correlation: i32,
+    expect_response: bool,
+) -> io::Result<Option<Vec<u8>>> {
+    stream.write_all(request).await?;
+    if !expect_response {
+        return Ok(None);
+    }
+    loop {
+        let len = stream.read_i32().await?;
+        if len < 4 || len as usize > MAX_RESPONSE {
+            return Err(invalid("invalid Kafka response length"));
+        }
+        let mut response = vec![0; len as usize];
+        stream.read_exact(&mut response).await?;
+        if response[..4] == correlation.to_be_bytes() {
+            response.drain(..4);
+            return Ok(Some(response));
+        }
+    }
+}
+
+fn request(api_key: i16, version: i16, correlation: i32, body: &[u8]) -> Vec<u8> {
+    let mut header = Vec::with_capacity(16 + body.len());
+    header.extend_from_slice(&api_key.to_be_bytes());
+    header.extend_from_slice(&version.to_be_bytes());
+    header.extend_from_slice(&correlation.to_be_bytes());
+    string(&mut header, CLIENT_ID);
+    header.extend_from_slice(body);
+
+    let mut request = Vec::with_capacity(4 + header.len());
+    request.extend_from_slice(&(header.len() as i32).to_be_bytes());
+    request.extend_from_slice(&header);
+    request
+}
+
+fn produce(correlation: i32, topic: &str, partition: i32, acks: Acks, value: &[u8]) -> Vec<u8> {
+    let batch = record_batch(value);
+    let mut body = Vec::with_capacity(64 + topic.len() + batch.len());
+    body.extend_from_slice(&(-1i16).to_be_bytes());
+    body.extend_from_slice(&acks.code().to_be_bytes());
+    body.extend_from_slice(&REQUEST_TIMEOUT_MS.to_be_bytes());
+    body.extend_from_slice(&1i32.to_be_bytes());
+    string(&mut body, topic);
+    body.extend_from_slice(&1i32.to_be_bytes());
+    body.extend_from_slice(&partition.to_be_bytes());
+    body.extend_from_slice(&(batch.len() as i32).to_be_bytes());
+    body.extend_from_slice(&batch);
+    request(PRODUCE, 3, correlation, &body)
+}
+
+fn list_offsets(correlation: i32, topic: &str, partition: i32) -> Vec<u8> {
+    let mut body = Vec::with_capacity(32 + topic.len());
+    body.extend_from_slice(&(-1i32).to_be_bytes());
+    body.extend_from_slice(&1i32.to_be_bytes());
+    string(&mut body, topic);
+    body.extend_from_slice(&1i32.to_be_bytes());
+    body.extend_from_slice(&partition.to_be_bytes());
+    body.extend_from_slice(&(-1i64).to_be_bytes());
+    request(LIST_OFFSETS, 1, correlation, &body)
+}
+
+fn metadata(correlation: i32, topic: &str) -> Vec<u8> {
+    let mut body = Vec::with_capacity(8 + topic.len());
+    body.extend_from_slice(&1i32.to_be_bytes());
+    string(&mut body, topic);
+    body.push(1);
+    request(METADATA, 4, correlation, &body)
+}
+
+fn fetch(correlation: i32, topic: &str, partition: i32, offset: i64) -> Vec<u8> {
+    let mut body = Vec::with_capacity(48 + topic.len());
+    body.extend_from_slice(&(-1i32).to_be_bytes());
+    body.extend_from_slice(&FETCH_MAX_WAIT_MS.to_be_bytes());
+    body.extend_from_slice(&1i32.to_be_bytes());
+    body.extend_from_slice(&FETCH_MAX_BYTES.to_be_bytes());
+    body.push(0);
+    body.extend_from_slice(&1i32.to_be_bytes());
+    string(&mut body, topic);
+    body.extend_from_slice(&1i32.to_be_bytes());
+    body.extend_from_slice(&partition.to_be_bytes());
+    body.extend_from_slice(&offset.to_be_bytes());
+    body.extend_from_slice(&FETCH_MAX_BYTES.to_be_bytes());
+    request(FETCH, 4, correlation, &body)
+}
+
+fn record_batch(value: &[u8]) -> Vec<u8> {
+    let mut record = vec![0];
+    varint(&mut record, 0);
+    varint(&mut record, 0);
+    varint(&mut record, -1);
+    varint(&mut record, value.len() as i64);
+    record.extend_from_slice(value);
+    varint(&mut record, 0);
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64;
+    let mut checked = Vec::with_capacity(48 + record.len());
+    checked.extend_from_slice(&0i16.to_be_bytes());
+    checked.extend_from_slice(&0i32.to_be_bytes());
+    checked.extend_from_slice(&now.to_be_bytes());
+    checked.extend_from_slice(&now.to_be_bytes());
+    checked.extend_from_slice(&(-1i64).to_be_bytes());
+    checked.extend_from_slice(&(-1i16).to_be_bytes());
+    checked.extend_from_slice(&(-1i32).to_be_bytes());
+    checked.extend_from_slice(&1i32.to_be_bytes());
+    varint(&mut checked, record.len() as i64);
+    checked.extend_from_slice(&record);
+
+    let mut batch = Vec::with_capacity(21 + checked.len());
+    batch.extend_from_slice(&0i64.to_be_bytes());
+    batch.extend_from_slice(&(9 + checked.len() as i32).to_be_bytes());
+    batch.extend_from_slice(&(-1i32).to_be_bytes());
+    batch.push(2);
+    batch.extend_from_slice(&crc32c(&checked).to_be_bytes());
+    batch.extend_from_slice(&checked);
+    batch
+}
+
+fn produced(response: &[u8]) -> Option<i16> {
+    let mut reader = Reader(response);
+    reader.array()?;
+    reader.string()?;
+    reader.array()?;
+    reader.i32()?;
+    reader.i16()
+}
+
+fn latest_offset(response: &[u8]) -> Option<i64> {
+    let mut reader = Reader(response);
+    reader.array()?;
+    reader.string()?;
+    reader.array()?;
+    reader.i32()?;
+    if reader.i16()? != 0 {
+        return None;
+    }
+    reader.i64()?;
+    reader.i64()
+}
+
+fn partition_leader(response: &[u8], partition: i32) -> Option<(String, u16)> {
+    let mut reader = Reader(response);
+    reader.i32()?;
+    let mut brokers = Vec::new();
+    for _ in 0..reader.array()? {
+        let node = reader.i32()?;
+        let host = str::from_utf8(reader.string()?).ok()?.to_string();
+        let port = u16::try_from(reader.i32()?).ok()?;
+        reader.nullable_string()?;
+        brokers.push((node, host, port));
+    }
+    reader.nullable_string()?;
+    reader.i32()?;
+    reader.array()?;
+    if reader.i16()? != 0 {
+        return None;
+    }
+    reader.string()?;
+    reader.take(1)?;
+    for _ in 0..reader.array()? {
+        reader.i16()?;
+        let index = reader.i32()?;
+        let leader = reader.i32()?;
+        reader.skip_ints()?;
+        reader.skip_ints()?;
+        if index == partition {
+            return brokers
+                .into_iter()
+                .find(|(node, _, _)| *node == leader)
+                .map(|(_, host, port)| (host, port));
+        }
+    }
+    None
+}
+
+fn fetched(response: &[u8]) -> Option<&[u8]> {
+    let mut reader = Reader(response);
+    reader.i32()?;
+    reader.array()?;
+    reader.string()?;
+    reader.array()?;
+    reader.i32()?;
+    if reader.i16()? != 0 {
+        return None;
+    }
+    reader.i64()?;
+    reader.i64()?;
+    let aborted = reader.i32()?;
+    for _ in 0..aborted.max(0) {
+        reader.i64()?;
+        reader.i64()?;
+    }
+    reader.bytes()
+}
+
+fn read_batches(mut records: &[u8], mut offset: i64, mut delivered: impl FnMut(&[u8])) -> i64 {
+    while records.len() >= 12 {
+        let mut header = Reader(records);
+        let (Some(base), Some(len)) = (header.i64(), header.i32()) else {
+            break;
+        };
+        let Some(batch) = usize::try_from(len)
+            .ok()
+            .and_then(|len| records.get(12..12 + len))
+        else {
+            break;
+        };
+        records = &records[12 + batch.len()..];
+
+        let mut reader = Reader(batch);
+        let Some(attributes) = reader.take(9).and_then(|_| reader.i16()) else {
+            break;
+        };
+        let (Some(last), Some(_)) = (reader.i32(), reader.take(30)) else {
+            break;
+        };
+        let Some(count) = reader.i32() else {
+            break;
+        };
+        offset = offset.max(base + last as i64 + 1);
+        if attributes & 0x07 != 0 {
+            continue;
+        }
+        for _ in 0..count.max(0) {
+            let Some((value, rest)) = record_value(reader.0) else {
+                break;
+            };
+            reader.0 = rest;
+            if let Some(value) = value {
+                delivered(value);
+            }
+        }
+    }
+    offset
+}
+
+fn record_value(data: &[u8]) -> Option<(Option<&[u8]>, &[u8])> {
+    let mut reader = Reader(data);
+    let len = usize::try_from(reader.varint()?).ok()?;
+    let rest = data.get(data.len() - reader.0.len() + len..)?;
+    let mut record = Reader(reader.take(len)?);
+    record.take(1)?;
+    record.varint()?;
+    record.varint()?;
+    if let Ok(key) = usize::try_from(record.varint()?) {
+        record.take(key)?;
+    }
+    let value = match usize::try_from(record.varint()?) {
+        Ok(value) => Some(record.take(value)?),
+        Err(_) => None,
+    };
+    Some((value, rest))
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let data = self.0.get(..n)?;
+        self.0 = &self.0[n..];
+        Some(data)
+    }
+
+    fn i16(&mut self) -> Option<i16> {
+        Some(i16::from_be_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    fn i32(&mut self) -> Option<i32> {
+        Some(i32::from_be_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn i64(&mut self) -> Option<i64> {
+        Some(i64::from_be_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn array(&mut self) -> Option<usize> {
+        usize::try_from(self.i32()?).ok().filter(|len| *len > 0)
+    }
+
+    fn string(&mut self) -> Option<&'a [u8]> {
+        let len = usize::try_from(self.i16()?).ok()?;
+        self.take(len)
+    }
+
+    fn nullable_string(&mut self) -> Option<Option<&'a [u8]>> {
+        match usize::try_from(self.i16()?) {
+            Ok(len) => self.take(len).map(Some),
+            Err(_) => Some(None),
+        }
+    }
+
+    fn skip_ints(&mut self) -> Option<()> {
+        let len = usize::try_from(self.i32()?).unwrap_or_default();
+        self.take(len * 4).map(|_| ())
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = usize::try_from(self.i32()?).ok()?;
+        self.take(len)
+    }
+
+    fn varint(&mut self) -> Option<i64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Some((value >> 1) as i64 ^ -((value & 1) as i64));
+            }
+        }
+        None
+    }
+}
+
+fn string(buf: &mut Vec<u8>, value: &str) {
+    buf.extend_from_slice(&(value.len() as i16).to_be_bytes());
+    buf.extend_from_slice(value.as_bytes());
+}
+
+fn varint(buf: &mut Vec<u8>, value: i64) {
+    let mut value = ((value << 1) ^ (value >> 63)) as u64;
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn crc32c(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = match crc & 1 {
+                    1 => (crc >> 1) ^ 0x82f6_3b78,
+                    _ => crc >> 1,
+                };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+    !data.iter().fold(!0, |crc, byte| {
+        TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn i16s(buf: &mut Vec<u8>, values: &[i16]) {
+        for value in values {
+            buf.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+
+    fn i32s(buf: &mut Vec<u8>, values: &[i32]) {
+        for value in values {
+            buf.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+
+    /// A topic array holding one partition, as produce, list-offsets and fetch responses
+    /// start.
+    fn partition_response(buf: &mut Vec<u8>, partition: i32, error: i16) {
+        i32s(buf, &[1]);
+        string(buf, "load");
+        i32s(buf, &[1, partition]);
+        i16s(buf, &[error]);
+    }
+
+    #[test]
+    fn crc32c_matches_the_castagnoli_check_value() {
+        assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+        assert_eq!(crc32c(b""), 0);
+    }
+
+    #[test]
+    fn varints_round_trip_with_zigzag_encoding() {
+        let mut buf = Vec::new();
+        varint(&mut buf, -1);
+        assert_eq!(buf, [1]);
+        for value in [0, 1, -64, 300, i64::MAX, i64::MIN] {
+            let mut buf = Vec::new();
+            varint(&mut buf, value);
+            let mut reader = Reader(&buf);
+            assert_eq!(reader.varint(), Some(value));
+            assert!(reader.0.is_empty());
+        }
+        assert_eq!(Reader(&[0x80]).varint(), None);
+    }
+
+    #[test]
+    fn requests_carry_a_size_and_header() {
+        let req = request(METADATA, 4, 7, b"body");
+        let mut reader = Reader(&req);
+        assert_eq!(reader.i32(), Some(req.len() as i32 - 4));
+        assert_eq!((reader.i16(), reader.i16()), (Some(METADATA), Some(4)));
+        assert_eq!(reader.i32(), Some(7));
+        assert_eq!(reader.string(), Some(CLIENT_ID.as_bytes()));
+        assert_eq!(reader.0, b"body");
+    }
+
+    #[test]
+    fn record_batches_are_checksummed_and_read_back() {
+        let batch = record_batch(b"hello");
+        let crc = u32::from_be_bytes(batch[17..21].try_into().unwrap());
+        assert_eq!(crc, crc32c(&batch[21..]));
+
+        let mut records = batch.clone();
+        records.extend_from_slice(&record_batch(b"world"));
+        records.extend_from_slice(&batch[..20]);
+        let mut values = Vec::new();
+        let next = read_batches(&records, 0, |value| values.push(value.to_vec()));
+        assert_eq!(values, [b"hello".to_vec(), b"world".to_vec()]);
+        assert_eq!(next, 1);
+    }
+
+    #[test]
+    fn produce_and_offset_responses_are_parsed() {
+        let mut produce = Vec::new();
+        partition_response(&mut produce, 0, NOT_LEADER);
+        assert_eq!(produced(&produce), Some(NOT_LEADER));
+
+        let mut offsets = Vec::new();
+        partition_response(&mut offsets, 0, 0);
+        offsets.extend_from_slice(&(-1i64).to_be_bytes());
+        offsets.extend_from_slice(&42i64.to_be_bytes());
+        assert_eq!(latest_offset(&offsets), Some(42));
+        assert_eq!(latest_offset(&produce), None);
+    }
+
+    #[test]
+    fn fetch_responses_yield_their_record_bytes() {
+        let mut response = Vec::new();
+        i32s(&mut response, &[0]);
+        partition_response(&mut response, 0, 0);
+        response.extend_from_slice(&5i64.to_be_bytes());
+        response.extend_from_slice(&5i64.to_be_bytes());
+        i32s(&mut response, &[-1, 3]);
+        response.extend_from_slice(b"abc");
+        assert_eq!(fetched(&response), Some(&b"abc"[..]));
+    }
+
+    #[test]
+    fn metadata_finds_the_partition_leader() {
+        let mut response = Vec::new();
+        i32s(&mut response, &[0, 2]);
+        for (node, host) in [(1, "a"), (2, "b")] {
+            i32s(&mut response, &[node]);
+            string(&mut response, host);
+            i32s(&mut response, &[9092]);
+            i16s(&mut response, &[-1]);
+        }
+        i16s(&mut response, &[-1]);
+        i32s(&mut response, &[1, 1]);
+        i16s(&mut response, &[0]);
+        string(&mut response, "load");
+        response.push(0);
+        i32s(&mut response, &[2]);
+        for (index, leader) in [(0, 1), (1, 2)] {
+            i16s(&mut response, &[0]);
+            i32s(&mut response, &[index, leader, 1, leader, 0]);
+        }
+
+        assert_eq!(partition_leader(&response, 1), Some(("b".into(), 9092)));
+        assert_eq!(partition_leader(&response, 0), Some(("a".into(), 9092)));
+        assert_eq!(partition_leader(&response, 5), None);
+    }
+
+    #[test]
+    fn users_spread_over_partitions_and_topics_are_checked() {
+        let kafka = Kafka::new("load").partitions(3);
+        let partitions: Vec<_> = (0..5).map(|user| kafka.partition(user)).collect();
+        assert_eq!(partitions, [0, 1, 2, 0, 1]);
+        assert_eq!(Kafka::new("load").partitions(0).partitions, 1);
+
+        assert!(kafka.validate().is_ok());
+        assert!(Kafka::new("").validate().is_err());
+        assert!(Kafka::new("x".repeat(250)).validate().is_err());
+        assert_eq!(Acks::default().code(), -1);
+    }
+}
@@ -1,31 +1,252 @@
 use std::{
-    convert::Infallible,
+    collections::{BTreeMap, BTreeSet},
     error::Error,
-    iter,
-    net::ToSocketAddrs,
+    future::Future,
+    mem,
+    net::{IpAddr, SocketAddr},
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, OnceLock,
     },
     thread,
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime},
 };
 
-use futures::{stream::FuturesUnordered, StreamExt};
-use hyper::{client::HttpConnector, Body, Method, Request, Response, Uri};
+#[cfg(any(feature = "tls", feature = "native-tls"))]
+use std::path::PathBuf;
 
-pub use result::BenchmarkResult;
+use futures::{
+    stream::{FuturesUnordered, Stream},
+    StreamExt, TryStreamExt,
+};
+use hyper::{
+    body::Bytes,
+    header::{HeaderName, HeaderValue, CONNECTION},
+    Body, Method, Request, Response, Uri,
+};
+use tokio::io::AsyncRead;
+
+#[cfg(feature = "rhai")]
+pub use crate::rhai::RhaiScript;
+pub use accuracy::PercentileAccuracy;
+pub use annotation::{Annotation, Annotator};
+pub use breakdown::TimeBreakdown;
+pub use cache::CacheStatus;
+pub use calibrate::Calibration;
+pub use chaos::Chaos;
+pub use class::TrafficClass;
+pub use clock::ClockSource;
+pub use config::RunConfig;
+pub use curve::RateCurve;
+pub use dns::{DnsQuery, RecordType};
+pub use fairness::{Fairness, UserStats};
+pub use feed::Feed;
+pub use fleet::{Fleet, FleetResult};
+pub use graphql::{graphql_errors, graphql_query};
+#[cfg(feature = "grpc")]
+pub use grpc::{grpc_request, GrpcStreaming};
+pub use identity::IdentityProfile;
+#[cfg(feature = "kafka")]
+pub use kafka::{Acks, Kafka};
+pub use limits::Exhaustion;
+#[cfg(feature = "lua")]
+pub use lua::WrkScript;
+pub use matcher::{all_of, any_of, not, Matcher};
+pub use mix::RequestMix;
+#[cfg(feature = "mqtt")]
+pub use mqtt::{Mqtt, QoS};
+pub use operation::Mode;
+pub use proxy::{ProxyHeader, ProxyVersion};
+pub use random::RandomValue;
+pub use record::{RecordedBody, RecordedRequest, Recording, RecordingProxy, ReplaySpeed};
+pub use remote::SshCluster;
+pub use report::{NdjsonReporter, Reporter, Snapshot};
+pub use resolve::{AddressFamily, Resolve};
+#[cfg(feature = "resp")]
+pub use resp::Resp;
+pub use result::{BenchmarkResult, TcpFailure};
+#[cfg(feature = "s3")]
+pub use s3::S3;
+pub use scenario::{Client, Scenario};
+pub use seed::{CleanupReport, Seed};
+pub use session::TlsSession;
+pub use shared::{Counter, Gauge, Shared};
+pub use stage::{StageResult, Stages, StagesResult};
+pub use timeline::{Bucket, ErrorBurst, ErrorKind, Timeline};
+pub use transport::{Backend, Protocol, TlsBackend};
 pub use uri::UriExt;
+pub use user::{UserContext, VirtualUser};
+pub use warning::Warning;
+#[cfg(feature = "websocket")]
+pub use websocket::WebSocketReporter;
+#[cfg(feature = "websocket")]
+pub use ws::WebSocket;
+pub use zerg_macros::scenario;
+
+use accuracy::ExactSamples;
+use bind::BindPool;
+use budget::RequestBudget;
+use calibrate::NullServer;
+use cap::{CapClosed, ConnectionCap};
+use chaos::{Fault, Faults};
+use churn::Churn;
+use class::Class;
+use clock::Clock;
+#[cfg(feature = "tls")]
+use doh::{DohResolver, DohStats};
+use echo::{EchoTracker, EchoUser};
+use expect::{Continue, ContinueClient};
+use feed::FeedUser;
+use graphql::GraphqlUser;
+#[cfg(feature = "grpc")]
+use grpc::{GrpcStats, GrpcUser, Stream as GrpcStream};
+use identity::IdentifiedUser;
+#[cfg(feature = "lua")]
+use lua::WrkState;
+use metrics::{Metrics, Recorder};
+use operation::Operation;
+use panic::{PanicGuard, StartGate};
+use phase::Barriers;
+use pipeline::Pipeline;
+#[cfg(feature = "http3")]
+use quic::{QuicClient, QuicConfig};
+use random::RandomizedUser;
+use redact::Redactor;
+use redirect::{Followed, RedirectError};
+use resolve::{
+    http_client, lookup, AddressPool, Https, KeepAlive, Resolver, Sockets, SystemResolver,
+};
+use result::Outcome;
+use schedule::{Schedule, Slot};
+#[cfg(any(feature = "lua", feature = "rhai"))]
+use script::ScriptErrors;
+use session::TlsStats;
+#[cfg(any(feature = "tls", feature = "native-tls"))]
+use session::{ClientIdentity, TlsOptions};
+use transport::{Runtime, Transport};
+use tunnel::Tunnel;
+use upload::{MakeBody, UploadTracker};
+#[cfg(feature = "io-uring")]
+use uring::UringClient;
+use user::{ClosureUser, UserFactory};
 
+mod accuracy;
+mod annotation;
+mod archive;
+mod bind;
+mod breakdown;
+mod budget;
+mod cache;
+mod calibrate;
+mod cap;
+mod chaos;
+mod churn;
+mod class;
+mod clock;
+mod config;
+mod curve;
+mod dns;
+#[cfg(feature = "tls")]
+mod doh;
+mod echo;
+#[cfg(feature = "compression")]
+mod encoding;
+mod expect;
+mod fairness;
+mod feed;
+mod fleet;
+mod graphql;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod identity;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod limits;
+#[cfg(feature = "lua")]
+mod lua;
+mod matcher;
+mod metrics;
+mod mix;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+#[cfg(feature = "native-tls")]
+mod native;
+mod operation;
+mod panic;
+mod phase;
+mod pipeline;
+mod proxy;
+#[cfg(feature = "http3")]
+mod quic;
+mod random;
+mod record;
+mod redact;
+mod redirect;
+mod remote;
+mod report;
+mod resolve;
+#[cfg(feature = "resp")]
+mod resp;
 mod result;
+#[cfg(feature = "rhai")]
+mod rhai;
+#[cfg(feature = "s3")]
+mod s3;
+mod scenario;
+mod schedule;
+#[cfg(any(feature = "lua", feature = "rhai"))]
+mod script;
+mod seed;
+mod session;
+mod shared;
+mod sse;
+mod stage;
+mod timeline;
+#[cfg(feature = "tls")]
+mod tls;
+mod transport;
+mod tunnel;
+mod upload;
 mod uri;
+#[cfg(feature = "io-uring")]
+mod uring;
+mod user;
+mod warning;
+#[cfg(feature = "websocket")]
+mod websocket;
+mod wire;
+#[cfg(feature = "websocket")]
+mod ws;
 pub mod http {
-    pub use hyper::{header, Body, Method, Request, Response, StatusCode, Uri};
+    pub use hyper::{body::Bytes, header, Body, Method, Request, Response, StatusCode, Uri};
 }
+#[cfg(feature = "grpc")]
+pub use prost;
+#[cfg(feature = "tls")]
+pub use rustls;
 
-type MakeRequest = Arc<dyn Fn(&Uri) -> Request<Body> + Send + Sync + 'static>;
+type MakeRequest = Arc<dyn Fn(&Uri, &UserContext) -> Request<Body> + Send + Sync + 'static>;
 type Expectation = Arc<dyn Fn(Response<Body>) -> bool + Send + Sync + 'static>;
 
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+const CUSTOM_URI: &str = "custom://localhost/";
+const DEFAULT_DURATION: Duration = Duration::from_secs(1);
+
+fn report(
+    reporters: &mut [Box<dyn Reporter>],
+    metrics: &Metrics,
+    offset: Duration,
+    interval: Duration,
+) {
+    if let Some(result) = metrics.take_interval() {
+        let snapshot = Snapshot::new(offset, interval, &result, metrics.total_requests());
+        for reporter in reporters.iter_mut() {
+            reporter.report(&snapshot);
+        }
+    }
+}
+
 pub fn swarm<T>(uri: T) -> SwarmBuilder
 where
     Uri: TryFrom<T>,
@@ -36,11 +257,79 @@ where
 
 pub struct Swarm {
     uri: Uri,
-    duration: Duration,
+    addr: SocketAddr,
+    resolver: Resolver,
+    dns_refresh: Option<Duration>,
+    sockets: Sockets,
+    duration: Option<Duration>,
     threads: usize,
     concurrency: usize,
-    make_request: MakeRequest,
-    expectation_matcher: Expectation,
+    user_factory: UserFactory,
+    annotator: Annotator,
+    shared: Shared,
+    clock: Clock,
+    schedule: Option<Schedule>,
+    scenario: Option<Scenario>,
+    classes: Arc<[Class]>,
+    #[cfg(feature = "websocket")]
+    websocket: Option<WebSocket>,
+    #[cfg(feature = "mqtt")]
+    mqtt: Option<Mqtt>,
+    #[cfg(feature = "resp")]
+    resp: Option<Resp>,
+    #[cfg(feature = "kafka")]
+    kafka: Option<Kafka>,
+    #[cfg(feature = "s3")]
+    s3: Option<S3>,
+    backend: Backend,
+    pipeline: Option<usize>,
+    sse: bool,
+    follow_redirects: Option<usize>,
+    expect_continue: Option<usize>,
+    #[cfg(feature = "compression")]
+    decompress: bool,
+    protocol: Protocol,
+    connections_per_thread: usize,
+    mode: Mode,
+    operation: Option<Operation>,
+    tls_stats: TlsStats,
+    proxy: Option<ProxyHeader>,
+    tunnel: Option<Tunnel>,
+    keepalive: KeepAlive,
+    https: Option<Https>,
+    #[cfg(feature = "http3")]
+    quic: Option<QuicConfig>,
+    tenants: Option<Feed>,
+    seed: Option<Seed>,
+    region: Option<String>,
+    matcher: Option<Matcher>,
+    echo: Option<Arc<EchoTracker>>,
+    uploads: Option<Arc<UploadTracker>>,
+    #[cfg(feature = "tls")]
+    doh: Option<Arc<DohStats>>,
+    #[cfg(any(feature = "lua", feature = "rhai"))]
+    script_errors: Option<Arc<ScriptErrors>>,
+    #[cfg(feature = "lua")]
+    wrk: Option<Arc<WrkState>>,
+    faults: Option<Arc<Faults>>,
+    budget: Option<Arc<RequestBudget>>,
+    churn: Option<Arc<Churn>>,
+    template: Option<Arc<OnceLock<Request<()>>>>,
+    #[cfg(feature = "grpc")]
+    grpc: Option<Arc<GrpcStats>>,
+    #[cfg(feature = "grpc")]
+    grpc_streaming: Option<GrpcStreaming>,
+    buffered: bool,
+    redactor: Redactor,
+    config: RunConfig,
+    reporters: Vec<Box<dyn Reporter>>,
+    report_interval: Duration,
+    raise_fd_limit: bool,
+    timing_capacity: Option<usize>,
+    percentile_check: Option<Arc<ExactSamples>>,
+    latency_budgets: BTreeMap<String, Duration>,
+    counted_failures: BTreeSet<TcpFailure>,
+    abort_on_panic: bool,
 }
 
 impl Swarm {
@@ -48,123 +337,1084 @@ impl Swarm {
         SwarmBuilder::default()
     }
 
-    pub fn zerg(self) -> BenchmarkResult {
+    pub fn custom<F, Fut, E>(op: F) -> SwarmBuilder
+    where
+        F: Fn(&UserContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + 'static,
+        E: Into<Box<dyn Error + Send + Sync>>,
+    {
+        SwarmBuilder {
+            custom: Some(operation::custom(op)),
+            ..Swarm::builder().uri(CUSTOM_URI)
+        }
+    }
+
+    pub fn zerg(mut self) -> BenchmarkResult {
         let running = Arc::new(AtomicBool::new(false));
+        let fd_limit = match self.raise_fd_limit {
+            true => limits::raise_fd_limit().ok().or_else(limits::fd_limit),
+            false => limits::fd_limit(),
+        };
 
-        let host = self.uri.authority().map(|auth| auth.to_string()).unwrap();
-        let addr = host.to_socket_addrs().unwrap().next().unwrap();
+        let addr = self.addr;
+        let dns = self.resolver.clone();
 
-        let dns = tower::service_fn(move |_| async move { Ok::<_, Infallible>(iter::once(addr)) });
+        let seeded = self.seed.as_ref().map(|seed| {
+            seed.run(
+                &self.uri,
+                http_client(
+                    dns.clone(),
+                    self.protocol,
+                    self.proxy.clone(),
+                    self.tunnel.clone(),
+                    self.keepalive,
+                    self.https.clone(),
+                    self.sockets.uncapped(),
+                ),
+            )
+        });
+        let seeded_ids = seeded.as_ref().and_then(|seeded| seeded.ids.clone());
 
         let uri = Arc::new(self.uri);
+        let sequence = Arc::new(AtomicU64::new(0));
+        let epoch = Instant::now();
+        let started_at = SystemTime::now();
+        let deadline = self.duration.map(|duration| epoch + duration);
+        let session_deadline = deadline.unwrap_or(epoch);
+        self.annotator.start(epoch);
+
+        let users_per_thread = self.concurrency / self.threads;
+        let barriers = Barriers::new(users_per_thread * self.threads, deadline);
+        let start_gate = Arc::new(StartGate::new(self.threads + 1));
+        for reporter in self.reporters.iter_mut() {
+            reporter.start(&self.config);
+        }
+        let metrics = Metrics::default()
+            .with_timing_capacity(self.timing_capacity)
+            .with_budgets(mem::take(&mut self.latency_budgets));
+        let metrics = Arc::new(match self.reporters.is_empty() {
+            true => metrics,
+            false => metrics.with_intervals(),
+        });
+        let worker_died = Arc::new(AtomicBool::new(false));
 
-        let results = (0..self.threads)
-            .map(|_| {
+        let threads = (0..self.threads)
+            .map(|thread| {
                 let running = running.clone();
                 let uri = uri.clone();
-                let make_request = self.make_request.clone();
-                let expectation_matcher = self.expectation_matcher.clone();
+                let user_factory = self.user_factory.clone();
+                let sequence = sequence.clone();
+                let schedule = self.schedule.clone();
+                let scenario = self.scenario.clone();
+                let classes = self.classes.clone();
+                let faults = self.faults.clone();
+                let budget = self.budget.clone();
+                let churn = self.churn.clone();
+                let template = self.template.clone();
+                #[cfg(feature = "websocket")]
+                let websocket = self.websocket.clone();
+                #[cfg(feature = "mqtt")]
+                let mqtt = self.mqtt.clone();
+                #[cfg(feature = "resp")]
+                let resp = self.resp.clone();
+                #[cfg(feature = "kafka")]
+                let kafka = self.kafka.clone();
+                #[cfg(feature = "s3")]
+                let s3 = self.s3.clone();
+                let barriers = barriers.clone();
+                let shared = self.shared.clone();
+                let clock = self.clock.clone();
+                let dns = dns.clone();
+                let backend = self.backend;
+                let pipeline = self.pipeline;
+                let sse = self.sse;
+                let follow_redirects = self.follow_redirects;
+                let expect_continue = self.expect_continue;
+                #[cfg(feature = "compression")]
+                let decompress = self.decompress;
+                #[cfg(feature = "grpc")]
+                let grpc_streaming = self.grpc_streaming.zip(self.grpc.clone());
+                let protocol = self.protocol;
+                let connections = self.connections_per_thread;
+                let operation = self.operation.clone();
+                let proxy = self.proxy.clone();
+                let tunnel = self.tunnel.clone();
+                let keepalive = self.keepalive;
+                let buffered = self.buffered;
+                let https = self.https.clone();
+                let sockets = self.sockets.clone();
+                #[cfg(feature = "http3")]
+                let quic = self.quic.clone();
+                let tenants = self.tenants.clone();
+                let seeded_ids = seeded_ids.clone();
+                let start_gate = start_gate.clone();
+                let metrics = metrics.clone();
+                let exact = self
+                    .percentile_check
+                    .clone()
+                    .filter(|check| check.retains(thread));
+                let worker_died = worker_died.clone();
 
                 std::thread::spawn(move || {
-                    let runtime = tokio::runtime::Builder::new_current_thread()
-                        .enable_all()
-                        .build()
-                        .unwrap();
+                    let mut guard = PanicGuard::new(worker_died, start_gate);
+                    let runtime = Runtime::new(backend);
+
+                    let recorder = Recorder::new(metrics, exact);
+                    let connect = {
+                        let proxy = proxy.clone();
+                        move || {
+                            http_client(
+                                dns.clone(),
+                                protocol,
+                                proxy.clone(),
+                                tunnel.clone(),
+                                keepalive,
+                                https.clone(),
+                                sockets.clone(),
+                            )
+                        }
+                    };
+                    let clients = (0..connections).map(|_| connect()).collect::<Vec<_>>();
 
-                    let results = (0..self.concurrency / self.threads).map(|_| {
+                    let users = (0..users_per_thread).map(|i| {
                         let uri = uri.clone();
                         let running = running.clone();
-                        let make_request = make_request.clone();
-                        let expectation_matcher = expectation_matcher.clone();
+                        let user_factory = user_factory.clone();
+                        let sequence = sequence.clone();
+                        let schedule = schedule.clone();
+                        let scenario = scenario.clone();
+                        let classes = classes.clone();
+                        let faults = faults.clone();
+                        let budget = budget.clone();
+                        let churn = churn.clone();
+                        let template = template.clone();
+                        let connect = connect.clone();
+                        #[cfg(feature = "websocket")]
+                        let websocket = websocket.clone();
+                        #[cfg(feature = "mqtt")]
+                        let mqtt = mqtt.clone();
+                        #[cfg(feature = "resp")]
+                        let resp = resp.clone();
+                        #[cfg(feature = "kafka")]
+                        let kafka = kafka.clone();
+                        #[cfg(feature = "s3")]
+                        let s3 = s3.clone();
+                        let barriers = barriers.clone();
+                        let shared = shared.clone();
+                        let clock = clock.clone();
+                        let recorder = recorder.clone();
+                        let operation = operation.clone();
+                        let proxy = proxy.clone();
+                        let tenants = tenants.clone();
+                        let seeded_ids = seeded_ids.clone();
+                        #[cfg(feature = "http3")]
+                        let quic = quic.clone();
+                        #[cfg(feature = "grpc")]
+                        let grpc_streaming = grpc_streaming.clone();
+
+                        let http = clients[i % connections].clone();
 
                         async move {
-                            let mut http_connector = HttpConnector::new_with_resolver(dns);
-                            http_connector.set_nodelay(true);
+                            let mut ctx = UserContext::new(
+                                thread * users_per_thread + i,
+                                epoch,
+                                self.duration,
+                                budget.clone(),
+                                shared,
+                            );
+                            ctx.tenant = tenants.as_ref().and_then(Feed::next);
+                            ctx.seeded = seeded_ids;
+                            let class = classes
+                                .iter()
+                                .find(|class| class.users.contains(&ctx.id))
+                                .cloned();
+                            ctx.class = class.as_ref().map(|class| class.name.clone());
+                            let schedule = match &class {
+                                Some(class) => class.schedule.clone(),
+                                None => schedule,
+                            };
+                            let recorder = recorder.for_user(ctx.id, ctx.tenant.clone());
+
+                            if let Some(scenario) = scenario {
+                                let client = Client::new(
+                                    http, uri, &scenario, ctx, barriers, clock, recorder,
+                                );
+                                while running.load(Ordering::Relaxed) {
+                                    client.begin_iteration();
+                                    scenario.run(client.clone()).await;
+                                }
+                                return;
+                            }
+
+                            #[cfg(feature = "websocket")]
+                            if let Some(websocket) = websocket {
+                                let session = ws::Session {
+                                    addr,
+                                    epoch,
+                                    deadline: session_deadline,
+                                    clock,
+                                    recorder,
+                                };
+                                session
+                                    .run(&uri, &websocket, &mut ctx, &running, &sequence)
+                                    .await;
+                                return;
+                            }
+
+                            #[cfg(feature = "mqtt")]
+                            if let Some(mqtt) = mqtt {
+                                let session = mqtt::Session {
+                                    addr,
+                                    epoch,
+                                    deadline: session_deadline,
+                                    clock,
+                                    recorder,
+                                };
+                                session.run(&mqtt, &mut ctx, &running, &sequence).await;
+                                return;
+                            }
+
+                            #[cfg(feature = "resp")]
+                            if let Some(resp) = resp {
+                                let session = resp::Session {
+                                    addr,
+                                    epoch,
+                                    deadline: session_deadline,
+                                    clock,
+                                    recorder,
+                                    schedule,
+                                };
+                                session.run(&resp, &mut ctx, &running, &sequence).await;
+                                return;
+                            }
+
+                            #[cfg(feature = "kafka")]
+                            if let Some(kafka) = kafka {
+                                let session = kafka::Session {
+                                    addr,
+                                    epoch,
+                                    deadline: session_deadline,
+                                    clock,
+                                    recorder,
+                                    schedule,
+                                };
+                                session.run(&kafka, &mut ctx, &running, &sequence).await;
+                                return;
+                            }
+
+                            #[cfg(feature = "s3")]
+                            if let Some(s3) = s3 {
+                                let session = s3::Session {
+                                    http,
+                                    uri,
+                                    epoch,
+                                    deadline: session_deadline,
+                                    clock,
+                                    recorder,
+                                    schedule,
+                                };
+                                session.run(&s3, &mut ctx, &running, &sequence).await;
+                                return;
+                            }
+
+                            if let Some(operation) = operation {
+                                while running.load(Ordering::Relaxed) {
+                                    let ticket = schedule
+                                        .as_ref()
+                                        .map(|schedule| schedule.claim(&sequence, epoch));
+                                    ctx.sequence = match &ticket {
+                                        Some(ticket) => ticket.n,
+                                        None => sequence.fetch_add(1, Ordering::Relaxed),
+                                    };
+                                    let mut late = Duration::ZERO;
+                                    if let (Some(schedule), Some(ticket)) = (&schedule, &ticket) {
+                                        match schedule
+                                            .wait(ticket, epoch, deadline, &recorder)
+                                            .await
+                                        {
+                                            Slot::Due(lateness) => late = lateness,
+                                            Slot::Taken => continue,
+                                            Slot::Closed => break,
+                                        }
+                                        if !running.load(Ordering::Relaxed) {
+                                            break;
+                                        }
+                                    }
+
+                                    let start = Instant::now();
+                                    let timer = clock.now();
+                                    let outcome = operation(&ctx).await;
+                                    recorder.record(
+                                        start.saturating_duration_since(epoch),
+                                        clock.elapsed(timer) + late,
+                                        outcome,
+                                    );
+                                    ctx.iteration += 1;
+                                }
+                                return;
+                            }
+
+                            let mut user = (user_factory)();
+                            user.on_start(&ctx);
+
+                            if let Some(depth) = pipeline {
+                                let pipeline = Pipeline {
+                                    addr,
+                                    depth,
+                                    epoch,
+                                    deadline: session_deadline,
+                                    clock,
+                                    recorder,
+                                    proxy,
+                                };
+                                pipeline
+                                    .run(&uri, &mut *user, &mut ctx, &running, &sequence)
+                                    .await;
+                                user.on_stop(&ctx);
+                                return;
+                            }
+
+                            #[cfg(feature = "grpc")]
+                            if let Some((streaming, stats)) = grpc_streaming {
+                                let stream = GrpcStream {
+                                    http,
+                                    streaming,
+                                    stats,
+                                    epoch,
+                                    deadline: session_deadline,
+                                    clock,
+                                    recorder,
+                                };
+                                stream
+                                    .run(&uri, &mut *user, &mut ctx, &running, &sequence)
+                                    .await;
+                                user.on_stop(&ctx);
+                                return;
+                            }
 
-                            let http: hyper::Client<_, hyper::Body> =
-                                hyper::Client::builder().build(http_connector);
+                            if sse {
+                                let session = sse::Session {
+                                    http,
+                                    epoch,
+                                    deadline: session_deadline,
+                                    clock,
+                                    recorder,
+                                };
+                                session
+                                    .run(&uri, &mut *user, &mut ctx, &running, &sequence)
+                                    .await;
+                                user.on_stop(&ctx);
+                                return;
+                            }
 
-                            let mut result = BenchmarkResult::default();
+                            let mut transport = match backend {
+                                Backend::Tokio if buffered => Transport::Buffered(http),
+                                Backend::Tokio => Transport::Hyper(http),
+                                #[cfg(feature = "io-uring")]
+                                Backend::IoUring => Transport::Uring(UringClient::new(addr)),
+                            };
+                            #[cfg(feature = "http3")]
+                            if let Some(quic) = quic {
+                                transport = Transport::Quic(QuicClient::new(addr, quic));
+                            }
+                            if let Some(min_body) = expect_continue {
+                                transport =
+                                    Transport::Continue(ContinueClient::new(addr, min_body));
+                            }
 
+                            let mut reconnecting = false;
+                            let started = Instant::now();
+                            let mut spent = TimeBreakdown::default();
                             while running.load(Ordering::Relaxed) {
+                                let ticket = schedule.as_ref().map(|schedule| match &class {
+                                    Some(class) => schedule.claim(&class.sequence, epoch),
+                                    None => schedule.claim(&sequence, epoch),
+                                });
+                                ctx.sequence = match (&ticket, &class) {
+                                    (Some(ticket), None) => ticket.n,
+                                    _ => sequence.fetch_add(1, Ordering::Relaxed),
+                                };
+
+                                let mut late = Duration::ZERO;
+                                if let (Some(schedule), Some(ticket)) = (&schedule, &ticket) {
+                                    let waiting = Instant::now();
+                                    let slot =
+                                        schedule.wait(ticket, epoch, deadline, &recorder).await;
+                                    spent.rate_limited += waiting.elapsed();
+                                    match slot {
+                                        Slot::Due(lateness) => late = lateness,
+                                        Slot::Taken => continue,
+                                        Slot::Closed => break,
+                                    }
+                                    if !running.load(Ordering::Relaxed) {
+                                        break;
+                                    }
+                                }
+                                if budget.as_ref().is_some_and(|budget| !budget.take()) {
+                                    break;
+                                }
+
+                                let mut req = match user.next_request(&uri, &ctx) {
+                                    Some(req) => req,
+                                    None => {
+                                        if let Some(budget) = &budget {
+                                            budget.refund();
+                                        }
+                                        break;
+                                    }
+                                };
+                                #[cfg(any(feature = "lua", feature = "rhai"))]
+                                if script::failed(&req) {
+                                    recorder.record_failed(epoch.elapsed());
+                                    ctx.iteration += 1;
+                                    tokio::task::yield_now().await;
+                                    continue;
+                                }
+                                if let Some(template) = template
+                                    .as_ref()
+                                    .filter(|template| template.get().is_none())
+                                {
+                                    let mut head = Request::new(());
+                                    *head.method_mut() = req.method().clone();
+                                    *head.headers_mut() = req.headers().clone();
+                                    let _ = template.set(head);
+                                }
+                                let label = req.extensions_mut().remove::<mix::Label>();
+                                let closing = churn
+                                    .as_ref()
+                                    .is_some_and(|churn| churn.due(epoch.elapsed()));
+                                if closing {
+                                    req.headers_mut()
+                                        .insert(CONNECTION, HeaderValue::from_static("close"));
+                                }
+                                if let Some(faults) = &faults {
+                                    match faults.roll() {
+                                        Some(Fault::Drop) => {
+                                            faults.record(Fault::Drop);
+                                            if let Some(budget) = &budget {
+                                                budget.refund();
+                                            }
+                                            ctx.iteration += 1;
+                                            tokio::task::yield_now().await;
+                                            continue;
+                                        }
+                                        Some(Fault::Abort) => {
+                                            let req = chaos::truncate(req).await;
+                                            let _ = transport.request(req).await;
+                                            faults.record(Fault::Abort);
+                                            if let Some(budget) = &budget {
+                                                budget.refund();
+                                            }
+                                            ctx.iteration += 1;
+                                            continue;
+                                        }
+                                        None => {}
+                                    }
+                                }
+                                #[cfg(feature = "compression")]
+                                if decompress {
+                                    encoding::accept(&mut req);
+                                }
+                                let origin = match follow_redirects {
+                                    Some(_) => Some(redirect::Origin::of(&mut req).await),
+                                    None => None,
+                                };
                                 let start = Instant::now();
-                                let req = (make_request)(&uri);
-                                match http.request(req).await {
+                                let timer = clock.now();
+                                let ((res, followed), connects) =
+                                    breakdown::measure_connecting(async {
+                                        match (origin, follow_redirects) {
+                                            (Some(Ok(origin)), Some(max_hops)) => {
+                                                let res = transport.request(req).await;
+                                                redirect::follow(
+                                                    &mut transport,
+                                                    res,
+                                                    origin,
+                                                    max_hops,
+                                                )
+                                                .await
+                                            }
+                                            (Some(Err(err)), _) => (Err(err), Followed::default()),
+                                            _ => {
+                                                (transport.request(req).await, Followed::default())
+                                            }
+                                        }
+                                    })
+                                    .await;
+                                let responded = Instant::now();
+                                #[cfg(feature = "compression")]
+                                let res = match res {
+                                    Ok(res) if decompress => {
+                                        encoding::decode(res).await.map(|(res, decoded)| {
+                                            if let Some(decoded) = decoded {
+                                                recorder.record_decoded(
+                                                    start.saturating_duration_since(epoch),
+                                                    decoded,
+                                                );
+                                            }
+                                            res
+                                        })
+                                    }
+                                    res => res,
+                                };
+                                spent.connecting += connects.elapsed;
+                                spent.waiting +=
+                                    (responded - start).saturating_sub(connects.elapsed);
+                                let interim = res
+                                    .as_ref()
+                                    .ok()
+                                    .and_then(|res| res.extensions().get::<Continue>().copied());
+                                let (outcome, cache) = match res {
                                     Ok(res) => {
-                                        if (expectation_matcher)(res) {
-                                            result.success += 1;
-                                        } else {
-                                            result.http_error += 1;
+                                        let cache = CacheStatus::of(res.headers());
+                                        match user.on_response(res, &ctx) {
+                                            true => (Outcome::Success, cache),
+                                            false => (Outcome::HttpError, cache),
                                         }
                                     }
-                                    Err(_) => result.tcp_error += 1,
+                                    #[cfg(feature = "compression")]
+                                    Err(err) if err.is::<encoding::CorruptBody>() => {
+                                        (Outcome::HttpError, None)
+                                    }
+                                    Err(err) if err.is::<RedirectError>() => {
+                                        (Outcome::HttpError, None)
+                                    }
+                                    Err(err) if CapClosed::caused(&*err) => break,
+                                    Err(err) => (Outcome::from_error(&*err), None),
+                                };
+                                spent.reading += responded.elapsed();
+                                let offset = start.saturating_duration_since(epoch);
+                                let elapsed = clock.elapsed(timer) + late;
+                                match (&label, &class) {
+                                    (Some(mix::Label(name)), _)
+                                    | (None, Some(Class { name, .. })) => recorder
+                                        .record_labeled(name, None, offset, elapsed, outcome),
+                                    (None, None) => recorder.record(offset, elapsed, outcome),
+                                }
+                                recorder.record_cache(cache, offset, elapsed, outcome);
+                                if connects.opened > 0 {
+                                    recorder.record_opened(offset, connects.opened);
+                                }
+                                if followed.hops > 0 || followed.looped {
+                                    recorder.record_redirects(offset, followed);
                                 }
-                                let elapsed = start.elapsed();
-                                result.elapsed = elapsed;
-                                result.timings.push(elapsed);
-                                result.min_time = result.min_time.min(elapsed);
-                                result.max_time = result.max_time.max(elapsed);
+                                if let Some(interim) = interim {
+                                    recorder.record_continue(offset, interim);
+                                }
+                                if reconnecting {
+                                    recorder.record_connect(churn::LABEL, offset, elapsed, outcome);
+                                }
+                                if closing {
+                                    transport.reconnect(&connect);
+                                }
+                                reconnecting = closing;
+                                ctx.iteration += 1;
                             }
 
-                            result
+                            spent.total = started.elapsed();
+                            recorder.record_time(epoch.elapsed(), spent);
+                            user.on_stop(&ctx);
                         }
                     });
 
-                    let results = FuturesUnordered::from_iter(results).collect::<Vec<_>>();
-                    let results = runtime.block_on(results);
-                    results.into_iter().sum()
+                    let users = FuturesUnordered::from_iter(users).collect::<()>();
+                    guard.start();
+                    runtime.block_on(users);
+                    recorder.flush();
                 })
             })
             .collect::<Vec<thread::JoinHandle<_>>>();
 
         running.store(true, Ordering::Relaxed);
+        start_gate.wait();
         let start = Instant::now();
-        thread::sleep(self.duration);
+        let cpu_started = limits::cpu_time();
+        let mut reporters = self.reporters;
+        let mut last_report = Duration::ZERO;
+        let mut last_refresh = Duration::ZERO;
+        loop {
+            let now = start.elapsed();
+            if self.duration.is_some_and(|duration| now >= duration)
+                || (self.abort_on_panic && worker_died.load(Ordering::Relaxed))
+                || threads.iter().all(|thread| thread.is_finished())
+            {
+                break;
+            }
+            let mut wake = now + POLL_INTERVAL;
+            if let Some(duration) = self.duration {
+                wake = wake.min(duration);
+            }
+            if !reporters.is_empty() {
+                wake = wake.min(last_report + self.report_interval);
+            }
+            if let Some(interval) = self.dns_refresh {
+                wake = wake.min(last_refresh + interval);
+            }
+            thread::sleep(wake.saturating_sub(now));
+
+            if let (Some(interval), Resolver::Rotating(pool)) = (self.dns_refresh, &dns) {
+                if start.elapsed() >= last_refresh + interval {
+                    pool.refresh();
+                    last_refresh = start.elapsed();
+                }
+            }
+
+            let next_report = last_report + self.report_interval;
+            if !reporters.is_empty() && start.elapsed() >= next_report {
+                report(&mut reporters, &metrics, next_report, self.report_interval);
+                last_report = next_report;
+            }
+        }
         running.store(false, Ordering::Relaxed);
+        if let Some(cap) = &self.sockets.cap {
+            cap.close();
+        }
         let elapsed = start.elapsed();
 
-        let mut results = results
+        if !reporters.is_empty() && elapsed > last_report {
+            report(&mut reporters, &metrics, elapsed, elapsed - last_report);
+        }
+
+        let panics = threads
             .into_iter()
-            .filter_map(|t| match t.join() {
-                Ok(results) => Some(results),
-                _ => None,
-            })
-            .sum::<BenchmarkResult>();
+            .filter_map(|t| t.join().err())
+            .map(|payload| panic::message(&*payload))
+            .collect::<Vec<_>>();
+        let cores = thread::available_parallelism().map_or(1, |cores| cores.get());
+        let client_cpu = cpu_started.zip(limits::cpu_time()).map(|(started, now)| {
+            now.saturating_sub(started).as_secs_f64()
+                / elapsed.as_secs_f64()
+                / self.threads.min(cores) as f64
+        });
+
+        let cleanup = match (&self.seed, &seeded_ids) {
+            (Some(seed), Some(ids)) => seed.verify(
+                &uri,
+                http_client(
+                    dns,
+                    self.protocol,
+                    self.proxy.clone(),
+                    self.tunnel.clone(),
+                    self.keepalive,
+                    self.https.clone(),
+                    self.sockets.uncapped(),
+                ),
+                ids,
+            ),
+            _ => None,
+        };
+
+        let mut results = metrics.take();
+        results.cleanup = cleanup;
+        results.worker_panics = panics.len();
+        results.first_panic = panics
+            .into_iter()
+            .next()
+            .map(|panic| self.redactor.text(&panic));
+        results.annotations = self.annotator.annotations();
+        results.redact(&self.redactor);
 
-        results.elapsed = elapsed;
+        results.set_elapsed(elapsed);
+        results.started_at = Some(started_at);
+        results.fd_limit = fd_limit;
+        results.client_cpu = client_cpu;
+        results.percentile_accuracy = self
+            .percentile_check
+            .as_ref()
+            .and_then(|check| check.accuracy());
+        results.seed = seeded.map(|seeded| (seeded.created, seeded.failed, seeded.elapsed));
+        results.counters = self.shared.counters();
+        results.clock = Some((self.clock.source(), self.clock.overhead()));
+        results.pipeline = self.pipeline;
+        results.mode = self.mode;
+        results.config = Some(match self.template.as_ref().and_then(|t| t.get()) {
+            Some(template) => self.config.with_request(template, &self.redactor),
+            None => self.config,
+        });
+        #[cfg(feature = "grpc")]
+        if let Some(grpc) = &self.grpc {
+            results.grpc_statuses = grpc.counts().collect();
+            results.grpc_messages = grpc.messages();
+        }
+        if let Some(echo) = &self.echo {
+            (
+                results.echo_checked,
+                results.echo_duplicates,
+                results.echo_mismatches,
+            ) = echo.counts();
+        }
+        if let Some(faults) = &self.faults {
+            (results.chaos_dropped, results.chaos_aborted) = faults.counts();
+        }
+        if let Some(cap) = &self.sockets.cap {
+            let (peak, queued, waited) = cap.counts();
+            results.connection_cap = Some(cap.max());
+            results.peak_connections = peak;
+            results.queued_connects = queued;
+            results.connect_queue_time = waited;
+        }
+        if let Some(uploads) = &self.uploads {
+            results.uploaded_bytes = uploads.bytes();
+        }
+        #[cfg(any(feature = "lua", feature = "rhai"))]
+        if let Some(errors) = &self.script_errors {
+            results.script_errors = errors.count();
+        }
+        #[cfg(feature = "tls")]
+        if let Some(doh) = &self.doh {
+            (results.doh_lookups, results.doh_failures, results.doh_time) = doh.counts();
+        }
+        #[cfg(feature = "s3")]
+        if let Some(s3) = &self.s3 {
+            results.uploaded_bytes = s3.uploaded();
+            results.downloaded_bytes = s3.downloaded();
+        }
+        if let Some(matcher) = &self.matcher {
+            for (name, failures) in matcher.failures() {
+                *results.expectations.entry(name).or_default() += failures;
+            }
+        }
+        (
+            results.tls_sessions,
+            results.tls_resumed,
+            results.negotiated_protocols,
+        ) = self.tls_stats.take();
+        if !results.negotiated_protocols.is_empty() {
+            results.requested_protocol = Some(self.protocol.alpn().to_string());
+        }
+        results.gauges = self.shared.gauges();
+        results.count_failures(&self.counted_failures);
+        let results = match self.region {
+            Some(region) => results.with_region(region),
+            None => results,
+        };
+        #[cfg(feature = "lua")]
+        let results = match self.wrk.map(|wrk| wrk.done(&results)) {
+            Some(Err(_)) => BenchmarkResult {
+                script_errors: results.script_errors + 1,
+                ..results
+            },
+            _ => results,
+        };
+        for reporter in reporters.iter_mut() {
+            reporter.finish(&results);
+        }
         results
     }
 }
 
 pub struct SwarmBuilder {
     uri: Result<Uri, Box<dyn Error + Send + Sync>>,
-    duration: Duration,
+    duration: Option<Duration>,
+    requests: Option<u64>,
     threads: usize,
     concurrency: usize,
     make_request: MakeRequest,
+    custom_request: bool,
+    body: Option<Bytes>,
+    body_stream: Option<MakeBody>,
     expectation_matcher: Expectation,
+    annotator: Annotator,
+    shared: Shared,
+    clock_source: ClockSource,
+    calibrate_clock: bool,
+    recording: Option<Arc<Recording>>,
+    replay_speed: ReplaySpeed,
+    scenario: Option<Scenario>,
+    classes: Vec<TrafficClass>,
+    mix: Option<RequestMix>,
+    #[cfg(feature = "websocket")]
+    websocket: Option<WebSocket>,
+    #[cfg(feature = "mqtt")]
+    mqtt: Option<Mqtt>,
+    #[cfg(feature = "resp")]
+    resp: Option<Resp>,
+    #[cfg(feature = "kafka")]
+    kafka: Option<Kafka>,
+    #[cfg(feature = "s3")]
+    s3: Option<S3>,
+    virtual_user: Option<UserFactory>,
+    custom: Option<Operation>,
+    backend: Backend,
+    pipeline: Option<usize>,
+    sse: bool,
+    follow_redirects: Option<usize>,
+    expect_continue: Option<usize>,
+    #[cfg(feature = "compression")]
+    decompress: bool,
+    protocol: Protocol,
+    prior_knowledge: bool,
+    streams_per_connection: usize,
+    max_streams: Option<usize>,
+    connections: Option<usize>,
+    resolver: Option<Arc<dyn Resolve>>,
+    connect_to: Option<SocketAddr>,
+    #[cfg(feature = "tls")]
+    doh: Option<Result<Uri, Box<dyn Error + Send + Sync>>>,
+    address_family: AddressFamily,
+    rotate_dns: bool,
+    dns_refresh: Option<Duration>,
+    bind_addresses: Option<Vec<IpAddr>>,
+    max_connections: Option<usize>,
+    mode: Mode,
+    rate: Option<f64>,
+    burst: Option<u64>,
+    rate_curve: Option<RateCurve>,
+    forward_proxy: Option<Result<Uri, Box<dyn Error + Send + Sync>>>,
+    proxy: Option<ProxyHeader>,
+    keepalive: KeepAlive,
+    random_headers: Vec<(String, RandomValue)>,
+    identity_profile: Option<IdentityProfile>,
+    echo_header: Option<String>,
+    chaos: Option<Chaos>,
+    churn: Option<f64>,
+    #[cfg(feature = "grpc")]
+    grpc: bool,
+    #[cfg(feature = "grpc")]
+    grpc_streaming: Option<GrpcStreaming>,
+    graphql: bool,
+    tenants: Option<Feed>,
+    feed: Option<Feed>,
+    seed: Option<Seed>,
+    region: Option<String>,
+    matcher: Option<Matcher>,
+    redactor: Redactor,
+    reporters: Vec<Box<dyn Reporter>>,
+    report_interval: Duration,
+    raise_fd_limit: bool,
+    timing_capacity: Option<usize>,
+    percentile_check: Option<usize>,
+    latency_budgets: BTreeMap<String, Duration>,
+    counted_failures: BTreeSet<TcpFailure>,
+    abort_on_panic: bool,
+    #[cfg(any(feature = "tls", feature = "native-tls"))]
+    tls: TlsOptions,
+    #[cfg(any(feature = "tls", feature = "native-tls"))]
+    tls_backend: TlsBackend,
+    #[cfg(feature = "lua")]
+    wrk_script: Option<WrkScript>,
+    #[cfg(feature = "rhai")]
+    rhai_script: Option<RhaiScript>,
+}
+
+/// Swarms that replace the HTTP request loop with their own, so none of its options apply and
+/// at most one of them can be set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Exclusive {
+    #[cfg(feature = "websocket")]
+    WebSocket,
+    #[cfg(feature = "mqtt")]
+    Mqtt,
+    Custom,
+    #[cfg(feature = "resp")]
+    Resp,
+    #[cfg(feature = "kafka")]
+    Kafka,
+    #[cfg(feature = "s3")]
+    S3,
+}
+
+impl Exclusive {
+    /// Speaks its own wire protocol, so HTTP protocol settings make no sense.
+    fn own_protocol(self) -> bool {
+        match self {
+            #[cfg(feature = "websocket")]
+            Self::WebSocket => true,
+            #[cfg(feature = "mqtt")]
+            Self::Mqtt => true,
+            Self::Custom => false,
+            #[cfg(feature = "resp")]
+            Self::Resp => true,
+            #[cfg(feature = "kafka")]
+            Self::Kafka => true,
+            #[cfg(feature = "s3")]
+            Self::S3 => false,
+        }
+    }
+
+    /// Connects through the configured HTTP proxy.
+    fn proxied(self) -> bool {
+        match self {
+            #[cfg(feature = "websocket")]
+            Self::WebSocket => true,
+            #[cfg(feature = "s3")]
+            Self::S3 => true,
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Exclusive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            #[cfg(feature = "websocket")]
+            Self::WebSocket => "WebSocket",
+            #[cfg(feature = "mqtt")]
+            Self::Mqtt => "MQTT",
+            Self::Custom => "custom operation",
+            #[cfg(feature = "resp")]
+            Self::Resp => "RESP",
+            #[cfg(feature = "kafka")]
+            Self::Kafka => "Kafka",
+            #[cfg(feature = "s3")]
+            Self::S3 => "S3",
+        })
+    }
 }
 
 impl Default for SwarmBuilder {
     fn default() -> Self {
         Self {
             uri: Err("missing uri".into()),
-            duration: Duration::from_secs(1),
+            duration: None,
+            requests: None,
             threads: 1,
             concurrency: 100,
-            make_request: Arc::new(|uri| {
+            make_request: Arc::new(|uri, _| {
                 Request::builder()
                     .uri(uri)
                     .method(Method::GET)
                     .body(Body::empty())
                     .unwrap()
             }),
+            custom_request: false,
+            body: None,
+            body_stream: None,
             expectation_matcher: Arc::new(|res| res.status().is_success()),
+            annotator: Annotator::default(),
+            shared: Shared::default(),
+            clock_source: ClockSource::default(),
+            calibrate_clock: false,
+            recording: None,
+            replay_speed: ReplaySpeed::Max,
+            scenario: None,
+            classes: Vec::new(),
+            mix: None,
+            #[cfg(feature = "websocket")]
+            websocket: None,
+            #[cfg(feature = "mqtt")]
+            mqtt: None,
+            #[cfg(feature = "resp")]
+            resp: None,
+            #[cfg(feature = "kafka")]
+            kafka: None,
+            #[cfg(feature = "s3")]
+            s3: None,
+            virtual_user: None,
+            custom: None,
+            backend: Backend::default(),
+            pipeline: None,
+            sse: false,
+            follow_redirects: None,
+            expect_continue: None,
+            #[cfg(feature = "compression")]
+            decompress: false,
+            protocol: Protocol::default(),
+            prior_knowledge: false,
+            streams_per_connection: 1,
+            max_streams: None,
+            connections: None,
+            resolver: None,
+            connect_to: None,
+            #[cfg(feature = "tls")]
+            doh: None,
+            address_family: AddressFamily::Any,
+            rotate_dns: false,
+            dns_refresh: None,
+            bind_addresses: None,
+            max_connections: None,
+            mode: Mode::default(),
+            rate: None,
+            burst: None,
+            rate_curve: None,
+            proxy: None,
+            forward_proxy: None,
+            keepalive: KeepAlive::default(),
+            random_headers: Vec::new(),
+            identity_profile: None,
+            echo_header: None,
+            chaos: None,
+            churn: None,
+            #[cfg(feature = "grpc")]
+            grpc: false,
+            #[cfg(feature = "grpc")]
+            grpc_streaming: None,
+            graphql: false,
+            tenants: None,
+            feed: None,
+            seed: None,
+            region: None,
+            matcher: None,
+            redactor: Redactor::default(),
+            reporters: Vec::new(),
+            report_interval: REPORT_INTERVAL,
+            raise_fd_limit: false,
+            timing_capacity: None,
+            percentile_check: None,
+            latency_budgets: BTreeMap::new(),
+            abort_on_panic: false,
+            counted_failures: BTreeSet::new(),
+            #[cfg(any(feature = "tls", feature = "native-tls"))]
+            tls: TlsOptions::default(),
+            #[cfg(any(feature = "tls", feature = "native-tls"))]
+            tls_backend: TlsBackend::default(),
+            #[cfg(feature = "lua")]
+            wrk_script: None,
+            #[cfg(feature = "rhai")]
+            rhai_script: None,
         }
     }
 }
 
 impl SwarmBuilder {
+    fn exclusive_modes(&self) -> Vec<Exclusive> {
+        let mut modes = Vec::new();
+        #[cfg(feature = "websocket")]
+        if self.websocket.is_some() {
+            modes.push(Exclusive::WebSocket);
+        }
+        #[cfg(feature = "mqtt")]
+        if self.mqtt.is_some() {
+            modes.push(Exclusive::Mqtt);
+        }
+        if self.custom.is_some() {
+            modes.push(Exclusive::Custom);
+        }
+        #[cfg(feature = "resp")]
+        if self.resp.is_some() {
+            modes.push(Exclusive::Resp);
+        }
+        #[cfg(feature = "kafka")]
+        if self.kafka.is_some() {
+            modes.push(Exclusive::Kafka);
+        }
+        #[cfg(feature = "s3")]
+        if self.s3.is_some() {
+            modes.push(Exclusive::S3);
+        }
+        modes
+    }
+
+    /// Whether anything configures the HTTP request loop that an exclusive swarm replaces.
+    fn shapes_http_loop(&self) -> bool {
+        let grpc = false;
+        #[cfg(feature = "grpc")]
+        let grpc = grpc || self.grpc;
+        self.mode != Mode::Http
+            || self.scenario.is_some()
+            || self.virtual_user.is_some()
+            || self.pipeline.is_some()
+            || self.feed.is_some()
+            || self.echo_header.is_some()
+            || self.chaos.is_some()
+            || self.churn.is_some()
+            || self.graphql
+            || self.sse
+            || grpc
+            || !self.classes.is_empty()
+            || self.backend != Backend::Tokio
+    }
+
     pub fn uri<T>(self, uri: T) -> Self
     where
         Uri: TryFrom<T>,
@@ -177,7 +1427,17 @@ impl SwarmBuilder {
     }
 
     pub fn duration(self, duration: Duration) -> Self {
-        Self { duration, ..self }
+        Self {
+            duration: Some(duration),
+            ..self
+        }
+    }
+
+    pub fn requests(self, requests: u64) -> Self {
+        Self {
+            requests: Some(requests),
+            ..self
+        }
     }
 
     pub fn threads(self, threads: usize) -> Self {
@@ -192,8 +1452,157 @@ impl SwarmBuilder {
     }
 
     pub fn request(self, f: impl Fn(&Uri) -> Request<Body> + Send + Sync + 'static) -> Self {
+        Self {
+            make_request: Arc::new(move |uri, _| f(uri)),
+            custom_request: true,
+            ..self
+        }
+    }
+
+    pub fn request_mix(self, mix: RequestMix) -> Self {
+        Self {
+            mix: Some(mix),
+            ..self
+        }
+    }
+
+    pub fn traffic_class(mut self, class: TrafficClass) -> Self {
+        self.classes.push(class);
+        self
+    }
+
+    pub fn request_with_context(
+        self,
+        f: impl Fn(&Uri, &UserContext) -> Request<Body> + Send + Sync + 'static,
+    ) -> Self {
         Self {
             make_request: Arc::new(f),
+            custom_request: true,
+            ..self
+        }
+    }
+
+    pub fn body_static(self, body: impl Into<Bytes>) -> Self {
+        Self {
+            body: Some(body.into()),
+            body_stream: None,
+            ..self
+        }
+    }
+
+    pub fn body_stream<S, E>(self, f: impl Fn(&UserContext) -> S + Send + Sync + 'static) -> Self
+    where
+        S: Stream<Item = Result<Bytes, E>> + Send + 'static,
+        E: Into<Box<dyn Error + Send + Sync>> + 'static,
+    {
+        Self {
+            body: None,
+            body_stream: Some(Arc::new(move |ctx| f(ctx).map_err(Into::into).boxed())),
+            ..self
+        }
+    }
+
+    pub fn body_reader<R>(self, f: impl Fn(&UserContext) -> R + Send + Sync + 'static) -> Self
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+    {
+        Self {
+            body: None,
+            body_stream: Some(Arc::new(move |ctx| upload::read(f(ctx)))),
+            ..self
+        }
+    }
+
+    pub fn replay(self, recording: Recording) -> Self {
+        let recording = Arc::new(recording);
+        Self {
+            make_request: {
+                let recording = recording.clone();
+                let prepared = OnceLock::new();
+                Arc::new(move |uri, ctx| {
+                    let prepared: &Vec<_> = prepared.get_or_init(|| recording.prepare(uri));
+                    prepared[ctx.sequence() as usize % prepared.len()].to_request()
+                })
+            },
+            custom_request: true,
+            recording: Some(recording),
+            ..self
+        }
+    }
+
+    pub fn replay_speed(self, replay_speed: ReplaySpeed) -> Self {
+        Self {
+            replay_speed,
+            ..self
+        }
+    }
+
+    pub fn scenario(self, scenario: Scenario) -> Self {
+        Self {
+            scenario: Some(scenario),
+            ..self
+        }
+    }
+
+    #[cfg(feature = "websocket")]
+    pub fn websocket(self, websocket: WebSocket) -> Self {
+        Self {
+            websocket: Some(websocket),
+            ..self
+        }
+    }
+
+    #[cfg(feature = "mqtt")]
+    pub fn mqtt(self, mqtt: Mqtt) -> Self {
+        Self {
+            mqtt: Some(mqtt),
+            ..self
+        }
+    }
+
+    #[cfg(feature = "resp")]
+    pub fn resp(self, resp: Resp) -> Self {
+        Self {
+            resp: Some(resp),
+            ..self
+        }
+    }
+
+    #[cfg(feature = "kafka")]
+    pub fn kafka(self, kafka: Kafka) -> Self {
+        Self {
+            kafka: Some(kafka),
+            ..self
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    pub fn s3(self, s3: S3) -> Self {
+        Self {
+            s3: Some(s3),
+            ..self
+        }
+    }
+
+    pub fn virtual_user<U: VirtualUser>(self, f: impl Fn() -> U + Send + Sync + 'static) -> Self {
+        Self {
+            virtual_user: Some(Arc::new(move || Box::new(f()))),
+            ..self
+        }
+    }
+
+    #[cfg(feature = "lua")]
+    pub fn wrk_script(self, script: WrkScript) -> Self {
+        Self {
+            wrk_script: Some(script),
+            ..self
+        }
+    }
+
+    #[cfg(feature = "rhai")]
+    pub fn rhai_script(self, script: RhaiScript) -> Self {
+        Self {
+            rhai_script: Some(script),
             ..self
         }
     }
@@ -205,27 +1614,1764 @@ impl SwarmBuilder {
         }
     }
 
-    pub fn build(self) -> Result<Swarm, Box<dyn Error + Send + Sync>> {
-        Ok(Swarm {
-            uri: self.uri?,
-            duration: self.duration,
-            threads: self.threads,
-            concurrency: self.concurrency,
-            make_request: self.make_request,
-            expectation_matcher: self.expectation_matcher,
-        })
+    pub fn matching(self, matcher: Matcher) -> Self {
+        let expectation = matcher.clone();
+        Self {
+            expectation_matcher: Arc::new(move |res| expectation.matches(&res)),
+            matcher: Some(matcher),
+            ..self
+        }
     }
 
-    pub fn zerg(self) -> Result<BenchmarkResult, Box<dyn Error + Send + Sync>> {
-        self.build().map(|swarm| swarm.zerg())
+    pub fn annotator(self, annotator: Annotator) -> Self {
+        Self { annotator, ..self }
     }
-}
 
-pub extern crate serde_json;
+    pub fn shared(self, shared: Shared) -> Self {
+        Self { shared, ..self }
+    }
 
-#[macro_export]
-macro_rules! json {
-    ($some_json:tt) => {
-        $crate::serde_json::to_vec(&$crate::serde_json::json!($some_json)).unwrap()
-    };
+    pub fn clock(self, clock_source: ClockSource) -> Self {
+        Self {
+            clock_source,
+            ..self
+        }
+    }
+
+    pub fn calibrate_clock(self, calibrate_clock: bool) -> Self {
+        Self {
+            calibrate_clock,
+            ..self
+        }
+    }
+
+    pub fn backend(self, backend: Backend) -> Self {
+        Self { backend, ..self }
+    }
+
+    pub fn pipeline(self, depth: usize) -> Self {
+        Self {
+            pipeline: Some(depth.max(1)),
+            ..self
+        }
+    }
+
+    pub fn server_sent_events(self) -> Self {
+        Self { sse: true, ..self }
+    }
+
+    pub fn protocol(self, protocol: Protocol) -> Self {
+        Self { protocol, ..self }
+    }
+
+    pub fn http2_prior_knowledge(self) -> Self {
+        Self {
+            protocol: Protocol::Http2,
+            prior_knowledge: true,
+            ..self
+        }
+    }
+
+    pub fn streams_per_connection(self, streams: usize) -> Self {
+        Self {
+            streams_per_connection: streams.max(1),
+            ..self
+        }
+    }
+
+    pub fn max_concurrent_streams_per_connection(self, streams: usize) -> Self {
+        Self {
+            max_streams: Some(streams.max(1)),
+            ..self
+        }
+    }
+
+    pub fn connections(self, connections: usize) -> Self {
+        Self {
+            connections: Some(connections.max(1)),
+            ..self
+        }
+    }
+
+    pub fn resolver(self, resolver: impl Resolve) -> Self {
+        Self {
+            resolver: Some(Arc::new(resolver)),
+            ..self
+        }
+    }
+
+    pub fn connect_to(self, addr: impl Into<SocketAddr>) -> Self {
+        Self {
+            connect_to: Some(addr.into()),
+            ..self
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    pub fn dns_over_https<T>(self, endpoint: T) -> Self
+    where
+        Uri: TryFrom<T>,
+        <Uri as TryFrom<T>>::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        Self {
+            doh: Some(TryFrom::try_from(endpoint).map_err(Into::into)),
+            ..self
+        }
+    }
+
+    pub fn address_family(self, address_family: AddressFamily) -> Self {
+        Self {
+            address_family,
+            ..self
+        }
+    }
+
+    pub fn rotate_dns(self) -> Self {
+        Self {
+            rotate_dns: true,
+            ..self
+        }
+    }
+
+    pub fn dns_refresh(self, interval: Duration) -> Self {
+        Self {
+            rotate_dns: true,
+            dns_refresh: Some(interval),
+            ..self
+        }
+    }
+
+    pub fn bind_addresses(self, addrs: Vec<IpAddr>) -> Self {
+        Self {
+            bind_addresses: Some(addrs),
+            ..self
+        }
+    }
+
+    pub fn max_connections(self, max: usize) -> Self {
+        Self {
+            max_connections: Some(max),
+            ..self
+        }
+    }
+
+    pub fn mode(self, mode: Mode) -> Self {
+        Self { mode, ..self }
+    }
+
+    pub fn rate(self, requests_per_second: f64) -> Self {
+        Self {
+            rate: Some(requests_per_second),
+            ..self
+        }
+    }
+
+    pub fn rate_curve(self, curve: RateCurve) -> Self {
+        Self {
+            rate_curve: Some(curve),
+            ..self
+        }
+    }
+
+    pub fn burst(self, burst: u64) -> Self {
+        Self {
+            burst: Some(burst).filter(|burst| *burst > 1),
+            ..self
+        }
+    }
+
+    #[cfg(any(feature = "tls", feature = "native-tls"))]
+    pub fn tls_backend(self, tls_backend: TlsBackend) -> Self {
+        Self {
+            tls_backend,
+            ..self
+        }
+    }
+
+    #[cfg(any(feature = "tls", feature = "native-tls"))]
+    pub fn danger_accept_invalid_certs(self, accept_invalid_certs: bool) -> Self {
+        Self {
+            tls: TlsOptions {
+                accept_invalid_certs,
+                ..self.tls
+            },
+            ..self
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    pub fn tls_resumption(self, resumption: bool) -> Self {
+        Self {
+            tls: TlsOptions {
+                resumption,
+                ..self.tls
+            },
+            ..self
+        }
+    }
+
+    #[cfg(any(feature = "tls", feature = "native-tls"))]
+    pub fn ca_bundle(self, path: impl Into<PathBuf>) -> Self {
+        Self {
+            tls: TlsOptions {
+                ca_bundle: Some(path.into()),
+                ..self.tls
+            },
+            ..self
+        }
+    }
+
+    #[cfg(any(feature = "tls", feature = "native-tls"))]
+    pub fn client_identity(self, cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+        Self {
+            tls: TlsOptions {
+                identity: Some(ClientIdentity {
+                    cert: cert.into(),
+                    key: key.into(),
+                }),
+                ..self.tls
+            },
+            ..self
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    pub fn tls_config(self, config: impl Into<Arc<rustls::ClientConfig>>) -> Self {
+        Self {
+            tls: TlsOptions {
+                config: Some(config.into()),
+                ..self.tls
+            },
+            ..self
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    pub fn pin_spki_sha256(mut self, pin: impl Into<String>) -> Self {
+        self.tls.pins.push(pin.into());
+        self
+    }
+
+    pub fn proxy<T>(self, uri: T) -> Self
+    where
+        Uri: TryFrom<T>,
+        <Uri as TryFrom<T>>::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        Self {
+            forward_proxy: Some(TryFrom::try_from(uri).map_err(Into::into)),
+            ..self
+        }
+    }
+
+    pub fn socks5(self, addr: impl AsRef<str>) -> Self {
+        self.proxy(format!("socks5://{}", addr.as_ref()))
+    }
+
+    pub fn proxy_protocol(self, header: ProxyHeader) -> Self {
+        Self {
+            proxy: Some(header),
+            ..self
+        }
+    }
+
+    pub fn http2_keepalive(mut self, interval: Duration) -> Self {
+        self.keepalive.interval = Some(interval);
+        self
+    }
+
+    pub fn http2_keepalive_timeout(mut self, timeout: Duration) -> Self {
+        self.keepalive.timeout = Some(timeout);
+        self
+    }
+
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.keepalive.idle_timeout = Some(timeout);
+        self
+    }
+
+    pub fn follow_redirects(self, max_hops: usize) -> Self {
+        Self {
+            follow_redirects: Some(max_hops),
+            ..self
+        }
+    }
+
+    pub fn expect_continue(self, min_body: usize) -> Self {
+        Self {
+            expect_continue: Some(min_body),
+            ..self
+        }
+    }
+
+    pub fn keep_alive(mut self, enabled: bool) -> Self {
+        self.keepalive.disabled = !enabled;
+        self
+    }
+
+    pub fn random_header(mut self, name: impl Into<String>, value: RandomValue) -> Self {
+        self.random_headers.push((name.into(), value));
+        self
+    }
+
+    pub fn identity_profile(self, profile: IdentityProfile) -> Self {
+        #[cfg(feature = "tls")]
+        let tls = TlsOptions {
+            fingerprint: profile.fingerprint(),
+            ..self.tls
+        };
+        Self {
+            #[cfg(feature = "tls")]
+            tls,
+            identity_profile: Some(profile),
+            ..self
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    pub fn decompress(self) -> Self {
+        Self {
+            decompress: true,
+            ..self
+        }
+    }
+
+    pub fn graphql(self) -> Self {
+        Self {
+            graphql: true,
+            ..self
+        }
+    }
+
+    #[cfg(feature = "grpc")]
+    pub fn grpc(self) -> Self {
+        Self {
+            grpc: true,
+            protocol: Protocol::Http2,
+            ..self
+        }
+    }
+
+    #[cfg(feature = "grpc")]
+    pub fn grpc_streaming(self, streaming: GrpcStreaming) -> Self {
+        Self {
+            grpc: true,
+            grpc_streaming: Some(streaming),
+            protocol: Protocol::Http2,
+            ..self
+        }
+    }
+
+    pub fn connection_churn(self, closes_per_second: f64) -> Self {
+        Self {
+            churn: Some(closes_per_second).filter(|rate| *rate > 0.0),
+            ..self
+        }
+    }
+
+    pub fn chaos(self, chaos: Chaos) -> Self {
+        Self {
+            chaos: Some(chaos).filter(|chaos| !chaos.is_noop()),
+            ..self
+        }
+    }
+
+    pub fn echo_id(self, header: impl Into<String>) -> Self {
+        Self {
+            echo_header: Some(header.into()),
+            ..self
+        }
+    }
+
+    pub fn tenants(self, feed: Feed) -> Self {
+        Self {
+            tenants: Some(feed),
+            ..self
+        }
+    }
+
+    pub fn feed(self, feed: Feed) -> Self {
+        Self {
+            feed: Some(feed),
+            ..self
+        }
+    }
+
+    pub fn seed(self, seed: Seed) -> Self {
+        Self {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
+    pub fn region(self, region: impl Into<String>) -> Self {
+        Self {
+            region: Some(region.into()),
+            ..self
+        }
+    }
+
+    pub fn secret(mut self, value: impl Into<String>) -> Self {
+        self.redactor.value(value.into());
+        self
+    }
+
+    pub fn secret_header(mut self, name: impl Into<String>) -> Self {
+        self.redactor.header(name.into());
+        self
+    }
+
+    pub fn reporter(mut self, reporter: impl Reporter) -> Self {
+        self.reporters.push(Box::new(reporter));
+        self
+    }
+
+    pub fn report_interval(self, report_interval: Duration) -> Self {
+        Self {
+            report_interval,
+            ..self
+        }
+    }
+
+    pub fn timing_memory(self, bytes: usize) -> Self {
+        Self {
+            timing_capacity: Some((bytes / mem::size_of::<Duration>()).max(1)),
+            ..self
+        }
+    }
+
+    pub fn percentile_check(self, workers: usize) -> Self {
+        Self {
+            percentile_check: Some(workers),
+            ..self
+        }
+    }
+
+    pub fn latency_budget(mut self, label: impl Into<String>, budget: Duration) -> Self {
+        self.latency_budgets.insert(label.into(), budget);
+        self
+    }
+
+    pub fn raise_fd_limit(self, raise_fd_limit: bool) -> Self {
+        Self {
+            raise_fd_limit,
+            ..self
+        }
+    }
+
+    pub fn count_tcp_failures(mut self, failures: impl IntoIterator<Item = TcpFailure>) -> Self {
+        self.counted_failures.extend(failures);
+        self
+    }
+
+    pub fn abort_on_panic(self, abort_on_panic: bool) -> Self {
+        Self {
+            abort_on_panic,
+            ..self
+        }
+    }
+
+    pub fn calibrate(self) -> Result<Calibration, Box<dyn Error + Send + Sync>> {
+        if !matches!(self.mode, Mode::Http | Mode::Connect) {
+            return Err(format!(
+                "{} mode cannot be calibrated against an HTTP server",
+                self.mode
+            )
+            .into());
+        }
+        let path = self
+            .uri
+            .as_ref()
+            .ok()
+            .and_then(|uri| uri.path_and_query())
+            .map(|path| path.to_string())
+            .unwrap_or_else(|| "/".to_string());
+
+        let server = NullServer::start()?;
+        let result = Self {
+            reporters: Vec::new(),
+            seed: None,
+            region: None,
+            ..self.uri(format!("http://{}{path}", server.local_addr()))
+        }
+        .zerg();
+        server.stop();
+        Ok(Calibration::new(result?))
+    }
+
+    pub fn build(self) -> Result<Swarm, Box<dyn Error + Send + Sync>> {
+        let exclusive = self.exclusive_modes();
+        let shapes_http_loop = self.shapes_http_loop();
+        let uri = self.uri?;
+        let duration = match (self.duration, self.requests) {
+            (Some(duration), _) => Some(duration),
+            (None, Some(_)) => None,
+            (None, None) => Some(DEFAULT_DURATION),
+        };
+
+        let forward_proxy = self.forward_proxy.transpose()?;
+        let host = uri.host().ok_or("missing host")?;
+        let port = uri.port_u16().unwrap_or(match uri.scheme_str() {
+            Some("https") => 443,
+            Some("dns") => 53,
+            Some("mqtt") => 1883,
+            Some("redis") => 6379,
+            Some("kafka") => 9092,
+            _ => 80,
+        });
+        let (host, port) = match &forward_proxy {
+            Some(proxy) => (
+                proxy.host().ok_or("missing proxy host")?,
+                proxy.port_u16().unwrap_or(Tunnel::default_port(proxy)),
+            ),
+            None => (host, port),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if self.connect_to.is_some()
+            && (forward_proxy.is_some()
+                || self.rotate_dns
+                || self.resolver.is_some()
+                || self.custom.is_some())
+        {
+            return Err(
+                "connect_to cannot be combined with proxies, custom resolvers or DNS rotation"
+                    .into(),
+            );
+        }
+        #[cfg(feature = "tls")]
+        let doh = match self.doh.transpose()? {
+            Some(_) if self.resolver.is_some() || self.connect_to.is_some() => {
+                return Err(
+                    "DNS-over-HTTPS cannot be combined with custom resolvers or connect_to".into(),
+                );
+            }
+            Some(endpoint) => Some(DohResolver::new(
+                endpoint,
+                TlsOptions {
+                    accept_invalid_certs: self.tls.accept_invalid_certs,
+                    ca_bundle: self.tls.ca_bundle.clone(),
+                    ..TlsOptions::default()
+                },
+            )?),
+            None => None,
+        };
+        #[cfg(feature = "tls")]
+        let doh_stats = doh.as_ref().map(DohResolver::stats);
+        let resolve = self.resolver.unwrap_or_else(|| Arc::new(SystemResolver));
+        #[cfg(feature = "tls")]
+        let resolve: Arc<dyn Resolve> = match doh {
+            Some(doh) => Arc::new(doh),
+            None => resolve,
+        };
+        let (addr, resolver) = match (self.connect_to, self.rotate_dns) {
+            _ if self.custom.is_some() => {
+                let addr = SocketAddr::from(([127, 0, 0, 1], port));
+                (addr, Resolver::Static(vec![addr].into()))
+            }
+            (Some(addr), _) => (addr, Resolver::Pinned(addr)),
+            (None, true) => {
+                let pool = AddressPool::resolve(resolve, host, port, self.address_family)?;
+                (pool.first(), Resolver::Rotating(Arc::new(pool)))
+            }
+            (None, false) => {
+                let addrs = lookup(&*resolve, host, port, self.address_family)?;
+                (addrs[0], Resolver::Static(addrs.into()))
+            }
+        };
+        let tunnel = forward_proxy
+            .as_ref()
+            .map(|proxy| Tunnel::new(proxy, addr))
+            .transpose()?;
+
+        if self.mode != Mode::Http
+            && (self.scenario.is_some()
+                || self.virtual_user.is_some()
+                || self.pipeline.is_some()
+                || self.feed.is_some())
+        {
+            return Err(format!("{} mode does not send HTTP requests", self.mode).into());
+        }
+
+        if let [first, second, ..] = exclusive[..] {
+            return Err(format!("{first} swarms cannot be combined with {second}").into());
+        }
+        if let Some(&exclusive) = exclusive.first() {
+            if shapes_http_loop {
+                return Err(format!(
+                    "{exclusive} swarms cannot be combined with other modes, scenarios, virtual users, feeds, traffic classes, pipelining, faults, churn, GraphQL or server-sent events and require the tokio backend"
+                )
+                .into());
+            }
+            if exclusive.own_protocol() && self.protocol != Protocol::Http1 {
+                return Err(format!("{exclusive} swarms do not use an HTTP protocol").into());
+            }
+            if !exclusive.proxied() && forward_proxy.is_some() {
+                return Err(format!("{exclusive} swarms cannot be sent through proxies").into());
+            }
+        }
+        #[cfg(feature = "websocket")]
+        if self.websocket.is_some() && uri.scheme_str() != Some("ws") {
+            return Err("WebSocket swarms require a ws:// target".into());
+        }
+        #[cfg(feature = "mqtt")]
+        if let Some(mqtt) = &self.mqtt {
+            if uri.scheme_str() != Some("mqtt") {
+                return Err("MQTT swarms require an mqtt:// target".into());
+            }
+            mqtt.validate()?;
+        }
+        #[cfg(feature = "resp")]
+        if let Some(resp) = &self.resp {
+            if uri.scheme_str() != Some("redis") {
+                return Err("RESP swarms require a redis:// target".into());
+            }
+            resp.validate()?;
+        }
+        #[cfg(feature = "kafka")]
+        if let Some(kafka) = &self.kafka {
+            if uri.scheme_str() != Some("kafka") {
+                return Err("Kafka swarms require a kafka:// target".into());
+            }
+            kafka.validate()?;
+        }
+        #[cfg(feature = "s3")]
+        if let Some(s3) = &self.s3 {
+            if !matches!(uri.scheme_str(), Some("http" | "https")) {
+                return Err("S3 swarms require an http:// or https:// target".into());
+            }
+            s3.validate()?;
+            if self.body.is_some() || self.body_stream.is_some() || self.follow_redirects.is_some()
+            {
+                return Err("S3 swarms cannot be combined with custom bodies or redirects".into());
+            }
+        }
+        #[cfg(feature = "grpc")]
+        if self.grpc
+            && (self.scenario.is_some()
+                || self.pipeline.is_some()
+                || self.backend != Backend::Tokio
+                || self.protocol != Protocol::Http2
+                || self.mode != Mode::Http)
+        {
+            return Err(
+                "gRPC calls are only supported for virtual users on the tokio backend over HTTP/2"
+                    .into(),
+            );
+        }
+        #[cfg(feature = "grpc")]
+        if let Some(streaming) = self.grpc_streaming {
+            if streaming == (GrpcStreaming::Bidi { messages: 0 }) {
+                return Err("bidirectional gRPC streams must send at least one message".into());
+            }
+            if self.echo_header.is_some()
+                || self.chaos.is_some()
+                || self.graphql
+                || !self.classes.is_empty()
+            {
+                return Err(
+                    "gRPC streaming calls cannot be combined with echo checks, chaos, GraphQL or traffic classes"
+                        .into(),
+                );
+            }
+        }
+        if self.churn.is_some()
+            && (self.scenario.is_some()
+                || self.pipeline.is_some()
+                || self.sse
+                || self.mode != Mode::Http
+                || self.protocol != Protocol::Http1)
+        {
+            return Err("connection churn closes HTTP/1 connections of virtual users".into());
+        }
+        if self.chaos.is_some()
+            && (self.scenario.is_some()
+                || self.pipeline.is_some()
+                || self.backend != Backend::Tokio
+                || self.mode != Mode::Http)
+        {
+            return Err(
+                "client-side faults are only injected into virtual user requests on the tokio backend"
+                    .into(),
+            );
+        }
+        if self.sse {
+            let grpc = false;
+            #[cfg(feature = "grpc")]
+            let grpc = grpc || self.grpc;
+            if self.mode != Mode::Http
+                || self.scenario.is_some()
+                || self.pipeline.is_some()
+                || self.echo_header.is_some()
+                || self.chaos.is_some()
+                || self.graphql
+                || grpc
+                || self.backend != Backend::Tokio
+            {
+                return Err(
+                    "server-sent events are only streamed by virtual users on the tokio backend"
+                        .into(),
+                );
+            }
+            #[cfg(feature = "http3")]
+            if self.protocol == Protocol::Http3 {
+                return Err("server-sent events are not supported over HTTP/3".into());
+            }
+        }
+        if self.graphql && (self.scenario.is_some() || self.mode != Mode::Http) {
+            return Err("GraphQL responses are checked by virtual users, not scenarios".into());
+        }
+        #[cfg(feature = "compression")]
+        if self.decompress
+            && (self.mode != Mode::Http
+                || self.scenario.is_some()
+                || self.pipeline.is_some()
+                || self.sse)
+        {
+            return Err("response decompression only applies to plain HTTP request loops".into());
+        }
+        #[cfg(all(feature = "compression", feature = "grpc"))]
+        if self.decompress && self.grpc_streaming.is_some() {
+            return Err("response decompression cannot be combined with gRPC streaming".into());
+        }
+        if self.follow_redirects.is_some()
+            && (self.mode != Mode::Http
+                || self.scenario.is_some()
+                || self.pipeline.is_some()
+                || self.sse)
+        {
+            return Err("following redirects only applies to plain HTTP request loops".into());
+        }
+        if self.expect_continue.is_some()
+            && (uri.scheme_str() != Some("http")
+                || self.mode != Mode::Http
+                || self.protocol != Protocol::Http1
+                || self.backend != Backend::Tokio
+                || self.scenario.is_some()
+                || self.pipeline.is_some()
+                || self.sse
+                || self.proxy.is_some()
+                || tunnel.is_some())
+        {
+            return Err(
+                "Expect: 100-continue requires plain HTTP/1 request loops on the tokio backend without pipelining or proxies"
+                    .into(),
+            );
+        }
+        #[cfg(feature = "grpc")]
+        if self.follow_redirects.is_some() && self.grpc_streaming.is_some() {
+            return Err("following redirects cannot be combined with gRPC streaming".into());
+        }
+        if self.graphql && self.body_stream.is_some() {
+            return Err("GraphQL requests cannot use streamed bodies".into());
+        }
+        if self.scenario.is_some() && self.echo_header.is_some() {
+            return Err("echoed request ids are checked by virtual users, not scenarios".into());
+        }
+        if self.scenario.is_some() && self.feed.is_some() {
+            return Err("feeds are consumed by virtual users, not scenarios".into());
+        }
+        if self.scenario.is_some() && self.backend != Backend::Tokio {
+            return Err("scenarios are only supported by the tokio backend".into());
+        }
+        if self.protocol == Protocol::Http2
+            && (self.backend != Backend::Tokio || self.pipeline.is_some())
+        {
+            return Err("HTTP/2 is only supported by the tokio backend without pipelining".into());
+        }
+        if self.prior_knowledge {
+            if uri.scheme_str() != Some("http") {
+                return Err("HTTP/2 prior knowledge requires an http:// target".into());
+            }
+            if self.protocol != Protocol::Http2 {
+                return Err(
+                    "HTTP/2 prior knowledge cannot be combined with another protocol".into(),
+                );
+            }
+        }
+        if (self.keepalive.interval.is_some() || self.keepalive.timeout.is_some())
+            && self.protocol != Protocol::Http2
+        {
+            return Err("keepalive pings require HTTP/2".into());
+        }
+        if self.keepalive.disabled
+            && (self.mode != Mode::Http
+                || self.protocol != Protocol::Http1
+                || self.backend != Backend::Tokio
+                || self.pipeline.is_some())
+        {
+            return Err(
+                "disabling keep-alive only applies to HTTP/1 on the tokio backend without pipelining"
+                    .into(),
+            );
+        }
+        if tunnel.is_some() {
+            let websocket = false;
+            #[cfg(feature = "websocket")]
+            let websocket = websocket || self.websocket.is_some();
+            let http3 = false;
+            #[cfg(feature = "http3")]
+            let http3 = http3 || self.protocol == Protocol::Http3;
+            if self.mode != Mode::Http
+                || self.backend != Backend::Tokio
+                || self.pipeline.is_some()
+                || self.proxy.is_some()
+                || websocket
+                || http3
+            {
+                return Err(
+                    "forward proxies only tunnel HTTP/1 and HTTP/2 on the tokio backend without pipelining or PROXY headers"
+                        .into(),
+                );
+            }
+        }
+        if self.rotate_dns {
+            let websocket = false;
+            #[cfg(feature = "websocket")]
+            let websocket = websocket || self.websocket.is_some();
+            let http3 = false;
+            #[cfg(feature = "http3")]
+            let http3 = http3 || self.protocol == Protocol::Http3;
+            if self.mode != Mode::Http
+                || self.backend != Backend::Tokio
+                || self.pipeline.is_some()
+                || tunnel.is_some()
+                || websocket
+                || http3
+            {
+                return Err(
+                    "DNS rotation only applies to HTTP/1 and HTTP/2 on the tokio backend without pipelining or forward proxies"
+                        .into(),
+                );
+            }
+        }
+        if let Some(requests) = self.requests {
+            if requests == 0 {
+                return Err("request budgets must allow at least one request".into());
+            }
+            if self.chaos.is_some_and(|chaos| chaos.drops_all()) {
+                return Err("request budgets cannot be spent when every request is dropped".into());
+            }
+            let sessions = false;
+            #[cfg(feature = "websocket")]
+            let sessions = sessions || self.websocket.is_some();
+            #[cfg(feature = "mqtt")]
+            let sessions = sessions || self.mqtt.is_some();
+            #[cfg(feature = "resp")]
+            let sessions = sessions || self.resp.is_some();
+            #[cfg(feature = "kafka")]
+            let sessions = sessions || self.kafka.is_some();
+            #[cfg(feature = "s3")]
+            let sessions = sessions || self.s3.is_some();
+            #[cfg(feature = "grpc")]
+            let sessions = sessions || self.grpc_streaming.is_some();
+            if self.mode != Mode::Http
+                || self.scenario.is_some()
+                || self.pipeline.is_some()
+                || self.sse
+                || sessions
+            {
+                return Err("request budgets only apply to plain HTTP request loops".into());
+            }
+        }
+        if self.percentile_check == Some(0) {
+            return Err("percentile checks need at least one worker".into());
+        }
+        if self.dns_refresh.is_some_and(|interval| interval.is_zero()) {
+            return Err("DNS refresh interval must be positive".into());
+        }
+        if let Some(addrs) = &self.bind_addresses {
+            if addrs.is_empty() {
+                return Err("bind address list cannot be empty".into());
+            }
+            let websocket = false;
+            #[cfg(feature = "websocket")]
+            let websocket = websocket || self.websocket.is_some();
+            let http3 = false;
+            #[cfg(feature = "http3")]
+            let http3 = http3 || self.protocol == Protocol::Http3;
+            let tls_storm =
+                matches!(self.mode, Mode::Storm(_)) && uri.scheme_str() == Some("https");
+            if !matches!(self.mode, Mode::Http | Mode::Connect | Mode::Storm(_))
+                || tls_storm
+                || self.backend != Backend::Tokio
+                || self.pipeline.is_some()
+                || websocket
+                || http3
+            {
+                return Err(
+                    "bind addresses only apply to HTTP/1, HTTP/2 and plain TCP connect modes on the tokio backend without pipelining"
+                        .into(),
+                );
+            }
+        }
+        let bind = self
+            .bind_addresses
+            .as_deref()
+            .map(|addrs| Arc::new(BindPool::new(addrs)));
+        if let Some(max) = self.max_connections {
+            if max == 0 {
+                return Err("connection caps must allow at least one connection".into());
+            }
+            let websocket = false;
+            #[cfg(feature = "websocket")]
+            let websocket = websocket || self.websocket.is_some();
+            let http3 = false;
+            #[cfg(feature = "http3")]
+            let http3 = http3 || self.protocol == Protocol::Http3;
+            let sessions = false;
+            #[cfg(feature = "mqtt")]
+            let sessions = sessions || self.mqtt.is_some();
+            #[cfg(feature = "resp")]
+            let sessions = sessions || self.resp.is_some();
+            #[cfg(feature = "kafka")]
+            let sessions = sessions || self.kafka.is_some();
+            if self.mode != Mode::Http
+                || self.backend != Backend::Tokio
+                || self.pipeline.is_some()
+                || self.expect_continue.is_some()
+                || websocket
+                || http3
+                || sessions
+            {
+                return Err(
+                    "connection caps only apply to HTTP/1 and HTTP/2 on the tokio backend without pipelining"
+                        .into(),
+                );
+            }
+        }
+        let cap = self
+            .max_connections
+            .map(|max| Arc::new(ConnectionCap::new(max)));
+        if self.proxy.is_some() && self.backend != Backend::Tokio {
+            return Err("PROXY protocol headers are only supported by the tokio backend".into());
+        }
+        if self.pipeline.is_some() {
+            if self.scenario.is_some() || self.backend != Backend::Tokio {
+                return Err(
+                    "pipelining is only supported for virtual users on the tokio backend".into(),
+                );
+            }
+            if matches!(self.replay_speed, ReplaySpeed::Multiplier(_)) && self.recording.is_some() {
+                return Err("pipelining cannot follow a replay schedule".into());
+            }
+        }
+        #[cfg(feature = "http3")]
+        if self.protocol == Protocol::Http3 {
+            if self.backend != Backend::Tokio
+                || self.pipeline.is_some()
+                || self.scenario.is_some()
+                || self.proxy.is_some()
+                || self.mode != Mode::Http
+            {
+                return Err(
+                    "HTTP/3 is only supported for virtual users on the tokio backend without pipelining or PROXY headers"
+                        .into(),
+                );
+            }
+            if uri.scheme_str() != Some("https") {
+                return Err("HTTP/3 requires an https target".into());
+            }
+        }
+
+        let scripted = false;
+        #[cfg(feature = "lua")]
+        let scripted = scripted || self.wrk_script.is_some();
+        #[cfg(feature = "rhai")]
+        let scripted = scripted || self.rhai_script.is_some();
+
+        if (self.body.is_some() || self.body_stream.is_some())
+            && (scripted || self.virtual_user.is_some() || self.scenario.is_some())
+        {
+            return Err(
+                "request bodies cannot be combined with scripts, virtual users or scenarios".into(),
+            );
+        }
+        if !self.classes.is_empty() {
+            let websocket = false;
+            #[cfg(feature = "websocket")]
+            let websocket = websocket || self.websocket.is_some();
+            if self.mode != Mode::Http
+                || self.scenario.is_some()
+                || self.virtual_user.is_some()
+                || self.pipeline.is_some()
+                || websocket
+                || scripted
+            {
+                return Err(
+                    "traffic classes cannot be combined with scenarios, virtual users, scripts or pipelining"
+                        .into(),
+                );
+            }
+            if self.rate.is_some() || self.rate_curve.is_some() || self.recording.is_some() {
+                return Err("traffic classes set their own rates".into());
+            }
+            if !class::concurrency(&self.classes).is_multiple_of(self.threads) {
+                return Err("traffic class users must divide evenly across threads".into());
+            }
+        }
+        if let Some(mix) = &self.mix {
+            mix.validate()?;
+            if self.mode != Mode::Http
+                || self.scenario.is_some()
+                || self.virtual_user.is_some()
+                || self.pipeline.is_some()
+                || self.recording.is_some()
+                || self.custom.is_some()
+                || self.sse
+                || scripted
+                || !self.classes.is_empty()
+            {
+                return Err(
+                    "request mixes cannot be combined with traffic classes, scenarios, virtual users, scripts, replays or pipelining"
+                        .into(),
+                );
+            }
+        }
+        let concurrency = match self.classes.is_empty() {
+            true => self.concurrency,
+            false => class::concurrency(&self.classes),
+        };
+        let users_per_thread = concurrency / self.threads;
+        let connections_per_thread = match self.connections {
+            Some(connections) => {
+                if self.streams_per_connection > 1 {
+                    return Err(
+                        "streams per connection cannot be combined with a fixed connection count"
+                            .into(),
+                    );
+                }
+                if connections > concurrency {
+                    return Err("connections cannot exceed concurrency".into());
+                }
+                if !connections.is_multiple_of(self.threads) {
+                    return Err("connections must divide evenly across threads".into());
+                }
+                connections / self.threads
+            }
+            None => {
+                users_per_thread.div_ceil(self.max_streams.unwrap_or(self.streams_per_connection))
+            }
+        };
+        let streams_per_connection = users_per_thread.div_ceil(connections_per_thread.max(1));
+        if let Some(max) = self.max_streams.filter(|max| streams_per_connection > *max) {
+            return Err(format!(
+                "{concurrency} users need {streams_per_connection} streams per connection, above the maximum of {max}"
+            )
+            .into());
+        }
+        if streams_per_connection > 1 && self.protocol != Protocol::Http2 {
+            return Err("multiple streams per connection require HTTP/2".into());
+        }
+        let (classes, make_request): (Arc<[Class]>, MakeRequest) = match self.classes.is_empty() {
+            true => (Arc::new([]), self.make_request),
+            false => class::plan(&self.classes),
+        };
+        let make_request = match &self.mix {
+            Some(mix) => mix.make_request(),
+            None => make_request,
+        };
+        let make_request: MakeRequest = match self.body {
+            Some(body) => Arc::new(move |uri, ctx| {
+                let mut req = make_request(uri, ctx);
+                *req.body_mut() = Body::from(body.clone());
+                req
+            }),
+            None => make_request,
+        };
+        let uploads = self
+            .body_stream
+            .map(|body| (body, Arc::new(UploadTracker::default())));
+        let make_request: MakeRequest = match &uploads {
+            Some((body, uploads)) => {
+                let (body, uploads) = (body.clone(), uploads.clone());
+                Arc::new(move |uri, ctx| {
+                    let mut req = make_request(uri, ctx);
+                    *req.body_mut() = uploads.body(body(ctx));
+                    req
+                })
+            }
+            None => make_request,
+        };
+        let uploads = uploads.map(|(_, uploads)| uploads);
+        let expectation_matcher = self.expectation_matcher;
+
+        #[cfg(any(feature = "lua", feature = "rhai"))]
+        let script_errors = None;
+        #[cfg(feature = "lua")]
+        let wrk = match self.wrk_script {
+            Some(script) => Some(Arc::new(script.start(&uri, addr, self.threads)?)),
+            None => None,
+        };
+        #[cfg(feature = "lua")]
+        let (make_request, expectation_matcher, script_errors): (
+            MakeRequest,
+            Expectation,
+            _,
+        ) = match &wrk {
+            Some(state) => {
+                let request = state.clone();
+                let response = state.clone();
+                (
+                    Arc::new(move |uri, _| request.request(uri)),
+                    Arc::new(move |res| response.response(res)),
+                    Some(state.errors()),
+                )
+            }
+            None => (make_request, expectation_matcher, script_errors),
+        };
+
+        #[cfg(feature = "rhai")]
+        let (make_request, expectation_matcher, script_errors): (
+            MakeRequest,
+            Expectation,
+            _,
+        ) = match self.rhai_script {
+            Some(script) => {
+                let state = Arc::new(script.compile()?);
+                let errors = state.errors();
+                let expect = state.clone();
+                (
+                    Arc::new(move |uri, ctx| state.request(uri, ctx.sequence())),
+                    Arc::new(move |res| expect.expect(&res)),
+                    Some(errors),
+                )
+            }
+            None => (make_request, expectation_matcher, script_errors),
+        };
+
+        let profile = match (&self.recording, self.replay_speed, self.rate) {
+            (Some(_), ReplaySpeed::Multiplier(speed), _) => format!("replay at {speed}x"),
+            (_, _, Some(rate)) => match self.burst {
+                Some(burst) => format!("open loop at {rate} reqs/sec in bursts of {burst}"),
+                None => format!("open loop at {rate} reqs/sec"),
+            },
+            _ => match &self.rate_curve {
+                Some(curve) => format!(
+                    "rate curve of {} points over {:.0}s",
+                    curve.points().len(),
+                    curve.span().as_secs_f64()
+                ),
+                None if self.sse => "server-sent events".to_string(),
+                None => match self.classes.len() {
+                    0 => "closed loop".to_string(),
+                    n => format!("{n} traffic classes"),
+                },
+            },
+        };
+        let config = RunConfig {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            target: RunConfig::target(&uri, &self.redactor),
+            method: None,
+            headers: Vec::new(),
+            mode: self.mode.to_string(),
+            protocol: format!("{:?}", self.protocol),
+            backend: format!("{:?}", self.backend),
+            threads: self.threads,
+            concurrency,
+            duration_ms: duration.map(|duration| duration.as_millis() as u64),
+            requests: self.requests,
+            profile,
+            pipeline: self.pipeline,
+            streams_per_connection,
+            region: self.region.clone(),
+        };
+        // A single request template describes the run; mixes, classes, scripts and users vary
+        // per request, so they only get the rest of the config. User closures are not run
+        // here: their template is the first request the run actually sends.
+        let (config, template) = match self.mode == Mode::Http
+            && !scripted
+            && self.mix.is_none()
+            && classes.is_empty()
+            && self.scenario.is_none()
+            && self.virtual_user.is_none()
+        {
+            true if self.custom_request => (config, Some(Arc::default())),
+            true => (
+                config.with_request(&Request::get(&uri).body(())?, &self.redactor),
+                None,
+            ),
+            false => (config, None),
+        };
+
+        if self.burst.is_some() && self.rate.is_none() {
+            return Err("a burst size requires a rate".into());
+        }
+        if self
+            .rate
+            .into_iter()
+            .chain(class::rates(&self.classes))
+            .any(|rate| !rate.is_finite() || rate <= 0.0)
+        {
+            return Err("rates must be finite and positive".into());
+        }
+        let paced = self.rate.is_some()
+            || self.rate_curve.is_some()
+            || (self.recording.is_some()
+                && matches!(self.replay_speed, ReplaySpeed::Multiplier(_)));
+        let unpaced = self.pipeline.is_some() || self.sse;
+        #[cfg(feature = "grpc")]
+        let unpaced = unpaced || self.grpc_streaming.is_some();
+        #[cfg(feature = "websocket")]
+        let unpaced = unpaced || self.websocket.is_some();
+        #[cfg(feature = "mqtt")]
+        let unpaced = unpaced || self.mqtt.is_some();
+        if paced && unpaced {
+            return Err(
+                "rates, rate curves and timed replays cannot pace pipelined, SSE, gRPC streaming, WebSocket or MQTT loops"
+                    .into(),
+            );
+        }
+        if self.rate_curve.is_some()
+            && (self.rate.is_some()
+                || (self.recording.is_some()
+                    && matches!(self.replay_speed, ReplaySpeed::Multiplier(_))))
+        {
+            return Err("a rate curve cannot be combined with a rate or a timed replay".into());
+        }
+        if self
+            .recording
+            .as_ref()
+            .is_some_and(|recording| recording.is_empty())
+        {
+            return Err("cannot replay an empty recording".into());
+        }
+        if let ReplaySpeed::Multiplier(speed) = self.replay_speed {
+            if !speed.is_finite() || speed <= 0.0 {
+                return Err("replay speed must be finite and positive".into());
+            }
+        }
+        let schedule = match (self.recording, self.replay_speed, self.rate) {
+            (Some(_), ReplaySpeed::Multiplier(_), Some(_)) => {
+                return Err("rate cannot be combined with a timed replay".into());
+            }
+            (Some(recording), ReplaySpeed::Multiplier(speed), None) => {
+                Some(Schedule::replay(recording, speed))
+            }
+            (_, _, Some(rate)) => match self.burst {
+                Some(burst) => Some(Schedule::token_bucket(rate, burst)),
+                None => Some(Schedule::constant(rate)),
+            },
+            _ => self.rate_curve.as_ref().map(Schedule::curve),
+        }
+        .map(|schedule| schedule.for_users(concurrency));
+
+        if uri.scheme_str() == Some("https")
+            && self
+                .identity_profile
+                .as_ref()
+                .is_some_and(|profile| profile.fingerprint().is_some())
+        {
+            #[cfg(not(feature = "tls"))]
+            return Err("TLS fingerprint options require the tls feature".into());
+            #[cfg(feature = "tls")]
+            if self.tls_backend != TlsBackend::Rustls {
+                return Err(
+                    "TLS fingerprint options are only supported by the rustls backend".into(),
+                );
+            }
+        }
+
+        let tls_stats = TlsStats::default();
+        #[cfg(feature = "http3")]
+        let quic = match self.protocol {
+            Protocol::Http3 => Some(QuicConfig::new(&uri, &self.tls)?),
+            _ => None,
+        };
+        let https = match (uri.scheme_str(), &self.mode) {
+            #[cfg(feature = "http3")]
+            (Some("https"), Mode::Http) if self.protocol == Protocol::Http3 => None,
+            (Some("https"), Mode::Http)
+                if self.backend != Backend::Tokio || self.pipeline.is_some() =>
+            {
+                return Err(
+                    "HTTPS is only supported by the tokio backend without pipelining".into(),
+                );
+            }
+            #[cfg(any(feature = "tls", feature = "native-tls"))]
+            (Some("https"), Mode::Http) => Some(Https::new(
+                &uri,
+                &self.tls,
+                self.tls_backend,
+                self.protocol,
+                tls_stats.clone(),
+            )?),
+            #[cfg(not(any(feature = "tls", feature = "native-tls")))]
+            (Some("https"), Mode::Http) => {
+                return Err("HTTPS requires the tls or native-tls feature".into())
+            }
+            _ => None,
+        };
+        let operation = match &self.mode {
+            Mode::Http => self.custom,
+            Mode::Connect => Some(operation::connect(
+                addr,
+                self.proxy.clone(),
+                bind.clone(),
+                Duration::ZERO,
+            )),
+            #[cfg(feature = "tls")]
+            Mode::Storm(_)
+                if uri.scheme_str() == Some("https") && self.tls_backend != TlsBackend::Rustls =>
+            {
+                return Err("TLS connection storms only support the rustls backend".into());
+            }
+            #[cfg(feature = "tls")]
+            Mode::Storm(hold) if uri.scheme_str() == Some("https") => Some(tls::handshake(
+                addr,
+                tls::server_name(&uri)?,
+                tls::client_config(&self.tls, self.protocol)?,
+                false,
+                self.proxy.clone(),
+                *hold,
+                tls_stats.clone(),
+            )),
+            #[cfg(not(feature = "tls"))]
+            Mode::Storm(_) if uri.scheme_str() == Some("https") => {
+                return Err("TLS connection storms require the tls feature".into());
+            }
+            Mode::Storm(hold) => Some(operation::connect(
+                addr,
+                self.proxy.clone(),
+                bind.clone(),
+                *hold,
+            )),
+            Mode::Dns(query) => Some(dns::lookup(addr, query)?),
+            #[cfg(feature = "tls")]
+            Mode::TlsHandshake if self.tls_backend != TlsBackend::Rustls => {
+                return Err("TLS handshake mode only supports the rustls backend".into());
+            }
+            #[cfg(feature = "tls")]
+            Mode::TlsHandshake => Some(tls::handshake(
+                addr,
+                tls::server_name(&uri)?,
+                tls::client_config(&self.tls, self.protocol)?,
+                self.tls.resumption,
+                self.proxy.clone(),
+                Duration::ZERO,
+                tls_stats.clone(),
+            )),
+        };
+
+        let scenario = self
+            .scenario
+            .map(|scenario| scenario.expecting(expectation_matcher.clone()));
+        let user_factory = match self.virtual_user {
+            Some(user_factory) => user_factory,
+            None => Arc::new(move || {
+                Box::new(ClosureUser {
+                    make_request: make_request.clone(),
+                    expectation_matcher: expectation_matcher.clone(),
+                }) as Box<dyn VirtualUser>
+            }),
+        };
+
+        let user_factory: UserFactory = match self.feed {
+            Some(feed) => Arc::new(move || {
+                Box::new(FeedUser {
+                    inner: user_factory(),
+                    feed: feed.clone(),
+                }) as Box<dyn VirtualUser>
+            }),
+            None => user_factory,
+        };
+
+        let user_factory: UserFactory = match self.random_headers.is_empty() {
+            true => user_factory,
+            false => {
+                let headers = Arc::new(
+                    self.random_headers
+                        .into_iter()
+                        .map(|(name, value)| Ok((name.parse()?, value)))
+                        .collect::<Result<Vec<_>, Box<dyn Error + Send + Sync>>>()?,
+                );
+                Arc::new(move || {
+                    Box::new(RandomizedUser {
+                        inner: user_factory(),
+                        headers: headers.clone(),
+                    }) as Box<dyn VirtualUser>
+                })
+            }
+        };
+
+        #[cfg(feature = "grpc")]
+        let grpc = self.grpc.then(|| Arc::new(GrpcStats::default()));
+        #[cfg(feature = "grpc")]
+        let buffered = self.graphql || grpc.is_some();
+        #[cfg(not(feature = "grpc"))]
+        let buffered = self.graphql;
+        #[cfg(feature = "lua")]
+        let buffered = buffered || wrk.is_some();
+        #[cfg(feature = "grpc")]
+        let user_factory: UserFactory = match &grpc {
+            None => user_factory,
+            Some(stats) => {
+                let stats = stats.clone();
+                Arc::new(move || {
+                    Box::new(GrpcUser {
+                        inner: user_factory(),
+                        stats: stats.clone(),
+                    }) as Box<dyn VirtualUser>
+                })
+            }
+        };
+
+        let user_factory: UserFactory = match &self.identity_profile {
+            None => user_factory,
+            Some(profile) => {
+                let headers = Arc::new(profile.headers()?);
+                Arc::new(move || {
+                    Box::new(IdentifiedUser {
+                        inner: user_factory(),
+                        headers: headers.clone(),
+                    }) as Box<dyn VirtualUser>
+                })
+            }
+        };
+
+        let user_factory: UserFactory = match self.graphql {
+            false => user_factory,
+            true => Arc::new(move || {
+                Box::new(GraphqlUser {
+                    inner: user_factory(),
+                }) as Box<dyn VirtualUser>
+            }),
+        };
+
+        let mut echo = None;
+        let user_factory: UserFactory = match self.echo_header {
+            None => user_factory,
+            Some(header) => {
+                let header: HeaderName = header.parse()?;
+                let tracker = Arc::new(EchoTracker::default());
+                echo = Some(tracker.clone());
+                Arc::new(move || {
+                    Box::new(EchoUser {
+                        inner: user_factory(),
+                        header: header.clone(),
+                        tracker: tracker.clone(),
+                    }) as Box<dyn VirtualUser>
+                })
+            }
+        };
+
+        Ok(Swarm {
+            uri,
+            addr,
+            resolver,
+            dns_refresh: self.dns_refresh,
+            sockets: Sockets { bind, cap },
+            duration,
+            threads: self.threads,
+            concurrency,
+            user_factory,
+            annotator: self.annotator,
+            shared: self.shared,
+            clock: Clock::new(self.clock_source, self.calibrate_clock),
+            schedule,
+            scenario,
+            classes,
+            #[cfg(feature = "websocket")]
+            websocket: self.websocket,
+            #[cfg(feature = "mqtt")]
+            mqtt: self.mqtt,
+            #[cfg(feature = "resp")]
+            resp: self.resp,
+            #[cfg(feature = "kafka")]
+            kafka: self.kafka,
+            #[cfg(feature = "s3")]
+            s3: self.s3,
+            backend: self.backend,
+            pipeline: self.pipeline,
+            sse: self.sse,
+            follow_redirects: self.follow_redirects,
+            expect_continue: self.expect_continue,
+            #[cfg(feature = "compression")]
+            decompress: self.decompress,
+            protocol: self.protocol,
+            connections_per_thread,
+            mode: self.mode,
+            operation,
+            tls_stats,
+            proxy: self.proxy,
+            tunnel,
+            keepalive: self.keepalive,
+            https,
+            #[cfg(feature = "http3")]
+            quic,
+            tenants: self.tenants,
+            seed: self.seed,
+            region: self.region,
+            matcher: self.matcher,
+            echo,
+            uploads,
+            #[cfg(feature = "tls")]
+            doh: doh_stats,
+            #[cfg(any(feature = "lua", feature = "rhai"))]
+            script_errors,
+            #[cfg(feature = "lua")]
+            wrk,
+            faults: self.chaos.map(|chaos| Arc::new(Faults::new(chaos))),
+            budget: self
+                .requests
+                .map(|requests| Arc::new(RequestBudget::new(requests))),
+            churn: self.churn.map(|rate| Arc::new(Churn::new(rate))),
+            template,
+            buffered,
+            #[cfg(feature = "grpc")]
+            grpc,
+            #[cfg(feature = "grpc")]
+            grpc_streaming: self.grpc_streaming,
+            redactor: self.redactor,
+            config,
+            reporters: self.reporters,
+            report_interval: self.report_interval,
+            raise_fd_limit: self.raise_fd_limit,
+            timing_capacity: self.timing_capacity,
+            percentile_check: self
+                .percentile_check
+                .map(|workers| Arc::new(ExactSamples::new(self.threads, workers))),
+            latency_budgets: self.latency_budgets,
+            abort_on_panic: self.abort_on_panic,
+            counted_failures: self.counted_failures,
+        })
+    }
+
+    pub fn zerg(self) -> Result<BenchmarkResult, Box<dyn Error + Send + Sync>> {
+        self.build().map(|swarm| swarm.zerg())
+    }
+}
+
+pub extern crate serde_json;
+
+#[macro_export]
+macro_rules! json {
+    ($some_json:tt) => {
+        $crate::serde_json::to_vec(&$crate::serde_json::json!($some_json)).unwrap()
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        sync::Mutex,
+    };
+
+    use super::*;
+
+    /// Answers every bodiless request with an empty 200 until the test process exits.
+    fn serve() -> SocketAddr {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                std::thread::spawn(move || {
+                    let mut buf = [0; 4096];
+                    let mut pending = Vec::new();
+                    while let Ok(n @ 1..) = stream.read(&mut buf) {
+                        pending.extend_from_slice(&buf[..n]);
+                        while let Some(end) = pending.windows(4).position(|w| w == b"\r\n\r\n") {
+                            pending.drain(..end + 4);
+                            let ok = b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n";
+                            if stream.write_all(ok).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn config_describes_the_first_real_request() {
+        let addr = serve();
+        let result = swarm(format!("http://{addr}/").as_str())
+            .concurrency(1)
+            .requests(3)
+            .request(|uri| {
+                Request::put(uri)
+                    .header("authorization", "Bearer hunter2")
+                    .header("x-trace", "abc")
+                    .body(Body::empty())
+                    .unwrap()
+            })
+            .build()
+            .unwrap()
+            .zerg();
+
+        let config = result.config().unwrap();
+        assert_eq!(config.method(), Some("PUT"));
+        let headers: Vec<_> = config.headers().collect();
+        assert!(headers.contains(&("authorization", redact::REDACTED)));
+        assert!(headers.contains(&("x-trace", "abc")));
+    }
+
+    #[test]
+    fn scenarios_use_the_swarm_matcher_and_survive_bad_paths() {
+        let addr = serve();
+        let result = swarm(format!("http://{addr}/").as_str())
+            .concurrency(1)
+            .duration(Duration::from_millis(50))
+            .matching(Matcher::status(201))
+            .scenario(Scenario::new("flow", |client: Client| async move {
+                assert!(client.get("/not a path").await.is_err());
+                assert!(client.get("/ok").await.is_ok());
+            }))
+            .build()
+            .unwrap()
+            .zerg();
+
+        assert!(result.http_error_count() > 0);
+        assert_eq!(result.success_count(), 0);
+        assert_eq!(result.worker_panic_count(), 0);
+    }
+
+    #[test]
+    fn scenario_steps_are_labelled_by_position_or_name() {
+        let addr = serve();
+        let result = swarm(format!("http://{addr}/").as_str())
+            .concurrency(1)
+            .duration(Duration::from_millis(50))
+            .scenario(Scenario::new("flow", |client: Client| async move {
+                client.get("/").await.ok();
+                client.step("login").post("/login", "").await.ok();
+            }))
+            .build()
+            .unwrap()
+            .zerg();
+
+        let labels: Vec<_> = result.labels().map(|(label, _)| label).collect();
+        assert_eq!(labels, ["flow/1", "flow/login"]);
+        assert_eq!(result.http_error_count(), 0);
+    }
+
+    struct Quitter;
+
+    impl VirtualUser for Quitter {
+        fn next_request(&mut self, uri: &Uri, ctx: &UserContext) -> Option<Request<Body>> {
+            (ctx.id() != 0).then(|| Request::get(uri).body(Body::empty()).unwrap())
+        }
+    }
+
+    #[test]
+    fn budgets_are_refunded_when_users_stop_or_requests_abort() {
+        let addr = serve();
+        let uri = format!("http://{addr}/");
+        let quitting = swarm(uri.as_str())
+            .concurrency(2)
+            .requests(10)
+            .virtual_user(|| Quitter)
+            .build()
+            .unwrap()
+            .zerg();
+        assert_eq!(quitting.total_request_count(), 10);
+
+        let aborting = swarm(uri.as_str())
+            .concurrency(1)
+            .requests(10)
+            .chaos(Chaos::new().abort_connections(0.5))
+            .build()
+            .unwrap()
+            .zerg();
+        assert_eq!(aborting.total_request_count(), 10);
+    }
+
+    struct Lifecycle(Arc<Mutex<Vec<&'static str>>>);
+
+    impl VirtualUser for Lifecycle {
+        fn on_start(&mut self, _ctx: &UserContext) {
+            self.0.lock().unwrap().push("start");
+        }
+
+        fn next_request(&mut self, uri: &Uri, _ctx: &UserContext) -> Option<Request<Body>> {
+            self.0.lock().unwrap().push("request");
+            Some(Request::get(uri).body(Body::empty()).unwrap())
+        }
+
+        fn on_response(&mut self, res: Response<Body>, _ctx: &UserContext) -> bool {
+            self.0.lock().unwrap().push("response");
+            res.status().is_success()
+        }
+
+        fn on_stop(&mut self, _ctx: &UserContext) {
+            self.0.lock().unwrap().push("stop");
+        }
+    }
+
+    #[test]
+    fn virtual_users_start_answer_each_request_and_stop() {
+        let addr = serve();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let log = events.clone();
+        let result = swarm(format!("http://{addr}/").as_str())
+            .concurrency(1)
+            .requests(3)
+            .virtual_user(move || Lifecycle(log.clone()))
+            .build()
+            .unwrap()
+            .zerg();
+
+        assert_eq!(result.success_count(), 3);
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "start", "request", "response", "request", "response", "request", "response",
+                "stop"
+            ]
+        );
+    }
+
+    #[test]
+    fn pipelined_users_complete_requests_without_errors() {
+        let addr = serve();
+        let result = swarm(format!("http://{addr}/").as_str())
+            .concurrency(2)
+            .duration(Duration::from_millis(100))
+            .pipeline(4)
+            .build()
+            .unwrap()
+            .zerg();
+
+        assert!(result.success_count() > 0);
+        assert_eq!(result.tcp_error_count(), 0);
+    }
+
+    fn rejection(builder: SwarmBuilder) -> String {
+        builder.build().err().unwrap().to_string()
+    }
+
+    fn class() -> TrafficClass {
+        TrafficClass::new("read", |uri, _| {
+            Request::get(uri).body(Body::empty()).unwrap()
+        })
+    }
+
+    #[test]
+    fn exclusive_swarms_reject_http_loop_options() {
+        let custom = || Swarm::custom(|_| async { Ok::<_, std::io::Error>(()) });
+        assert!(rejection(custom().traffic_class(class())).starts_with("custom operation swarms"));
+        assert!(custom().build().is_ok());
+
+        #[cfg(feature = "mqtt")]
+        assert!(rejection(
+            swarm("mqtt://127.0.0.1:1883")
+                .mqtt(Mqtt::new("t"))
+                .traffic_class(class())
+        )
+        .starts_with("MQTT swarms cannot be combined"));
+        #[cfg(feature = "websocket")]
+        {
+            let websocket =
+                || swarm("ws://127.0.0.1:9001").websocket(WebSocket::text(|_| "hi".into()));
+            assert!(rejection(websocket().server_sent_events()).starts_with("WebSocket swarms"));
+            #[cfg(feature = "mqtt")]
+            assert_eq!(
+                rejection(websocket().mqtt(Mqtt::new("t"))),
+                "WebSocket swarms cannot be combined with MQTT"
+            );
+        }
+    }
 }
@@ -0,0 +1,175 @@
+use std::{error::Error, fmt::Display, io, mem, time::Duration};
+
+use crate::result::Outcome;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exhaustion {
+    FileDescriptors,
+    LocalPorts,
+}
+
+impl Exhaustion {
+    fn of(err: &(dyn Error + 'static)) -> Option<Self> {
+        let mut source = Some(err);
+        while let Some(err) = source {
+            if let Some(code) = err
+                .downcast_ref::<io::Error>()
+                .and_then(io::Error::raw_os_error)
+            {
+                match code {
+                    libc::EMFILE | libc::ENFILE => return Some(Self::FileDescriptors),
+                    libc::EADDRNOTAVAIL | libc::EADDRINUSE => return Some(Self::LocalPorts),
+                    _ => {}
+                }
+            }
+            source = err.source();
+        }
+        None
+    }
+}
+
+impl Display for Exhaustion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FileDescriptors => write!(f, "file descriptor limit reached"),
+            Self::LocalPorts => write!(f, "local ports exhausted"),
+        }
+    }
+}
+
+impl Outcome {
+    pub(crate) fn from_error(err: &(dyn Error + 'static)) -> Self {
+        #[cfg(feature = "http3")]
+        if err.is::<crate::quic::HandshakeError>() {
+            return Outcome::QuicHandshake;
+        }
+        #[cfg(any(feature = "tls", feature = "native-tls"))]
+        if is_tls(err) {
+            return Outcome::TlsHandshake;
+        }
+        match Exhaustion::of(err) {
+            Some(exhaustion) => Outcome::Exhausted(exhaustion),
+            None if is_connect(err) => Outcome::ConnectError,
+            None => Outcome::TcpError,
+        }
+    }
+}
+
+fn is_connect(err: &(dyn Error + 'static)) -> bool {
+    if err
+        .downcast_ref::<hyper::Error>()
+        .is_some_and(hyper::Error::is_connect)
+    {
+        return true;
+    }
+    err.downcast_ref::<io::Error>().is_some_and(|err| {
+        matches!(
+            err.kind(),
+            io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::HostUnreachable
+                | io::ErrorKind::NetworkUnreachable
+        )
+    })
+}
+
+#[cfg(any(feature = "tls", feature = "native-tls"))]
+fn is_tls(err: &(dyn Error + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        let err = match err.downcast_ref::<io::Error>().and_then(io::Error::get_ref) {
+            Some(inner) => inner,
+            None => err,
+        };
+        #[cfg(feature = "tls")]
+        if err.is::<rustls::Error>() {
+            return true;
+        }
+        #[cfg(feature = "native-tls")]
+        if err.is::<native_tls::Error>() {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+pub(crate) fn fd_limit() -> Option<u64> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    match unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } {
+        0 => Some(limit.rlim_cur),
+        _ => None,
+    }
+}
+
+pub(crate) fn raise_fd_limit() -> io::Result<u64> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if limit.rlim_cur < limit.rlim_max {
+        limit.rlim_cur = limit.rlim_max;
+        if unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(limit.rlim_cur)
+}
+
+pub(crate) fn cpu_time() -> Option<Duration> {
+    let mut usage = unsafe { mem::zeroed::<libc::rusage>() };
+    match unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } {
+        0 => Some(timeval(usage.ru_utime) + timeval(usage.ru_stime)),
+        _ => None,
+    }
+}
+
+fn timeval(time: libc::timeval) -> Duration {
+    Duration::new(time.tv_sec as u64, time.tv_usec as u32 * 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exhaustion_is_found_through_wrapped_errors() {
+        let fds = io::Error::from_raw_os_error(libc::EMFILE);
+        assert_eq!(Exhaustion::of(&fds), Some(Exhaustion::FileDescriptors));
+        let ports = io::Error::other(io::Error::from_raw_os_error(libc::EADDRNOTAVAIL));
+        assert_eq!(
+            Exhaustion::of(ports.get_ref().unwrap()),
+            Some(Exhaustion::LocalPorts)
+        );
+        assert_eq!(Exhaustion::LocalPorts.to_string(), "local ports exhausted");
+
+        assert!(matches!(
+            Outcome::from_error(&fds),
+            Outcome::Exhausted(Exhaustion::FileDescriptors)
+        ));
+        let refused = io::Error::from(io::ErrorKind::ConnectionRefused);
+        assert!(matches!(
+            Outcome::from_error(&refused),
+            Outcome::ConnectError
+        ));
+        let reset = io::Error::from(io::ErrorKind::ConnectionReset);
+        assert!(matches!(Outcome::from_error(&reset), Outcome::TcpError));
+    }
+
+    #[test]
+    fn process_limits_and_cpu_time_are_readable() {
+        assert!(fd_limit().is_some_and(|limit| limit > 0));
+        assert!(raise_fd_limit().unwrap() >= fd_limit().unwrap());
+        assert!(cpu_time().is_some());
+        let time = libc::timeval {
+            tv_sec: 2,
+            tv_usec: 500_000,
+        };
+        assert_eq!(timeval(time), Duration::from_millis(2500));
+    }
+}
@@ -0,0 +1,618 @@
+use std::{
+    cell::RefCell,
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    error::Error,
+    fs,
+    net::SocketAddr,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use futures::FutureExt;
+use hyper::{body, Body, Request, Response, Uri};
+use mlua::{Function, Lua, Table, UserData, UserDataFields, UserDataMethods, Value};
+
+use crate::{result::BenchmarkResult, script::ScriptErrors, uri::try_rebase};
+
+static NEXT_SCRIPT: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static STATES: RefCell<HashMap<usize, Local>> = RefCell::new(HashMap::new());
+}
+
+pub struct WrkScript {
+    source: String,
+    name: String,
+    args: Vec<String>,
+}
+
+impl WrkScript {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let path = path.as_ref();
+        Ok(Self {
+            source: fs::read_to_string(path)?,
+            name: path.display().to_string(),
+            args: Vec::new(),
+        })
+    }
+
+    pub fn from_source(source: impl Into<String>) -> Self {
+        Self {
+            source: source.into(),
+            name: "script".to_string(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Arguments handed to each thread's `init(args)`, like the ones after `--` on wrk's
+    /// command line.
+    pub fn args(self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            args: args.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    /// Loads the script into the main state and calls `setup(thread)` once per worker thread.
+    pub(crate) fn start(
+        self,
+        uri: &Uri,
+        addr: SocketAddr,
+        threads: usize,
+    ) -> Result<WrkState, Box<dyn Error + Send + Sync>> {
+        let main = load(&self.source, &self.name, uri)?;
+        let threads: Vec<Thread> = (0..threads.max(1))
+            .map(|id| Thread {
+                id: id + 1,
+                addr,
+                vars: Arc::default(),
+                local: false,
+            })
+            .collect();
+        if let Some(setup) = main.globals().get::<Option<Function>>("setup")? {
+            for thread in &threads {
+                setup.call::<()>(thread.clone())?;
+            }
+        }
+
+        Ok(WrkState {
+            id: NEXT_SCRIPT.fetch_add(1, Ordering::Relaxed),
+            source: self.source,
+            name: self.name,
+            args: self.args,
+            uri: uri.clone(),
+            threads,
+            next_thread: AtomicUsize::new(0),
+            main: Mutex::new(main),
+            errors: Arc::default(),
+        })
+    }
+}
+
+/// A script shared by every worker. Each worker thread lazily gets its own Lua state, so
+/// `request` and `response` never contend; only `setup` and `done` run on the main state.
+pub(crate) struct WrkState {
+    id: usize,
+    source: String,
+    name: String,
+    args: Vec<String>,
+    uri: Uri,
+    threads: Vec<Thread>,
+    next_thread: AtomicUsize,
+    main: Mutex<Lua>,
+    errors: Arc<ScriptErrors>,
+}
+
+impl WrkState {
+    pub(crate) fn errors(&self) -> Arc<ScriptErrors> {
+        self.errors.clone()
+    }
+
+    pub(crate) fn request(&self, uri: &Uri) -> Request<Body> {
+        match self.try_request(uri) {
+            Ok(req) => req,
+            Err(_) => self.errors.failed(),
+        }
+    }
+
+    pub(crate) fn response(&self, res: Response<Body>) -> bool {
+        match self.try_response(res) {
+            Ok(success) => success,
+            Err(_) => {
+                self.errors.record();
+                false
+            }
+        }
+    }
+
+    /// Calls `done(summary, latency, requests)` on the main state once the run is over.
+    pub(crate) fn done(
+        &self,
+        result: &BenchmarkResult,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let lua = self.main.lock().unwrap();
+        let Some(done) = lua.globals().get::<Option<Function>>("done")? else {
+            return Ok(());
+        };
+
+        let summary = lua.create_table()?;
+        summary.set("duration", result.total_time().as_micros() as u64)?;
+        summary.set("requests", result.total_request_count())?;
+        summary.set("bytes", result.downloaded_bytes())?;
+        let errors = lua.create_table()?;
+        errors.set("connect", result.connect_error_count())?;
+        errors.set("read", result.transfer_error_count())?;
+        errors.set("write", 0)?;
+        errors.set("status", result.http_error_count())?;
+        errors.set("timeout", result.continue_timeout_count())?;
+        summary.set("errors", errors)?;
+
+        let latency = Stats::new(result.timings().map(|t| t.as_secs_f64() * 1e6).collect());
+        let resolution = result.timeline().resolution().as_secs_f64();
+        let requests = Stats::new(
+            result
+                .timeline()
+                .buckets()
+                .map(|(_, bucket)| bucket.total_count() as f64 / resolution)
+                .collect(),
+        );
+        done.call::<()>((summary, latency, requests))?;
+        Ok(())
+    }
+
+    fn local<R>(
+        &self,
+        f: impl FnOnce(&Lua) -> Result<R, Box<dyn Error + Send + Sync>>,
+    ) -> Result<R, Box<dyn Error + Send + Sync>> {
+        STATES.with(|states| {
+            let mut states = states.borrow_mut();
+            let local = match states.entry(self.id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(self.spawn()?),
+            };
+            f(&local.lua)
+        })
+    }
+
+    /// Builds the calling worker thread's state: copies in whatever `setup` stored with
+    /// `thread:set`, exposes the thread as `wrk.thread` and calls `init(args)`.
+    fn spawn(&self) -> Result<Local, Box<dyn Error + Send + Sync>> {
+        let index = self.next_thread.fetch_add(1, Ordering::Relaxed) % self.threads.len();
+        let thread = Thread {
+            local: true,
+            ..self.threads[index].clone()
+        };
+
+        let lua = load(&self.source, &self.name, &self.uri)?;
+        for (name, var) in thread.vars.lock().unwrap().iter() {
+            lua.globals().set(name.as_str(), var.to_lua(&lua)?)?;
+        }
+        let wrk: Table = lua.globals().get("wrk")?;
+        wrk.set("thread", thread.clone())?;
+        if let Some(init) = lua.globals().get::<Option<Function>>("init")? {
+            init.call::<()>(lua.create_sequence_from(self.args.iter().map(String::as_str))?)?;
+        }
+        Ok(Local { lua, thread })
+    }
+
+    fn try_request(&self, uri: &Uri) -> Result<Request<Body>, Box<dyn Error + Send + Sync>> {
+        let raw = self.local(|lua| {
+            Ok(match lua.globals().get::<Option<Function>>("request")? {
+                Some(request) => request.call::<mlua::String>(())?.as_bytes().to_vec(),
+                None => {
+                    let wrk: Table = lua.globals().get("wrk")?;
+                    let format: Function = wrk.get("format")?;
+                    format.call::<mlua::String>(())?.as_bytes().to_vec()
+                }
+            })
+        })?;
+        parse_request(uri, &raw)
+    }
+
+    fn try_response(&self, res: Response<Body>) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let (parts, body) = res.into_parts();
+        let success = parts.status.is_success();
+        self.local(|lua| {
+            let response = match lua.globals().get::<Option<Function>>("response")? {
+                Some(response) => response,
+                None => return Ok(success),
+            };
+
+            let headers = lua.create_table()?;
+            for (name, value) in &parts.headers {
+                if let Ok(value) = value.to_str() {
+                    headers.set(name.as_str(), value)?;
+                }
+            }
+            let body = match body::to_bytes(body).now_or_never() {
+                Some(body) => Value::String(lua.create_string(body?)?),
+                None => Value::Nil,
+            };
+
+            Ok(
+                match response.call::<Option<bool>>((parts.status.as_u16(), headers, body))? {
+                    Some(success) => success,
+                    None => success,
+                },
+            )
+        })
+    }
+}
+
+/// A worker thread's own state. When the thread exits, its script globals are copied back
+/// so `thread:get` in `done` sees their final values, as wrk's does.
+struct Local {
+    lua: Lua,
+    thread: Thread,
+}
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        let fresh = Lua::new();
+        let builtins = fresh.globals();
+        let mut vars = self.thread.vars.lock().unwrap();
+        for (name, value) in self.lua.globals().pairs::<String, Value>().flatten() {
+            if name == "wrk" || builtins.contains_key(name.as_str()).unwrap_or(true) {
+                continue;
+            }
+            if let Ok(var) = Var::from_lua(value) {
+                vars.insert(name, var);
+            }
+        }
+    }
+}
+
+/// wrk's `thread` object. The main state's copy reads and writes the shared variables
+/// directly; a worker's copy works on its own globals.
+#[derive(Clone)]
+struct Thread {
+    id: usize,
+    addr: SocketAddr,
+    vars: Arc<Mutex<BTreeMap<String, Var>>>,
+    local: bool,
+}
+
+impl UserData for Thread {
+    fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("id", |_, thread| Ok(thread.id));
+        fields.add_field_method_get("addr", |_, thread| Ok(thread.addr.to_string()));
+    }
+
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("set", |lua, thread, (name, value): (String, Value)| {
+            let var = Var::from_lua(value.clone())?;
+            if thread.local {
+                lua.globals().set(name.as_str(), value)?;
+            }
+            thread.vars.lock().unwrap().insert(name, var);
+            Ok(())
+        });
+        methods.add_method("get", |lua, thread, name: String| {
+            if thread.local {
+                return lua.globals().get::<Value>(name);
+            }
+            match thread.vars.lock().unwrap().get(&name) {
+                Some(var) => var.to_lua(lua),
+                None => Ok(Value::Nil),
+            }
+        });
+    }
+}
+
+/// A Lua value that can be copied between states.
+#[derive(Clone, Debug, PartialEq)]
+enum Var {
+    Nil,
+    Boolean(bool),
+    Integer(i64),
+    Number(f64),
+    String(Vec<u8>),
+    Table(Vec<(Var, Var)>),
+}
+
+impl Var {
+    fn from_lua(value: Value) -> mlua::Result<Self> {
+        Self::nested(value, 0)
+    }
+
+    fn nested(value: Value, depth: usize) -> mlua::Result<Self> {
+        if depth > 32 {
+            return Err(mlua::Error::runtime(
+                "cannot share tables nested this deeply",
+            ));
+        }
+        Ok(match value {
+            Value::Nil => Self::Nil,
+            Value::Boolean(b) => Self::Boolean(b),
+            Value::Integer(i) => Self::Integer(i),
+            Value::Number(n) => Self::Number(n),
+            Value::String(s) => Self::String(s.as_bytes().to_vec()),
+            Value::Table(table) => Self::Table(
+                table
+                    .pairs::<Value, Value>()
+                    .map(|pair| {
+                        let (key, value) = pair?;
+                        Ok((
+                            Self::nested(key, depth + 1)?,
+                            Self::nested(value, depth + 1)?,
+                        ))
+                    })
+                    .collect::<mlua::Result<_>>()?,
+            ),
+            other => {
+                return Err(mlua::Error::runtime(format!(
+                    "cannot share a {} between threads",
+                    other.type_name()
+                )))
+            }
+        })
+    }
+
+    fn to_lua(&self, lua: &Lua) -> mlua::Result<Value> {
+        Ok(match self {
+            Self::Nil => Value::Nil,
+            Self::Boolean(b) => Value::Boolean(*b),
+            Self::Integer(i) => Value::Integer(*i),
+            Self::Number(n) => Value::Number(*n),
+            Self::String(s) => Value::String(lua.create_string(s)?),
+            Self::Table(pairs) => {
+                let table = lua.create_table()?;
+                for (key, value) in pairs {
+                    table.raw_set(key.to_lua(lua)?, value.to_lua(lua)?)?;
+                }
+                Value::Table(table)
+            }
+        })
+    }
+}
+
+/// The `latency` and `requests` objects handed to `done`.
+struct Stats {
+    sorted: Vec<f64>,
+}
+
+impl Stats {
+    fn new(mut values: Vec<f64>) -> Self {
+        values.sort_by(f64::total_cmp);
+        Self { sorted: values }
+    }
+
+    fn mean(&self) -> f64 {
+        match self.sorted.len() {
+            0 => 0.0,
+            n => self.sorted.iter().sum::<f64>() / n as f64,
+        }
+    }
+
+    fn stdev(&self) -> f64 {
+        if self.sorted.len() < 2 {
+            return 0.0;
+        }
+        let mean = self.mean();
+        let variance = self.sorted.iter().map(|v| (v - mean).powi(2)).sum::<f64>()
+            / (self.sorted.len() - 1) as f64;
+        variance.sqrt()
+    }
+
+    /// Nearest-rank percentile, `p` in 0..=100 as wrk takes it.
+    fn percentile(&self, p: f64) -> f64 {
+        if self.sorted.is_empty() {
+            return 0.0;
+        }
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * self.sorted.len() as f64).ceil() as usize;
+        self.sorted[rank.clamp(1, self.sorted.len()) - 1]
+    }
+}
+
+impl UserData for Stats {
+    fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("min", |_, stats| {
+            Ok(stats.sorted.first().copied().unwrap_or(0.0))
+        });
+        fields.add_field_method_get("max", |_, stats| {
+            Ok(stats.sorted.last().copied().unwrap_or(0.0))
+        });
+        fields.add_field_method_get("mean", |_, stats| Ok(stats.mean()));
+        fields.add_field_method_get("stdev", |_, stats| Ok(stats.stdev()));
+    }
+
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("percentile", |_, stats, p: f64| Ok(stats.percentile(p)));
+    }
+}
+
+/// Creates a state with the `wrk` table and runs the script's top level.
+fn load(source: &str, name: &str, uri: &Uri) -> Result<Lua, Box<dyn Error + Send + Sync>> {
+    let lua = Lua::new();
+    let wrk = lua.create_table()?;
+    wrk.set("scheme", uri.scheme_str().unwrap_or("http"))?;
+    wrk.set("host", uri.host().unwrap_or_default())?;
+    wrk.set("port", uri.port_u16())?;
+    wrk.set("method", "GET")?;
+    wrk.set(
+        "path",
+        uri.path_and_query().map(|p| p.as_str()).unwrap_or("/"),
+    )?;
+    wrk.set("headers", lua.create_table()?)?;
+    wrk.set("body", mlua::Nil)?;
+    wrk.set(
+        "format",
+        lua.create_function(
+            |lua,
+             (method, path, headers, body): (
+                Option<String>,
+                Option<String>,
+                Option<Table>,
+                Option<String>,
+            )| {
+                let wrk: Table = lua.globals().get("wrk")?;
+                let method = method.unwrap_or(wrk.get("method")?);
+                let path = path.unwrap_or(wrk.get("path")?);
+                let headers = headers.unwrap_or(wrk.get("headers")?);
+                let body = body.or(wrk.get("body")?);
+
+                let mut req = format!("{method} {path} HTTP/1.1\r\n");
+                for pair in headers.pairs::<String, String>() {
+                    let (name, value) = pair?;
+                    req.push_str(&format!("{name}: {value}\r\n"));
+                }
+                if let Some(body) = &body {
+                    req.push_str(&format!("Content-Length: {}\r\n", body.len()));
+                }
+                req.push_str("\r\n");
+                if let Some(body) = &body {
+                    req.push_str(body);
+                }
+                Ok(req)
+            },
+        )?,
+    )?;
+    lua.globals().set("wrk", wrk)?;
+
+    lua.load(source).set_name(name).exec()?;
+    Ok(lua)
+}
+
+fn parse_request(uri: &Uri, raw: &[u8]) -> Result<Request<Body>, Box<dyn Error + Send + Sync>> {
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut parsed = httparse::Request::new(&mut headers);
+    let offset = match parsed.parse(raw)? {
+        httparse::Status::Complete(offset) => offset,
+        httparse::Status::Partial => raw.len(),
+    };
+
+    let mut req = Request::builder()
+        .uri(try_rebase(uri, parsed.path.unwrap_or("/"))?)
+        .method(parsed.method.unwrap_or("GET"));
+    for header in parsed.headers.iter() {
+        if !header.name.eq_ignore_ascii_case("content-length") {
+            req = req.header(header.name, header.value);
+        }
+    }
+    Ok(req.body(Body::from(raw[offset..].to_vec()))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start(source: &str, threads: usize) -> WrkState {
+        WrkScript::from_source(source)
+            .args(["fast"])
+            .start(
+                &"http://localhost:8080/".parse().unwrap(),
+                SocketAddr::from(([127, 0, 0, 1], 8080)),
+                threads,
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn threads_get_their_own_state() {
+        let state = Arc::new(start(
+            r#"
+            function setup(thread)
+                thread:set("offset", thread.id * 100)
+            end
+            function init(args)
+                mode = args[1]
+                count = 0
+            end
+            function request()
+                count = count + 1
+                return wrk.format(nil, "/" .. mode .. "/" .. (offset + count))
+            end
+            "#,
+            2,
+        ));
+
+        let mut paths: Vec<String> = (0..2)
+            .map(|_| {
+                let state = state.clone();
+                std::thread::spawn(move || {
+                    let uri = "http://localhost:8080/".parse().unwrap();
+                    state.request(&uri);
+                    state.request(&uri).uri().path().to_string()
+                })
+                .join()
+                .unwrap()
+            })
+            .collect();
+        paths.sort();
+        assert_eq!(paths, ["/fast/102", "/fast/202"]);
+    }
+
+    #[test]
+    fn done_sees_thread_variables() {
+        let state = Arc::new(start(
+            r#"
+            threads = {}
+            function setup(thread)
+                table.insert(threads, thread)
+            end
+            function init(args)
+                seen = 0
+            end
+            function response(status, headers, body)
+                seen = seen + #body
+            end
+            function done(summary, latency, requests)
+                total = 0
+                for _, thread in ipairs(threads) do
+                    total = total + thread:get("seen")
+                end
+                p50 = latency:percentile(50)
+            end
+            "#,
+            1,
+        ));
+
+        let worker = state.clone();
+        std::thread::spawn(move || {
+            assert!(worker.response(Response::new(Body::from("hello"))));
+            assert!(worker.response(Response::new(Body::from("abc"))));
+        })
+        .join()
+        .unwrap();
+
+        state.done(&BenchmarkResult::default()).unwrap();
+        let lua = state.main.lock().unwrap();
+        assert_eq!(lua.globals().get::<i64>("total").unwrap(), 8);
+        assert_eq!(lua.globals().get::<f64>("p50").unwrap(), 0.0);
+    }
+
+    #[test]
+    fn vars_round_trip_between_states() {
+        let from = Lua::new();
+        let value: Value = from
+            .load(r#"{ 1, "two", nested = { yes = true } }"#)
+            .eval()
+            .unwrap();
+        let var = Var::from_lua(value).unwrap();
+
+        let to = Lua::new();
+        to.globals().set("copy", var.to_lua(&to).unwrap()).unwrap();
+        let check: bool = to
+            .load(r#"return copy[1] == 1 and copy[2] == "two" and copy.nested.yes"#)
+            .eval()
+            .unwrap();
+        assert!(check);
+
+        let function = Value::Function(from.create_function(|_, ()| Ok(())).unwrap());
+        assert!(Var::from_lua(function).is_err());
+    }
+
+    #[test]
+    fn stats_use_nearest_rank_percentiles() {
+        let stats = Stats::new(vec![4.0, 1.0, 3.0, 2.0]);
+        assert_eq!(stats.percentile(50.0), 2.0);
+        assert_eq!(stats.percentile(75.0), 3.0);
+        assert_eq!(stats.percentile(100.0), 4.0);
+        assert_eq!(stats.percentile(0.0), 1.0);
+        assert_eq!(stats.mean(), 2.5);
+        assert_eq!(Stats::new(Vec::new()).percentile(99.0), 0.0);
+    }
+}
@@ -0,0 +1,178 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use hyper::{header::HeaderName, Body, Response};
+
+type Check = Arc<dyn Fn(&Response<Body>) -> bool + Send + Sync + 'static>;
+
+#[derive(Clone)]
+pub struct Matcher(Node);
+
+#[derive(Clone)]
+enum Node {
+    Leaf {
+        name: Arc<str>,
+        check: Check,
+        failures: Arc<AtomicUsize>,
+    },
+    All(Vec<Matcher>),
+    Any(Vec<Matcher>),
+    Not(Box<Matcher>),
+}
+
+impl Matcher {
+    pub fn new(
+        name: impl Into<Arc<str>>,
+        check: impl Fn(&Response<Body>) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self(Node::Leaf {
+            name: name.into(),
+            check: Arc::new(check),
+            failures: Arc::default(),
+        })
+    }
+
+    pub fn success() -> Self {
+        Self::new("success", |res| res.status().is_success())
+    }
+
+    pub fn status(status: u16) -> Self {
+        Self::new(format!("status {status}"), move |res| {
+            res.status().as_u16() == status
+        })
+    }
+
+    pub fn header(name: &str) -> Self {
+        let header = HeaderName::from_bytes(name.as_bytes()).ok();
+        Self::new(format!("header {name}"), move |res| {
+            header
+                .as_ref()
+                .is_some_and(|header| res.headers().contains_key(header))
+        })
+    }
+
+    pub fn header_value(name: &str, value: impl Into<String>) -> Self {
+        let header = HeaderName::from_bytes(name.as_bytes()).ok();
+        let value = value.into();
+        Self::new(format!("header {name}: {value}"), move |res| {
+            header.as_ref().is_some_and(|header| {
+                res.headers()
+                    .get_all(header)
+                    .iter()
+                    .any(|found| found.as_bytes() == value.as_bytes())
+            })
+        })
+    }
+
+    pub(crate) fn matches(&self, res: &Response<Body>) -> bool {
+        self.evaluate(res, false)
+    }
+
+    fn evaluate(&self, res: &Response<Body>, negated: bool) -> bool {
+        match &self.0 {
+            Node::Leaf {
+                check, failures, ..
+            } => {
+                let matched = check(res);
+                if matched == negated {
+                    failures.fetch_add(1, Ordering::Relaxed);
+                }
+                matched
+            }
+            Node::All(matchers) => {
+                matchers
+                    .iter()
+                    .filter(|matcher| !matcher.evaluate(res, negated))
+                    .count()
+                    == 0
+            }
+            Node::Any(matchers) => {
+                matchers
+                    .iter()
+                    .filter(|matcher| matcher.evaluate(res, negated))
+                    .count()
+                    > 0
+            }
+            Node::Not(matcher) => !matcher.evaluate(res, !negated),
+        }
+    }
+
+    pub(crate) fn failures(&self) -> Vec<(String, usize)> {
+        self.collect_failures(false)
+    }
+
+    fn collect_failures(&self, negated: bool) -> Vec<(String, usize)> {
+        match &self.0 {
+            Node::Leaf { name, failures, .. } => {
+                let failures = failures.load(Ordering::Relaxed);
+                match negated {
+                    true => vec![(format!("not {name}"), failures)],
+                    false => vec![(name.to_string(), failures)],
+                }
+            }
+            Node::All(matchers) | Node::Any(matchers) => matchers
+                .iter()
+                .flat_map(|matcher| matcher.collect_failures(negated))
+                .collect(),
+            Node::Not(matcher) => matcher.collect_failures(!negated),
+        }
+    }
+}
+
+pub fn all_of(matchers: impl IntoIterator<Item = Matcher>) -> Matcher {
+    Matcher(Node::All(matchers.into_iter().collect()))
+}
+
+pub fn any_of(matchers: impl IntoIterator<Item = Matcher>) -> Matcher {
+    Matcher(Node::Any(matchers.into_iter().collect()))
+}
+
+pub fn not(matcher: Matcher) -> Matcher {
+    Matcher(Node::Not(Box::new(matcher)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(status: u16, headers: &[(&str, &str)]) -> Response<Body> {
+        let mut res = Response::builder().status(status);
+        for (name, value) in headers {
+            res = res.header(*name, *value);
+        }
+        res.body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn leaves_check_status_and_headers() {
+        let ok = response(200, &[("x-cache", "HIT"), ("x-cache", "MISS")]);
+        assert!(Matcher::success().matches(&ok));
+        assert!(!Matcher::status(201).matches(&ok));
+        assert!(Matcher::header("X-Cache").matches(&ok));
+        assert!(Matcher::header_value("x-cache", "MISS").matches(&ok));
+        assert!(!Matcher::header_value("x-cache", "miss").matches(&ok));
+        assert!(!Matcher::header("bad header").matches(&ok));
+    }
+
+    #[test]
+    fn failures_are_charged_to_the_leaves_that_decided() {
+        let matcher = all_of([
+            Matcher::success(),
+            any_of([Matcher::status(200), Matcher::status(204)]),
+            not(Matcher::header("x-error")),
+        ]);
+        assert!(matcher.matches(&response(200, &[])));
+        assert!(!matcher.matches(&response(500, &[("x-error", "1")])));
+        assert_eq!(
+            matcher.failures(),
+            [
+                ("success".to_string(), 1),
+                ("status 200".to_string(), 1),
+                ("status 204".to_string(), 2),
+                ("not header x-error".to_string(), 1),
+            ]
+        );
+    }
+}
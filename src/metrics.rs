@@ -0,0 +1,317 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    mem,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+#[cfg(feature = "compression")]
+use crate::encoding::Decoded;
+use crate::{
+    accuracy::ExactSamples,
+    breakdown::TimeBreakdown,
+    cache::CacheStatus,
+    expect::Continue,
+    redirect::Followed,
+    result::{merge_reservoirs, BenchmarkResult, Outcome},
+};
+
+const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Default)]
+pub(crate) struct Metrics {
+    total: Mutex<BenchmarkResult>,
+    interval: Option<Mutex<BenchmarkResult>>,
+    timing_capacity: Option<usize>,
+    budgets: BTreeMap<String, Duration>,
+}
+
+impl Metrics {
+    pub(crate) fn with_intervals(self) -> Self {
+        Self {
+            interval: Some(Mutex::default()),
+            ..self
+        }
+    }
+
+    pub(crate) fn with_timing_capacity(self, timing_capacity: Option<usize>) -> Self {
+        Self {
+            timing_capacity,
+            ..self
+        }
+    }
+
+    pub(crate) fn with_budgets(self, budgets: BTreeMap<String, Duration>) -> Self {
+        Self { budgets, ..self }
+    }
+
+    pub(crate) fn take(&self) -> BenchmarkResult {
+        mem::take(&mut *self.total.lock().unwrap())
+    }
+
+    pub(crate) fn take_interval(&self) -> Option<BenchmarkResult> {
+        let interval = self.interval.as_ref()?;
+        Some(mem::take(&mut *interval.lock().unwrap()))
+    }
+
+    pub(crate) fn total_requests(&self) -> usize {
+        self.total.lock().unwrap().total_request_count()
+    }
+
+    fn merge(&self, batch: BenchmarkResult) {
+        if let Some(interval) = &self.interval {
+            let mut interval = interval.lock().unwrap();
+            match self.timing_capacity {
+                Some(capacity) => {
+                    interval.timings = merge_reservoirs(
+                        mem::take(&mut interval.timings),
+                        interval.timings_seen,
+                        batch.timings.clone(),
+                        batch.timings_seen,
+                        capacity,
+                    );
+                }
+                None => interval.timings.extend_from_slice(&batch.timings),
+            }
+            interval.timings_seen += batch.timings_seen;
+            interval.success += batch.success;
+            interval.http_error += batch.http_error;
+            interval.tcp_error += batch.tcp_error;
+            interval.min_time = interval.min_time.min(batch.min_time);
+            interval.max_time = interval.max_time.max(batch.max_time);
+        }
+        let mut total = self.total.lock().unwrap();
+        match self.timing_capacity {
+            Some(capacity) => total.merge_sampled(batch, capacity),
+            None => *total += batch,
+        }
+    }
+}
+
+struct Inner {
+    metrics: Arc<Metrics>,
+    batch: RefCell<BenchmarkResult>,
+    exact: Option<(Arc<ExactSamples>, RefCell<Vec<Duration>>)>,
+    next_flush: Cell<Duration>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        self.metrics.merge(self.batch.take());
+        self.retain();
+    }
+}
+
+impl Inner {
+    fn retain(&self) {
+        if let Some((exact, samples)) = &self.exact {
+            exact.extend(&samples.take());
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Recorder {
+    inner: Rc<Inner>,
+    user: Option<usize>,
+    tenant: Option<Arc<str>>,
+}
+
+impl Recorder {
+    pub(crate) fn new(metrics: Arc<Metrics>, exact: Option<Arc<ExactSamples>>) -> Self {
+        Self {
+            inner: Rc::new(Inner {
+                metrics,
+                batch: RefCell::new(BenchmarkResult::default()),
+                exact: exact.map(|exact| (exact, RefCell::default())),
+                next_flush: Cell::new(FLUSH_INTERVAL),
+            }),
+            user: None,
+            tenant: None,
+        }
+    }
+
+    pub(crate) fn for_user(&self, user: usize, tenant: Option<Arc<str>>) -> Self {
+        self.inner.batch.borrow_mut().users.entry(user).or_default();
+        Self {
+            inner: self.inner.clone(),
+            user: Some(user),
+            tenant,
+        }
+    }
+
+    pub(crate) fn record(&self, offset: Duration, elapsed: Duration, outcome: Outcome) {
+        self.record_exact(elapsed);
+        self.batch(offset, |batch| {
+            batch.record(offset, elapsed, outcome);
+            self.record_user(batch, offset, elapsed, outcome);
+        });
+    }
+
+    pub(crate) fn record_labeled(
+        &self,
+        label: &str,
+        phase: Option<&str>,
+        offset: Duration,
+        elapsed: Duration,
+        outcome: Outcome,
+    ) {
+        self.record_exact(elapsed);
+        self.batch(offset, |batch| {
+            batch.record_labeled(label, offset, elapsed, outcome);
+            if let Some(budget) = self.inner.metrics.budgets.get(label) {
+                batch.record_budget(label, elapsed, *budget);
+            }
+            if let Some(phase) = phase {
+                batch.record_phase(phase, offset, elapsed, outcome);
+            }
+            self.record_user(batch, offset, elapsed, outcome);
+        });
+    }
+
+    pub(crate) fn record_cache(
+        &self,
+        status: Option<CacheStatus>,
+        offset: Duration,
+        elapsed: Duration,
+        outcome: Outcome,
+    ) {
+        if let Some(status) = status {
+            self.batch(offset, |batch| {
+                batch.record_cache(status, offset, elapsed, outcome)
+            });
+        }
+    }
+
+    pub(crate) fn record_connect(
+        &self,
+        label: &str,
+        offset: Duration,
+        elapsed: Duration,
+        outcome: Outcome,
+    ) {
+        self.batch(offset, |batch| {
+            batch.record_connect(label, offset, elapsed, outcome)
+        });
+    }
+
+    pub(crate) fn record_time(&self, offset: Duration, spent: TimeBreakdown) {
+        self.batch(offset, |batch| batch.time_breakdown += spent);
+    }
+
+    pub(crate) fn record_opened(&self, offset: Duration, connections: usize) {
+        self.batch(offset, |batch| batch.opened_connections += connections);
+    }
+
+    #[cfg(feature = "websocket")]
+    pub(crate) fn record_idle(&self, offset: Duration) {
+        self.batch(offset, |batch| batch.idle_connections += 1);
+    }
+
+    #[cfg(feature = "websocket")]
+    pub(crate) fn record_push(&self, offset: Duration) {
+        self.batch(offset, |batch| batch.pushed_messages += 1);
+    }
+
+    pub(crate) fn record_drop(&self, offset: Duration) {
+        self.batch(offset, |batch| batch.dropped_connections += 1);
+    }
+
+    #[cfg(feature = "compression")]
+    pub(crate) fn record_decoded(&self, offset: Duration, decoded: Decoded) {
+        self.batch(offset, |batch| {
+            batch.compressed_responses += 1;
+            batch.wire_bytes += decoded.wire;
+            batch.decoded_bytes += decoded.decoded;
+        });
+    }
+
+    pub(crate) fn record_redirects(&self, offset: Duration, followed: Followed) {
+        self.batch(offset, |batch| {
+            batch.redirects_followed += followed.hops;
+            batch.redirect_loops += followed.looped as usize;
+        });
+    }
+
+    pub(crate) fn record_continue(&self, offset: Duration, interim: Continue) {
+        self.batch(offset, |batch| match interim {
+            Continue::Interim(latency) => {
+                batch.continue_responses += 1;
+                batch.continue_time += latency;
+            }
+            Continue::Rejected => batch.continue_rejections += 1,
+            Continue::TimedOut => batch.continue_timeouts += 1,
+        });
+    }
+
+    pub(crate) fn record_failed(&self, offset: Duration) {
+        self.batch(offset, |batch| batch.record_failed(offset));
+    }
+
+    pub(crate) fn record_backlog(&self, offset: Duration, depth: u64, lag: Duration) {
+        self.batch(offset, |batch| batch.record_backlog(offset, depth, lag));
+    }
+
+    pub(crate) fn flush(&self) {
+        let batch = self.inner.batch.take();
+        self.inner.metrics.merge(batch);
+        self.inner.retain();
+    }
+
+    fn record_exact(&self, elapsed: Duration) {
+        if let Some((_, samples)) = &self.inner.exact {
+            samples.borrow_mut().push(elapsed);
+        }
+    }
+
+    fn record_user(
+        &self,
+        batch: &mut BenchmarkResult,
+        offset: Duration,
+        elapsed: Duration,
+        outcome: Outcome,
+    ) {
+        if let Some(user) = self.user {
+            batch.record_user(user, elapsed);
+        }
+        if let Some(tenant) = &self.tenant {
+            batch.record_tenant(tenant, offset, elapsed, outcome);
+        }
+    }
+
+    fn batch(&self, offset: Duration, f: impl FnOnce(&mut BenchmarkResult)) {
+        f(&mut self.inner.batch.borrow_mut());
+        if offset >= self.inner.next_flush.get() {
+            self.inner.next_flush.set(offset + FLUSH_INTERVAL);
+            self.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intervals_track_the_fastest_request() {
+        let metrics = Metrics::default()
+            .with_intervals()
+            .with_timing_capacity(Some(8));
+        for elapsed in [5, 2, 9] {
+            let mut batch = BenchmarkResult::default();
+            batch.record(
+                Duration::ZERO,
+                Duration::from_millis(elapsed),
+                Outcome::Success,
+            );
+            metrics.merge(batch);
+        }
+
+        let interval = metrics.take_interval().unwrap();
+        assert_eq!(interval.min_time, Duration::from_millis(2));
+        assert_eq!(interval.max_time, Duration::from_millis(9));
+        assert_eq!(interval.timings.len(), 3);
+    }
+}
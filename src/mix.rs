@@ -0,0 +1,230 @@
+use std::{sync::Arc, time::Duration};
+
+use hyper::{Body, Request, Uri};
+use rand::Rng;
+
+use crate::{user::UserContext, MakeRequest};
+
+#[derive(Debug, Clone)]
+pub(crate) struct Label(pub(crate) Arc<str>);
+
+#[derive(Clone)]
+struct Entry {
+    name: Arc<str>,
+    weights: Vec<(Duration, f64)>,
+    make_request: MakeRequest,
+}
+
+impl Entry {
+    fn weight_at(&self, offset: Duration) -> f64 {
+        let after = self.weights.partition_point(|(at, _)| *at <= offset);
+        match (
+            self.weights.get(after.wrapping_sub(1)),
+            self.weights.get(after),
+        ) {
+            (Some((from, start)), Some((to, end))) => {
+                let progress = (offset - *from).as_secs_f64() / (*to - *from).as_secs_f64();
+                start + (end - start) * progress
+            }
+            (Some((_, weight)), None) | (None, Some((_, weight))) => *weight,
+            (None, None) => 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct RequestMix {
+    entries: Vec<Entry>,
+    unknown: Vec<String>,
+}
+
+impl RequestMix {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn request(
+        mut self,
+        name: impl Into<Arc<str>>,
+        weight: f64,
+        f: impl Fn(&Uri, &UserContext) -> Request<Body> + Send + Sync + 'static,
+    ) -> Self {
+        self.entries.push(Entry {
+            name: name.into(),
+            weights: vec![(Duration::ZERO, weight)],
+            make_request: Arc::new(f),
+        });
+        self
+    }
+
+    pub fn drift(mut self, name: &str, at: Duration, weight: f64) -> Self {
+        match self.entries.iter_mut().find(|entry| &*entry.name == name) {
+            Some(entry) => {
+                entry.weights.retain(|(offset, _)| *offset != at);
+                entry.weights.push((at, weight));
+                entry.weights.sort_by_key(|(offset, _)| *offset);
+            }
+            None => self.unknown.push(name.to_string()),
+        }
+        self
+    }
+
+    pub fn weights_at(&self, offset: Duration) -> Vec<(&str, f64)> {
+        self.entries
+            .iter()
+            .map(|entry| (&*entry.name, entry.weight_at(offset)))
+            .collect()
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.entries.is_empty() {
+            return Err("request mixes need at least one request".into());
+        }
+        if let Some(name) = self.unknown.first() {
+            return Err(format!("request mix drift for unknown request {name}"));
+        }
+        for (i, entry) in self.entries.iter().enumerate() {
+            if self.entries[..i]
+                .iter()
+                .any(|other| other.name == entry.name)
+            {
+                return Err(format!("duplicate request mix name: {}", entry.name));
+            }
+        }
+        if self
+            .entries
+            .iter()
+            .flat_map(|entry| entry.weights.iter().map(|(_, weight)| *weight))
+            .any(|weight| !weight.is_finite() || weight < 0.0)
+        {
+            return Err("request mix weights must be finite and non-negative".into());
+        }
+        let keyframes = self
+            .entries
+            .iter()
+            .flat_map(|entry| entry.weights.iter().map(|(offset, _)| *offset));
+        for offset in keyframes {
+            if self
+                .weights_at(offset)
+                .iter()
+                .all(|(_, weight)| *weight == 0.0)
+            {
+                return Err(format!(
+                    "request mix has no positive weight at {:.2}s",
+                    offset.as_secs_f64()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn make_request(&self) -> MakeRequest {
+        let entries = self.entries.clone();
+        Arc::new(move |uri, ctx| {
+            let offset = ctx.epoch.elapsed();
+            let weights = entries
+                .iter()
+                .map(|entry| entry.weight_at(offset))
+                .collect::<Vec<_>>();
+            let mut roll = rand::thread_rng().gen::<f64>() * weights.iter().sum::<f64>();
+            let entry = entries
+                .iter()
+                .zip(&weights)
+                .find(|(_, weight)| {
+                    roll -= **weight;
+                    roll < 0.0
+                })
+                .or_else(|| {
+                    entries
+                        .iter()
+                        .zip(&weights)
+                        .rfind(|(_, weight)| **weight > 0.0)
+                })
+                .map(|(entry, _)| entry)
+                .expect("request mixes have a positive weight");
+            let mut req = (entry.make_request)(uri, ctx);
+            req.extensions_mut().insert(Label(entry.name.clone()));
+            req
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::shared::Shared;
+
+    fn get(uri: &Uri, _: &UserContext) -> Request<Body> {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn weights_interpolate_between_drift_keyframes() {
+        let mix = RequestMix::new()
+            .request("read", 1.0, get)
+            .request("write", 0.0, get)
+            .drift("write", Duration::from_secs(10), 4.0);
+        assert_eq!(
+            mix.weights_at(Duration::ZERO),
+            [("read", 1.0), ("write", 0.0)]
+        );
+        assert_eq!(
+            mix.weights_at(Duration::from_secs(5)),
+            [("read", 1.0), ("write", 2.0)]
+        );
+        assert_eq!(
+            mix.weights_at(Duration::from_secs(60)),
+            [("read", 1.0), ("write", 4.0)]
+        );
+        assert!(mix.validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_mixes_are_rejected() {
+        let error = |mix: RequestMix| mix.validate().unwrap_err();
+        assert_eq!(
+            error(RequestMix::new()),
+            "request mixes need at least one request"
+        );
+        assert_eq!(
+            error(
+                RequestMix::new()
+                    .request("a", 1.0, get)
+                    .drift("b", Duration::ZERO, 1.0)
+            ),
+            "request mix drift for unknown request b"
+        );
+        assert_eq!(
+            error(
+                RequestMix::new()
+                    .request("a", 1.0, get)
+                    .request("a", 1.0, get)
+            ),
+            "duplicate request mix name: a"
+        );
+        assert!(error(RequestMix::new().request("a", -1.0, get)).contains("non-negative"));
+        assert_eq!(
+            error(
+                RequestMix::new()
+                    .request("a", 1.0, get)
+                    .drift("a", Duration::from_secs(2), 0.0)
+            ),
+            "request mix has no positive weight at 2.00s"
+        );
+    }
+
+    #[test]
+    fn requests_are_labelled_with_the_entry_that_made_them() {
+        let make_request = RequestMix::new()
+            .request("never", 0.0, get)
+            .request("always", 1.0, get)
+            .make_request();
+        let ctx = UserContext::new(0, Instant::now(), None, None, Shared::default());
+        for _ in 0..10 {
+            let req = make_request(&"http://localhost/".parse().unwrap(), &ctx);
+            assert_eq!(&*req.extensions().get::<Label>().unwrap().0, "always");
+        }
+    }
+}
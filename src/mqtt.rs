@@ -0,0 +1,466 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    io,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use futures::future;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream,
+    },
+    sync::Notify,
+    time::{interval_at, timeout_at, MissedTickBehavior},
+};
+
+use crate::{clock::Clock, metrics::Recorder, result::Outcome, user::UserContext};
+
+const CONNECT_LABEL: &str = "mqtt connect";
+const PUBACK_LABEL: &str = "mqtt puback";
+const MAX_PACKET: usize = 1 << 20;
+const STAMP: usize = 16;
+
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const PUBACK: u8 = 0x40;
+const SUBSCRIBE: u8 = 0x82;
+const SUBACK: u8 = 0x90;
+const DISCONNECT: u8 = 0xe0;
+
+type MakePayload = Arc<dyn Fn(&UserContext) -> Vec<u8> + Send + Sync + 'static>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QoS {
+    #[default]
+    AtMostOnce,
+    AtLeastOnce,
+}
+
+#[derive(Clone)]
+pub struct Mqtt {
+    topic: String,
+    payload: MakePayload,
+    qos: QoS,
+    rate: Option<f64>,
+    shared: bool,
+}
+
+impl Mqtt {
+    pub fn new(topic: impl Into<String>) -> Self {
+        Self {
+            topic: topic.into(),
+            payload: Arc::new(|_| Vec::new()),
+            qos: QoS::default(),
+            rate: None,
+            shared: false,
+        }
+    }
+
+    pub fn payload(
+        self,
+        payload: impl Fn(&UserContext) -> Vec<u8> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            payload: Arc::new(payload),
+            ..self
+        }
+    }
+
+    pub fn qos(self, qos: QoS) -> Self {
+        Self { qos, ..self }
+    }
+
+    pub fn rate(self, messages_per_second: f64) -> Self {
+        Self {
+            rate: Some(messages_per_second).filter(|rate| *rate > 0.0),
+            ..self
+        }
+    }
+
+    pub fn shared_topic(self) -> Self {
+        Self {
+            shared: true,
+            ..self
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), &'static str> {
+        if self.topic.is_empty() {
+            return Err("MQTT topic cannot be empty");
+        }
+        if self.topic.len() > u16::MAX as usize - 32 {
+            return Err("MQTT topic is too long");
+        }
+        if self.topic.contains(['+', '#']) {
+            return Err("MQTT publish topics cannot contain wildcards");
+        }
+        Ok(())
+    }
+
+    fn topic(&self, user: usize) -> String {
+        match self.shared {
+            true => self.topic.clone(),
+            false => format!("{}/{user}", self.topic),
+        }
+    }
+}
+
+pub(crate) struct Session {
+    pub(crate) addr: SocketAddr,
+    pub(crate) epoch: Instant,
+    pub(crate) deadline: Instant,
+    pub(crate) clock: Clock,
+    pub(crate) recorder: Recorder,
+}
+
+#[derive(Default)]
+struct Link {
+    acks: RefCell<HashMap<u16, (Duration, u64)>>,
+    delivered: Notify,
+    closed: Cell<bool>,
+}
+
+impl Session {
+    pub(crate) async fn run(
+        &self,
+        mqtt: &Mqtt,
+        ctx: &mut UserContext,
+        running: &AtomicBool,
+        sequence: &AtomicU64,
+    ) {
+        let topic = mqtt.topic(ctx.id());
+        while running.load(Ordering::Relaxed) {
+            let Some(stream) = self.connect(&topic, ctx.id()).await else {
+                continue;
+            };
+
+            let (reader, writer) = stream.into_split();
+            let link = Link::default();
+            let user = ctx.id();
+            let (dropped, mut writer) = future::join(
+                self.receive(reader, user, &link),
+                self.publish(writer, mqtt, ctx, running, sequence, &link),
+            )
+            .await;
+
+            match dropped {
+                true => self
+                    .recorder
+                    .record_drop(Instant::now().saturating_duration_since(self.epoch)),
+                false => {
+                    writer.write_all(&packet(DISCONNECT, &[])).await.ok();
+                    break;
+                }
+            }
+        }
+    }
+
+    async fn publish(
+        &self,
+        mut writer: OwnedWriteHalf,
+        mqtt: &Mqtt,
+        ctx: &mut UserContext,
+        running: &AtomicBool,
+        sequence: &AtomicU64,
+        link: &Link,
+    ) -> OwnedWriteHalf {
+        let mut ticks = mqtt.rate.map(|rate| {
+            let period = Duration::from_secs_f64(1.0 / rate);
+            let mut ticks = interval_at((Instant::now() + period).into(), period);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            ticks
+        });
+        let topic = mqtt.topic(ctx.id());
+        let mut packet_id = 0u16;
+
+        while running.load(Ordering::Relaxed) && !link.closed.get() {
+            if let Some(ticks) = &mut ticks {
+                if timeout_at(self.deadline.into(), ticks.tick())
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+
+            ctx.sequence = sequence.fetch_add(1, Ordering::Relaxed);
+            let offset = Instant::now().saturating_duration_since(self.epoch);
+            let timer = self.clock.now();
+            let mut payload = Vec::with_capacity(STAMP);
+            payload.extend_from_slice(&timer.to_be_bytes());
+            payload.extend_from_slice(&(ctx.id() as u64).to_be_bytes());
+            payload.extend_from_slice(&(mqtt.payload)(ctx));
+            let id = match mqtt.qos {
+                QoS::AtMostOnce => None,
+                QoS::AtLeastOnce => {
+                    packet_id = packet_id.wrapping_add(1).max(1);
+                    link.acks.borrow_mut().insert(packet_id, (offset, timer));
+                    Some(packet_id)
+                }
+            };
+            if writer
+                .write_all(&publish(&topic, id, &payload))
+                .await
+                .is_err()
+            {
+                self.recorder
+                    .record(offset, self.clock.elapsed(timer), Outcome::TcpError);
+                break;
+            }
+            ctx.iteration += 1;
+
+            if ticks.is_none()
+                && timeout_at(self.deadline.into(), link.delivered.notified())
+                    .await
+                    .is_err()
+            {
+                break;
+            }
+        }
+        writer
+    }
+
+    async fn receive(&self, reader: OwnedReadHalf, user: usize, link: &Link) -> bool {
+        let mut reader = BufReader::new(reader);
+        let dropped = loop {
+            let packet = match timeout_at(self.deadline.into(), read_packet(&mut reader)).await {
+                Ok(Ok(packet)) => packet,
+                Ok(Err(_)) => break true,
+                Err(_) => break false,
+            };
+            match packet.kind & 0xf0 {
+                PUBLISH => {
+                    let Some(payload) = published(&packet) else {
+                        continue;
+                    };
+                    let timer = u64::from_be_bytes(payload[..8].try_into().unwrap());
+                    let sender = u64::from_be_bytes(payload[8..STAMP].try_into().unwrap());
+                    let elapsed = self.clock.elapsed(timer);
+                    let offset = Instant::now()
+                        .saturating_duration_since(self.epoch)
+                        .saturating_sub(elapsed);
+                    self.recorder.record(offset, elapsed, Outcome::Success);
+                    if sender == user as u64 {
+                        link.delivered.notify_one();
+                    }
+                }
+                PUBACK if packet.body.len() >= 2 => {
+                    let id = u16::from_be_bytes([packet.body[0], packet.body[1]]);
+                    if let Some((offset, timer)) = link.acks.borrow_mut().remove(&id) {
+                        self.recorder.record_connect(
+                            PUBACK_LABEL,
+                            offset,
+                            self.clock.elapsed(timer),
+                            Outcome::Success,
+                        );
+                    }
+                }
+                _ => {}
+            }
+        };
+        link.closed.set(true);
+        link.delivered.notify_one();
+        dropped
+    }
+
+    async fn connect(&self, topic: &str, user: usize) -> Option<TcpStream> {
+        let start = Instant::now();
+        let timer = self.clock.now();
+        let connected = self.handshake(topic, user).await;
+        let outcome = match &connected {
+            Ok(_) => Outcome::Success,
+            Err(err) => Outcome::from_error(err),
+        };
+        self.recorder.record_connect(
+            CONNECT_LABEL,
+            start.saturating_duration_since(self.epoch),
+            self.clock.elapsed(timer),
+            outcome,
+        );
+        connected.ok()
+    }
+
+    async fn handshake(&self, topic: &str, user: usize) -> io::Result<TcpStream> {
+        let mut stream = TcpStream::connect(self.addr).await?;
+        stream.set_nodelay(true).ok();
+
+        let mut body = Vec::new();
+        string(&mut body, "MQTT");
+        body.extend_from_slice(&[4, 0x02, 0, 0]);
+        string(&mut body, &format!("zerg-{}-{user}", std::process::id()));
+        stream.write_all(&packet(CONNECT, &body)).await?;
+        let connack = read_packet(&mut stream).await?;
+        if connack.kind != CONNACK || connack.body.get(1) != Some(&0) {
+            return Err(refused("MQTT broker refused the connection"));
+        }
+
+        let mut body = 1u16.to_be_bytes().to_vec();
+        string(&mut body, topic);
+        body.push(0);
+        stream.write_all(&packet(SUBSCRIBE, &body)).await?;
+        let suback = read_packet(&mut stream).await?;
+        if suback.kind != SUBACK || suback.body.get(2).is_none_or(|code| *code >= 0x80) {
+            return Err(refused("MQTT broker refused the subscription"));
+        }
+        Ok(stream)
+    }
+}
+
+struct Packet {
+    kind: u8,
+    body: Vec<u8>,
+}
+
+async fn read_packet(reader: &mut (impl AsyncRead + Unpin)) -> io::Result<Packet> {
+    let kind = reader.read_u8().await?;
+    let mut len = 0;
+    let mut shift = 0;
+    loop {
+        let byte = reader.read_u8().await?;
+        len |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+        if shift > 21 {
+            return Err(invalid("malformed MQTT packet length"));
+        }
+    }
+    if len > MAX_PACKET {
+        return Err(invalid("MQTT packet too large"));
+    }
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body).await?;
+    Ok(Packet { kind, body })
+}
+
+fn published(packet: &Packet) -> Option<&[u8]> {
+    let topic = u16::from_be_bytes([*packet.body.first()?, *packet.body.get(1)?]) as usize;
+    let id = match packet.kind & 0x06 {
+        0 => 0,
+        _ => 2,
+    };
+    packet
+        .body
+        .get(2 + topic + id..)
+        .filter(|payload| payload.len() >= STAMP)
+}
+
+fn publish(topic: &str, id: Option<u16>, payload: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(2 + topic.len() + 2 + payload.len());
+    string(&mut body, topic);
+    if let Some(id) = id {
+        body.extend_from_slice(&id.to_be_bytes());
+    }
+    body.extend_from_slice(payload);
+    match id {
+        Some(_) => packet(PUBLISH | 0x02, &body),
+        None => packet(PUBLISH, &body),
+    }
+}
+
+fn packet(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(5 + body.len());
+    packet.push(kind);
+    let mut len = body.len();
+    loop {
+        let byte = (len % 128) as u8;
+        len /= 128;
+        match len {
+            0 => {
+                packet.push(byte);
+                break;
+            }
+            _ => packet.push(byte | 0x80),
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+fn string(buf: &mut Vec<u8>, value: &str) {
+    buf.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buf.extend_from_slice(value.as_bytes());
+}
+
+fn refused(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionRefused, message.to_string())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(bytes: &[u8]) -> io::Result<Packet> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(read_packet(&mut &bytes[..]))
+    }
+
+    #[test]
+    fn packet_lengths_use_the_variable_byte_encoding() {
+        assert_eq!(packet(DISCONNECT, &[]), [DISCONNECT, 0]);
+        let long = packet(PUBLISH, &[7; 321]);
+        assert_eq!(&long[..3], [PUBLISH, 0xc1, 0x02]);
+
+        let round_trip = read(&long).unwrap();
+        assert_eq!((round_trip.kind, round_trip.body.len()), (PUBLISH, 321));
+    }
+
+    #[test]
+    fn oversized_or_malformed_packets_are_rejected() {
+        let kind = |bytes: &[u8]| read(bytes).err().unwrap().kind();
+        assert_eq!(
+            kind(&[PUBLISH, 0xff, 0xff, 0xff, 0xff]),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            kind(&[PUBLISH, 0x81, 0x80, 0x40]),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(kind(&[PUBLISH, 4, 0]), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn published_payloads_skip_the_topic_and_packet_id() {
+        let stamp = [1; STAMP];
+        for id in [None, Some(9)] {
+            let bytes = publish("load/1", id, &stamp);
+            let packet = read(&bytes).unwrap();
+            assert_eq!(packet.kind, PUBLISH | if id.is_some() { 0x02 } else { 0 });
+            assert_eq!(published(&packet), Some(&stamp[..]));
+        }
+
+        let short = read(&publish("load/1", None, b"tiny")).unwrap();
+        assert_eq!(published(&short), None);
+    }
+
+    #[test]
+    fn topics_are_per_user_unless_shared() {
+        assert_eq!(Mqtt::new("load").topic(3), "load/3");
+        assert_eq!(Mqtt::new("load").shared_topic().topic(3), "load");
+    }
+
+    #[test]
+    fn invalid_topics_are_refused() {
+        assert!(Mqtt::new("load").validate().is_ok());
+        assert!(Mqtt::new("").validate().is_err());
+        assert!(Mqtt::new("load/#").validate().is_err());
+        assert!(Mqtt::new("x".repeat(u16::MAX as usize)).validate().is_err());
+        assert_eq!(Mqtt::new("load").rate(0.0).rate, None);
+    }
+}
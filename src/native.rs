@@ -0,0 +1,98 @@
+use std::{error::Error, fs};
+
+use hyper::Uri;
+use native_tls::{Certificate, Identity, TlsConnector};
+use tokio::net::TcpStream;
+use tokio_native_tls::TlsStream;
+
+use crate::{
+    session::{TlsOptions, TlsStats},
+    transport::Protocol,
+};
+
+#[derive(Clone)]
+pub(crate) struct Https {
+    connector: tokio_native_tls::TlsConnector,
+    domain: String,
+    stats: TlsStats,
+}
+
+impl Https {
+    pub(crate) fn new(
+        uri: &Uri,
+        options: &TlsOptions,
+        protocol: Protocol,
+        stats: TlsStats,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        #[cfg(feature = "tls")]
+        if !options.pins.is_empty() {
+            return Err("certificate pinning is not supported by the native TLS backend".into());
+        }
+        #[cfg(feature = "tls")]
+        if options.config.is_some() {
+            return Err("a rustls client config cannot be used with the native TLS backend".into());
+        }
+
+        let mut builder = TlsConnector::builder();
+        builder
+            .danger_accept_invalid_certs(options.accept_invalid_certs)
+            .request_alpns(&[protocol.alpn()]);
+        if let Some(path) = &options.ca_bundle {
+            for cert in Certificate::stack_from_pem(&fs::read(path)?)? {
+                builder.add_root_certificate(cert);
+            }
+        }
+        if let Some(identity) = &options.identity {
+            let (cert, key) = identity.pem()?;
+            builder.identity(Identity::from_pkcs8(&cert, &key)?);
+        }
+
+        let domain = uri.host().ok_or("missing host")?;
+        Ok(Self {
+            connector: builder.build()?.into(),
+            domain: domain
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_string(),
+            stats,
+        })
+    }
+
+    pub(crate) async fn connect(
+        &self,
+        stream: TcpStream,
+    ) -> Result<TlsStream<TcpStream>, Box<dyn Error + Send + Sync>> {
+        let stream = self.connector.connect(&self.domain, stream).await?;
+        self.stats
+            .negotiated(stream.get_ref().negotiated_alpn()?.as_deref());
+        Ok(stream)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn https(uri: &str, options: &TlsOptions) -> Result<Https, Box<dyn Error + Send + Sync>> {
+        let uri = uri.parse().unwrap();
+        Https::new(&uri, options, Protocol::Http1, TlsStats::default())
+    }
+
+    #[test]
+    fn ipv6_hosts_lose_their_brackets_for_sni() {
+        let ipv6 = https("https://[::1]:8443/", &TlsOptions::default()).unwrap();
+        assert_eq!(ipv6.domain, "::1");
+        assert!(https("/only-a-path", &TlsOptions::default()).is_err());
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn rustls_only_options_are_refused() {
+        let pinned = TlsOptions {
+            pins: vec!["sha256/AAAA".into()],
+            ..TlsOptions::default()
+        };
+        let err = https("https://localhost/", &pinned).err().unwrap();
+        assert!(err.to_string().contains("pinning"), "{err}");
+    }
+}
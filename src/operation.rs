@@ -0,0 +1,145 @@
+use std::{
+    error::Error, fmt::Display, future::Future, io, net::SocketAddr, sync::Arc, time::Duration,
+};
+
+use futures::future::LocalBoxFuture;
+use serde::{Deserialize, Serialize};
+use tokio::{io::AsyncReadExt, net::TcpStream, time::timeout};
+
+use crate::{
+    bind::BindPool, dns::DnsQuery, proxy::ProxyHeader, result::Outcome, user::UserContext,
+};
+
+pub(crate) type Operation =
+    Arc<dyn Fn(&UserContext) -> LocalBoxFuture<'static, Outcome> + Send + Sync + 'static>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Mode {
+    #[default]
+    Http,
+    Connect,
+    Dns(DnsQuery),
+    Storm(Duration),
+    #[cfg(feature = "tls")]
+    TlsHandshake,
+}
+
+impl Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Http => write!(f, "HTTP"),
+            Self::Connect => write!(f, "TCP connect"),
+            Self::Dns(query) => write!(f, "DNS {query}"),
+            Self::Storm(hold) => write!(f, "connection storm holding {}ms", hold.as_millis()),
+            #[cfg(feature = "tls")]
+            Self::TlsHandshake => write!(f, "TLS handshake"),
+        }
+    }
+}
+
+pub(crate) fn connect(
+    addr: SocketAddr,
+    proxy: Option<ProxyHeader>,
+    bind: Option<Arc<BindPool>>,
+    hold: Duration,
+) -> Operation {
+    Arc::new(move |_| {
+        let proxy = proxy.clone();
+        let bind = bind.clone();
+        Box::pin(async move {
+            let connected = match bind {
+                Some(bind) => bind.connect(addr).await,
+                None => TcpStream::connect(addr).await,
+            };
+            let mut stream = match connected {
+                Ok(stream) => stream,
+                Err(err) => return Outcome::from_error(&err),
+            };
+            if let Some(proxy) = proxy {
+                if proxy.write_to(&mut stream).await.is_err() {
+                    return Outcome::TcpError;
+                }
+            }
+            if !hold.is_zero() {
+                tokio::spawn(async move {
+                    timeout(hold, stream.read(&mut [0; 1])).await.ok();
+                });
+            }
+            Outcome::Success
+        })
+    })
+}
+
+pub(crate) fn custom<F, Fut, E>(op: F) -> Operation
+where
+    F: Fn(&UserContext) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), E>> + 'static,
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    Arc::new(move |ctx| {
+        let op = op(ctx);
+        Box::pin(async move {
+            match op.await {
+                Ok(()) => Outcome::Success,
+                Err(err) => {
+                    let err = err.into();
+                    match err.is::<io::Error>() {
+                        true => Outcome::from_error(&*err),
+                        false => Outcome::HttpError,
+                    }
+                }
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, time::Instant};
+
+    use super::*;
+    use crate::shared::Shared;
+
+    fn run(op: &Operation) -> Outcome {
+        let ctx = UserContext::new(0, Instant::now(), None, None, Shared::default());
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(op(&ctx))
+    }
+
+    #[test]
+    fn modes_describe_themselves() {
+        assert_eq!(Mode::Connect.to_string(), "TCP connect");
+        assert_eq!(
+            Mode::Storm(Duration::from_millis(250)).to_string(),
+            "connection storm holding 250ms"
+        );
+        assert_eq!(
+            Mode::Dns(DnsQuery::new("example.com")).to_string(),
+            "DNS A example.com"
+        );
+    }
+
+    #[test]
+    fn connects_succeed_or_report_why_not() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let op = connect(addr, None, None, Duration::ZERO);
+        assert!(matches!(run(&op), Outcome::Success));
+
+        drop(listener);
+        assert!(matches!(run(&op), Outcome::ConnectError));
+    }
+
+    #[test]
+    fn custom_errors_are_classified_by_type() {
+        let ok = custom(|_| async { Ok::<_, io::Error>(()) });
+        assert!(matches!(run(&ok), Outcome::Success));
+        let io = custom(|_| async { Err(io::Error::from(io::ErrorKind::ConnectionReset)) });
+        assert!(matches!(run(&io), Outcome::TcpError));
+        let other = custom(|_| async { Err("bad answer") });
+        assert!(matches!(run(&other), Outcome::HttpError));
+    }
+}
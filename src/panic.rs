@@ -0,0 +1,134 @@
+use std::{
+    any::Any,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+};
+
+#[derive(Debug)]
+pub(crate) struct StartGate {
+    pending: Mutex<usize>,
+    opened: Condvar,
+}
+
+impl StartGate {
+    pub(crate) fn new(parties: usize) -> Self {
+        Self {
+            pending: Mutex::new(parties),
+            opened: Condvar::new(),
+        }
+    }
+
+    fn arrive(&self) {
+        let mut pending = self.pending.lock().unwrap();
+        *pending = pending.saturating_sub(1);
+        if *pending == 0 {
+            self.opened.notify_all();
+        }
+    }
+
+    pub(crate) fn wait(&self) {
+        self.arrive();
+        let pending = self.pending.lock().unwrap();
+        drop(
+            self.opened
+                .wait_while(pending, |pending| *pending > 0)
+                .unwrap(),
+        );
+    }
+}
+
+pub(crate) struct PanicGuard {
+    died: Arc<AtomicBool>,
+    gate: Option<Arc<StartGate>>,
+}
+
+impl PanicGuard {
+    pub(crate) fn new(died: Arc<AtomicBool>, gate: Arc<StartGate>) -> Self {
+        Self {
+            died,
+            gate: Some(gate),
+        }
+    }
+
+    pub(crate) fn start(&mut self) {
+        if let Some(gate) = self.gate.take() {
+            gate.wait();
+        }
+    }
+}
+
+impl Drop for PanicGuard {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.died.store(true, Ordering::Relaxed);
+        }
+        if let Some(gate) = self.gate.take() {
+            gate.arrive();
+        }
+    }
+}
+
+pub(crate) fn message(payload: &(dyn Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => match payload.downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => "unknown panic".to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic;
+
+    use super::*;
+
+    #[test]
+    fn the_gate_opens_once_every_party_arrives() {
+        let gate = Arc::new(StartGate::new(3));
+        let waiters: Vec<_> = (0..3)
+            .map(|_| {
+                let gate = gate.clone();
+                thread::spawn(move || gate.wait())
+            })
+            .collect();
+        for waiter in waiters {
+            waiter.join().unwrap();
+        }
+        assert_eq!(*gate.pending.lock().unwrap(), 0);
+    }
+
+    #[test]
+    fn panicking_workers_are_marked_dead_and_still_open_the_gate() {
+        let died = Arc::new(AtomicBool::new(false));
+        let gate = Arc::new(StartGate::new(2));
+        let worker = {
+            let guard = PanicGuard::new(died.clone(), gate.clone());
+            thread::spawn(move || {
+                let _guard = guard;
+                panic!("boom");
+            })
+        };
+        let payload = worker.join().unwrap_err();
+        assert_eq!(message(&*payload), "boom");
+        assert!(died.load(Ordering::Relaxed));
+
+        gate.wait();
+
+        let mut guard = PanicGuard::new(Arc::default(), Arc::new(StartGate::new(1)));
+        guard.start();
+        assert!(guard.gate.is_none() && !guard.died.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn panic_messages_come_from_str_or_string_payloads() {
+        let payload = panic::catch_unwind(|| panic!("{}", 42)).unwrap_err();
+        assert_eq!(message(&*payload), "42");
+        let payload = panic::catch_unwind(|| panic::panic_any(7)).unwrap_err();
+        assert_eq!(message(&*payload), "unknown panic");
+    }
+}
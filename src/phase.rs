@@ -0,0 +1,73 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use tokio::sync::Barrier;
+
+pub(crate) struct Barriers {
+    users: usize,
+    deadline: Option<Instant>,
+    barriers: Mutex<HashMap<String, Arc<Barrier>>>,
+}
+
+impl Barriers {
+    pub(crate) fn new(users: usize, deadline: Option<Instant>) -> Arc<Self> {
+        Arc::new(Self {
+            users,
+            deadline,
+            barriers: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub(crate) async fn wait(&self, name: &str) -> bool {
+        let barrier = self
+            .barriers
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_insert_with(|| Arc::new(Barrier::new(self.users)))
+            .clone();
+
+        match self.deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline.into(), barrier.wait())
+                .await
+                .is_ok(),
+            None => {
+                barrier.wait().await;
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn block_on<F: std::future::Future>(f: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(f)
+    }
+
+    #[test]
+    fn users_meet_at_each_named_barrier() {
+        let barriers = Barriers::new(2, None);
+        let met =
+            block_on(async { futures::join!(barriers.wait("login"), barriers.wait("login")) });
+        assert_eq!(met, (true, true));
+        assert_eq!(barriers.barriers.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn barriers_give_up_at_the_deadline() {
+        let barriers = Barriers::new(2, Some(Instant::now() + Duration::from_millis(20)));
+        assert!(!block_on(barriers.wait("checkout")));
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    collections::VecDeque,
+    net::SocketAddr,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use hyper::{body, Method, Uri};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::{sleep_until, timeout_at},
+};
+
+#[cfg(any(feature = "lua", feature = "rhai"))]
+use crate::script;
+use crate::{
+    cache::CacheStatus,
+    clock::Clock,
+    metrics::Recorder,
+    proxy::ProxyHeader,
+    result::Outcome,
+    user::{UserContext, VirtualUser},
+    wire,
+};
+
+const READ_SIZE: usize = 16 * 1024;
+const RECONNECT_DELAY: Duration = Duration::from_millis(100);
+
+struct InFlight {
+    sequence: u64,
+    start: Instant,
+    timer: u64,
+    head_only: bool,
+}
+
+pub(crate) struct Pipeline {
+    pub(crate) addr: SocketAddr,
+    pub(crate) depth: usize,
+    pub(crate) epoch: Instant,
+    pub(crate) deadline: Instant,
+    pub(crate) clock: Clock,
+    pub(crate) recorder: Recorder,
+    pub(crate) proxy: Option<ProxyHeader>,
+}
+
+impl Pipeline {
+    pub(crate) async fn run(
+        &self,
+        uri: &Uri,
+        user: &mut dyn VirtualUser,
+        ctx: &mut UserContext,
+        running: &AtomicBool,
+        sequence: &AtomicU64,
+    ) {
+        let mut in_flight = VecDeque::with_capacity(self.depth);
+        let mut write = Vec::new();
+        let mut read = Vec::new();
+        let mut exhausted = false;
+
+        while running.load(Ordering::Relaxed) && !exhausted {
+            let start = Instant::now();
+            let mut stream = match TcpStream::connect(self.addr).await {
+                Ok(stream) => stream,
+                Err(err) => {
+                    self.fail(start, self.clock.now(), Outcome::from_error(&err));
+                    self.backoff().await;
+                    continue;
+                }
+            };
+            stream.set_nodelay(true).ok();
+            if let Some(proxy) = &self.proxy {
+                if proxy.write_to(&mut stream).await.is_err() {
+                    self.fail(start, self.clock.now(), Outcome::TcpError);
+                    self.backoff().await;
+                    continue;
+                }
+            }
+            read.clear();
+            let mut eof = false;
+
+            loop {
+                while in_flight.len() < self.depth && running.load(Ordering::Relaxed) {
+                    let n = sequence.fetch_add(1, Ordering::Relaxed);
+                    ctx.sequence = n;
+                    let req = match user.next_request(uri, ctx) {
+                        Some(req) => req,
+                        None => {
+                            exhausted = true;
+                            break;
+                        }
+                    };
+                    #[cfg(any(feature = "lua", feature = "rhai"))]
+                    if script::failed(&req) {
+                        self.recorder.record_failed(self.epoch.elapsed());
+                        ctx.iteration += 1;
+                        continue;
+                    }
+
+                    let head_only = req.method() == Method::HEAD;
+                    let (parts, body) = req.into_parts();
+                    let body = match body::to_bytes(body).await {
+                        Ok(body) => body,
+                        Err(_) => {
+                            self.fail(Instant::now(), self.clock.now(), Outcome::TcpError);
+                            ctx.iteration += 1;
+                            continue;
+                        }
+                    };
+                    wire::encode_request(&parts, &body, &mut write);
+                    in_flight.push_back(InFlight {
+                        sequence: n,
+                        start: Instant::now(),
+                        timer: self.clock.now(),
+                        head_only,
+                    });
+                }
+
+                if !write.is_empty() {
+                    let written = stream.write_all(&write).await;
+                    write.clear();
+                    if written.is_err() {
+                        break;
+                    }
+                }
+
+                let Some(next) = in_flight.front() else {
+                    return;
+                };
+
+                match wire::parse_response(&read, next.head_only, eof) {
+                    Ok(Some((res, len))) => {
+                        read.drain(..len);
+                        let next = in_flight.pop_front().unwrap();
+                        let elapsed = self.clock.elapsed(next.timer);
+                        let keep_alive = wire::keep_alive(&res);
+
+                        ctx.sequence = next.sequence;
+                        let cache = CacheStatus::of(res.headers());
+                        let outcome = match user.on_response(res, ctx) {
+                            true => Outcome::Success,
+                            false => Outcome::HttpError,
+                        };
+                        let offset = next.start.saturating_duration_since(self.epoch);
+                        self.recorder.record(offset, elapsed, outcome);
+                        self.recorder.record_cache(cache, offset, elapsed, outcome);
+                        ctx.iteration += 1;
+
+                        if !keep_alive {
+                            break;
+                        }
+                    }
+                    Ok(None) if eof => break,
+                    Ok(None) => {
+                        read.reserve(READ_SIZE);
+                        match timeout_at(self.deadline.into(), stream.read_buf(&mut read)).await {
+                            Ok(Ok(0)) => eof = true,
+                            Ok(Err(_)) => break,
+                            Ok(Ok(_)) => {}
+                            Err(_) => return,
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+
+            for request in in_flight.drain(..) {
+                self.fail(request.start, request.timer, Outcome::TcpError);
+            }
+        }
+    }
+
+    async fn backoff(&self) {
+        sleep_until((Instant::now() + RECONNECT_DELAY).min(self.deadline).into()).await;
+    }
+
+    fn fail(&self, start: Instant, timer: u64, outcome: Outcome) {
+        self.recorder.record(
+            start.saturating_duration_since(self.epoch),
+            self.clock.elapsed(timer),
+            outcome,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        sync::Arc,
+        thread,
+    };
+
+    use hyper::{Body, Request};
+
+    use super::*;
+    use crate::{clock::ClockSource, metrics::Metrics, shared::Shared};
+
+    struct Three;
+
+    impl VirtualUser for Three {
+        fn next_request(&mut self, uri: &Uri, ctx: &UserContext) -> Option<Request<Body>> {
+            (ctx.sequence() < 3).then(|| Request::get(uri).body(Body::empty()).unwrap())
+        }
+    }
+
+    #[test]
+    fn pipelined_requests_are_answered_in_order_until_the_user_is_done() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let (mut read, mut answered) = (Vec::new(), 0);
+            while answered < 3 {
+                let mut buf = [0; 1024];
+                let n = stream.read(&mut buf).unwrap();
+                read.extend_from_slice(&buf[..n]);
+                while let Some(end) = read.windows(4).position(|w| w == b"\r\n\r\n") {
+                    read.drain(..end + 4);
+                    stream
+                        .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok")
+                        .unwrap();
+                    answered += 1;
+                }
+            }
+        });
+
+        let metrics = Arc::new(Metrics::default());
+        let epoch = Instant::now();
+        let pipeline = Pipeline {
+            addr,
+            depth: 2,
+            epoch,
+            deadline: epoch + Duration::from_secs(5),
+            clock: Clock::new(ClockSource::Std, false),
+            recorder: Recorder::new(metrics.clone(), None),
+            proxy: None,
+        };
+        let uri = format!("http://{addr}/").parse().unwrap();
+        let mut ctx = UserContext::new(0, epoch, None, None, Shared::default());
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(pipeline.run(
+                &uri,
+                &mut Three,
+                &mut ctx,
+                &AtomicBool::new(true),
+                &AtomicU64::new(0),
+            ));
+        drop(pipeline);
+        server.join().unwrap();
+
+        let result = metrics.take();
+        assert_eq!(result.success_count(), 3);
+        assert_eq!(result.tcp_error_count(), 0);
+        assert_eq!(ctx.iteration(), 3);
+    }
+}
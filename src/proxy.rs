@@ -0,0 +1,154 @@
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use tokio::{io::AsyncWriteExt, net::TcpStream};
+
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyVersion {
+    V1,
+    V2,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProxyHeader {
+    version: ProxyVersion,
+    sources: Vec<SocketAddr>,
+    next: Arc<AtomicUsize>,
+}
+
+impl ProxyHeader {
+    pub fn v1() -> Self {
+        Self::new(ProxyVersion::V1)
+    }
+
+    pub fn v2() -> Self {
+        Self::new(ProxyVersion::V2)
+    }
+
+    fn new(version: ProxyVersion) -> Self {
+        Self {
+            version,
+            sources: Vec::new(),
+            next: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn source(mut self, source: SocketAddr) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    pub fn version(&self) -> ProxyVersion {
+        self.version
+    }
+
+    pub(crate) fn encode(&self, local: SocketAddr, peer: SocketAddr) -> Vec<u8> {
+        let source = match self.sources.len() {
+            0 => local,
+            len => self.sources[self.next.fetch_add(1, Ordering::Relaxed) % len],
+        };
+        let (source, destination) = match (source.ip(), peer.ip()) {
+            (IpAddr::V4(_), IpAddr::V4(_)) | (IpAddr::V6(_), IpAddr::V6(_)) => (source, peer),
+            _ => (to_v6(source), to_v6(peer)),
+        };
+
+        match self.version {
+            ProxyVersion::V1 => {
+                let family = match source {
+                    SocketAddr::V4(_) => "TCP4",
+                    SocketAddr::V6(_) => "TCP6",
+                };
+                format!(
+                    "PROXY {family} {} {} {} {}\r\n",
+                    source.ip(),
+                    destination.ip(),
+                    source.port(),
+                    destination.port()
+                )
+                .into_bytes()
+            }
+            ProxyVersion::V2 => {
+                let (family, src, dst) = (
+                    if source.is_ipv4() { 0x11 } else { 0x21 },
+                    octets(source.ip()),
+                    octets(destination.ip()),
+                );
+                let mut header = V2_SIGNATURE.to_vec();
+                header.extend_from_slice(&[0x21, family]);
+                header.extend_from_slice(&((src.len() + dst.len() + 4) as u16).to_be_bytes());
+                header.extend_from_slice(&src);
+                header.extend_from_slice(&dst);
+                header.extend_from_slice(&source.port().to_be_bytes());
+                header.extend_from_slice(&destination.port().to_be_bytes());
+                header
+            }
+        }
+    }
+
+    pub(crate) async fn write_to(&self, stream: &mut TcpStream) -> io::Result<()> {
+        let header = self.encode(stream.local_addr()?, stream.peer_addr()?);
+        stream.write_all(&header).await
+    }
+}
+
+fn to_v6(addr: SocketAddr) -> SocketAddr {
+    match addr.ip() {
+        IpAddr::V4(ip) => SocketAddr::new(IpAddr::V6(ip.to_ipv6_mapped()), addr.port()),
+        IpAddr::V6(_) => addr,
+    }
+}
+
+fn octets(ip: IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(ip) => ip.octets().to_vec(),
+        IpAddr::V6(ip) => ip.octets().to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v1_headers_are_text_lines() {
+        let header = ProxyHeader::v1().source("203.0.113.7:4000".parse().unwrap());
+        assert_eq!(
+            header.encode(
+                "127.0.0.1:5000".parse().unwrap(),
+                "10.0.0.1:80".parse().unwrap()
+            ),
+            b"PROXY TCP4 203.0.113.7 10.0.0.1 4000 80\r\n"
+        );
+
+        let mixed = ProxyHeader::v1().encode(
+            "127.0.0.1:5000".parse().unwrap(),
+            "[::1]:443".parse().unwrap(),
+        );
+        assert_eq!(mixed, b"PROXY TCP6 ::ffff:127.0.0.1 ::1 5000 443\r\n");
+    }
+
+    #[test]
+    fn v2_headers_are_binary_and_rotate_sources() {
+        let header = ProxyHeader::v2()
+            .source("10.0.0.1:1".parse().unwrap())
+            .source("10.0.0.2:2".parse().unwrap());
+        let peer = "10.0.0.9:80".parse().unwrap();
+        let local = "127.0.0.1:5".parse().unwrap();
+
+        let first = header.encode(local, peer);
+        let mut expected = V2_SIGNATURE.to_vec();
+        expected.extend([0x21, 0x11, 0, 12, 10, 0, 0, 1, 10, 0, 0, 9, 0, 1, 0, 80]);
+        assert_eq!(first, expected);
+        assert_eq!(header.encode(local, peer)[16..20], [10, 0, 0, 2]);
+        assert_eq!(header.encode(local, peer)[16..20], [10, 0, 0, 1]);
+        assert_eq!(header.version(), ProxyVersion::V2);
+    }
+}
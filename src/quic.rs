@@ -0,0 +1,167 @@
+use std::{error::Error, fmt::Display, future::poll_fn, net::SocketAddr, sync::Arc};
+
+use h3::client::SendRequest;
+use hyper::{
+    body::{self, Buf, Bytes},
+    Body, Request, Response, Uri,
+};
+use quinn::{crypto::rustls::QuicClientConfig, ClientConfig, Endpoint};
+
+use crate::{session::TlsOptions, tls, transport::Protocol};
+
+#[derive(Debug)]
+pub(crate) struct HandshakeError(Box<dyn Error + Send + Sync>);
+
+impl Display for HandshakeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "QUIC handshake failed: {}", self.0)
+    }
+}
+
+impl Error for HandshakeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.0)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct QuicConfig {
+    config: ClientConfig,
+    server_name: String,
+}
+
+impl QuicConfig {
+    pub(crate) fn new(
+        uri: &Uri,
+        options: &TlsOptions,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let crypto = tls::client_config(options, Protocol::Http3)?;
+        let host = uri.host().ok_or("missing host")?;
+        Ok(Self {
+            config: ClientConfig::new(Arc::new(QuicClientConfig::try_from((*crypto).clone())?)),
+            server_name: host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_string(),
+        })
+    }
+}
+
+pub(crate) struct QuicClient {
+    addr: SocketAddr,
+    config: QuicConfig,
+    endpoint: Option<Endpoint>,
+    connection: Option<SendRequest<h3_quinn::OpenStreams, Bytes>>,
+}
+
+impl QuicClient {
+    pub(crate) fn new(addr: SocketAddr, config: QuicConfig) -> Self {
+        Self {
+            addr,
+            config,
+            endpoint: None,
+            connection: None,
+        }
+    }
+
+    pub(crate) async fn request(
+        &mut self,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let mut connection = match self.connection.take() {
+            Some(connection) => connection,
+            None => self.connect().await.map_err(HandshakeError)?,
+        };
+        let res = send(&mut connection, req).await?;
+        self.connection = Some(connection);
+        Ok(res)
+    }
+
+    async fn connect(
+        &mut self,
+    ) -> Result<SendRequest<h3_quinn::OpenStreams, Bytes>, Box<dyn Error + Send + Sync>> {
+        let endpoint = match &self.endpoint {
+            Some(endpoint) => endpoint.clone(),
+            None => {
+                let bind: SocketAddr = match self.addr {
+                    SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+                    SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+                };
+                let mut endpoint = Endpoint::client(bind)?;
+                endpoint.set_default_client_config(self.config.config.clone());
+                self.endpoint = Some(endpoint.clone());
+                endpoint
+            }
+        };
+
+        let connection = endpoint
+            .connect(self.addr, &self.config.server_name)?
+            .await?;
+        let (mut driver, send_request) =
+            h3::client::new(h3_quinn::Connection::new(connection)).await?;
+        tokio::spawn(async move {
+            poll_fn(|cx| driver.poll_close(cx)).await;
+        });
+        Ok(send_request)
+    }
+}
+
+async fn send(
+    connection: &mut SendRequest<h3_quinn::OpenStreams, Bytes>,
+    req: Request<Body>,
+) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+    let (parts, req_body) = req.into_parts();
+    let mut builder = http::Request::builder()
+        .method(parts.method.as_str())
+        .uri(parts.uri.to_string());
+    for (name, value) in &parts.headers {
+        builder = builder.header(name.as_str(), value.as_bytes());
+    }
+    let req_body = body::to_bytes(req_body).await?;
+
+    let mut stream = connection.send_request(builder.body(())?).await?;
+    if !req_body.is_empty() {
+        stream.send_data(req_body).await?;
+    }
+    stream.finish().await?;
+
+    let head = stream.recv_response().await?;
+    let mut res_body = Vec::new();
+    while let Some(mut chunk) = stream.recv_data().await? {
+        while chunk.has_remaining() {
+            let bytes = chunk.chunk();
+            res_body.extend_from_slice(bytes);
+            let len = bytes.len();
+            chunk.advance(len);
+        }
+    }
+
+    let mut res = Response::builder().status(head.status().as_u16());
+    for (name, value) in head.headers() {
+        res = res.header(name.as_str(), value.as_bytes());
+    }
+    Ok(res.body(Body::from(res_body))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handshake_errors_name_quic_and_keep_their_cause() {
+        let err = HandshakeError("connection refused".into());
+        assert_eq!(err.to_string(), "QUIC handshake failed: connection refused");
+        assert_eq!(err.source().unwrap().to_string(), "connection refused");
+    }
+
+    #[test]
+    fn ipv6_hosts_lose_their_brackets_for_sni() {
+        let options = TlsOptions::default();
+        let uri = "https://[::1]:4433/".parse().unwrap();
+        assert_eq!(QuicConfig::new(&uri, &options).unwrap().server_name, "::1");
+
+        let relative = Uri::from_static("/only-a-path");
+        let err = QuicConfig::new(&relative, &options).err().unwrap();
+        assert_eq!(err.to_string(), "missing host");
+    }
+}
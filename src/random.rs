@@ -0,0 +1,149 @@
+use std::{
+    error::Error,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+};
+
+use hyper::{
+    header::{HeaderName, HeaderValue},
+    Body, Request, Response, Uri,
+};
+use rand::{seq::SliceRandom, Rng};
+
+use crate::user::{UserContext, VirtualUser};
+
+#[derive(Debug, Clone)]
+enum Kind {
+    OneOf(Vec<HeaderValue>),
+    Ipv4 { base: u32, size: u64 },
+    Ipv6 { base: u128, size: u128 },
+}
+
+#[derive(Debug, Clone)]
+pub struct RandomValue(Kind);
+
+impl RandomValue {
+    pub fn one_of<I, V>(values: I) -> Result<Self, Box<dyn Error + Send + Sync>>
+    where
+        I: IntoIterator<Item = V>,
+        V: AsRef<str>,
+    {
+        let values = values
+            .into_iter()
+            .map(|value| HeaderValue::from_str(value.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if values.is_empty() {
+            return Err("at least one header value is required".into());
+        }
+        Ok(Self(Kind::OneOf(values)))
+    }
+
+    pub fn ip_in(cidr: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let (ip, prefix) = cidr.split_once('/').ok_or("expected a CIDR range")?;
+        let prefix: u32 = prefix.parse()?;
+        match ip.parse::<IpAddr>()? {
+            IpAddr::V4(ip) if prefix <= 32 => {
+                let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+                Ok(Self(Kind::Ipv4 {
+                    base: u32::from(ip) & mask,
+                    size: 1u64 << (32 - prefix),
+                }))
+            }
+            IpAddr::V6(ip) if prefix <= 128 => {
+                let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+                Ok(Self(Kind::Ipv6 {
+                    base: u128::from(ip) & mask,
+                    size: 1u128.checked_shl(128 - prefix).unwrap_or(0),
+                }))
+            }
+            _ => Err("invalid CIDR prefix length".into()),
+        }
+    }
+
+    pub(crate) fn sample(&self, rng: &mut impl Rng) -> HeaderValue {
+        match &self.0 {
+            Kind::OneOf(values) => values.choose(rng).unwrap().clone(),
+            Kind::Ipv4 { base, size } => {
+                let ip = Ipv4Addr::from(base + rng.gen_range(0..*size) as u32);
+                HeaderValue::from_str(&ip.to_string()).unwrap()
+            }
+            Kind::Ipv6 { base, size } => {
+                let offset = match size {
+                    0 => rng.gen(),
+                    size => rng.gen_range(0..*size),
+                };
+                let ip = Ipv6Addr::from(base + offset);
+                HeaderValue::from_str(&ip.to_string()).unwrap()
+            }
+        }
+    }
+}
+
+pub(crate) struct RandomizedUser {
+    pub(crate) inner: Box<dyn VirtualUser>,
+    pub(crate) headers: Arc<Vec<(HeaderName, RandomValue)>>,
+}
+
+impl VirtualUser for RandomizedUser {
+    fn on_start(&mut self, ctx: &UserContext) {
+        self.inner.on_start(ctx)
+    }
+
+    fn next_request(&mut self, uri: &Uri, ctx: &UserContext) -> Option<Request<Body>> {
+        let mut req = self.inner.next_request(uri, ctx)?;
+        let mut rng = rand::thread_rng();
+        for (name, value) in self.headers.iter() {
+            req.headers_mut()
+                .insert(name.clone(), value.sample(&mut rng));
+        }
+        Some(req)
+    }
+
+    fn on_response(&mut self, res: Response<Body>, ctx: &UserContext) -> bool {
+        self.inner.on_response(res, ctx)
+    }
+
+    fn on_stop(&mut self, ctx: &UserContext) {
+        self.inner.on_stop(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cidr_samples_stay_inside_the_range() {
+        let mut rng = rand::thread_rng();
+        let v4 = RandomValue::ip_in("10.1.2.3/24").unwrap();
+        for _ in 0..100 {
+            let ip: Ipv4Addr = v4.sample(&mut rng).to_str().unwrap().parse().unwrap();
+            assert_eq!(ip.octets()[..3], [10, 1, 2]);
+        }
+        let single = RandomValue::ip_in("192.0.2.1/32").unwrap();
+        assert_eq!(single.sample(&mut rng), "192.0.2.1");
+
+        let v6 = RandomValue::ip_in("2001:db8::/64").unwrap();
+        let ip: Ipv6Addr = v6.sample(&mut rng).to_str().unwrap().parse().unwrap();
+        assert_eq!(ip.segments()[..4], [0x2001, 0xdb8, 0, 0]);
+        let any = RandomValue::ip_in("::/0").unwrap();
+        assert!(any
+            .sample(&mut rng)
+            .to_str()
+            .unwrap()
+            .parse::<Ipv6Addr>()
+            .is_ok());
+    }
+
+    #[test]
+    fn bad_ranges_and_values_are_rejected() {
+        assert!(RandomValue::ip_in("10.0.0.0").is_err());
+        assert!(RandomValue::ip_in("10.0.0.0/33").is_err());
+        assert!(RandomValue::ip_in("host/8").is_err());
+        assert!(RandomValue::one_of(Vec::<&str>::new()).is_err());
+        assert!(RandomValue::one_of(["bad\nvalue"]).is_err());
+
+        let one = RandomValue::one_of(["blue"]).unwrap();
+        assert_eq!(one.sample(&mut rand::thread_rng()), "blue");
+    }
+}
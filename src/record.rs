@@ -0,0 +1,594 @@
+use std::{
+    convert::Infallible,
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    net::SocketAddr,
+    path::Path,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::channel::oneshot;
+use hyper::{
+    body::{self, Bytes},
+    header::{HeaderName, HeaderValue, CONNECTION, CONTENT_TYPE, HOST},
+    service::{make_service_fn, service_fn},
+    Body, Client, HeaderMap, Method, Request, Response, Server, StatusCode, Uri,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    redact::Redactor,
+    uri::{rebase, try_rebase},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    Multiplier(f64),
+    Max,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RecordedBody {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl RecordedBody {
+    fn new(bytes: Bytes) -> Option<Self> {
+        if bytes.is_empty() {
+            return None;
+        }
+        Some(match String::from_utf8(bytes.to_vec()) {
+            Ok(text) => RecordedBody::Text(text),
+            Err(err) => RecordedBody::Binary(err.into_bytes()),
+        })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            RecordedBody::Text(text) => text.as_bytes(),
+            RecordedBody::Binary(bytes) => bytes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    offset_us: u64,
+    method: String,
+    path: String,
+    #[serde(default)]
+    headers: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<RecordedBody>,
+}
+
+impl RecordedRequest {
+    pub fn offset(&self) -> Duration {
+        Duration::from_micros(self.offset_us)
+    }
+
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub fn body(&self) -> Option<&RecordedBody> {
+        self.body.as_ref()
+    }
+
+    fn validate(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        Method::try_from(self.method.as_str())?;
+        try_rebase(&Uri::default(), &self.path)?;
+        for (name, value) in self.headers() {
+            HeaderName::try_from(name)?;
+            HeaderValue::try_from(value)?;
+        }
+        Ok(())
+    }
+
+    fn redacted(&self, redactor: &Redactor) -> Self {
+        Self {
+            path: redactor.text(&self.path),
+            headers: self
+                .headers()
+                .map(|(name, value)| (name.to_string(), redactor.header_text(name, value)))
+                .collect(),
+            body: self.body.as_ref().map(|body| match body {
+                RecordedBody::Text(text) => RecordedBody::Text(redactor.text(text)),
+                RecordedBody::Binary(bytes) => RecordedBody::Binary(bytes.clone()),
+            }),
+            ..self.clone()
+        }
+    }
+
+    pub(crate) fn prepare(&self, target: &Uri) -> PreparedRequest {
+        let headers = self
+            .headers()
+            .map(|(name, value)| {
+                (
+                    HeaderName::try_from(name).unwrap(),
+                    HeaderValue::try_from(value).unwrap(),
+                )
+            })
+            .collect();
+
+        PreparedRequest {
+            method: Method::try_from(self.method.as_str()).unwrap(),
+            uri: rebase(target, &self.path),
+            headers,
+            body: self
+                .body
+                .as_ref()
+                .map(|body| Bytes::copy_from_slice(body.as_bytes()))
+                .unwrap_or_default(),
+        }
+    }
+
+    pub(crate) fn to_request(&self, target: &Uri) -> Request<Body> {
+        self.prepare(target).to_request()
+    }
+}
+
+pub(crate) struct PreparedRequest {
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl PreparedRequest {
+    pub(crate) fn to_request(&self) -> Request<Body> {
+        let mut req = Request::new(Body::from(self.body.clone()));
+        *req.method_mut() = self.method.clone();
+        *req.uri_mut() = self.uri.clone();
+        *req.headers_mut() = self.headers.clone();
+        req
+    }
+}
+
+/// Headers that describe one connection rather than the request, so replaying them verbatim
+/// would be wrong.
+const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    requests: Vec<RecordedRequest>,
+    redactor: Redactor,
+    started_at: Option<SystemTime>,
+}
+
+impl Recording {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn requests(&self) -> &[RecordedRequest] {
+        &self.requests
+    }
+
+    pub fn secret(mut self, value: impl Into<String>) -> Self {
+        self.redactor.value(value.into());
+        self
+    }
+
+    pub fn secret_header(mut self, name: impl Into<String>) -> Self {
+        self.redactor.header(name.into());
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    pub(crate) fn prepare(&self, target: &Uri) -> Vec<PreparedRequest> {
+        self.requests
+            .iter()
+            .map(|req| req.prepare(target))
+            .collect()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Self::from_reader(File::open(path)?)
+    }
+
+    pub fn from_reader(reader: impl Read) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut requests = Vec::new();
+        for line in BufReader::new(reader).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                let request: RecordedRequest = serde_json::from_str(&line)?;
+                request.validate()?;
+                requests.push(request);
+            }
+        }
+        Ok(Self {
+            requests,
+            ..Self::default()
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.to_writer(File::create(path)?)
+    }
+
+    pub fn to_writer(&self, writer: impl Write) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut writer = BufWriter::new(writer);
+        for request in &self.requests {
+            serde_json::to_writer(&mut writer, &request.redacted(&self.redactor))?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn save_har(
+        &self,
+        path: impl AsRef<Path>,
+        base: &Uri,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.to_har_writer(File::create(path)?, base)
+    }
+
+    /// Writes the recording as a HAR 1.2 log with URLs against `base`, redacted like
+    /// [`Recording::to_writer`]. The proxy keeps no responses, so each entry has an empty one,
+    /// and entries start at the wall-clock time the proxy was started (the Unix epoch for
+    /// recordings that were loaded from disk).
+    pub fn to_har_writer(
+        &self,
+        writer: impl Write,
+        base: &Uri,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let started_at = self.started_at.unwrap_or(UNIX_EPOCH);
+        let entries = self
+            .requests
+            .iter()
+            .map(|request| {
+                let request = request.redacted(&self.redactor);
+                let headers: Vec<_> = request
+                    .headers()
+                    .map(|(name, value)| json!({ "name": name, "value": value }))
+                    .collect();
+                let mut har = json!({
+                    "method": request.method,
+                    "url": try_rebase(base, &request.path)?.to_string(),
+                    "httpVersion": "HTTP/1.1",
+                    "headers": headers,
+                    "queryString": [],
+                    "cookies": [],
+                    "headersSize": -1,
+                    "bodySize": request.body.as_ref().map_or(0, |body| body.as_bytes().len()),
+                });
+                if let Some(body) = &request.body {
+                    let mime = request
+                        .headers()
+                        .find(|(name, _)| name.eq_ignore_ascii_case(CONTENT_TYPE.as_str()))
+                        .map_or("application/octet-stream", |(_, value)| value);
+                    har["postData"] = match body {
+                        RecordedBody::Text(text) => json!({ "mimeType": mime, "text": text }),
+                        RecordedBody::Binary(bytes) => json!({
+                            "mimeType": mime,
+                            "text": STANDARD.encode(bytes),
+                            "encoding": "base64",
+                        }),
+                    };
+                }
+                Ok(json!({
+                    "startedDateTime": rfc3339(started_at + request.offset()),
+                    "time": 0,
+                    "request": har,
+                    "response": {
+                        "status": 0,
+                        "statusText": "",
+                        "httpVersion": "HTTP/1.1",
+                        "headers": [],
+                        "cookies": [],
+                        "content": { "size": 0, "mimeType": "" },
+                        "redirectURL": "",
+                        "headersSize": -1,
+                        "bodySize": -1,
+                    },
+                    "cache": {},
+                    "timings": { "send": 0, "wait": 0, "receive": 0 },
+                }))
+            })
+            .collect::<Result<Vec<_>, Box<dyn Error + Send + Sync>>>()?;
+
+        let har = json!({
+            "log": {
+                "version": "1.2",
+                "creator": { "name": "zerg", "version": env!("CARGO_PKG_VERSION") },
+                "entries": entries,
+            }
+        });
+        let mut writer = BufWriter::new(writer);
+        serde_json::to_writer(&mut writer, &har)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+fn rfc3339(time: SystemTime) -> String {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since.as_secs();
+    // Days since the epoch to a civil date, after Howard Hinnant's `civil_from_days`.
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    let time = secs % 86_400;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60,
+        since.subsec_millis()
+    )
+}
+
+pub struct RecordingProxy {
+    local_addr: SocketAddr,
+    recording: Arc<Mutex<Recording>>,
+    shutdown: oneshot::Sender<()>,
+    handle: thread::JoinHandle<()>,
+}
+
+impl RecordingProxy {
+    pub fn start<T>(
+        addr: impl Into<SocketAddr>,
+        upstream: T,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>>
+    where
+        Uri: TryFrom<T>,
+        <Uri as TryFrom<T>>::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        let addr = addr.into();
+        let upstream = Arc::new(Uri::try_from(upstream).map_err(Into::into)?);
+        let recording = Arc::new(Mutex::new(Recording::new()));
+        let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+        let (bound_tx, bound_rx) = mpsc::channel();
+
+        let handle = {
+            let recording = recording.clone();
+            thread::spawn(move || {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap();
+
+                runtime.block_on(async move {
+                    let server = match Server::try_bind(&addr) {
+                        Ok(server) => server,
+                        Err(err) => {
+                            bound_tx.send(Err(err.to_string())).ok();
+                            return;
+                        }
+                    };
+
+                    let start = Instant::now();
+                    recording.lock().unwrap().started_at = Some(SystemTime::now());
+                    let client = Client::new();
+
+                    let make_service = make_service_fn(move |_| {
+                        let upstream = upstream.clone();
+                        let recording = recording.clone();
+                        let client = client.clone();
+                        async move {
+                            Ok::<_, Infallible>(service_fn(move |req| {
+                                forward(
+                                    req,
+                                    start,
+                                    upstream.clone(),
+                                    recording.clone(),
+                                    client.clone(),
+                                )
+                            }))
+                        }
+                    });
+
+                    let server = server.serve(make_service);
+                    bound_tx.send(Ok(server.local_addr())).ok();
+                    server
+                        .with_graceful_shutdown(async move {
+                            shutdown_rx.await.ok();
+                        })
+                        .await
+                        .ok();
+                });
+            })
+        };
+
+        let local_addr = bound_rx.recv()??;
+
+        Ok(Self {
+            local_addr,
+            recording,
+            shutdown,
+            handle,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn recording(&self) -> Recording {
+        self.recording.lock().unwrap().clone()
+    }
+
+    pub fn stop(self) -> Recording {
+        self.shutdown.send(()).ok();
+        self.handle.join().ok();
+        self.recording.lock().unwrap().clone()
+    }
+}
+
+fn end_to_end(headers: &HeaderMap) -> Vec<(String, String)> {
+    // Besides the fixed hop-by-hop set, `Connection` may name more headers that only apply
+    // to this hop.
+    let nominated: Vec<String> = headers
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .collect();
+    headers
+        .iter()
+        .filter(|(name, _)| {
+            *name != HOST
+                && !HOP_BY_HOP.contains(&name.as_str())
+                && !nominated.iter().any(|nominated| nominated == name.as_str())
+        })
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+async fn forward(
+    req: Request<Body>,
+    start: Instant,
+    upstream: Arc<Uri>,
+    recording: Arc<Mutex<Recording>>,
+    client: Client<hyper::client::HttpConnector>,
+) -> Result<Response<Body>, Infallible> {
+    let offset = start.elapsed();
+    let (parts, body) = req.into_parts();
+    let body = match body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(_) => return Ok(status(StatusCode::BAD_REQUEST)),
+    };
+
+    let path = parts
+        .uri
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or("/")
+        .to_string();
+
+    let recorded = RecordedRequest {
+        offset_us: offset.as_micros() as u64,
+        method: parts.method.to_string(),
+        path,
+        headers: end_to_end(&parts.headers),
+        body: RecordedBody::new(body.clone()),
+    };
+
+    let mut req = recorded.to_request(&upstream);
+    *req.body_mut() = Body::from(body);
+    {
+        let requests = &mut recording.lock().unwrap().requests;
+        let at = requests.partition_point(|req| req.offset_us <= recorded.offset_us);
+        requests.insert(at, recorded);
+    }
+
+    match client.request(req).await {
+        Ok(res) => Ok(res),
+        Err(_) => Ok(status(StatusCode::BAD_GATEWAY)),
+    }
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hop_by_hop_headers_are_not_recorded() {
+        let mut headers = HeaderMap::new();
+        headers.insert(HOST, HeaderValue::from_static("localhost"));
+        headers.insert(CONNECTION, HeaderValue::from_static("keep-alive, X-Hop"));
+        headers.insert("keep-alive", HeaderValue::from_static("timeout=5"));
+        headers.insert("transfer-encoding", HeaderValue::from_static("chunked"));
+        headers.insert("proxy-authorization", HeaderValue::from_static("Basic x"));
+        headers.insert("x-hop", HeaderValue::from_static("1"));
+        headers.insert("x-trace", HeaderValue::from_static("2"));
+
+        assert_eq!(
+            end_to_end(&headers),
+            vec![("x-trace".to_string(), "2".to_string())]
+        );
+    }
+
+    #[test]
+    fn timestamps_are_rfc3339() {
+        assert_eq!(rfc3339(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            rfc3339(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "2000-02-29T00:00:00.000Z"
+        );
+        assert_eq!(
+            rfc3339(UNIX_EPOCH + Duration::from_millis(1_700_000_000_250)),
+            "2023-11-14T22:13:20.250Z"
+        );
+    }
+
+    #[test]
+    fn har_export_is_redacted_and_absolute() {
+        let ndjson = concat!(
+            r#"{"offset_us":1500000,"method":"POST","path":"/login?key=s3cret","#,
+            r#""headers":[["content-type","application/json"],["authorization","Bearer s3cret"]],"#,
+            r#""body":"{\"password\":\"s3cret\"}"}"#,
+        );
+        let recording = Recording::from_reader(ndjson.as_bytes())
+            .unwrap()
+            .secret("s3cret");
+
+        let mut har = Vec::new();
+        recording
+            .to_har_writer(&mut har, &"http://example.com".parse().unwrap())
+            .unwrap();
+        let har: serde_json::Value = serde_json::from_slice(&har).unwrap();
+
+        assert_eq!(har["log"]["version"], "1.2");
+        let entry = &har["log"]["entries"][0];
+        assert_eq!(entry["startedDateTime"], "1970-01-01T00:00:01.500Z");
+        let request = &entry["request"];
+        assert_eq!(request["method"], "POST");
+        assert!(request["url"]
+            .as_str()
+            .unwrap()
+            .starts_with("http://example.com/login?key="));
+        assert_eq!(request["postData"]["mimeType"], "application/json");
+        assert!(!har.to_string().contains("s3cret"));
+    }
+}
@@ -0,0 +1,93 @@
+use hyper::header::{HeaderName, HeaderValue};
+
+pub(crate) const REDACTED: &str = "[redacted]";
+
+const SECRET_HEADERS: [&str; 5] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+];
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Redactor {
+    values: Vec<String>,
+    headers: Vec<String>,
+}
+
+impl Redactor {
+    pub(crate) fn value(&mut self, value: String) {
+        if !value.is_empty() && !self.values.contains(&value) {
+            self.values.push(value);
+            self.values
+                .sort_by_key(|value| std::cmp::Reverse(value.len()));
+        }
+    }
+
+    pub(crate) fn header(&mut self, name: String) {
+        self.headers.push(name.to_ascii_lowercase());
+    }
+
+    pub(crate) fn header_value(&self, name: &HeaderName, value: &HeaderValue) -> String {
+        match value.is_sensitive() || self.secret(name.as_str()) {
+            true => REDACTED.to_string(),
+            false => self.text(&String::from_utf8_lossy(value.as_bytes())),
+        }
+    }
+
+    pub(crate) fn header_text(&self, name: &str, value: &str) -> String {
+        match self.secret(&name.to_ascii_lowercase()) {
+            true => REDACTED.to_string(),
+            false => self.text(value),
+        }
+    }
+
+    fn secret(&self, name: &str) -> bool {
+        self.headers.iter().any(|header| header == name) || SECRET_HEADERS.contains(&name)
+    }
+
+    pub(crate) fn text(&self, text: &str) -> String {
+        self.values.iter().fold(text.to_string(), |text, value| {
+            text.replace(value, REDACTED)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn longer_secrets_are_redacted_first() {
+        let mut redactor = Redactor::default();
+        redactor.value("abc".into());
+        redactor.value("abcdef".into());
+        redactor.value(String::new());
+        assert_eq!(
+            redactor.text("key=abcdef&id=abc"),
+            "key=[redacted]&id=[redacted]"
+        );
+        assert_eq!(redactor.text("nothing here"), "nothing here");
+    }
+
+    #[test]
+    fn secret_headers_are_redacted_whole() {
+        let mut redactor = Redactor::default();
+        redactor.header("X-Session".into());
+        redactor.value("tok".into());
+
+        assert_eq!(redactor.header_text("Authorization", "Bearer x"), REDACTED);
+        assert_eq!(redactor.header_text("x-session", "1"), REDACTED);
+        assert_eq!(redactor.header_text("x-trace", "a-tok-b"), "a-[redacted]-b");
+
+        let mut sensitive = HeaderValue::from_static("plain");
+        sensitive.set_sensitive(true);
+        let name = HeaderName::from_static("x-plain");
+        assert_eq!(redactor.header_value(&name, &sensitive), REDACTED);
+        assert_eq!(
+            redactor.header_value(&name, &HeaderValue::from_static("plain")),
+            "plain"
+        );
+    }
+}
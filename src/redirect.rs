@@ -0,0 +1,223 @@
+use std::{collections::HashSet, error::Error, fmt::Display, mem};
+
+use hyper::{
+    body::{self, Bytes},
+    header::{CONTENT_LENGTH, CONTENT_TYPE, LOCATION, TRANSFER_ENCODING},
+    http::uri::{Parts, PathAndQuery},
+    Body, HeaderMap, Method, Request, Response, StatusCode, Uri,
+};
+
+use crate::transport::Transport;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RedirectError {
+    Loop,
+    Limit,
+}
+
+impl Display for RedirectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Loop => write!(f, "redirect loop"),
+            Self::Limit => write!(f, "too many redirects"),
+        }
+    }
+}
+
+impl Error for RedirectError {}
+
+pub(crate) struct Origin {
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl Origin {
+    pub(crate) async fn of(req: &mut Request<Body>) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let body = body::to_bytes(mem::take(req.body_mut())).await?;
+        *req.body_mut() = Body::from(body.clone());
+        Ok(Self {
+            method: req.method().clone(),
+            uri: req.uri().clone(),
+            headers: req.headers().clone(),
+            body,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Followed {
+    pub(crate) hops: usize,
+    pub(crate) looped: bool,
+}
+
+pub(crate) async fn follow(
+    transport: &mut Transport,
+    mut res: Result<Response<Body>, Box<dyn Error + Send + Sync>>,
+    origin: Origin,
+    max_hops: usize,
+) -> (
+    Result<Response<Body>, Box<dyn Error + Send + Sync>>,
+    Followed,
+) {
+    let Origin {
+        mut method,
+        mut uri,
+        mut headers,
+        mut body,
+    } = origin;
+    let mut visited = HashSet::from([uri.clone()]);
+    let mut followed = Followed::default();
+
+    loop {
+        let Ok(response) = &res else {
+            return (res, followed);
+        };
+        let Some(next) = location(response.status(), response.headers(), &uri) else {
+            return (res, followed);
+        };
+        if !visited.insert(next.clone()) {
+            followed.looped = true;
+            return (Err(Box::new(RedirectError::Loop)), followed);
+        }
+        if followed.hops == max_hops {
+            return (Err(Box::new(RedirectError::Limit)), followed);
+        }
+
+        let status = response.status();
+        if let Ok(response) = res {
+            body::to_bytes(response.into_body()).await.ok();
+        }
+        if status == StatusCode::SEE_OTHER && method != Method::HEAD
+            || matches!(status, StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND)
+                && method == Method::POST
+        {
+            method = Method::GET;
+            headers.remove(CONTENT_TYPE);
+            body = Bytes::new();
+        }
+        headers.remove(CONTENT_LENGTH);
+        headers.remove(TRANSFER_ENCODING);
+        uri = next;
+
+        let mut req = Request::new(Body::from(body.clone()));
+        *req.method_mut() = method.clone();
+        *req.uri_mut() = uri.clone();
+        *req.headers_mut() = headers.clone();
+        followed.hops += 1;
+        res = transport.request(req).await;
+    }
+}
+
+fn location(status: StatusCode, headers: &HeaderMap, current: &Uri) -> Option<Uri> {
+    if !matches!(
+        status,
+        StatusCode::MOVED_PERMANENTLY
+            | StatusCode::FOUND
+            | StatusCode::SEE_OTHER
+            | StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT
+    ) {
+        return None;
+    }
+    let location = headers.get(LOCATION)?.to_str().ok()?.trim();
+    let next = match location.parse::<Uri>().ok() {
+        Some(uri) if uri.scheme().is_some() => uri,
+        _ if location.starts_with("//") => format!("{}:{location}", current.scheme_str()?)
+            .parse()
+            .ok()?,
+        _ => {
+            let path = match location.starts_with('/') {
+                true => location.to_string(),
+                false => {
+                    let base = current.path();
+                    format!(
+                        "{}{location}",
+                        &base[..base.rfind('/').map_or(0, |i| i + 1)]
+                    )
+                }
+            };
+            let mut parts = Parts::default();
+            parts.scheme = current.scheme().cloned();
+            parts.authority = current.authority().cloned();
+            parts.path_and_query = Some(path.parse::<PathAndQuery>().ok()?);
+            Uri::from_parts(parts).ok()?
+        }
+    };
+    match next.scheme() == current.scheme() && next.authority() == current.authority() {
+        true => Some(next),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn next(status: u16, location: &str) -> Option<String> {
+        let mut headers = HeaderMap::new();
+        headers.insert(LOCATION, location.parse().unwrap());
+        let current = "http://example.com/a/b?q=1".parse().unwrap();
+        location_of(status, &headers, &current)
+    }
+
+    fn location_of(status: u16, headers: &HeaderMap, current: &Uri) -> Option<String> {
+        location(StatusCode::from_u16(status).unwrap(), headers, current).map(|uri| uri.to_string())
+    }
+
+    #[test]
+    fn locations_resolve_against_the_current_uri() {
+        assert_eq!(
+            next(302, "/login").as_deref(),
+            Some("http://example.com/login")
+        );
+        assert_eq!(
+            next(301, "c?d=2").as_deref(),
+            Some("http://example.com/a/c?d=2")
+        );
+        assert_eq!(
+            next(307, "//example.com/x").as_deref(),
+            Some("http://example.com/x")
+        );
+        assert_eq!(
+            next(308, "http://example.com/y").as_deref(),
+            Some("http://example.com/y")
+        );
+    }
+
+    #[test]
+    fn other_origins_and_statuses_are_not_followed() {
+        assert_eq!(next(302, "https://example.com/"), None);
+        assert_eq!(next(302, "http://other.com/"), None);
+        assert_eq!(next(200, "/login"), None);
+        assert_eq!(next(304, "/login"), None);
+        assert_eq!(
+            location_of(
+                302,
+                &HeaderMap::new(),
+                &"http://example.com/".parse().unwrap()
+            ),
+            None
+        );
+        assert_eq!(RedirectError::Loop.to_string(), "redirect loop");
+    }
+
+    #[test]
+    fn origins_keep_the_body_for_later_hops() {
+        let mut req = Request::post("http://example.com/")
+            .header(CONTENT_TYPE, "text/plain")
+            .body(Body::from("payload"))
+            .unwrap();
+        let (origin, resent) = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let origin = Origin::of(&mut req).await.unwrap();
+                (origin, body::to_bytes(req.into_body()).await.unwrap())
+            });
+        assert_eq!((origin.body, resent), ("payload".into(), "payload".into()));
+        assert_eq!(origin.method, Method::POST);
+        assert_eq!(origin.headers[CONTENT_TYPE], "text/plain");
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    env,
+    error::Error,
+    io,
+    path::PathBuf,
+    process::{Command, Stdio},
+    thread,
+};
+
+use crate::result::BenchmarkResult;
+
+const REMOTE_DIR: &str = "/tmp";
+const WORKER_ENV: &str = "ZERG_WORKER";
+
+#[derive(Debug, Clone)]
+struct SshWorker {
+    host: String,
+    region: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SshCluster {
+    binary: PathBuf,
+    args: Vec<String>,
+    workers: Vec<SshWorker>,
+    remote_dir: String,
+    ssh_options: Vec<String>,
+    scp_options: Vec<String>,
+}
+
+impl SshCluster {
+    pub fn new(binary: impl Into<PathBuf>) -> Self {
+        Self {
+            binary: binary.into(),
+            args: Vec::new(),
+            workers: Vec::new(),
+            remote_dir: REMOTE_DIR.to_string(),
+            ssh_options: Vec::new(),
+            scp_options: Vec::new(),
+        }
+    }
+
+    pub fn agent(
+        f: impl FnOnce(usize) -> BenchmarkResult,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let worker = env::var(WORKER_ENV)
+            .map_err(|_| format!("{WORKER_ENV} is not set, start the agent through SshCluster"))?
+            .parse()
+            .map_err(|_| format!("{WORKER_ENV} is not a worker index"))?;
+        f(worker).to_writer(io::stdout().lock())
+    }
+
+    pub fn worker(mut self, host: impl Into<String>) -> Self {
+        self.workers.push(SshWorker {
+            host: host.into(),
+            region: None,
+        });
+        self
+    }
+
+    pub fn worker_in(mut self, host: impl Into<String>, region: impl Into<String>) -> Self {
+        self.workers.push(SshWorker {
+            host: host.into(),
+            region: Some(region.into()),
+        });
+        self
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn remote_dir(self, remote_dir: impl Into<String>) -> Self {
+        Self {
+            remote_dir: remote_dir.into(),
+            ..self
+        }
+    }
+
+    pub fn ssh_option(mut self, option: impl Into<String>) -> Self {
+        self.ssh_options.push(option.into());
+        self
+    }
+
+    pub fn scp_option(mut self, option: impl Into<String>) -> Self {
+        self.scp_options.push(option.into());
+        self
+    }
+
+    pub fn run(&self) -> Result<BenchmarkResult, Box<dyn Error + Send + Sync>> {
+        if self.workers.is_empty() {
+            return Err("no remote workers configured".into());
+        }
+        let name = self
+            .binary
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or("invalid agent binary path")?;
+        let remote = format!("{}/{name}", self.remote_dir.trim_end_matches('/'));
+
+        let handles = self
+            .workers
+            .iter()
+            .enumerate()
+            .map(|(index, worker)| {
+                let cluster = self.clone();
+                let worker = worker.clone();
+                let remote = remote.clone();
+                thread::spawn(move || cluster.run_worker(index, &worker, &remote))
+            })
+            .collect::<Vec<_>>();
+
+        let mut results = Vec::with_capacity(handles.len());
+        for handle in handles {
+            let result = handle
+                .join()
+                .map_err(|_| "remote worker thread panicked")??;
+            results.push(result);
+        }
+        Ok(BenchmarkResult::merge_aligned(results))
+    }
+
+    fn run_worker(
+        &self,
+        index: usize,
+        worker: &SshWorker,
+        remote: &str,
+    ) -> Result<BenchmarkResult, Box<dyn Error + Send + Sync>> {
+        let copied = Command::new("scp")
+            .args(&self.scp_options)
+            .arg("-q")
+            .arg(&self.binary)
+            .arg(format!("{}:{remote}", worker.host))
+            .stdout(Stdio::null())
+            .output()?;
+        if !copied.status.success() {
+            return Err(format!(
+                "copying agent to {} failed: {}",
+                worker.host,
+                String::from_utf8_lossy(&copied.stderr).trim()
+            )
+            .into());
+        }
+
+        let mut command = format!(
+            "chmod +x {remote} && {WORKER_ENV}={index} {remote}",
+            remote = quote(remote)
+        );
+        for arg in &self.args {
+            command.push(' ');
+            command.push_str(&quote(arg));
+        }
+
+        let output = Command::new("ssh")
+            .args(&self.ssh_options)
+            .arg(&worker.host)
+            .arg(command)
+            .output()?;
+        if !output.status.success() {
+            return Err(format!(
+                "agent on {} failed: {}",
+                worker.host,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+
+        let result = BenchmarkResult::from_reader(&output.stdout[..])
+            .map_err(|err| format!("invalid result from {}: {err}", worker.host))?;
+        Ok(result.with_region(worker.region.as_deref().unwrap_or(&worker.host)))
+    }
+}
+
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arguments_are_single_quoted_for_the_remote_shell() {
+        assert_eq!(quote("plain"), "'plain'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+        assert_eq!(quote("$HOME; rm"), "'$HOME; rm'");
+    }
+
+    #[test]
+    fn clusters_need_workers_and_a_binary_name() {
+        let error = |cluster: SshCluster| cluster.run().err().unwrap().to_string();
+        assert_eq!(
+            error(SshCluster::new("/bin/agent")),
+            "no remote workers configured"
+        );
+        assert_eq!(
+            error(SshCluster::new("/").worker("host")),
+            "invalid agent binary path"
+        );
+    }
+}
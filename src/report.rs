@@ -0,0 +1,221 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::Path,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::RunConfig, result::BenchmarkResult};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    offset_ms: u64,
+    interval_ms: u64,
+    success: usize,
+    http_error: usize,
+    tcp_error: usize,
+    requests_per_second: f64,
+    p50_ms: f64,
+    p90_ms: f64,
+    p99_ms: f64,
+    max_ms: f64,
+    total_requests: usize,
+}
+
+impl Snapshot {
+    pub(crate) fn new(
+        offset: Duration,
+        interval: Duration,
+        result: &BenchmarkResult,
+        total_requests: usize,
+    ) -> Self {
+        let percentiles = result.percentiles();
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let (p50, p90, p99, max) = match result.timings.is_empty() {
+            true => (0.0, 0.0, 0.0, 0.0),
+            false => (
+                ms(percentiles.percentile(0.50)),
+                ms(percentiles.percentile(0.90)),
+                ms(percentiles.percentile(0.99)),
+                ms(result.max_time),
+            ),
+        };
+        Self {
+            offset_ms: offset.as_millis() as u64,
+            interval_ms: interval.as_millis() as u64,
+            success: result.success,
+            http_error: result.http_error,
+            tcp_error: result.tcp_error,
+            requests_per_second: match interval.is_zero() {
+                true => 0.0,
+                false => result.total_request_count() as f64 / interval.as_secs_f64(),
+            },
+            p50_ms: p50,
+            p90_ms: p90,
+            p99_ms: p99,
+            max_ms: max,
+            total_requests,
+        }
+    }
+
+    pub fn offset(&self) -> Duration {
+        Duration::from_millis(self.offset_ms)
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms)
+    }
+
+    pub fn success_count(&self) -> usize {
+        self.success
+    }
+
+    pub fn http_error_count(&self) -> usize {
+        self.http_error
+    }
+
+    pub fn tcp_error_count(&self) -> usize {
+        self.tcp_error
+    }
+
+    pub fn requests_per_second(&self) -> f64 {
+        self.requests_per_second
+    }
+
+    pub fn p50_ms(&self) -> f64 {
+        self.p50_ms
+    }
+
+    pub fn p90_ms(&self) -> f64 {
+        self.p90_ms
+    }
+
+    pub fn p99_ms(&self) -> f64 {
+        self.p99_ms
+    }
+
+    pub fn max_ms(&self) -> f64 {
+        self.max_ms
+    }
+
+    pub fn total_requests(&self) -> usize {
+        self.total_requests
+    }
+}
+
+pub trait Reporter: Send + 'static {
+    fn start(&mut self, _config: &RunConfig) {}
+
+    fn report(&mut self, snapshot: &Snapshot);
+
+    fn finish(&mut self, _result: &BenchmarkResult) {}
+}
+
+pub struct NdjsonReporter {
+    file: File,
+}
+
+impl NdjsonReporter {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            file: File::create(path)?,
+        })
+    }
+
+    pub fn append(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            file: File::options().create(true).append(true).open(path)?,
+        })
+    }
+}
+
+impl Reporter for NdjsonReporter {
+    fn start(&mut self, config: &RunConfig) {
+        if let Ok(mut line) = serde_json::to_string(&serde_json::json!({
+            "config": config,
+            "fingerprint": config.fingerprint(),
+        })) {
+            line.push('\n');
+            let _ = self.file.write_all(line.as_bytes());
+        }
+    }
+
+    fn report(&mut self, snapshot: &Snapshot) {
+        if let Ok(mut line) = serde_json::to_string(snapshot) {
+            line.push('\n');
+            let _ = self.file.write_all(line.as_bytes());
+        }
+    }
+
+    fn finish(&mut self, _result: &BenchmarkResult) {
+        let _ = self.file.sync_data();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::result::Outcome;
+
+    #[test]
+    fn snapshots_summarize_one_interval() {
+        let mut result = BenchmarkResult::default();
+        for ms in [10, 20, 30, 40] {
+            result.record(Duration::ZERO, Duration::from_millis(ms), Outcome::Success);
+        }
+        result.record(Duration::ZERO, Duration::from_millis(5), Outcome::HttpError);
+        let snapshot = Snapshot::new(
+            Duration::from_secs(3),
+            Duration::from_millis(500),
+            &result,
+            42,
+        );
+        assert_eq!(snapshot.offset(), Duration::from_secs(3));
+        assert_eq!(
+            (snapshot.success_count(), snapshot.http_error_count()),
+            (4, 1)
+        );
+        assert_eq!(snapshot.requests_per_second(), 10.0);
+        assert_eq!(snapshot.max_ms(), 40.0);
+        assert!(snapshot.p50_ms() > 0.0 && snapshot.p50_ms() <= snapshot.p99_ms());
+        assert_eq!(snapshot.total_requests(), 42);
+
+        let empty = Snapshot::new(
+            Duration::ZERO,
+            Duration::ZERO,
+            &BenchmarkResult::default(),
+            0,
+        );
+        assert_eq!((empty.requests_per_second(), empty.p99_ms()), (0.0, 0.0));
+    }
+
+    #[test]
+    fn ndjson_reporters_write_one_snapshot_per_line() {
+        let path = std::env::temp_dir().join(format!("zerg-report-{}.ndjson", std::process::id()));
+        let snapshot = Snapshot::new(
+            Duration::ZERO,
+            Duration::from_secs(1),
+            &BenchmarkResult::default(),
+            0,
+        );
+        let mut reporter = NdjsonReporter::create(&path).unwrap();
+        reporter.report(&snapshot);
+        drop(reporter);
+        let mut reporter = NdjsonReporter::append(&path).unwrap();
+        reporter.report(&snapshot);
+        reporter.finish(&BenchmarkResult::default());
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<Snapshot> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].interval(), Duration::from_secs(1));
+        fs::remove_file(path).ok();
+    }
+}
@@ -0,0 +1,487 @@
+use std::{
+    convert::Infallible,
+    error::Error,
+    future::{ready, Ready},
+    io,
+    net::{SocketAddr, ToSocketAddrs},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+    vec,
+};
+
+use futures::future::BoxFuture;
+use hyper::{
+    client::{
+        connect::{dns::Name, Connected, Connection},
+        HttpConnector,
+    },
+    Uri,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::TcpStream,
+    sync::OwnedSemaphorePermit,
+};
+use tower::Service;
+
+#[cfg(feature = "native-tls")]
+use crate::native;
+#[cfg(feature = "tls")]
+use crate::tls;
+use crate::{
+    bind::BindPool, breakdown, cap::ConnectionCap, proxy::ProxyHeader, transport::Protocol,
+    tunnel::Tunnel,
+};
+#[cfg(any(feature = "tls", feature = "native-tls"))]
+use crate::{
+    session::{TlsOptions, TlsStats},
+    transport::TlsBackend,
+};
+
+pub(crate) type HttpClient = hyper::Client<Connector, hyper::Body>;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct KeepAlive {
+    pub(crate) interval: Option<Duration>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) idle_timeout: Option<Duration>,
+    pub(crate) disabled: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Sockets {
+    pub(crate) bind: Option<Arc<BindPool>>,
+    pub(crate) cap: Option<Arc<ConnectionCap>>,
+}
+
+impl Sockets {
+    pub(crate) fn uncapped(&self) -> Self {
+        Self {
+            bind: self.bind.clone(),
+            cap: None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) enum Https {
+    #[cfg(feature = "tls")]
+    Rustls(tls::Https),
+    #[cfg(feature = "native-tls")]
+    NativeTls(native::Https),
+}
+
+#[cfg(any(feature = "tls", feature = "native-tls"))]
+impl Https {
+    pub(crate) fn new(
+        uri: &Uri,
+        options: &TlsOptions,
+        backend: TlsBackend,
+        protocol: Protocol,
+        stats: TlsStats,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        match backend {
+            #[cfg(feature = "tls")]
+            TlsBackend::Rustls => Ok(Self::Rustls(tls::Https::new(
+                uri, options, protocol, stats,
+            )?)),
+            #[cfg(not(feature = "tls"))]
+            TlsBackend::Rustls => Err("the rustls backend requires the tls feature".into()),
+            #[cfg(feature = "native-tls")]
+            TlsBackend::NativeTls => Ok(Self::NativeTls(native::Https::new(
+                uri, options, protocol, stats,
+            )?)),
+            #[cfg(not(feature = "native-tls"))]
+            TlsBackend::NativeTls => {
+                Err("the native TLS backend requires the native-tls feature".into())
+            }
+        }
+    }
+}
+
+pub(crate) fn http_client(
+    dns: Resolver,
+    protocol: Protocol,
+    proxy: Option<ProxyHeader>,
+    tunnel: Option<Tunnel>,
+    keepalive: KeepAlive,
+    https: Option<Https>,
+    sockets: Sockets,
+) -> HttpClient {
+    let pinned = dns.pinned();
+    let mut http = HttpConnector::new_with_resolver(dns);
+    http.set_nodelay(true);
+    http.enforce_http(https.is_none());
+
+    let mut builder = hyper::Client::builder();
+    builder
+        .http2_only(protocol == Protocol::Http2)
+        .http2_keep_alive_interval(keepalive.interval)
+        .http2_keep_alive_while_idle(keepalive.interval.is_some());
+    if let Some(timeout) = keepalive.timeout {
+        builder.http2_keep_alive_timeout(timeout);
+    }
+    if let Some(idle_timeout) = keepalive.idle_timeout {
+        builder.pool_idle_timeout(idle_timeout);
+    }
+    if keepalive.disabled {
+        builder.pool_max_idle_per_host(0);
+    }
+    builder.build(Connector {
+        http,
+        pinned,
+        proxy,
+        tunnel,
+        https,
+        sockets,
+    })
+}
+
+#[derive(Clone)]
+pub(crate) struct Connector {
+    http: HttpConnector<Resolver>,
+    pinned: Option<SocketAddr>,
+    proxy: Option<ProxyHeader>,
+    tunnel: Option<Tunnel>,
+    https: Option<Https>,
+    sockets: Sockets,
+}
+
+impl Service<Uri> for Connector {
+    type Response = Stream;
+    type Error = Box<dyn Error + Send + Sync>;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.http.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let https = match uri.scheme_str() {
+            Some("https") => self.https.clone(),
+            _ => None,
+        };
+        let mut http = self.http.clone();
+        if let Some(bind) = &self.sockets.bind {
+            bind.apply(&mut http);
+        }
+        let (connecting, tunnel) = match &self.tunnel {
+            Some(tunnel) => (http.call(tunnel.proxy.clone()), Some((tunnel.clone(), uri))),
+            None => (http.call(pinned_to(uri, self.pinned)), None),
+        };
+        let proxy = self.proxy.clone();
+        let cap = self.sockets.cap.clone();
+        Box::pin(async move {
+            let permit = match cap {
+                Some(cap) => Some(cap.acquire().await?),
+                None => None,
+            };
+            let started = Instant::now();
+            let io: Result<Io, Self::Error> = async {
+                let mut stream = connecting.await?;
+                if let Some(proxy) = proxy {
+                    proxy.write_to(&mut stream).await?;
+                }
+                if let Some((tunnel, uri)) = tunnel {
+                    tunnel.open(&mut stream, &uri).await?;
+                }
+                match https {
+                    #[cfg(feature = "tls")]
+                    Some(Https::Rustls(https)) => {
+                        Ok(Io::Tls(Box::new(https.connect(stream).await?)))
+                    }
+                    #[cfg(feature = "native-tls")]
+                    Some(Https::NativeTls(https)) => {
+                        Ok(Io::NativeTls(Box::new(https.connect(stream).await?)))
+                    }
+                    None => Ok(Io::Plain(stream)),
+                }
+            }
+            .await;
+            breakdown::record_connecting(started.elapsed(), io.is_ok());
+            Ok(Stream {
+                io: io?,
+                _permit: permit,
+            })
+        })
+    }
+}
+
+/// Points the connect at the pinned address. Rewriting the whole authority, not just the port,
+/// matters for IP-literal targets, which the connector dials without asking the resolver.
+fn pinned_to(uri: Uri, pinned: Option<SocketAddr>) -> Uri {
+    let Some(addr) = pinned else {
+        return uri;
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.authority = addr.to_string().parse().ok();
+    Uri::from_parts(parts).unwrap_or(uri)
+}
+
+pub(crate) struct Stream {
+    io: Io,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+enum Io {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<tokio_rustls::client::TlsStream<TcpStream>>),
+    #[cfg(feature = "native-tls")]
+    NativeTls(Box<tokio_native_tls::TlsStream<TcpStream>>),
+}
+
+impl Connection for Stream {
+    fn connected(&self) -> Connected {
+        match &self.io {
+            Io::Plain(stream) => stream.connected(),
+            #[cfg(feature = "tls")]
+            Io::Tls(stream) => {
+                let (tcp, session) = stream.get_ref();
+                match session.alpn_protocol() {
+                    Some(b"h2") => tcp.connected().negotiated_h2(),
+                    _ => tcp.connected(),
+                }
+            }
+            #[cfg(feature = "native-tls")]
+            Io::NativeTls(stream) => {
+                let tls = stream.get_ref();
+                let tcp = tls.get_ref().get_ref();
+                match tls.negotiated_alpn() {
+                    Ok(Some(alpn)) if alpn == b"h2" => tcp.connected().negotiated_h2(),
+                    _ => tcp.connected(),
+                }
+            }
+        }
+    }
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().io {
+            Io::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(feature = "tls")]
+            Io::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(feature = "native-tls")]
+            Io::NativeTls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match &mut self.get_mut().io {
+            Io::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(feature = "tls")]
+            Io::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(feature = "native-tls")]
+            Io::NativeTls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().io {
+            Io::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(feature = "tls")]
+            Io::Tls(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(feature = "native-tls")]
+            Io::NativeTls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().io {
+            Io::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(feature = "tls")]
+            Io::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(feature = "native-tls")]
+            Io::NativeTls(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+pub trait Resolve: Send + Sync + 'static {
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>>;
+}
+
+impl<F> Resolve for F
+where
+    F: Fn(&str, u16) -> io::Result<Vec<SocketAddr>> + Send + Sync + 'static,
+{
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        self(host, port)
+    }
+}
+
+pub(crate) struct SystemResolver;
+
+impl Resolve for SystemResolver {
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        Ok((host, port).to_socket_addrs()?.collect())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AddressFamily {
+    #[default]
+    Any,
+    Ipv4,
+    Ipv6,
+    PreferIpv4,
+    PreferIpv6,
+}
+
+impl AddressFamily {
+    fn order(self, addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        let (v4, v6): (Vec<_>, Vec<_>) = addrs.iter().partition(|addr| addr.is_ipv4());
+        match self {
+            Self::Any => addrs,
+            Self::Ipv4 => v4,
+            Self::Ipv6 => v6,
+            Self::PreferIpv4 => v4.into_iter().chain(v6).collect(),
+            Self::PreferIpv6 => v6.into_iter().chain(v4).collect(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) enum Resolver {
+    Static(Arc<[SocketAddr]>),
+    Rotating(Arc<AddressPool>),
+    Pinned(SocketAddr),
+}
+
+impl Resolver {
+    fn pinned(&self) -> Option<SocketAddr> {
+        match self {
+            Self::Pinned(addr) => Some(*addr),
+            _ => None,
+        }
+    }
+}
+
+impl Service<Name> for Resolver {
+    type Response = vec::IntoIter<SocketAddr>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _name: Name) -> Self::Future {
+        ready(Ok(match self {
+            Self::Static(addrs) => addrs.to_vec(),
+            Self::Rotating(pool) => pool.next(),
+            Self::Pinned(addr) => vec![*addr],
+        }
+        .into_iter()))
+    }
+}
+
+pub(crate) struct AddressPool {
+    resolver: Arc<dyn Resolve>,
+    host: String,
+    port: u16,
+    family: AddressFamily,
+    addrs: RwLock<Vec<SocketAddr>>,
+    next: AtomicUsize,
+}
+
+impl AddressPool {
+    pub(crate) fn resolve(
+        resolver: Arc<dyn Resolve>,
+        host: &str,
+        port: u16,
+        family: AddressFamily,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            addrs: RwLock::new(lookup(&*resolver, host, port, family)?),
+            resolver,
+            host: host.to_string(),
+            port,
+            family,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    pub(crate) fn first(&self) -> SocketAddr {
+        self.addrs.read().unwrap()[0]
+    }
+
+    pub(crate) fn refresh(&self) {
+        if let Ok(addrs) = lookup(&*self.resolver, &self.host, self.port, self.family) {
+            *self.addrs.write().unwrap() = addrs;
+        }
+    }
+
+    fn next(&self) -> Vec<SocketAddr> {
+        let addrs = self.addrs.read().unwrap();
+        let preferred = match self.family {
+            AddressFamily::PreferIpv4 | AddressFamily::PreferIpv6 => addrs
+                .iter()
+                .take_while(|addr| addr.is_ipv4() == addrs[0].is_ipv4())
+                .count(),
+            _ => addrs.len(),
+        };
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % preferred;
+        addrs[start..preferred]
+            .iter()
+            .chain(&addrs[..start])
+            .chain(&addrs[preferred..])
+            .copied()
+            .collect()
+    }
+}
+
+pub(crate) fn lookup(
+    resolver: &dyn Resolve,
+    host: &str,
+    port: u16,
+    family: AddressFamily,
+) -> io::Result<Vec<SocketAddr>> {
+    let addrs = resolver.resolve(host, port)?;
+    if addrs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "could not resolve host",
+        ));
+    }
+    let addrs = family.order(addrs);
+    match addrs.is_empty() {
+        true => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("host has no addresses for {family:?}"),
+        )),
+        false => Ok(addrs),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pinned_connects_replace_the_whole_authority() {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 8080));
+        let uri: Uri = "http://10.1.0.0/path?q=1".parse().unwrap();
+        assert_eq!(
+            pinned_to(uri.clone(), Some(addr)),
+            "http://127.0.0.1:8080/path?q=1"
+        );
+        assert_eq!(pinned_to(uri.clone(), None), uri);
+    }
+}
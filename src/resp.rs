@@ -0,0 +1,422 @@
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    time::timeout_at,
+};
+
+use crate::{
+    clock::Clock,
+    metrics::Recorder,
+    result::Outcome,
+    schedule::{Schedule, Slot},
+    user::UserContext,
+};
+
+const CONNECT_LABEL: &str = "resp connect";
+const MAX_LINE: usize = 64 * 1024;
+const MAX_BULK: usize = 512 * 1024 * 1024;
+
+type MakeArgs = Arc<dyn Fn(&UserContext) -> Vec<Vec<u8>> + Send + Sync + 'static>;
+
+#[derive(Clone)]
+struct Command {
+    name: Arc<str>,
+    weight: u32,
+    args: MakeArgs,
+}
+
+#[derive(Clone, Default)]
+pub struct Resp {
+    commands: Vec<Command>,
+    username: Option<String>,
+    password: Option<String>,
+    database: Option<u32>,
+}
+
+impl Resp {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn command(
+        mut self,
+        name: impl Into<Arc<str>>,
+        weight: u32,
+        args: impl Fn(&UserContext) -> Vec<Vec<u8>> + Send + Sync + 'static,
+    ) -> Self {
+        self.commands.push(Command {
+            name: name.into(),
+            weight,
+            args: Arc::new(args),
+        });
+        self
+    }
+
+    pub fn password(self, password: impl Into<String>) -> Self {
+        Self {
+            password: Some(password.into()),
+            ..self
+        }
+    }
+
+    pub fn username(self, username: impl Into<String>) -> Self {
+        Self {
+            username: Some(username.into()),
+            ..self
+        }
+    }
+
+    pub fn database(self, database: u32) -> Self {
+        Self {
+            database: Some(database),
+            ..self
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), &'static str> {
+        if self.commands.is_empty() {
+            return Err("RESP swarms need at least one command");
+        }
+        if self.commands.iter().any(|command| command.name.is_empty()) {
+            return Err("RESP command names cannot be empty");
+        }
+        if self.commands.iter().all(|command| command.weight == 0) {
+            return Err("at least one RESP command needs a positive weight");
+        }
+        if self.username.is_some() && self.password.is_none() {
+            return Err("a RESP username requires a password");
+        }
+        Ok(())
+    }
+
+    fn pick(&self, sequence: u64) -> &Command {
+        let total = self
+            .commands
+            .iter()
+            .map(|command| command.weight as u64)
+            .sum::<u64>();
+        let mut slot = sequence % total;
+        for command in &self.commands {
+            match slot.checked_sub(command.weight as u64) {
+                Some(rest) => slot = rest,
+                None => return command,
+            }
+        }
+        unreachable!()
+    }
+}
+
+pub(crate) struct Session {
+    pub(crate) addr: SocketAddr,
+    pub(crate) epoch: Instant,
+    pub(crate) deadline: Instant,
+    pub(crate) clock: Clock,
+    pub(crate) recorder: Recorder,
+    pub(crate) schedule: Option<Schedule>,
+}
+
+impl Session {
+    pub(crate) async fn run(
+        &self,
+        resp: &Resp,
+        ctx: &mut UserContext,
+        running: &AtomicBool,
+        sequence: &AtomicU64,
+    ) {
+        let mut connection = None;
+        while running.load(Ordering::Relaxed) {
+            let ticket = self
+                .schedule
+                .as_ref()
+                .map(|schedule| schedule.claim(sequence, self.epoch));
+            ctx.sequence = match &ticket {
+                Some(ticket) => ticket.n,
+                None => sequence.fetch_add(1, Ordering::Relaxed),
+            };
+            let mut late = Duration::ZERO;
+            if let (Some(schedule), Some(ticket)) = (&self.schedule, &ticket) {
+                match schedule
+                    .wait(ticket, self.epoch, Some(self.deadline), &self.recorder)
+                    .await
+                {
+                    Slot::Due(lateness) => late = lateness,
+                    Slot::Taken => continue,
+                    Slot::Closed => break,
+                }
+                if !running.load(Ordering::Relaxed) {
+                    break;
+                }
+            }
+
+            let stream = match &mut connection {
+                Some(stream) => stream,
+                None => match self.connect(resp).await {
+                    Some(stream) => connection.insert(stream),
+                    None => continue,
+                },
+            };
+
+            let command = resp.pick(ctx.sequence);
+            let mut args = vec![command.name.as_bytes().to_vec()];
+            args.extend((command.args)(ctx));
+            let start = Instant::now();
+            let timer = self.clock.now();
+            let reply = timeout_at(self.deadline.into(), call(stream, &args)).await;
+            let outcome = match reply {
+                Ok(Ok(Reply::Value)) => Outcome::Success,
+                Ok(Ok(Reply::Error)) => Outcome::HttpError,
+                Ok(Err(_)) => Outcome::TcpError,
+                Err(_) => break,
+            };
+            self.recorder.record_labeled(
+                &command.name,
+                None,
+                start.saturating_duration_since(self.epoch),
+                self.clock.elapsed(timer) + late,
+                outcome,
+            );
+            if outcome == Outcome::TcpError {
+                self.recorder
+                    .record_drop(Instant::now().saturating_duration_since(self.epoch));
+                connection = None;
+            }
+            ctx.iteration += 1;
+        }
+    }
+
+    async fn connect(&self, resp: &Resp) -> Option<BufReader<TcpStream>> {
+        let start = Instant::now();
+        let timer = self.clock.now();
+        let connected = timeout_at(self.deadline.into(), self.handshake(resp)).await;
+        let offset = start.saturating_duration_since(self.epoch);
+        let elapsed = self.clock.elapsed(timer);
+        match connected {
+            Ok(Ok(stream)) => {
+                self.recorder
+                    .record_connect(CONNECT_LABEL, offset, elapsed, Outcome::Success);
+                Some(stream)
+            }
+            Ok(Err(err)) => {
+                let outcome = Outcome::from_error(&err);
+                self.recorder
+                    .record_connect(CONNECT_LABEL, offset, elapsed, outcome);
+                self.recorder.record(offset, elapsed, outcome);
+                None
+            }
+            Err(_) => None,
+        }
+    }
+
+    async fn handshake(&self, resp: &Resp) -> io::Result<BufReader<TcpStream>> {
+        let stream = TcpStream::connect(self.addr).await?;
+        stream.set_nodelay(true).ok();
+        let mut stream = BufReader::new(stream);
+
+        if let Some(password) = &resp.password {
+            let mut auth = vec![b"AUTH".to_vec()];
+            auth.extend(
+                resp.username
+                    .iter()
+                    .map(|username| username.clone().into_bytes()),
+            );
+            auth.push(password.clone().into_bytes());
+            if call(&mut stream, &auth).await? == Reply::Error {
+                return Err(refused("RESP server rejected the credentials"));
+            }
+        }
+        if let Some(database) = resp.database {
+            let select = [b"SELECT".to_vec(), database.to_string().into_bytes()];
+            if call(&mut stream, &select).await? == Reply::Error {
+                return Err(refused("RESP server refused to select the database"));
+            }
+        }
+        Ok(stream)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reply {
+    Value,
+    Error,
+}
+
+async fn call(stream: &mut BufReader<TcpStream>, args: &[Vec<u8>]) -> io::Result<Reply> {
+    stream.get_mut().write_all(&encode(args)).await?;
+    read_reply(stream).await
+}
+
+fn encode(args: &[Vec<u8>]) -> Vec<u8> {
+    let len = args.iter().map(|arg| arg.len() + 16).sum::<usize>();
+    let mut buf = Vec::with_capacity(16 + len);
+    buf.extend_from_slice(format!("*{}\r\n", args.len()).as_bytes());
+    for arg in args {
+        buf.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        buf.extend_from_slice(arg);
+        buf.extend_from_slice(b"\r\n");
+    }
+    buf
+}
+
+async fn read_reply(stream: &mut BufReader<TcpStream>) -> io::Result<Reply> {
+    let mut reply = None;
+    let mut pending = 1usize;
+    let mut line = Vec::new();
+    while pending > 0 {
+        pending -= 1;
+        line.clear();
+        (&mut *stream)
+            .take(MAX_LINE as u64)
+            .read_until(b'\n', &mut line)
+            .await?;
+        if line.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "RESP server closed the connection",
+            ));
+        }
+        if !line.ends_with(b"\r\n") {
+            return Err(invalid("malformed RESP reply"));
+        }
+        let kind = line[0];
+        let value = &line[1..line.len() - 2];
+        reply.get_or_insert(match kind {
+            b'-' | b'!' => Reply::Error,
+            _ => Reply::Value,
+        });
+        match kind {
+            b'+' | b'-' | b':' | b'_' | b',' | b'#' | b'(' => {}
+            b'$' | b'!' | b'=' => {
+                if let Some(len) = length(value)? {
+                    if len > MAX_BULK {
+                        return Err(invalid("RESP bulk reply too large"));
+                    }
+                    let mut bulk = vec![0; len + 2];
+                    stream.read_exact(&mut bulk).await?;
+                }
+            }
+            b'*' | b'~' | b'>' => pending += length(value)?.unwrap_or(0),
+            b'%' => pending += 2 * length(value)?.unwrap_or(0),
+            b'|' => pending += 2 * length(value)?.unwrap_or(0) + 1,
+            _ => return Err(invalid("unknown RESP reply type")),
+        }
+    }
+    Ok(reply.unwrap_or(Reply::Value))
+}
+
+fn length(value: &[u8]) -> io::Result<Option<usize>> {
+    let len = std::str::from_utf8(value)
+        .ok()
+        .and_then(|value| value.parse::<i64>().ok())
+        .ok_or_else(|| invalid("malformed RESP length"))?;
+    Ok(usize::try_from(len).ok())
+}
+
+fn refused(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionRefused, message.to_string())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// Feeds `bytes` to [`read_reply`] over loopback, then reads one more reply to
+    /// check the first one was consumed exactly.
+    fn replies(bytes: &'static [u8]) -> io::Result<(Reply, Reply)> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+            let client = TcpStream::connect(listener.local_addr()?).await?;
+            let (mut server, _) = listener.accept().await?;
+            server.write_all(bytes).await?;
+            server.write_all(b"+END\r\n").await?;
+            drop(server);
+            let mut stream = BufReader::new(client);
+            Ok((
+                read_reply(&mut stream).await?,
+                read_reply(&mut stream).await?,
+            ))
+        })
+    }
+
+    fn resp() -> Resp {
+        Resp::new().command("GET", 1, |_| vec![b"GET".to_vec(), b"k".to_vec()])
+    }
+
+    #[test]
+    fn commands_are_encoded_as_bulk_arrays() {
+        assert_eq!(
+            encode(&[b"SET".to_vec(), b"k".to_vec(), b"".to_vec()]),
+            b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$0\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn replies_are_read_whole_including_nested_aggregates() {
+        assert_eq!(replies(b"+OK\r\n").unwrap().0, Reply::Value);
+        assert_eq!(replies(b"-ERR no\r\n").unwrap().0, Reply::Error);
+        assert_eq!(replies(b"$-1\r\n").unwrap(), (Reply::Value, Reply::Value));
+        assert_eq!(
+            replies(b"*2\r\n$3\r\nfoo\r\n*1\r\n:1\r\n").unwrap(),
+            (Reply::Value, Reply::Value)
+        );
+        assert_eq!(
+            replies(b"%1\r\n+k\r\n$1\r\nv\r\n").unwrap(),
+            (Reply::Value, Reply::Value)
+        );
+        assert_eq!(replies(b"!3\r\nbad\r\n").unwrap().0, Reply::Error);
+    }
+
+    #[test]
+    fn malformed_replies_are_rejected() {
+        let kind = |bytes| replies(bytes).unwrap_err().kind();
+        assert_eq!(kind(b"?\r\n"), io::ErrorKind::InvalidData);
+        assert_eq!(kind(b"$x\r\n"), io::ErrorKind::InvalidData);
+        assert_eq!(kind(b"+no crlf\n"), io::ErrorKind::InvalidData);
+        assert_eq!(length(b"-1").unwrap(), None);
+    }
+
+    #[test]
+    fn commands_are_picked_by_weight() {
+        let resp = resp().command("SET", 3, |_| Vec::new());
+        let picks: String = (0..8).map(|n| &resp.pick(n).name[..1]).collect();
+        assert_eq!(picks, "GSSSGSSS");
+    }
+
+    #[test]
+    fn invalid_setups_are_refused() {
+        assert!(resp().validate().is_ok());
+        assert!(Resp::new().validate().is_err());
+        assert!(Resp::new()
+            .command("", 1, |_| Vec::new())
+            .validate()
+            .is_err());
+        assert!(Resp::new()
+            .command("GET", 0, |_| Vec::new())
+            .validate()
+            .is_err());
+        assert_eq!(
+            resp().username("admin").validate(),
+            Err("a RESP username requires a password")
+        );
+        assert!(resp().username("admin").password("pw").validate().is_ok());
+    }
+}
@@ -1,26 +1,443 @@
 use std::{
+    collections::{BTreeMap, BTreeSet},
     fmt::Display,
     iter::Sum,
+    mem,
     ops::{Add, AddAssign},
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
 use tdigest::TDigest;
 
+use crate::{
+    accuracy::PercentileAccuracy,
+    annotation::Annotation,
+    breakdown::TimeBreakdown,
+    cache::CacheStatus,
+    clock::ClockSource,
+    config::RunConfig,
+    fairness::{Fairness, UserStats},
+    limits::Exhaustion,
+    operation::Mode,
+    redact::Redactor,
+    seed::CleanupReport,
+    session::TlsSession,
+    timeline::{ErrorBurst, Timeline},
+    warning::Warning,
+};
+
+const BURST_THRESHOLD: f64 = 0.5;
+pub(crate) const STARVATION_THRESHOLD: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    Success,
+    HttpError,
+    TcpError,
+    ConnectError,
+    Exhausted(Exhaustion),
+    #[cfg(feature = "http3")]
+    QuicHandshake,
+    #[cfg(any(feature = "tls", feature = "native-tls"))]
+    TlsHandshake,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum TcpFailure {
+    Connect,
+    Transfer,
+}
+
 #[derive(Debug, Clone)]
 pub struct BenchmarkResult {
     pub(crate) success: usize,
     pub(crate) http_error: usize,
     pub(crate) tcp_error: usize,
+    pub(crate) connect_errors: usize,
+    pub(crate) counted_failures: BTreeSet<TcpFailure>,
+    pub(crate) time_breakdown: TimeBreakdown,
     pub(crate) elapsed: Duration,
+    pub(crate) started_at: Option<SystemTime>,
     pub(crate) min_time: Duration,
     pub(crate) max_time: Duration,
     pub(crate) timings: Vec<Duration>,
+    pub(crate) timings_seen: usize,
+    pub(crate) timeline: Timeline,
+    pub(crate) annotations: Vec<Annotation>,
+    pub(crate) labels: BTreeMap<String, BenchmarkResult>,
+    pub(crate) latency_budget: Option<Duration>,
+    pub(crate) budget_violations: usize,
+    pub(crate) phases: BTreeMap<String, BenchmarkResult>,
+    pub(crate) tenants: BTreeMap<String, BenchmarkResult>,
+    pub(crate) users: BTreeMap<usize, UserStats>,
+    pub(crate) cache: BTreeMap<CacheStatus, BenchmarkResult>,
+    pub(crate) regions: BTreeMap<String, BenchmarkResult>,
+    pub(crate) phase_spans: BTreeMap<String, (Duration, Duration)>,
+    pub(crate) counters: BTreeMap<String, u64>,
+    pub(crate) expectations: BTreeMap<String, usize>,
+    pub(crate) opened_connections: usize,
+    pub(crate) dropped_connections: usize,
+    pub(crate) idle_connections: usize,
+    pub(crate) pushed_messages: usize,
+    pub(crate) grpc_statuses: BTreeMap<String, usize>,
+    pub(crate) grpc_messages: usize,
+    pub(crate) uploaded_bytes: u64,
+    pub(crate) downloaded_bytes: u64,
+    pub(crate) doh_lookups: usize,
+    pub(crate) doh_failures: usize,
+    pub(crate) doh_time: Duration,
+    pub(crate) compressed_responses: usize,
+    pub(crate) wire_bytes: u64,
+    pub(crate) decoded_bytes: u64,
+    pub(crate) redirects_followed: usize,
+    pub(crate) redirect_loops: usize,
+    pub(crate) script_errors: usize,
+    pub(crate) continue_responses: usize,
+    pub(crate) continue_time: Duration,
+    pub(crate) continue_rejections: usize,
+    pub(crate) continue_timeouts: usize,
+    pub(crate) connection_cap: Option<usize>,
+    pub(crate) peak_connections: usize,
+    pub(crate) queued_connects: usize,
+    pub(crate) connect_queue_time: Duration,
+    pub(crate) client_cpu: Option<f64>,
+    pub(crate) percentile_accuracy: Option<PercentileAccuracy>,
+    pub(crate) echo_checked: usize,
+    pub(crate) echo_duplicates: usize,
+    pub(crate) echo_mismatches: usize,
+    pub(crate) chaos_dropped: usize,
+    pub(crate) chaos_aborted: usize,
+    pub(crate) gauges: BTreeMap<String, i64>,
+    pub(crate) max_queue_depth: u64,
+    pub(crate) late: usize,
+    pub(crate) schedule_lag: Duration,
+    pub(crate) clock: Option<(ClockSource, Duration)>,
+    pub(crate) pipeline: Option<usize>,
+    pub(crate) mode: Mode,
+    pub(crate) tls_sessions: BTreeMap<TlsSession, usize>,
+    pub(crate) tls_resumed: usize,
+    pub(crate) negotiated_protocols: BTreeMap<String, usize>,
+    pub(crate) requested_protocol: Option<String>,
+    pub(crate) tls_handshake_errors: usize,
+    pub(crate) fd_exhausted: usize,
+    pub(crate) ports_exhausted: usize,
+    pub(crate) quic_handshake_errors: usize,
+    pub(crate) fd_limit: Option<u64>,
+    pub(crate) seed: Option<(usize, usize, Duration)>,
+    pub(crate) cleanup: Option<CleanupReport>,
+    pub(crate) worker_panics: usize,
+    pub(crate) first_panic: Option<String>,
+    pub(crate) config: Option<RunConfig>,
 }
 
 impl BenchmarkResult {
+    pub(crate) fn record(&mut self, offset: Duration, elapsed: Duration, outcome: Outcome) {
+        let bucket = self.timeline.bucket_mut(offset);
+        match outcome {
+            Outcome::Success => {
+                self.success += 1;
+                bucket.success += 1;
+            }
+            Outcome::HttpError => {
+                self.http_error += 1;
+                bucket.http_error += 1;
+            }
+            Outcome::TcpError => {
+                self.tcp_error += 1;
+                bucket.tcp_error += 1;
+            }
+            Outcome::ConnectError => {
+                self.tcp_error += 1;
+                bucket.tcp_error += 1;
+                self.connect_errors += 1;
+            }
+            Outcome::Exhausted(exhaustion) => {
+                self.tcp_error += 1;
+                bucket.tcp_error += 1;
+                self.connect_errors += 1;
+                match exhaustion {
+                    Exhaustion::FileDescriptors => self.fd_exhausted += 1,
+                    Exhaustion::LocalPorts => self.ports_exhausted += 1,
+                }
+            }
+            #[cfg(feature = "http3")]
+            Outcome::QuicHandshake => {
+                self.tcp_error += 1;
+                bucket.tcp_error += 1;
+                self.connect_errors += 1;
+                self.quic_handshake_errors += 1;
+            }
+            #[cfg(any(feature = "tls", feature = "native-tls"))]
+            Outcome::TlsHandshake => {
+                self.tcp_error += 1;
+                bucket.tcp_error += 1;
+                self.connect_errors += 1;
+                self.tls_handshake_errors += 1;
+            }
+        }
+        self.timings.push(elapsed);
+        self.timings_seen += 1;
+        self.min_time = self.min_time.min(elapsed);
+        self.max_time = self.max_time.max(elapsed);
+    }
+
+    pub(crate) fn record_failed(&mut self, offset: Duration) {
+        self.timeline.bucket_mut(offset).http_error += 1;
+        self.http_error += 1;
+    }
+
+    pub(crate) fn record_backlog(&mut self, offset: Duration, depth: u64, lag: Duration) {
+        let bucket = self.timeline.bucket_mut(offset);
+        bucket.queue_depth = bucket.queue_depth.max(depth);
+        self.max_queue_depth = self.max_queue_depth.max(depth);
+        self.late += 1;
+        self.schedule_lag += lag;
+    }
+
+    pub(crate) fn record_labeled(
+        &mut self,
+        label: &str,
+        offset: Duration,
+        elapsed: Duration,
+        outcome: Outcome,
+    ) {
+        self.record(offset, elapsed, outcome);
+        record_in(&mut self.labels, label, offset, elapsed, outcome);
+    }
+
+    pub(crate) fn record_connect(
+        &mut self,
+        label: &str,
+        offset: Duration,
+        elapsed: Duration,
+        outcome: Outcome,
+    ) {
+        record_in(&mut self.labels, label, offset, elapsed, outcome);
+    }
+
+    pub(crate) fn record_budget(&mut self, label: &str, elapsed: Duration, budget: Duration) {
+        if let Some(labeled) = self.labels.get_mut(label) {
+            labeled.latency_budget = Some(budget);
+            if elapsed > budget {
+                labeled.budget_violations += 1;
+            }
+        }
+    }
+
+    pub(crate) fn record_phase(
+        &mut self,
+        phase: &str,
+        offset: Duration,
+        elapsed: Duration,
+        outcome: Outcome,
+    ) {
+        record_in(&mut self.phases, phase, offset, elapsed, outcome);
+        let end = offset + elapsed;
+        match self.phase_spans.get_mut(phase) {
+            Some((start, last)) => {
+                *start = (*start).min(offset);
+                *last = (*last).max(end);
+            }
+            None => {
+                self.phase_spans.insert(phase.to_string(), (offset, end));
+            }
+        }
+    }
+
+    pub(crate) fn record_tenant(
+        &mut self,
+        tenant: &str,
+        offset: Duration,
+        elapsed: Duration,
+        outcome: Outcome,
+    ) {
+        record_in(&mut self.tenants, tenant, offset, elapsed, outcome);
+    }
+
+    pub(crate) fn record_cache(
+        &mut self,
+        status: CacheStatus,
+        offset: Duration,
+        elapsed: Duration,
+        outcome: Outcome,
+    ) {
+        self.cache
+            .entry(status)
+            .or_default()
+            .record(offset, elapsed, outcome);
+    }
+
+    pub(crate) fn merge_sampled(&mut self, mut rhs: BenchmarkResult, capacity: usize) {
+        let reservoirs = 1
+            + union_len(&self.labels, &rhs.labels)
+            + union_len(&self.phases, &rhs.phases)
+            + union_len(&self.tenants, &rhs.tenants)
+            + union_len(&self.cache, &rhs.cache)
+            + union_len(&self.regions, &rhs.regions);
+        let share = (capacity / reservoirs).max(1);
+        self.sample(mem::take(&mut rhs.timings), rhs.timings_seen, share);
+        merge_sampled_into(&mut self.labels, mem::take(&mut rhs.labels), share);
+        merge_sampled_into(&mut self.phases, mem::take(&mut rhs.phases), share);
+        merge_sampled_into(&mut self.tenants, mem::take(&mut rhs.tenants), share);
+        merge_sampled_into(&mut self.cache, mem::take(&mut rhs.cache), share);
+        merge_sampled_into(&mut self.regions, mem::take(&mut rhs.regions), share);
+        *self += rhs;
+    }
+
+    fn sample(&mut self, batch: Vec<Duration>, batch_seen: usize, capacity: usize) {
+        self.timings = merge_reservoirs(
+            mem::take(&mut self.timings),
+            self.timings_seen,
+            batch,
+            batch_seen,
+            capacity,
+        );
+    }
+
+    pub(crate) fn record_user(&mut self, user: usize, elapsed: Duration) {
+        let stats = self.users.entry(user).or_default();
+        stats.requests += 1;
+        stats.total_time += elapsed;
+    }
+
+    fn shift(&mut self, by: Duration) {
+        self.timeline.shift(by);
+        for annotation in self.annotations.iter_mut() {
+            annotation.shift(by);
+        }
+        for (start, end) in self.phase_spans.values_mut() {
+            *start += by;
+            *end += by;
+        }
+        for result in self
+            .labels
+            .values_mut()
+            .chain(self.phases.values_mut())
+            .chain(self.tenants.values_mut())
+            .chain(self.cache.values_mut())
+            .chain(self.regions.values_mut())
+        {
+            result.shift(by);
+        }
+    }
+
+    pub(crate) fn redact(&mut self, redactor: &Redactor) {
+        for annotation in &mut self.annotations {
+            *annotation = Annotation::new(annotation.offset(), redactor.text(annotation.text()));
+        }
+        for (label, result) in mem::take(&mut self.labels) {
+            *self.labels.entry(redactor.text(&label)).or_default() += result;
+        }
+    }
+
+    pub(crate) fn set_elapsed(&mut self, elapsed: Duration) {
+        self.elapsed = elapsed;
+        for labeled in self.labels.values_mut() {
+            labeled.set_elapsed(elapsed);
+        }
+        for tenant in self.tenants.values_mut() {
+            tenant.set_elapsed(elapsed);
+        }
+        for cached in self.cache.values_mut() {
+            cached.set_elapsed(elapsed);
+        }
+        for (phase, result) in self.phases.iter_mut() {
+            let (start, end) = self.phase_spans[phase];
+            result.set_elapsed(end - start);
+        }
+    }
+
+    pub fn merge_aligned(results: impl IntoIterator<Item = BenchmarkResult>) -> BenchmarkResult {
+        let results = results.into_iter().collect::<Vec<_>>();
+        let reference = results.iter().filter_map(|result| result.started_at).min();
+
+        let mut elapsed = Duration::ZERO;
+        let mut spans = BTreeMap::<String, (Duration, Duration)>::new();
+        let mut merged = BenchmarkResult::default();
+        for mut result in results {
+            let offset = match (reference, result.started_at) {
+                (Some(reference), Some(started_at)) => started_at
+                    .duration_since(reference)
+                    .unwrap_or(Duration::ZERO),
+                _ => Duration::ZERO,
+            };
+            elapsed = elapsed.max(offset + result.elapsed);
+            for (region, regional) in &result.regions {
+                let end = offset + regional.elapsed;
+                let span = spans.entry(region.clone()).or_insert((offset, end));
+                span.0 = span.0.min(offset);
+                span.1 = span.1.max(end);
+            }
+            result.shift(offset);
+            merged += result;
+        }
+        merged.started_at = reference;
+        merged.set_elapsed(elapsed);
+        for (region, (start, end)) in spans {
+            if let Some(regional) = merged.regions.get_mut(&region) {
+                regional.set_elapsed(end - start);
+            }
+        }
+        merged
+    }
+
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        let regional = BenchmarkResult {
+            labels: BTreeMap::new(),
+            phases: BTreeMap::new(),
+            tenants: BTreeMap::new(),
+            users: BTreeMap::new(),
+            cache: BTreeMap::new(),
+            regions: BTreeMap::new(),
+            ..self.clone()
+        };
+        self.regions = BTreeMap::from([(region.into(), regional)]);
+        self
+    }
+
+    pub fn regions(&self) -> impl Iterator<Item = (&str, &BenchmarkResult)> {
+        self.regions
+            .iter()
+            .map(|(region, result)| (region.as_str(), result))
+    }
+
+    pub fn region(&self, region: &str) -> Option<&BenchmarkResult> {
+        self.regions.get(region)
+    }
+
+    pub fn with_start_time(self, started_at: SystemTime) -> Self {
+        Self {
+            started_at: Some(started_at),
+            ..self
+        }
+    }
+
+    pub fn with_clock_offset(self, offset_secs: f64) -> Self {
+        let started_at = self.started_at.map(|started_at| {
+            let offset = Duration::from_secs_f64(offset_secs.abs());
+            match offset_secs < 0.0 {
+                true => started_at - offset,
+                false => started_at + offset,
+            }
+        });
+        Self { started_at, ..self }
+    }
+
+    pub fn started_at(&self) -> Option<SystemTime> {
+        self.started_at
+    }
+
     pub fn total_request_count(&self) -> usize {
-        self.success + self.http_error
+        let mut total = self.success + self.http_error;
+        if self.counted_failures.contains(&TcpFailure::Connect) {
+            total += self.connect_error_count();
+        }
+        if self.counted_failures.contains(&TcpFailure::Transfer) {
+            total += self.transfer_error_count();
+        }
+        total
     }
 
     pub fn requests_per_second(&self) -> f64 {
@@ -35,6 +452,32 @@ impl BenchmarkResult {
         self.http_error
     }
 
+    pub fn connect_error_count(&self) -> usize {
+        self.connect_errors
+    }
+
+    pub fn transfer_error_count(&self) -> usize {
+        self.tcp_error - self.connect_errors
+    }
+
+    pub fn time_breakdown(&self) -> Option<&TimeBreakdown> {
+        Some(&self.time_breakdown).filter(|breakdown| !breakdown.is_empty())
+    }
+
+    pub(crate) fn count_failures(&mut self, failures: &BTreeSet<TcpFailure>) {
+        self.counted_failures = failures.clone();
+        for result in self
+            .labels
+            .values_mut()
+            .chain(self.phases.values_mut())
+            .chain(self.tenants.values_mut())
+            .chain(self.cache.values_mut())
+            .chain(self.regions.values_mut())
+        {
+            result.count_failures(failures);
+        }
+    }
+
     pub fn tcp_error_count(&self) -> usize {
         self.tcp_error
     }
@@ -44,7 +487,7 @@ impl BenchmarkResult {
     }
 
     pub fn average_time(&self) -> Option<Duration> {
-        match self.total_request_count() {
+        match self.timings.len() {
             0 => None,
             n => Some(self.timings().sum::<Duration>() / n as u32),
         }
@@ -58,25 +501,399 @@ impl BenchmarkResult {
             .map(|time| avg - time)
             .map(|val| val * val)
             .sum::<f64>()
-            / self.total_request_count() as f64;
+            / self.timings.len() as f64;
         Some(Duration::from_secs_f64(var.sqrt()))
     }
 
+    pub fn max_queue_depth(&self) -> u64 {
+        self.max_queue_depth
+    }
+
+    pub fn late_request_count(&self) -> usize {
+        self.late
+    }
+
+    pub fn average_schedule_lag(&self) -> Option<Duration> {
+        match self.late {
+            0 => None,
+            n => Some(self.schedule_lag / n as u32),
+        }
+    }
+
+    pub fn exhaustion_count(&self, exhaustion: Exhaustion) -> usize {
+        match exhaustion {
+            Exhaustion::FileDescriptors => self.fd_exhausted,
+            Exhaustion::LocalPorts => self.ports_exhausted,
+        }
+    }
+
+    pub fn quic_handshake_error_count(&self) -> usize {
+        self.quic_handshake_errors
+    }
+
+    pub fn fd_limit(&self) -> Option<u64> {
+        self.fd_limit
+    }
+
+    pub fn seeded_count(&self) -> usize {
+        self.seed.map(|(created, _, _)| created).unwrap_or_default()
+    }
+
+    pub fn seed_error_count(&self) -> usize {
+        self.seed.map(|(_, failed, _)| failed).unwrap_or_default()
+    }
+
+    pub fn cleanup(&self) -> Option<&CleanupReport> {
+        self.cleanup.as_ref()
+    }
+
+    pub fn worker_panic_count(&self) -> usize {
+        self.worker_panics
+    }
+
+    pub fn first_panic(&self) -> Option<&str> {
+        self.first_panic.as_deref()
+    }
+
+    pub fn config(&self) -> Option<&RunConfig> {
+        self.config.as_ref()
+    }
+
+    pub fn clock_source(&self) -> Option<ClockSource> {
+        self.clock.map(|(source, _)| source)
+    }
+
+    pub fn timing_overhead(&self) -> Option<Duration> {
+        self.clock.map(|(_, overhead)| overhead)
+    }
+
+    pub fn mode(&self) -> &Mode {
+        &self.mode
+    }
+
+    pub fn tls_sessions(&self) -> &BTreeMap<TlsSession, usize> {
+        &self.tls_sessions
+    }
+
+    pub fn tls_handshake_count(&self) -> usize {
+        self.tls_sessions.values().sum()
+    }
+
+    pub fn tls_resumed_count(&self) -> usize {
+        self.tls_resumed
+    }
+
+    pub fn tls_full_handshake_count(&self) -> usize {
+        self.tls_handshake_count() - self.tls_resumed
+    }
+
+    pub fn tls_resumption_rate(&self) -> f64 {
+        let handshakes = self.tls_handshake_count();
+        if handshakes == 0 {
+            return 0.0;
+        }
+        self.tls_resumed as f64 / handshakes as f64
+    }
+
+    pub fn negotiated_protocols(&self) -> &BTreeMap<String, usize> {
+        &self.negotiated_protocols
+    }
+
+    pub fn protocol_mismatch_count(&self) -> usize {
+        match &self.requested_protocol {
+            Some(requested) => self
+                .negotiated_protocols
+                .iter()
+                .filter(|(protocol, _)| *protocol != requested)
+                .map(|(_, connections)| connections)
+                .sum(),
+            None => 0,
+        }
+    }
+
+    pub fn tls_handshake_error_count(&self) -> usize {
+        self.tls_handshake_errors
+    }
+
+    pub fn pipeline_depth(&self) -> Option<usize> {
+        self.pipeline
+    }
+
     pub fn timings(&self) -> impl Iterator<Item = Duration> + '_ {
         self.timings.iter().copied()
     }
 
+    pub fn timeline(&self) -> &Timeline {
+        &self.timeline
+    }
+
+    pub fn error_bursts(&self, threshold: f64) -> Vec<ErrorBurst> {
+        self.timeline.error_bursts(threshold)
+    }
+
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.annotations
+    }
+
+    pub fn labels(&self) -> impl Iterator<Item = (&str, &BenchmarkResult)> {
+        self.labels
+            .iter()
+            .map(|(label, result)| (label.as_str(), result))
+    }
+
+    pub fn label(&self, label: &str) -> Option<&BenchmarkResult> {
+        self.labels.get(label)
+    }
+
+    pub fn latency_budget(&self) -> Option<Duration> {
+        self.latency_budget
+    }
+
+    pub fn budget_violation_count(&self) -> usize {
+        self.budget_violations
+    }
+
+    pub fn phases(&self) -> impl Iterator<Item = (&str, &BenchmarkResult)> {
+        self.phases
+            .iter()
+            .map(|(phase, result)| (phase.as_str(), result))
+    }
+
+    pub fn phase(&self, phase: &str) -> Option<&BenchmarkResult> {
+        self.phases.get(phase)
+    }
+
+    pub fn tenants(&self) -> impl Iterator<Item = (&str, &BenchmarkResult)> {
+        self.tenants
+            .iter()
+            .map(|(tenant, result)| (tenant.as_str(), result))
+    }
+
+    pub fn tenant(&self, tenant: &str) -> Option<&BenchmarkResult> {
+        self.tenants.get(tenant)
+    }
+
+    pub fn users(&self) -> impl Iterator<Item = (usize, &UserStats)> {
+        self.users.iter().map(|(id, stats)| (*id, stats))
+    }
+
+    pub fn fairness(&self, threshold: f64) -> Option<Fairness> {
+        Fairness::new(&self.users, threshold)
+    }
+
+    pub fn cache_statuses(&self) -> impl Iterator<Item = (CacheStatus, &BenchmarkResult)> {
+        self.cache.iter().map(|(status, result)| (*status, result))
+    }
+
+    pub fn cache_status(&self, status: CacheStatus) -> Option<&BenchmarkResult> {
+        self.cache.get(&status)
+    }
+
+    pub fn cache_hit_ratio(&self) -> Option<f64> {
+        let count = |status| {
+            self.cache
+                .get(&status)
+                .map(BenchmarkResult::total_request_count)
+                .unwrap_or_default()
+        };
+        let (hits, misses) = (count(CacheStatus::Hit), count(CacheStatus::Miss));
+        match hits + misses {
+            0 => None,
+            total => Some(hits as f64 / total as f64),
+        }
+    }
+
+    pub fn opened_connection_count(&self) -> usize {
+        self.opened_connections
+    }
+
+    pub fn connections_per_second(&self) -> f64 {
+        (self.opened_connections as f64 / self.elapsed.as_millis() as f64) * 1000.0
+    }
+
+    pub fn dropped_connection_count(&self) -> usize {
+        self.dropped_connections
+    }
+
+    pub fn idle_connection_count(&self) -> usize {
+        self.idle_connections
+    }
+
+    pub fn pushed_message_count(&self) -> usize {
+        self.pushed_messages
+    }
+
+    pub fn grpc_statuses(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.grpc_statuses
+            .iter()
+            .map(|(status, count)| (status.as_str(), *count))
+    }
+
+    pub fn grpc_message_count(&self) -> usize {
+        self.grpc_messages
+    }
+
+    pub fn grpc_messages_per_second(&self) -> f64 {
+        (self.grpc_messages as f64 / self.elapsed.as_millis() as f64) * 1000.0
+    }
+
+    pub fn uploaded_bytes(&self) -> u64 {
+        self.uploaded_bytes
+    }
+
+    pub fn upload_bytes_per_second(&self) -> f64 {
+        (self.uploaded_bytes as f64 / self.elapsed.as_millis() as f64) * 1000.0
+    }
+
+    pub fn downloaded_bytes(&self) -> u64 {
+        self.downloaded_bytes
+    }
+
+    pub fn download_bytes_per_second(&self) -> f64 {
+        (self.downloaded_bytes as f64 / self.elapsed.as_millis() as f64) * 1000.0
+    }
+
+    pub fn doh_lookup_count(&self) -> usize {
+        self.doh_lookups
+    }
+
+    pub fn doh_failure_count(&self) -> usize {
+        self.doh_failures
+    }
+
+    pub fn doh_resolution_time(&self) -> Duration {
+        self.doh_time
+    }
+
+    pub fn compressed_response_count(&self) -> usize {
+        self.compressed_responses
+    }
+
+    pub fn wire_bytes(&self) -> u64 {
+        self.wire_bytes
+    }
+
+    pub fn decoded_bytes(&self) -> u64 {
+        self.decoded_bytes
+    }
+
+    pub fn redirect_count(&self) -> usize {
+        self.redirects_followed
+    }
+
+    pub fn redirect_loop_count(&self) -> usize {
+        self.redirect_loops
+    }
+
+    pub fn script_error_count(&self) -> usize {
+        self.script_errors
+    }
+
+    pub fn continue_count(&self) -> usize {
+        self.continue_responses
+    }
+
+    pub fn continue_latency(&self) -> Option<Duration> {
+        match self.continue_responses {
+            0 => None,
+            count => Some(self.continue_time / count as u32),
+        }
+    }
+
+    pub fn continue_rejection_count(&self) -> usize {
+        self.continue_rejections
+    }
+
+    pub fn continue_timeout_count(&self) -> usize {
+        self.continue_timeouts
+    }
+
+    pub fn connection_cap(&self) -> Option<usize> {
+        self.connection_cap
+    }
+
+    pub fn peak_connections(&self) -> usize {
+        self.peak_connections
+    }
+
+    pub fn queued_connect_count(&self) -> usize {
+        self.queued_connects
+    }
+
+    pub fn connect_queue_time(&self) -> Duration {
+        self.connect_queue_time
+    }
+
+    pub fn client_cpu(&self) -> Option<f64> {
+        self.client_cpu
+    }
+
+    pub fn percentile_accuracy(&self) -> Option<&PercentileAccuracy> {
+        self.percentile_accuracy.as_ref()
+    }
+
+    pub fn warnings(&self) -> Vec<Warning> {
+        Warning::detect(self)
+    }
+
+    pub fn compression_savings(&self) -> Option<f64> {
+        match self.decoded_bytes {
+            0 => None,
+            decoded => Some(1.0 - self.wire_bytes as f64 / decoded as f64),
+        }
+    }
+
+    pub fn echo_checked_count(&self) -> usize {
+        self.echo_checked
+    }
+
+    pub fn echo_duplicate_count(&self) -> usize {
+        self.echo_duplicates
+    }
+
+    pub fn echo_mismatch_count(&self) -> usize {
+        self.echo_mismatches
+    }
+
+    pub fn chaos_dropped_count(&self) -> usize {
+        self.chaos_dropped
+    }
+
+    pub fn chaos_aborted_count(&self) -> usize {
+        self.chaos_aborted
+    }
+
+    pub fn expectation_failures(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.expectations
+            .iter()
+            .map(|(name, failures)| (name.as_str(), *failures))
+    }
+
+    pub fn counters(&self) -> impl Iterator<Item = (&str, u64)> {
+        self.counters
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+    }
+
+    pub fn gauges(&self) -> impl Iterator<Item = (&str, i64)> {
+        self.gauges
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+    }
+
     pub fn percentiles(&self) -> Percentiles {
-        let tdigest = TDigest::new_with_size(100);
-        Percentiles(
-            tdigest.merge_unsorted(self.timings.iter().map(|dur| dur.as_secs_f64()).collect()),
-        )
+        Percentiles::new(&self.timings)
     }
 }
 
 pub struct Percentiles(TDigest);
 
 impl Percentiles {
+    pub(crate) fn new(timings: &[Duration]) -> Self {
+        let tdigest = TDigest::new_with_size(100);
+        Self(tdigest.merge_unsorted(timings.iter().map(|dur| dur.as_secs_f64()).collect()))
+    }
+
     pub fn percentile(&self, q: f64) -> Duration {
         Duration::from_secs_f64(self.0.estimate_quantile(q))
     }
@@ -88,10 +905,81 @@ impl Default for BenchmarkResult {
             success: Default::default(),
             http_error: Default::default(),
             tcp_error: Default::default(),
+            connect_errors: 0,
+            counted_failures: BTreeSet::new(),
+            time_breakdown: TimeBreakdown::default(),
             elapsed: Duration::ZERO,
+            started_at: None,
             min_time: Duration::MAX,
             max_time: Duration::ZERO,
-            timings: Vec::with_capacity(100000),
+            timings: Vec::new(),
+            timings_seen: 0,
+            timeline: Timeline::default(),
+            annotations: Vec::new(),
+            labels: BTreeMap::new(),
+            latency_budget: None,
+            budget_violations: 0,
+            phases: BTreeMap::new(),
+            tenants: BTreeMap::new(),
+            users: BTreeMap::new(),
+            cache: BTreeMap::new(),
+            regions: BTreeMap::new(),
+            phase_spans: BTreeMap::new(),
+            counters: BTreeMap::new(),
+            expectations: BTreeMap::new(),
+            opened_connections: 0,
+            dropped_connections: 0,
+            idle_connections: 0,
+            pushed_messages: 0,
+            grpc_statuses: BTreeMap::new(),
+            grpc_messages: 0,
+            uploaded_bytes: 0,
+            downloaded_bytes: 0,
+            doh_lookups: 0,
+            doh_failures: 0,
+            doh_time: Duration::ZERO,
+            compressed_responses: 0,
+            wire_bytes: 0,
+            decoded_bytes: 0,
+            redirects_followed: 0,
+            redirect_loops: 0,
+            script_errors: 0,
+            continue_responses: 0,
+            continue_time: Duration::ZERO,
+            continue_rejections: 0,
+            continue_timeouts: 0,
+            connection_cap: None,
+            peak_connections: 0,
+            queued_connects: 0,
+            connect_queue_time: Duration::ZERO,
+            client_cpu: None,
+            percentile_accuracy: None,
+            echo_checked: 0,
+            echo_duplicates: 0,
+            echo_mismatches: 0,
+            chaos_dropped: 0,
+            chaos_aborted: 0,
+            gauges: BTreeMap::new(),
+            max_queue_depth: 0,
+            late: 0,
+            schedule_lag: Duration::ZERO,
+            clock: None,
+            pipeline: None,
+            mode: Mode::default(),
+            tls_sessions: BTreeMap::new(),
+            tls_resumed: 0,
+            negotiated_protocols: BTreeMap::new(),
+            requested_protocol: None,
+            tls_handshake_errors: 0,
+            fd_exhausted: 0,
+            ports_exhausted: 0,
+            quic_handshake_errors: 0,
+            fd_limit: None,
+            seed: None,
+            cleanup: None,
+            worker_panics: 0,
+            first_panic: None,
+            config: None,
         }
     }
 }
@@ -102,6 +990,18 @@ impl Display for BenchmarkResult {
         let total_requests = self.total_request_count();
         writeln!(f, "Requests:  {} in {:.2}s", total_requests, seconds)?;
         writeln!(f, "Reqs/sec:  {:.2}", self.requests_per_second())?;
+        if self.mode != Mode::Http {
+            writeln!(f, "Mode:      {}", self.mode)?;
+        }
+        if let Some(config) = &self.config {
+            writeln!(f, "Config:    {config}")?;
+        }
+        if let Some(depth) = self.pipeline {
+            writeln!(
+                f,
+                "Pipeline:  {depth} in flight per connection, latency includes queueing"
+            )?;
+        }
 
         if self.success > 0 && self.http_error > 0 {
             writeln!(f, "Success:   {}", self.success)?;
@@ -110,7 +1010,66 @@ impl Display for BenchmarkResult {
             writeln!(f, "Errors:    {}", self.http_error)?;
         }
         if self.tcp_error > 0 {
-            writeln!(f, "TCP error: {}", self.tcp_error)?;
+            writeln!(
+                f,
+                "TCP error: {} ({} before sending, {} mid-request)",
+                self.tcp_error,
+                self.connect_error_count(),
+                self.transfer_error_count()
+            )?;
+        }
+        if self.quic_handshake_errors > 0 {
+            writeln!(
+                f,
+                "QUIC:      {} handshakes failed",
+                self.quic_handshake_errors
+            )?;
+        }
+        if self.timings.len() < self.timings_seen {
+            writeln!(
+                f,
+                "Timings:   {} of {} sampled, latencies are estimates",
+                self.timings.len(),
+                self.timings_seen
+            )?;
+        }
+        if let Some((created, failed, elapsed)) = self.seed {
+            writeln!(
+                f,
+                "Seeded:    {created} created, {failed} failed in {:.2}s before the run",
+                elapsed.as_secs_f64()
+            )?;
+        }
+        if let Some(cleanup) = &self.cleanup {
+            writeln!(f, "Cleanup:   {cleanup}")?;
+        }
+        if self.worker_panics > 0 {
+            writeln!(
+                f,
+                "Panics:    {} worker threads died, results are partial ({})",
+                self.worker_panics,
+                self.first_panic.as_deref().unwrap_or_default(),
+            )?;
+        }
+        if self.fd_exhausted > 0 {
+            let limit = self
+                .fd_limit
+                .map(|limit| format!(" (limit {limit})"))
+                .unwrap_or_default();
+            writeln!(
+                f,
+                "Limits:    {} connections failed: {}{limit}, raise `ulimit -n` or enable raise_fd_limit",
+                self.fd_exhausted,
+                Exhaustion::FileDescriptors,
+            )?;
+        }
+        if self.ports_exhausted > 0 {
+            writeln!(
+                f,
+                "Limits:    {} connections failed: {}, reuse connections or widen the ephemeral port range",
+                self.ports_exhausted,
+                Exhaustion::LocalPorts,
+            )?;
         }
 
         let percentiles = self.percentiles();
@@ -125,23 +1084,308 @@ impl Display for BenchmarkResult {
         writeln!(f, "P50:       {p50:.2}ms")?;
         writeln!(f, "Min:       {:.2}ms", self.min_time.as_millis() as f64)?;
         writeln!(f, "Max:       {:.2}ms", self.max_time.as_millis() as f64)?;
+
+        if let Some((source, overhead)) = self.clock.filter(|(_, overhead)| !overhead.is_zero()) {
+            writeln!(
+                f,
+                "Clock:     {source:?} ({}ns overhead subtracted)",
+                overhead.as_nanos()
+            )?;
+        }
+
+        if let Some(lag) = self.average_schedule_lag() {
+            writeln!(
+                f,
+                "Backlog:   max {} queued, {} sent late (avg {:.2}ms behind, included in latency)",
+                self.max_queue_depth,
+                self.late,
+                lag.as_secs_f64() * 1000.0
+            )?;
+        }
+
+        for (session, handshakes) in &self.tls_sessions {
+            writeln!(f, "TLS:       {session} ({handshakes} handshakes)")?;
+        }
+        if !self.tls_sessions.is_empty() {
+            writeln!(
+                f,
+                "Resumed:   {:.2}% ({} full handshakes, {} resumed)",
+                self.tls_resumption_rate() * 100.0,
+                self.tls_full_handshake_count(),
+                self.tls_resumed
+            )?;
+        }
+        for (protocol, connections) in &self.negotiated_protocols {
+            writeln!(f, "ALPN:      {protocol} ({connections} connections)")?;
+        }
+        if let Some(requested) = &self.requested_protocol {
+            let mismatches = self.protocol_mismatch_count();
+            if mismatches > 0 {
+                writeln!(
+                    f,
+                    "Warning:   {mismatches} connections did not negotiate the requested {requested}"
+                )?;
+            }
+        }
+        if self.tls_handshake_errors > 0 {
+            writeln!(
+                f,
+                "TLS:       {} handshakes failed",
+                self.tls_handshake_errors
+            )?;
+        }
+
+        if let Some(breakdown) = self.time_breakdown() {
+            writeln!(f, "Time:      {breakdown}")?;
+        }
+
+        if let Some(fairness) = self
+            .fairness(STARVATION_THRESHOLD)
+            .filter(|fairness| fairness.user_count() > 1)
+        {
+            writeln!(f, "Fairness:  {fairness}")?;
+            if !fairness.starved_users().is_empty() {
+                writeln!(f, "Starved:   users {:?}", fairness.starved_users())?;
+            }
+        }
+
+        if let Some(ratio) = self.cache_hit_ratio() {
+            writeln!(f, "Cache:     {:.2}% hit ratio", ratio * 100.0)?;
+        }
+        for (status, result) in &self.cache {
+            let percentiles = result.percentiles();
+            writeln!(
+                f,
+                "Cache {status}: {} reqs, P50 {:.2}ms, P99 {:.2}ms",
+                result.total_request_count(),
+                percentiles.percentile(0.50).as_millis() as f64,
+                percentiles.percentile(0.99).as_millis() as f64,
+            )?;
+        }
+
+        for burst in self.error_bursts(BURST_THRESHOLD) {
+            writeln!(f, "Burst:     {burst}")?;
+        }
+        for annotation in &self.annotations {
+            writeln!(f, "Note:      {annotation}")?;
+        }
+
+        if self.opened_connections > 0 {
+            writeln!(
+                f,
+                "Connects:  {} opened, {:.2}/sec",
+                self.opened_connections,
+                self.connections_per_second()
+            )?;
+        }
+        if self.dropped_connections > 0 {
+            writeln!(f, "Dropped:   {} connections", self.dropped_connections)?;
+        }
+        if self.idle_connections > 0 {
+            writeln!(
+                f,
+                "Idle:      {} connections held, {} pushed messages received",
+                self.idle_connections, self.pushed_messages
+            )?;
+        }
+        if !self.grpc_statuses.is_empty() {
+            let statuses = self
+                .grpc_statuses
+                .iter()
+                .map(|(status, count)| format!("{status} {count}"))
+                .collect::<Vec<_>>();
+            writeln!(f, "gRPC:      {}", statuses.join(", "))?;
+        }
+        if self.grpc_messages > 0 {
+            writeln!(
+                f,
+                "Streamed:  {} messages, {:.2}/sec",
+                self.grpc_messages,
+                self.grpc_messages_per_second()
+            )?;
+        }
+        if self.uploaded_bytes > 0 {
+            writeln!(
+                f,
+                "Uploaded:  {:.2} MiB, {:.2} MiB/sec",
+                self.uploaded_bytes as f64 / (1024.0 * 1024.0),
+                self.upload_bytes_per_second() / (1024.0 * 1024.0)
+            )?;
+        }
+        if self.downloaded_bytes > 0 {
+            writeln!(
+                f,
+                "Received:  {:.2} MiB, {:.2} MiB/sec",
+                self.downloaded_bytes as f64 / (1024.0 * 1024.0),
+                self.download_bytes_per_second() / (1024.0 * 1024.0)
+            )?;
+        }
+        if self.doh_lookups > 0 {
+            writeln!(
+                f,
+                "DoH:       {} lookups, {} failed, {:.2}ms average resolution",
+                self.doh_lookups,
+                self.doh_failures,
+                self.doh_time.as_secs_f64() * 1000.0 / self.doh_lookups as f64
+            )?;
+        }
+        if self.redirects_followed > 0 || self.redirect_loops > 0 {
+            writeln!(
+                f,
+                "Redirects: {} followed, {} loops",
+                self.redirects_followed, self.redirect_loops
+            )?;
+        }
+        if self.script_errors > 0 {
+            writeln!(f, "Scripts:   {} script errors", self.script_errors)?;
+        }
+        if self.continue_responses + self.continue_rejections + self.continue_timeouts > 0 {
+            writeln!(
+                f,
+                "Continue:  {} interim responses, {:.2}ms average, {} rejected, {} timed out",
+                self.continue_responses,
+                self.continue_latency().unwrap_or_default().as_secs_f64() * 1000.0,
+                self.continue_rejections,
+                self.continue_timeouts
+            )?;
+        }
+        if let Some(cap) = self.connection_cap {
+            write!(
+                f,
+                "Conn cap:  {} max, {} peak open, {} connects queued",
+                cap, self.peak_connections, self.queued_connects
+            )?;
+            match self.queued_connects {
+                0 => writeln!(f)?,
+                queued => writeln!(
+                    f,
+                    ", {:.2}ms average wait",
+                    self.connect_queue_time.as_secs_f64() * 1000.0 / queued as f64
+                )?,
+            }
+        }
+        if let Some(savings) = self.compression_savings() {
+            writeln!(
+                f,
+                "Encoded:   {} compressed responses, {:.2} KiB on the wire, {:.2} KiB decoded ({:.1}% saved)",
+                self.compressed_responses,
+                self.wire_bytes as f64 / 1024.0,
+                self.decoded_bytes as f64 / 1024.0,
+                savings * 100.0
+            )?;
+        }
+        if self.echo_checked > 0 {
+            writeln!(
+                f,
+                "Echo ids:  {} checked, {} duplicated, {} mismatched",
+                self.echo_checked, self.echo_duplicates, self.echo_mismatches
+            )?;
+        }
+        if self.chaos_dropped > 0 || self.chaos_aborted > 0 {
+            writeln!(
+                f,
+                "Chaos:     {} requests dropped, {} connections aborted",
+                self.chaos_dropped, self.chaos_aborted
+            )?;
+        }
+        if let Some(accuracy) = &self.percentile_accuracy {
+            write!(
+                f,
+                "Accuracy:  {} exact samples from {} workers",
+                accuracy.samples, accuracy.workers
+            )?;
+            for (quantile, exact, estimate) in accuracy.quantiles() {
+                write!(
+                    f,
+                    ", P{} {:.2}ms vs {:.2}ms ({:.2}% off)",
+                    (quantile * 1000.0).round() / 10.0,
+                    exact.as_secs_f64() * 1000.0,
+                    estimate.as_secs_f64() * 1000.0,
+                    accuracy.error(quantile).unwrap_or_default() * 100.0
+                )?;
+            }
+            writeln!(f)?;
+        }
+        for (name, failures) in &self.expectations {
+            writeln!(f, "Expect {name}: {failures} failed")?;
+        }
+        for (name, value) in &self.counters {
+            writeln!(f, "Counter {name}: {value}")?;
+        }
+        for (name, value) in &self.gauges {
+            writeln!(f, "Gauge {name}: {value}")?;
+        }
+
+        for (label, result) in &self.labels {
+            let percentiles = result.percentiles();
+            write!(
+                f,
+                "{label}: {} reqs, {} errors, P50 {:.2}ms, P99 {:.2}ms",
+                result.total_request_count(),
+                result.http_error + result.tcp_error,
+                percentiles.percentile(0.50).as_millis() as f64,
+                percentiles.percentile(0.99).as_millis() as f64,
+            )?;
+            match result.latency_budget {
+                Some(budget) => writeln!(
+                    f,
+                    ", {} over {:.2}ms budget",
+                    result.budget_violations,
+                    budget.as_secs_f64() * 1000.0
+                )?,
+                None => writeln!(f)?,
+            }
+        }
+
+        for (phase, result) in &self.phases {
+            let percentiles = result.percentiles();
+            writeln!(
+                f,
+                "Phase {phase}: {} reqs in {:.2}s, {} errors, P50 {:.2}ms, P99 {:.2}ms",
+                result.total_request_count(),
+                result.elapsed.as_secs_f64(),
+                result.http_error + result.tcp_error,
+                percentiles.percentile(0.50).as_millis() as f64,
+                percentiles.percentile(0.99).as_millis() as f64,
+            )?;
+        }
+
+        for (tenant, result) in &self.tenants {
+            let percentiles = result.percentiles();
+            writeln!(
+                f,
+                "Tenant {tenant}: {} reqs ({:.2}/s), {} errors, P50 {:.2}ms, P99 {:.2}ms",
+                result.total_request_count(),
+                result.requests_per_second(),
+                result.http_error + result.tcp_error,
+                percentiles.percentile(0.50).as_millis() as f64,
+                percentiles.percentile(0.99).as_millis() as f64,
+            )?;
+        }
+
+        for (region, result) in &self.regions {
+            let percentiles = result.percentiles();
+            writeln!(
+                f,
+                "Region {region}: {} reqs ({:.2}/s), {} errors, P50 {:.2}ms, P99 {:.2}ms",
+                result.total_request_count(),
+                result.requests_per_second(),
+                result.http_error + result.tcp_error,
+                percentiles.percentile(0.50).as_millis() as f64,
+                percentiles.percentile(0.99).as_millis() as f64,
+            )?;
+        }
+
+        for warning in self.warnings() {
+            writeln!(f, "Warning:   {warning}")?;
+        }
         Ok(())
     }
 }
 
 impl Sum<BenchmarkResult> for BenchmarkResult {
     fn sum<I: Iterator<Item = BenchmarkResult>>(iter: I) -> Self {
-        iter.fold(BenchmarkResult::default(), |total, result| {
-            BenchmarkResult {
-                success: total.success + result.success,
-                http_error: total.http_error + result.http_error,
-                tcp_error: total.tcp_error + result.tcp_error,
-                elapsed: total.elapsed + result.elapsed,
-                min_time: total.min_time.min(result.min_time),
-                max_time: total.max_time.max(result.max_time),
-                timings: [total.timings, result.timings].concat(),
-            }
-        })
+        iter.fold(BenchmarkResult::default(), |total, result| total + result)
     }
 }
 
@@ -150,6 +1394,9 @@ impl AddAssign<BenchmarkResult> for BenchmarkResult {
         self.success += rhs.success;
         self.http_error += rhs.http_error;
         self.tcp_error += rhs.tcp_error;
+        self.connect_errors += rhs.connect_errors;
+        self.counted_failures.extend(rhs.counted_failures);
+        self.time_breakdown += rhs.time_breakdown;
         self.elapsed += rhs.elapsed;
         if self.min_time > rhs.min_time {
             self.min_time = rhs.min_time;
@@ -158,6 +1405,98 @@ impl AddAssign<BenchmarkResult> for BenchmarkResult {
             self.max_time = rhs.max_time;
         }
         self.timings.append(&mut rhs.timings);
+        self.timings_seen += rhs.timings_seen;
+        self.timeline += rhs.timeline;
+        self.max_queue_depth = self.max_queue_depth.max(rhs.max_queue_depth);
+        self.late += rhs.late;
+        self.clock = self.clock.or(rhs.clock);
+        self.started_at = match (self.started_at, rhs.started_at) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.schedule_lag += rhs.schedule_lag;
+        self.annotations.append(&mut rhs.annotations);
+        self.annotations
+            .sort_by_key(|annotation| annotation.offset());
+        for (name, value) in rhs.counters {
+            *self.counters.entry(name).or_default() += value;
+        }
+        self.opened_connections += rhs.opened_connections;
+        self.dropped_connections += rhs.dropped_connections;
+        self.idle_connections += rhs.idle_connections;
+        self.pushed_messages += rhs.pushed_messages;
+        self.echo_checked += rhs.echo_checked;
+        self.echo_duplicates += rhs.echo_duplicates;
+        self.echo_mismatches += rhs.echo_mismatches;
+        self.chaos_dropped += rhs.chaos_dropped;
+        self.chaos_aborted += rhs.chaos_aborted;
+        self.grpc_messages += rhs.grpc_messages;
+        self.uploaded_bytes += rhs.uploaded_bytes;
+        self.downloaded_bytes += rhs.downloaded_bytes;
+        self.doh_lookups += rhs.doh_lookups;
+        self.doh_failures += rhs.doh_failures;
+        self.doh_time += rhs.doh_time;
+        self.compressed_responses += rhs.compressed_responses;
+        self.wire_bytes += rhs.wire_bytes;
+        self.decoded_bytes += rhs.decoded_bytes;
+        self.redirects_followed += rhs.redirects_followed;
+        self.redirect_loops += rhs.redirect_loops;
+        self.script_errors += rhs.script_errors;
+        self.continue_responses += rhs.continue_responses;
+        self.continue_time += rhs.continue_time;
+        self.continue_rejections += rhs.continue_rejections;
+        self.continue_timeouts += rhs.continue_timeouts;
+        self.connection_cap = self.connection_cap.or(rhs.connection_cap);
+        self.peak_connections = self.peak_connections.max(rhs.peak_connections);
+        self.queued_connects += rhs.queued_connects;
+        self.connect_queue_time += rhs.connect_queue_time;
+        self.client_cpu = match (self.client_cpu, rhs.client_cpu) {
+            (Some(cpu), Some(other)) => Some(cpu.max(other)),
+            (cpu, other) => cpu.or(other),
+        };
+        for (status, count) in rhs.grpc_statuses {
+            *self.grpc_statuses.entry(status).or_default() += count;
+        }
+        for (name, failures) in rhs.expectations {
+            *self.expectations.entry(name).or_default() += failures;
+        }
+        for (name, value) in rhs.gauges {
+            *self.gauges.entry(name).or_default() += value;
+        }
+        for (session, handshakes) in rhs.tls_sessions {
+            *self.tls_sessions.entry(session).or_default() += handshakes;
+        }
+        self.tls_resumed += rhs.tls_resumed;
+        for (protocol, connections) in rhs.negotiated_protocols {
+            *self.negotiated_protocols.entry(protocol).or_default() += connections;
+        }
+        self.requested_protocol = self.requested_protocol.take().or(rhs.requested_protocol);
+        self.tls_handshake_errors += rhs.tls_handshake_errors;
+        self.latency_budget = self.latency_budget.or(rhs.latency_budget);
+        self.budget_violations += rhs.budget_violations;
+        self.fd_exhausted += rhs.fd_exhausted;
+        self.ports_exhausted += rhs.ports_exhausted;
+        self.quic_handshake_errors += rhs.quic_handshake_errors;
+        self.fd_limit = self.fd_limit.or(rhs.fd_limit);
+        self.seed = self.seed.or(rhs.seed);
+        self.percentile_accuracy = self.percentile_accuracy.take().or(rhs.percentile_accuracy);
+        self.cleanup = self.cleanup.take().or(rhs.cleanup);
+        self.worker_panics += rhs.worker_panics;
+        self.first_panic = self.first_panic.take().or(rhs.first_panic);
+        self.config = self.config.take().or(rhs.config);
+        for (user, stats) in rhs.users {
+            *self.users.entry(user).or_default() += stats;
+        }
+        merge_into(&mut self.labels, rhs.labels);
+        merge_into(&mut self.phases, rhs.phases);
+        merge_into(&mut self.tenants, rhs.tenants);
+        merge_into(&mut self.cache, rhs.cache);
+        merge_into(&mut self.regions, rhs.regions);
+        for (phase, (start, end)) in rhs.phase_spans {
+            let span = self.phase_spans.entry(phase).or_insert((start, end));
+            span.0 = span.0.min(start);
+            span.1 = span.1.max(end);
+        }
     }
 }
 
@@ -169,3 +1508,136 @@ impl Add<BenchmarkResult> for BenchmarkResult {
         self
     }
 }
+
+fn record_in(
+    map: &mut BTreeMap<String, BenchmarkResult>,
+    key: &str,
+    offset: Duration,
+    elapsed: Duration,
+    outcome: Outcome,
+) {
+    match map.get_mut(key) {
+        Some(result) => result.record(offset, elapsed, outcome),
+        None => {
+            let mut result = BenchmarkResult::default();
+            result.record(offset, elapsed, outcome);
+            map.insert(key.to_string(), result);
+        }
+    }
+}
+
+/// Merges two uniform samples, standing for `lhs_seen` and `rhs_seen` timings, into one of at
+/// most `capacity`. Each side contributes in proportion to what it stands for, so the result
+/// stays uniform even when one side was sampled far more heavily than the other.
+pub(crate) fn merge_reservoirs(
+    lhs: Vec<Duration>,
+    lhs_seen: usize,
+    rhs: Vec<Duration>,
+    rhs_seen: usize,
+    capacity: usize,
+) -> Vec<Duration> {
+    let total = (lhs_seen + rhs_seen) as f64;
+    if total == 0.0 {
+        return Vec::new();
+    }
+    // The largest sample both sides can fill at their share of the total.
+    let room = |len: usize, seen: usize| match seen {
+        0 => f64::INFINITY,
+        seen => len as f64 * total / seen as f64,
+    };
+    let size = (capacity as f64)
+        .min(room(lhs.len(), lhs_seen))
+        .min(room(rhs.len(), rhs_seen)) as usize;
+
+    let mut rng = rand::thread_rng();
+    let share = size as f64 * lhs_seen as f64 / total;
+    let from_lhs = (share as usize + usize::from(rng.gen::<f64>() < share.fract())).min(lhs.len());
+    let from_rhs = size.saturating_sub(from_lhs).min(rhs.len());
+
+    let mut merged: Vec<_> = lhs.choose_multiple(&mut rng, from_lhs).copied().collect();
+    merged.extend(rhs.choose_multiple(&mut rng, from_rhs));
+    merged
+}
+
+fn merge_sampled_into<K: Ord>(
+    map: &mut BTreeMap<K, BenchmarkResult>,
+    mut other: BTreeMap<K, BenchmarkResult>,
+    capacity: usize,
+) {
+    for (key, existing) in map.iter_mut() {
+        let mut result = other.remove(key).unwrap_or_default();
+        existing.sample(
+            mem::take(&mut result.timings),
+            result.timings_seen,
+            capacity,
+        );
+        *existing += result;
+    }
+    for (key, mut result) in other {
+        let timings = mem::take(&mut result.timings);
+        let entry = map.entry(key).or_default();
+        entry.sample(timings, result.timings_seen, capacity);
+        *entry += result;
+    }
+}
+
+fn union_len<K: Ord>(
+    map: &BTreeMap<K, BenchmarkResult>,
+    other: &BTreeMap<K, BenchmarkResult>,
+) -> usize {
+    map.len() + other.keys().filter(|key| !map.contains_key(key)).count()
+}
+
+fn merge_into<K: Ord>(map: &mut BTreeMap<K, BenchmarkResult>, other: BTreeMap<K, BenchmarkResult>) {
+    for (key, result) in other {
+        match map.get_mut(&key) {
+            Some(existing) => *existing += result,
+            None => {
+                map.insert(key, result);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sampled(millis: u64, kept: usize, seen: usize) -> BenchmarkResult {
+        BenchmarkResult {
+            timings: vec![Duration::from_millis(millis); kept],
+            timings_seen: seen,
+            ..BenchmarkResult::default()
+        }
+    }
+
+    #[test]
+    fn reservoirs_merge_in_proportion_to_what_they_saw() {
+        // 100k fast requests sampled down to 1k, against 1k slow ones kept in full.
+        let mut merged = sampled(1, 1000, 100_000);
+        merged.merge_sampled(sampled(100, 1000, 1000), 1000);
+
+        assert!(merged.timings.len() <= 1000);
+        assert_eq!(merged.timings_seen, 101_000);
+        let slow = merged
+            .timings
+            .iter()
+            .filter(|t| **t == Duration::from_millis(100))
+            .count();
+        assert!((9..=10).contains(&slow), "{slow} slow samples");
+        assert!(merged.percentiles().percentile(0.5) < Duration::from_millis(2));
+    }
+
+    #[test]
+    fn complete_samples_that_fit_are_kept_whole() {
+        let merged = merge_reservoirs(
+            vec![Duration::from_millis(1); 3],
+            3,
+            vec![Duration::from_millis(2); 4],
+            4,
+            10,
+        );
+        assert_eq!(merged.len(), 7);
+        assert!(merge_reservoirs(Vec::new(), 0, Vec::new(), 0, 10).is_empty());
+    }
+}
@@ -0,0 +1,143 @@
+use std::{error::Error, fs, path::Path, sync::Arc};
+
+use hyper::{Body, Request, Response, Uri};
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+
+use crate::{script::ScriptErrors, uri::try_rebase};
+
+pub struct RhaiScript {
+    source: String,
+}
+
+impl RhaiScript {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(Self {
+            source: fs::read_to_string(path)?,
+        })
+    }
+
+    pub fn from_source(source: impl Into<String>) -> Self {
+        Self {
+            source: source.into(),
+        }
+    }
+
+    pub(crate) fn compile(self) -> Result<RhaiState, Box<dyn Error + Send + Sync>> {
+        let engine = Engine::new();
+        let ast = engine.compile(&self.source)?;
+        if !ast
+            .iter_functions()
+            .any(|f| f.name == "request" && f.params.len() == 1)
+        {
+            return Err("rhai script must define `fn request(n)`".into());
+        }
+        let has_expect = ast.iter_functions().any(|f| f.name == "expect");
+        Ok(RhaiState {
+            engine,
+            ast,
+            has_expect,
+            errors: Arc::default(),
+        })
+    }
+}
+
+pub(crate) struct RhaiState {
+    engine: Engine,
+    ast: AST,
+    has_expect: bool,
+    errors: Arc<ScriptErrors>,
+}
+
+impl RhaiState {
+    pub(crate) fn errors(&self) -> Arc<ScriptErrors> {
+        self.errors.clone()
+    }
+
+    pub(crate) fn request(&self, uri: &Uri, n: u64) -> Request<Body> {
+        match self.try_request(uri, n) {
+            Ok(req) => req,
+            Err(_) => self.errors.failed(),
+        }
+    }
+
+    fn try_request(
+        &self,
+        uri: &Uri,
+        n: u64,
+    ) -> Result<Request<Body>, Box<dyn Error + Send + Sync>> {
+        let spec =
+            self.engine
+                .call_fn::<Map>(&mut Scope::new(), &self.ast, "request", (n as i64,))?;
+
+        let field = |name: &str| spec.get(name).map(|value| value.to_string());
+
+        let path = field("path").unwrap_or_else(|| "/".to_string());
+        let mut req = Request::builder().uri(try_rebase(uri, &path)?).method(
+            field("method")
+                .unwrap_or_else(|| "GET".to_string())
+                .as_str(),
+        );
+
+        if let Some(headers) = spec
+            .get("headers")
+            .and_then(|h| h.clone().try_cast::<Map>())
+        {
+            for (name, value) in headers {
+                req = req.header(name.as_str(), value.to_string());
+            }
+        }
+
+        let body = match field("body") {
+            Some(body) => Body::from(body),
+            None => Body::empty(),
+        };
+        Ok(req.body(body)?)
+    }
+
+    pub(crate) fn expect(&self, res: &Response<Body>) -> bool {
+        if !self.has_expect {
+            return res.status().is_success();
+        }
+
+        let mut headers = Map::new();
+        for (name, value) in res.headers() {
+            if let Ok(value) = value.to_str() {
+                headers.insert(name.as_str().into(), Dynamic::from(value.to_string()));
+            }
+        }
+
+        let mut response = Map::new();
+        response.insert("status".into(), Dynamic::from(res.status().as_u16() as i64));
+        response.insert("headers".into(), Dynamic::from(headers));
+
+        self.engine
+            .call_fn::<bool>(&mut Scope::new(), &self.ast, "expect", (response,))
+            .unwrap_or_else(|_| {
+                self.errors.record();
+                false
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripts_without_request_fail_to_compile() {
+        let err = RhaiScript::from_source("fn expect(res) { true }")
+            .compile()
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("fn request(n)"));
+        assert!(RhaiScript::from_source("fn request() { #{} }")
+            .compile()
+            .is_err());
+
+        let state = RhaiScript::from_source(r#"fn request(n) { #{ path: "/items/" + n } }"#)
+            .compile()
+            .unwrap();
+        let req = state.request(&"http://localhost".parse().unwrap(), 7);
+        assert_eq!(req.uri().path(), "/items/7");
+    }
+}
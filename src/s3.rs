@@ -0,0 +1,521 @@
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use futures::{future, stream, StreamExt};
+use hyper::{
+    body::{Bytes, HttpBody},
+    header::{HeaderValue, AUTHORIZATION, CONTENT_LENGTH, HOST},
+    Body, Method, Request, Uri,
+};
+use rand::Rng;
+use ring::{digest, hmac};
+use tokio::time::timeout_at;
+
+use crate::{
+    clock::Clock,
+    metrics::Recorder,
+    resolve::HttpClient,
+    result::Outcome,
+    schedule::{Schedule, Slot},
+    upload::UploadTracker,
+    uri::rebase,
+    user::UserContext,
+};
+
+const DEFAULT_OBJECTS: u64 = 1000;
+const DEFAULT_SIZE: u64 = 4096;
+const MAX_SIZE: u64 = 1024 * 1024 * 1024;
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+const SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+const SIGNED_HEADERS_WITH_TOKEN: &str = "host;x-amz-content-sha256;x-amz-date;x-amz-security-token";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Put,
+    Get,
+    Delete,
+}
+
+impl Op {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Put => "s3 PUT",
+            Self::Get => "s3 GET",
+            Self::Delete => "s3 DELETE",
+        }
+    }
+
+    fn method(self) -> Method {
+        match self {
+            Self::Put => Method::PUT,
+            Self::Get => Method::GET,
+            Self::Delete => Method::DELETE,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    next: AtomicU64,
+    stored: AtomicU64,
+    downloaded: AtomicU64,
+    uploads: Arc<UploadTracker>,
+    payload: OnceLock<Bytes>,
+}
+
+#[derive(Clone)]
+pub struct S3 {
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+    prefix: String,
+    objects: u64,
+    sizes: Vec<(u64, u32)>,
+    mix: [u32; 3],
+    state: Arc<State>,
+}
+
+impl S3 {
+    pub fn new(
+        bucket: impl Into<String>,
+        region: impl Into<String>,
+        access_key: impl Into<String>,
+        secret_key: impl Into<String>,
+    ) -> Self {
+        Self {
+            bucket: bucket.into(),
+            region: region.into(),
+            access_key: access_key.into(),
+            secret_key: secret_key.into(),
+            session_token: None,
+            prefix: "zerg/".to_string(),
+            objects: DEFAULT_OBJECTS,
+            sizes: Vec::new(),
+            mix: [1, 1, 0],
+            state: Arc::default(),
+        }
+    }
+
+    pub fn session_token(self, token: impl Into<String>) -> Self {
+        Self {
+            session_token: Some(token.into()),
+            ..self
+        }
+    }
+
+    pub fn prefix(self, prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            ..self
+        }
+    }
+
+    pub fn objects(self, objects: u64) -> Self {
+        Self { objects, ..self }
+    }
+
+    pub fn size(mut self, bytes: u64, weight: u32) -> Self {
+        self.sizes.push((bytes, weight));
+        self
+    }
+
+    pub fn mix(self, put: u32, get: u32, delete: u32) -> Self {
+        Self {
+            mix: [put, get, delete],
+            ..self
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), &'static str> {
+        if self.bucket.is_empty() || self.bucket.contains('/') {
+            return Err("S3 swarms need a bucket name without slashes");
+        }
+        if self.region.is_empty() {
+            return Err("S3 swarms need a signing region");
+        }
+        if self.objects == 0 {
+            return Err("S3 swarms need at least one object key");
+        }
+        if self.mix[0] == 0 {
+            return Err("S3 swarms need a positive PUT weight to have objects to read");
+        }
+        if !self.sizes.is_empty() && self.sizes.iter().all(|(_, weight)| *weight == 0) {
+            return Err("at least one S3 object size needs a positive weight");
+        }
+        if self.sizes.iter().any(|(bytes, _)| *bytes > MAX_SIZE) {
+            return Err("S3 object sizes are limited to 1 GiB");
+        }
+        Ok(())
+    }
+
+    pub(crate) fn uploaded(&self) -> u64 {
+        self.state.uploads.bytes()
+    }
+
+    pub(crate) fn downloaded(&self) -> u64 {
+        self.state.downloaded.load(Ordering::Relaxed)
+    }
+
+    fn pick(&self, sequence: u64) -> Op {
+        let total = self.mix.iter().map(|weight| *weight as u64).sum::<u64>();
+        let slot = sequence % total;
+        let op = match slot.checked_sub(self.mix[0] as u64) {
+            None => Op::Put,
+            Some(slot) if slot < self.mix[1] as u64 => Op::Get,
+            Some(_) => Op::Delete,
+        };
+        match self.state.stored.load(Ordering::Relaxed) {
+            0 => Op::Put,
+            _ => op,
+        }
+    }
+
+    fn key(&self, op: Op) -> u64 {
+        match op {
+            Op::Put => self.state.next.fetch_add(1, Ordering::Relaxed) % self.objects,
+            _ => {
+                let stored = self.state.stored.load(Ordering::Relaxed).min(self.objects);
+                rand::thread_rng().gen_range(0..stored.max(1))
+            }
+        }
+    }
+
+    fn sample_size(&self) -> u64 {
+        let total = self
+            .sizes
+            .iter()
+            .map(|(_, weight)| *weight as u64)
+            .sum::<u64>();
+        if total == 0 {
+            return DEFAULT_SIZE;
+        }
+        let mut slot = rand::thread_rng().gen_range(0..total);
+        for (bytes, weight) in &self.sizes {
+            match slot.checked_sub(*weight as u64) {
+                Some(rest) => slot = rest,
+                None => return *bytes,
+            }
+        }
+        unreachable!()
+    }
+
+    fn payload(&self, size: u64) -> Bytes {
+        let payload = self.state.payload.get_or_init(|| {
+            let largest = self
+                .sizes
+                .iter()
+                .map(|(bytes, _)| *bytes)
+                .max()
+                .unwrap_or(DEFAULT_SIZE);
+            let mut payload = vec![0; largest as usize];
+            rand::thread_rng().fill(&mut payload[..]);
+            Bytes::from(payload)
+        });
+        payload.slice(..size as usize)
+    }
+
+    fn request(&self, uri: &Uri, op: Op, key: u64) -> Request<Body> {
+        let path = format!("/{}/{}{key}", encode(&self.bucket), encode(&self.prefix));
+        let uri = rebase(uri, &path);
+        let host = uri.authority().map_or("", |authority| authority.as_str());
+        let date = timestamp(SystemTime::now());
+
+        let mut req = Request::new(Body::empty());
+        *req.method_mut() = op.method();
+        if op == Op::Put {
+            let size = self.sample_size();
+            let body = stream::once(future::ready(Ok(self.payload(size)))).boxed();
+            *req.body_mut() = self.state.uploads.body(body);
+            req.headers_mut().insert(CONTENT_LENGTH, size.into());
+        }
+
+        let mut canonical = format!(
+            "{}\n{path}\n\nhost:{host}\nx-amz-content-sha256:{UNSIGNED_PAYLOAD}\nx-amz-date:{date}\n",
+            op.method()
+        );
+        if let Some(token) = &self.session_token {
+            writeln!(canonical, "x-amz-security-token:{token}").unwrap();
+        }
+        let signed = match self.session_token {
+            Some(_) => SIGNED_HEADERS_WITH_TOKEN,
+            None => SIGNED_HEADERS,
+        };
+        write!(canonical, "\n{signed}\n{UNSIGNED_PAYLOAD}").unwrap();
+
+        let day = &date[..8];
+        let scope = format!("{day}/{}/s3/aws4_request", self.region);
+        let to_sign = format!(
+            "AWS4-HMAC-SHA256\n{date}\n{scope}\n{}",
+            hex(digest::digest(&digest::SHA256, canonical.as_bytes()).as_ref())
+        );
+        let key = [day, &self.region, "s3", "aws4_request"].iter().fold(
+            format!("AWS4{}", self.secret_key).into_bytes(),
+            |key, part| sign(&key, part.as_bytes()),
+        );
+        let signature = hex(&sign(&key, to_sign.as_bytes()));
+
+        let headers = req.headers_mut();
+        if let Ok(host) = HeaderValue::from_str(host) {
+            headers.insert(HOST, host);
+        }
+        headers.insert(
+            "x-amz-content-sha256",
+            HeaderValue::from_static(UNSIGNED_PAYLOAD),
+        );
+        headers.insert("x-amz-date", HeaderValue::from_str(&date).unwrap());
+        if let Some(token) = self
+            .session_token
+            .as_ref()
+            .and_then(|token| HeaderValue::from_str(token).ok())
+        {
+            headers.insert("x-amz-security-token", token);
+        }
+        if let Ok(authorization) = HeaderValue::from_str(&format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed}, Signature={signature}",
+            self.access_key
+        )) {
+            headers.insert(AUTHORIZATION, authorization);
+        }
+        *req.uri_mut() = uri;
+        req
+    }
+}
+
+pub(crate) struct Session {
+    pub(crate) http: HttpClient,
+    pub(crate) uri: Arc<Uri>,
+    pub(crate) epoch: Instant,
+    pub(crate) deadline: Instant,
+    pub(crate) clock: Clock,
+    pub(crate) recorder: Recorder,
+    pub(crate) schedule: Option<Schedule>,
+}
+
+impl Session {
+    pub(crate) async fn run(
+        &self,
+        s3: &S3,
+        ctx: &mut UserContext,
+        running: &AtomicBool,
+        sequence: &AtomicU64,
+    ) {
+        while running.load(Ordering::Relaxed) {
+            let ticket = self
+                .schedule
+                .as_ref()
+                .map(|schedule| schedule.claim(sequence, self.epoch));
+            ctx.sequence = match &ticket {
+                Some(ticket) => ticket.n,
+                None => sequence.fetch_add(1, Ordering::Relaxed),
+            };
+            let mut late = Duration::ZERO;
+            if let (Some(schedule), Some(ticket)) = (&self.schedule, &ticket) {
+                match schedule
+                    .wait(ticket, self.epoch, Some(self.deadline), &self.recorder)
+                    .await
+                {
+                    Slot::Due(lateness) => late = lateness,
+                    Slot::Taken => continue,
+                    Slot::Closed => break,
+                }
+                if !running.load(Ordering::Relaxed) {
+                    break;
+                }
+            }
+
+            let op = s3.pick(ctx.sequence);
+            let req = s3.request(&self.uri, op, s3.key(op));
+            let start = Instant::now();
+            let timer = self.clock.now();
+            let outcome = match timeout_at(self.deadline.into(), self.send(s3, req)).await {
+                Ok(outcome) => outcome,
+                Err(_) => break,
+            };
+            if op == Op::Put && outcome == Outcome::Success {
+                s3.state.stored.fetch_add(1, Ordering::Relaxed);
+            }
+            self.recorder.record_labeled(
+                op.label(),
+                None,
+                start.saturating_duration_since(self.epoch),
+                self.clock.elapsed(timer) + late,
+                outcome,
+            );
+            ctx.iteration += 1;
+        }
+    }
+
+    async fn send(&self, s3: &S3, req: Request<Body>) -> Outcome {
+        let res = match self.http.request(req).await {
+            Ok(res) => res,
+            Err(err) => return Outcome::from_error(&err),
+        };
+        let success = res.status().is_success();
+        let mut body = res.into_body();
+        while let Some(chunk) = body.data().await {
+            match chunk {
+                Ok(chunk) => {
+                    s3.state
+                        .downloaded
+                        .fetch_add(chunk.len() as u64, Ordering::Relaxed);
+                }
+                Err(err) => return Outcome::from_error(&err),
+            }
+        }
+        match success {
+            true => Outcome::Success,
+            false => Outcome::HttpError,
+        }
+    }
+}
+
+fn sign(key: &[u8], data: &[u8]) -> Vec<u8> {
+    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data)
+        .as_ref()
+        .to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        write!(hex, "{byte:02x}").unwrap();
+        hex
+    })
+}
+
+fn encode(segment: &str) -> String {
+    segment.bytes().fold(String::new(), |mut encoded, byte| {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => write!(encoded, "%{byte:02X}").unwrap(),
+        }
+        encoded
+    })
+}
+
+fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s3() -> S3 {
+        S3::new("bucket", "us-east-1", "AKID", "secret")
+    }
+
+    #[test]
+    fn timestamps_use_the_sigv4_basic_format() {
+        let at = |secs| timestamp(UNIX_EPOCH + Duration::from_secs(secs));
+        assert_eq!(at(0), "19700101T000000Z");
+        assert_eq!(at(1_440_938_160), "20150830T123600Z");
+        assert_eq!(at(951_782_400), "20000229T000000Z");
+    }
+
+    #[test]
+    fn path_segments_are_uri_encoded_keeping_slashes() {
+        assert_eq!(encode("zerg/obj-1.bin"), "zerg/obj-1.bin");
+        assert_eq!(encode("a b+ü"), "a%20b%2B%C3%BC");
+    }
+
+    #[test]
+    fn signatures_are_hex_hmac_sha256() {
+        assert_eq!(
+            hex(&sign(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn requests_are_signed_for_the_bucket_and_region() {
+        let s3 = s3().session_token("tok").prefix("run 1/");
+        let uri = Uri::from_static("http://localhost:9000/");
+        let req = s3.request(&uri, Op::Put, 3);
+
+        assert_eq!(req.method(), Method::PUT);
+        assert_eq!(req.uri(), "http://localhost:9000/bucket/run%201/3");
+        assert_eq!(req.headers()[HOST], "localhost:9000");
+        assert_eq!(
+            req.headers()[CONTENT_LENGTH],
+            DEFAULT_SIZE.to_string().as_str()
+        );
+        assert_eq!(req.headers()["x-amz-security-token"], "tok");
+
+        let date = req.headers()["x-amz-date"].to_str().unwrap();
+        let authorization = req.headers()[AUTHORIZATION].to_str().unwrap();
+        let (credential, signature) = authorization.split_once(", Signature=").unwrap();
+        assert_eq!(
+            credential,
+            format!(
+                "AWS4-HMAC-SHA256 Credential=AKID/{}/us-east-1/s3/aws4_request, \
+                 SignedHeaders={SIGNED_HEADERS_WITH_TOKEN}",
+                &date[..8]
+            )
+        );
+        assert_eq!(signature.len(), 64);
+
+        let get = s3.request(&uri, Op::Get, 3);
+        assert!(get.headers().get(CONTENT_LENGTH).is_none());
+    }
+
+    #[test]
+    fn only_puts_are_picked_until_objects_exist() {
+        let s3 = s3().mix(1, 1, 1);
+        assert!((0..3).all(|n| s3.pick(n) == Op::Put));
+        s3.state.stored.store(1, Ordering::Relaxed);
+        let ops: Vec<_> = (0..3).map(|n| s3.pick(n)).collect();
+        assert_eq!(ops, [Op::Put, Op::Get, Op::Delete]);
+
+        assert_eq!(s3.key(Op::Put), 0);
+        assert_eq!(s3.key(Op::Put), 1);
+        assert_eq!(s3.key(Op::Get), 0);
+    }
+
+    #[test]
+    fn sizes_are_sampled_by_weight_from_one_payload() {
+        assert_eq!(s3().sample_size(), DEFAULT_SIZE);
+        let s3 = s3().size(10, 0).size(64, 1);
+        assert_eq!(s3.sample_size(), 64);
+        assert_eq!(s3.payload(10), s3.payload(64).slice(..10));
+    }
+
+    #[test]
+    fn invalid_setups_are_refused() {
+        assert!(s3().validate().is_ok());
+        assert!(S3::new("a/b", "r", "k", "s").validate().is_err());
+        assert!(S3::new("b", "", "k", "s").validate().is_err());
+        assert!(s3().objects(0).validate().is_err());
+        assert!(s3().mix(0, 1, 0).validate().is_err());
+        assert!(s3().size(10, 0).validate().is_err());
+        assert!(s3().size(MAX_SIZE + 1, 1).validate().is_err());
+    }
+}
@@ -0,0 +1,258 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashSet,
+    error::Error,
+    future::Future,
+    rc::Rc,
+    sync::Arc,
+    time::Instant,
+};
+
+use futures::future::LocalBoxFuture;
+use hyper::{Body, Method, Request, Response, Uri};
+
+use crate::{
+    cache::CacheStatus, clock::Clock, metrics::Recorder, phase::Barriers, resolve::HttpClient,
+    result::Outcome, uri::try_rebase, user::UserContext, Expectation,
+};
+
+type RunScenario = Arc<dyn Fn(Client) -> LocalBoxFuture<'static, ()> + Send + Sync + 'static>;
+
+/// A named async flow run in a loop by every user, built with [`Scenario::new`] or the
+/// `#[zerg::scenario]` attribute and handed to [`SwarmBuilder::scenario`].
+///
+/// [`SwarmBuilder::scenario`]: crate::SwarmBuilder::scenario
+#[derive(Clone)]
+pub struct Scenario {
+    name: Arc<str>,
+    run: RunScenario,
+    expectation: Expectation,
+}
+
+impl Scenario {
+    pub fn new<F, Fut>(name: impl Into<String>, f: F) -> Self
+    where
+        F: Fn(Client) -> Fut + Send + Sync + 'static,
+        Fut: Future + 'static,
+    {
+        let f = Arc::new(f);
+        Self {
+            name: name.into().into(),
+            run: Arc::new(move |client| {
+                let f = f.clone();
+                Box::pin(async move {
+                    f(client).await;
+                })
+            }),
+            expectation: Arc::new(|res| res.status().is_success()),
+        }
+    }
+
+    pub(crate) fn expecting(self, expectation: Expectation) -> Self {
+        Self {
+            expectation,
+            ..self
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn run(&self, client: Client) -> LocalBoxFuture<'static, ()> {
+        (self.run)(client)
+    }
+}
+
+struct Inner {
+    http: HttpClient,
+    uri: Arc<Uri>,
+    scenario: Arc<str>,
+    expectation: Expectation,
+    ctx: RefCell<UserContext>,
+    step: Cell<usize>,
+    label: RefCell<Option<String>>,
+    step_labels: RefCell<Vec<Rc<str>>>,
+    phase: RefCell<Option<String>>,
+    barriers: Arc<Barriers>,
+    clock: Clock,
+    passed: RefCell<HashSet<String>>,
+    recorder: Recorder,
+}
+
+#[derive(Clone)]
+pub struct Client(Rc<Inner>);
+
+impl Client {
+    pub(crate) fn new(
+        http: HttpClient,
+        uri: Arc<Uri>,
+        scenario: &Scenario,
+        ctx: UserContext,
+        barriers: Arc<Barriers>,
+        clock: Clock,
+        recorder: Recorder,
+    ) -> Self {
+        Self(Rc::new(Inner {
+            http,
+            uri,
+            scenario: scenario.name.clone(),
+            expectation: scenario.expectation.clone(),
+            ctx: RefCell::new(ctx),
+            step: Cell::new(0),
+            label: RefCell::new(None),
+            step_labels: RefCell::new(Vec::new()),
+            phase: RefCell::new(None),
+            barriers,
+            clock,
+            passed: RefCell::new(HashSet::new()),
+            recorder,
+        }))
+    }
+
+    pub(crate) fn begin_iteration(&self) {
+        if self.0.step.get() > 0 {
+            self.0.ctx.borrow_mut().iteration += 1;
+        }
+        self.0.step.set(0);
+        self.0.label.borrow_mut().take();
+    }
+
+    pub fn uri(&self) -> &Uri {
+        &self.0.uri
+    }
+
+    pub fn context(&self) -> UserContext {
+        self.0.ctx.borrow().clone()
+    }
+
+    pub fn step(&self, label: impl AsRef<str>) -> &Self {
+        *self.0.label.borrow_mut() = Some(format!("{}/{}", self.0.scenario, label.as_ref()));
+        self
+    }
+
+    fn step_label(&self, step: usize) -> Rc<str> {
+        let mut labels = self.0.step_labels.borrow_mut();
+        while labels.len() < step {
+            let label = format!("{}/{}", self.0.scenario, labels.len() + 1);
+            labels.push(label.into());
+        }
+        labels[step - 1].clone()
+    }
+
+    pub async fn barrier(&self, name: &str) -> bool {
+        if self.0.passed.borrow().contains(name) {
+            return true;
+        }
+        let released = self.0.barriers.wait(name).await;
+        if released {
+            self.0.passed.borrow_mut().insert(name.to_string());
+        }
+        released
+    }
+
+    pub async fn phase(&self, name: &str) -> bool {
+        let released = self.barrier(name).await;
+        *self.0.phase.borrow_mut() = Some(name.to_string());
+        released
+    }
+
+    fn next_label(&self) -> Rc<str> {
+        let step = self.0.step.get() + 1;
+        self.0.step.set(step);
+        match self.0.label.borrow_mut().take() {
+            Some(label) => label.into(),
+            None => self.step_label(step),
+        }
+    }
+
+    /// Sends a request as the next step. Success is judged by the swarm's expectation or
+    /// matcher, which sees the response head; the body is left for the scenario to read.
+    pub async fn send(&self, req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
+        let label = self.next_label();
+        let epoch = self.0.ctx.borrow().epoch;
+
+        let start = Instant::now();
+        let timer = self.0.clock.now();
+        let res = self.0.http.request(req).await;
+        let elapsed = self.0.clock.elapsed(timer);
+
+        let outcome = match &res {
+            Ok(res) => {
+                let mut head = Response::new(Body::empty());
+                *head.status_mut() = res.status();
+                *head.version_mut() = res.version();
+                *head.headers_mut() = res.headers().clone();
+                match (self.0.expectation)(head) {
+                    true => Outcome::Success,
+                    false => Outcome::HttpError,
+                }
+            }
+            Err(err) => Outcome::from_error(err),
+        };
+        let offset = start.saturating_duration_since(epoch);
+        self.0.recorder.record_labeled(
+            &label,
+            self.0.phase.borrow().as_deref(),
+            offset,
+            elapsed,
+            outcome,
+        );
+        let cache = res
+            .as_ref()
+            .ok()
+            .and_then(|res| CacheStatus::of(res.headers()));
+        self.0
+            .recorder
+            .record_cache(cache, offset, elapsed, outcome);
+
+        res
+    }
+
+    pub async fn get(&self, path: &str) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        self.call(Method::GET, path, Body::empty()).await
+    }
+
+    pub async fn post(
+        &self,
+        path: &str,
+        body: impl Into<Body>,
+    ) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        self.call(Method::POST, path, body.into()).await
+    }
+
+    pub async fn put(
+        &self,
+        path: &str,
+        body: impl Into<Body>,
+    ) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        self.call(Method::PUT, path, body.into()).await
+    }
+
+    pub async fn delete(&self, path: &str) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        self.call(Method::DELETE, path, Body::empty()).await
+    }
+
+    /// Builds and sends a step. A path that does not make a valid URI still takes its step
+    /// and counts as a failed request instead of panicking the worker.
+    async fn call(
+        &self,
+        method: Method,
+        path: &str,
+        body: Body,
+    ) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let uri = match try_rebase(&self.0.uri, path) {
+            Ok(uri) => uri,
+            Err(err) => {
+                self.next_label();
+                let offset = self.0.ctx.borrow().epoch.elapsed();
+                self.0.recorder.record_failed(offset);
+                return Err(err.into());
+            }
+        };
+        let mut req = Request::new(body);
+        *req.method_mut() = method;
+        *req.uri_mut() = uri;
+        Ok(self.send(req).await?)
+    }
+}
@@ -0,0 +1,374 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{curve::RateCurve, metrics::Recorder, record::Recording};
+
+const STEAL_AFTER: Duration = Duration::from_millis(10);
+const CLAIMS: usize = 1 << 14;
+
+type Offset = Arc<dyn Fn(u64) -> Option<Duration> + Send + Sync>;
+
+#[derive(Clone)]
+pub(crate) struct Schedule {
+    offset: Offset,
+    claims: Arc<Claims>,
+}
+
+/// Who took which ticket. Every ticket below `cursor` is settled, and `claim` never hands out
+/// a ticket a full window past it, so a slot only ever holds its own ticket or an older,
+/// settled one: a later ticket in the slot means this one was already taken.
+struct Claims {
+    taken: Box<[AtomicU64]>,
+    cursor: AtomicU64,
+}
+
+impl Claims {
+    fn new(window: usize) -> Self {
+        Self {
+            taken: (0..window).map(|_| AtomicU64::new(0)).collect(),
+            cursor: AtomicU64::new(0),
+        }
+    }
+
+    fn window(&self) -> u64 {
+        self.taken.len() as u64
+    }
+
+    fn take(&self, n: u64) -> bool {
+        self.taken[(n % self.window()) as usize].fetch_max(n + 1, Ordering::AcqRel) <= n
+    }
+}
+
+pub(crate) struct Ticket {
+    pub(crate) n: u64,
+    stolen: bool,
+}
+
+pub(crate) enum Slot {
+    Due(Duration),
+    Taken,
+    Closed,
+}
+
+impl Schedule {
+    pub(crate) fn new(f: impl Fn(u64) -> Option<Duration> + Send + Sync + 'static) -> Self {
+        Self {
+            offset: Arc::new(f),
+            claims: Arc::new(Claims::new(CLAIMS)),
+        }
+    }
+
+    /// Widens the claim window so each of `users` can hold a ticket without stealing another's.
+    pub(crate) fn for_users(self, users: usize) -> Self {
+        let window = (users * 2).next_power_of_two().max(CLAIMS);
+        Self {
+            claims: Arc::new(Claims::new(window)),
+            ..self
+        }
+    }
+
+    pub(crate) fn constant(rate: f64) -> Self {
+        Self::new(move |n| Some(Duration::from_secs_f64(n as f64 / rate)))
+    }
+
+    pub(crate) fn token_bucket(rate: f64, burst: u64) -> Self {
+        let refill = burst as f64 / rate;
+        Self::new(move |n| Some(Duration::from_secs_f64((n / burst) as f64 * refill)))
+    }
+
+    pub(crate) fn curve(curve: &RateCurve) -> Self {
+        let mut count = 0.0;
+        let segments = curve
+            .points()
+            .iter()
+            .zip(
+                curve
+                    .points()
+                    .iter()
+                    .skip(1)
+                    .map(|(offset, _)| Some(*offset))
+                    .chain([None]),
+            )
+            .map(|(&(start, rate), end)| {
+                let segment = (start, rate, count);
+                if let Some(end) = end {
+                    count += (end - start).as_secs_f64() * rate;
+                }
+                segment
+            })
+            .collect::<Vec<_>>();
+
+        Self::new(move |n| {
+            let n = n as f64;
+            let i = segments.partition_point(|(_, _, count)| *count <= n);
+            let (start, rate, count) = segments[i.checked_sub(1)?];
+            if rate <= 0.0 {
+                return None;
+            }
+            Some(start + Duration::from_secs_f64((n - count) / rate))
+        })
+    }
+
+    pub(crate) fn replay(recording: Arc<Recording>, speed: f64) -> Self {
+        let last = recording
+            .requests()
+            .last()
+            .map(|req| req.offset())
+            .unwrap_or_default();
+        let span = last + last / recording.len().max(1) as u32;
+
+        Self::new(move |n| {
+            let len = recording.len() as u64;
+            let lap = span * (n / len) as u32;
+            let offset = lap + recording.requests()[(n % len) as usize].offset();
+            Some(offset.div_f64(speed))
+        })
+    }
+
+    pub(crate) fn offset(&self, n: u64) -> Option<Duration> {
+        (self.offset)(n)
+    }
+
+    /// Hands out the next ticket, or steals the oldest unsettled one when it is overdue or the
+    /// window is full. The oldest ticket is taken before the cursor moves past it, so it is
+    /// settled before its slot can be reused.
+    pub(crate) fn claim(&self, sequence: &AtomicU64, epoch: Instant) -> Ticket {
+        let now = epoch.elapsed();
+        let window = self.claims.window();
+        loop {
+            let cursor = self.claims.cursor.load(Ordering::Acquire);
+            let issued = sequence.load(Ordering::Relaxed);
+            let full = issued >= cursor + window;
+            let overdue = cursor < issued
+                && self
+                    .offset(cursor)
+                    .is_some_and(|offset| offset + STEAL_AFTER < now);
+            if full || overdue {
+                let stolen = self.claims.take(cursor);
+                let _ = self.claims.cursor.compare_exchange(
+                    cursor,
+                    cursor + 1,
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                );
+                if stolen {
+                    return Ticket {
+                        n: cursor,
+                        stolen: true,
+                    };
+                }
+                continue;
+            }
+            let next = sequence.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                (n < cursor + window).then_some(n + 1)
+            });
+            if let Ok(n) = next {
+                return Ticket { n, stolen: false };
+            }
+        }
+    }
+
+    pub(crate) async fn wait(
+        &self,
+        ticket: &Ticket,
+        epoch: Instant,
+        deadline: Option<Instant>,
+        recorder: &Recorder,
+    ) -> Slot {
+        let n = ticket.n;
+        let at = match self.offset(n) {
+            Some(offset) if deadline.is_none_or(|deadline| epoch + offset < deadline) => {
+                epoch + offset
+            }
+            _ => return Slot::Closed,
+        };
+        let now = Instant::now();
+        let late = now.saturating_duration_since(at);
+        if !late.is_zero() {
+            let offset = now.duration_since(epoch);
+            let depth = self.due(n, offset) - n;
+            recorder.record_backlog(offset, depth, late);
+        }
+        tokio::time::sleep_until(at.into()).await;
+        if !ticket.stolen && !self.claims.take(n) {
+            return Slot::Taken;
+        }
+        Slot::Due(late)
+    }
+
+    pub(crate) fn due(&self, n: u64, now: Duration) -> u64 {
+        let is_due = |k: u64| self.offset(k).is_some_and(|offset| offset <= now);
+        if !is_due(n) {
+            return n;
+        }
+
+        let mut low = n;
+        let mut step = 1;
+        while is_due(low + step) {
+            low += step;
+            step *= 2;
+        }
+
+        let mut high = low + step;
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if is_due(mid) {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        low + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::Metrics;
+
+    fn block_on<F: std::future::Future>(f: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(f)
+    }
+
+    fn windowed(rate: f64, window: usize) -> Schedule {
+        Schedule {
+            claims: Arc::new(Claims::new(window)),
+            ..Schedule::constant(rate)
+        }
+    }
+
+    #[test]
+    fn tickets_are_taken_once_even_across_laps_of_the_window() {
+        let claims = Claims::new(4);
+        assert!(claims.take(1));
+        assert!(!claims.take(1));
+        assert!(claims.take(5));
+        assert!(!claims.take(1));
+        assert!(claims.take(2));
+    }
+
+    #[test]
+    fn claims_hand_out_tickets_in_order_while_on_time() {
+        let schedule = Schedule::constant(10.0);
+        let sequence = AtomicU64::new(0);
+        let epoch = Instant::now();
+        for expected in 0..3 {
+            let ticket = schedule.claim(&sequence, epoch);
+            assert_eq!((ticket.n, ticket.stolen), (expected, false));
+        }
+    }
+
+    #[test]
+    fn overdue_tickets_are_stolen_from_their_owner() {
+        let schedule = Schedule::constant(1000.0);
+        let sequence = AtomicU64::new(0);
+        let epoch = Instant::now() - Duration::from_secs(1);
+        let late = schedule.claim(&sequence, epoch);
+        assert_eq!((late.n, late.stolen), (0, false));
+
+        let thief = schedule.claim(&sequence, epoch);
+        assert_eq!((thief.n, thief.stolen), (0, true));
+
+        let recorder = Recorder::new(Arc::new(Metrics::default()), None);
+        let owner = block_on(schedule.wait(&late, epoch, None, &recorder));
+        assert!(matches!(owner, Slot::Taken));
+        let stolen = block_on(schedule.wait(&thief, epoch, None, &recorder));
+        assert!(matches!(stolen, Slot::Due(_)));
+    }
+
+    #[test]
+    fn a_full_window_steals_instead_of_reusing_live_slots() {
+        let schedule = windowed(0.001, 4);
+        let sequence = AtomicU64::new(0);
+        let epoch = Instant::now();
+        for expected in 0..4 {
+            assert_eq!(schedule.claim(&sequence, epoch).n, expected);
+        }
+
+        let thief = schedule.claim(&sequence, epoch);
+        assert_eq!((thief.n, thief.stolen), (0, true));
+        assert!(!schedule.claims.take(0));
+        assert_eq!(schedule.claim(&sequence, epoch).n, 4);
+        assert!(schedule.claims.take(4));
+        assert!(schedule.claims.take(3));
+    }
+
+    fn offsets(schedule: &Schedule, tickets: &[u64]) -> Vec<Option<Duration>> {
+        tickets.iter().map(|&n| schedule.offset(n)).collect()
+    }
+
+    #[test]
+    fn constant_and_token_bucket_offsets() {
+        assert_eq!(
+            offsets(&Schedule::constant(4.0), &[0, 2, 8]),
+            [0, 500, 2000].map(|ms| Some(Duration::from_millis(ms)))
+        );
+        assert_eq!(
+            offsets(&Schedule::token_bucket(10.0, 5), &[0, 4, 5, 12]),
+            [0, 0, 500, 1000].map(|ms| Some(Duration::from_millis(ms)))
+        );
+    }
+
+    #[test]
+    fn curves_skip_idle_segments_and_close_on_a_final_zero_rate() {
+        let curve = RateCurve::new([
+            (Duration::ZERO, 10.0),
+            (Duration::from_secs(1), 0.0),
+            (Duration::from_secs(2), 20.0),
+        ])
+        .unwrap();
+        assert_eq!(
+            offsets(&Schedule::curve(&curve), &[0, 5, 10, 15]),
+            [0, 500, 2000, 2250].map(|ms| Some(Duration::from_millis(ms)))
+        );
+
+        let ending =
+            RateCurve::new([(Duration::ZERO, 10.0), (Duration::from_secs(1), 0.0)]).unwrap();
+        assert_eq!(
+            offsets(&Schedule::curve(&ending), &[9, 10]),
+            [Some(Duration::from_millis(900)), None]
+        );
+    }
+
+    #[test]
+    fn replays_loop_with_one_average_gap_between_laps() {
+        let ndjson = [0, 100_000, 200_000]
+            .map(|us| format!(r#"{{"offset_us":{us},"method":"GET","path":"/"}}"#))
+            .join("\n");
+        let recording = Arc::new(Recording::from_reader(ndjson.as_bytes()).unwrap());
+
+        let lap = Duration::from_millis(200) + Duration::from_millis(200) / 3;
+        assert_eq!(
+            offsets(&Schedule::replay(recording.clone(), 1.0), &[1, 3, 4]),
+            [
+                Duration::from_millis(100),
+                lap,
+                lap + Duration::from_millis(100)
+            ]
+            .map(Some)
+        );
+        assert_eq!(
+            offsets(&Schedule::replay(recording, 2.0), &[2]),
+            [Some(Duration::from_millis(100))]
+        );
+    }
+
+    #[test]
+    fn due_counts_the_tickets_scheduled_by_now() {
+        let schedule = Schedule::constant(10.0);
+        assert_eq!(schedule.due(0, Duration::from_secs(1)), 11);
+        assert_eq!(schedule.due(4, Duration::from_millis(450)), 5);
+        assert_eq!(schedule.due(20, Duration::from_secs(1)), 20);
+        assert_eq!(schedule.due(0, Duration::ZERO), 1);
+    }
+}
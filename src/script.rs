@@ -0,0 +1,44 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use hyper::{Body, Request};
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Failed;
+
+#[derive(Debug, Default)]
+pub(crate) struct ScriptErrors(AtomicUsize);
+
+impl ScriptErrors {
+    pub(crate) fn record(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn failed(&self) -> Request<Body> {
+        self.record();
+        let mut req = Request::new(Body::empty());
+        req.extensions_mut().insert(Failed);
+        req
+    }
+
+    pub(crate) fn count(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+pub(crate) fn failed(req: &Request<Body>) -> bool {
+    req.extensions().get::<Failed>().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_requests_are_marked_and_counted() {
+        let errors = ScriptErrors::default();
+        assert!(failed(&errors.failed()));
+        assert!(!failed(&Request::new(Body::empty())));
+        errors.record();
+        assert_eq!(errors.count(), 2);
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    fmt::Display,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use futures::{stream, StreamExt};
+use hyper::{body, Body, Request, StatusCode, Uri};
+use serde::{Deserialize, Serialize};
+
+use crate::{feed::Feed, resolve::HttpClient};
+
+const DEFAULT_CONCURRENCY: usize = 10;
+const LEAKED_SHOWN: usize = 5;
+
+type SeedRequest = Arc<dyn Fn(&Uri, &str) -> Request<Body> + Send + Sync + 'static>;
+type ExtractId = Arc<dyn Fn(&str, &[u8]) -> Option<String> + Send + Sync + 'static>;
+
+#[derive(Clone)]
+pub struct Seed {
+    feed: Feed,
+    request: SeedRequest,
+    extract: ExtractId,
+    verify: Option<SeedRequest>,
+    concurrency: usize,
+}
+
+impl Seed {
+    pub fn new(
+        feed: Feed,
+        request: impl Fn(&Uri, &str) -> Request<Body> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            feed,
+            request: Arc::new(request),
+            extract: Arc::new(|value, _| Some(value.to_string())),
+            verify: None,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+
+    pub fn id_from(
+        self,
+        extract: impl Fn(&[u8]) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            extract: Arc::new(move |_, body| extract(body)),
+            ..self
+        }
+    }
+
+    pub fn verify_cleanup(
+        self,
+        request: impl Fn(&Uri, &str) -> Request<Body> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            verify: Some(Arc::new(request)),
+            ..self
+        }
+    }
+
+    pub fn concurrency(self, concurrency: usize) -> Self {
+        Self {
+            concurrency: concurrency.max(1),
+            ..self
+        }
+    }
+
+    pub(crate) fn run(&self, uri: &Uri, http: HttpClient) -> Seeded {
+        let start = Instant::now();
+        let ids = runtime().block_on(
+            stream::iter(self.feed.values().to_vec())
+                .map(|value| {
+                    let req = (self.request)(uri, &value);
+                    let http = http.clone();
+                    async move {
+                        let res = http.request(req).await.ok()?;
+                        if !res.status().is_success() {
+                            return None;
+                        }
+                        let body = body::to_bytes(res.into_body()).await.ok()?;
+                        (self.extract)(&value, &body)
+                    }
+                })
+                .buffered(self.concurrency)
+                .collect::<Vec<_>>(),
+        );
+
+        let failed = ids.iter().filter(|id| id.is_none()).count();
+        let ids = ids.into_iter().flatten().collect::<Vec<_>>();
+        Seeded {
+            created: ids.len(),
+            failed,
+            elapsed: start.elapsed(),
+            ids: (!ids.is_empty()).then(|| Feed::new(ids)),
+        }
+    }
+
+    pub(crate) fn verify(&self, uri: &Uri, http: HttpClient, ids: &Feed) -> Option<CleanupReport> {
+        let verify = self.verify.as_ref()?;
+        let statuses = runtime().block_on(
+            stream::iter(ids.values().to_vec())
+                .map(|id| {
+                    let req = verify(uri, &id);
+                    let http = http.clone();
+                    async move {
+                        let status = http.request(req).await.ok().map(|res| res.status());
+                        (id, status)
+                    }
+                })
+                .buffered(self.concurrency)
+                .collect::<Vec<_>>(),
+        );
+
+        let mut report = CleanupReport {
+            checked: statuses.len(),
+            ..CleanupReport::default()
+        };
+        for (id, status) in statuses {
+            match status {
+                Some(StatusCode::NOT_FOUND | StatusCode::GONE) => {}
+                Some(_) => report.leaked.push(id.to_string()),
+                None => report.unverified += 1,
+            }
+        }
+        Some(report)
+    }
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CleanupReport {
+    checked: usize,
+    leaked: Vec<String>,
+    unverified: usize,
+}
+
+impl CleanupReport {
+    pub fn checked_count(&self) -> usize {
+        self.checked
+    }
+
+    pub fn leaked(&self) -> &[String] {
+        &self.leaked
+    }
+
+    pub fn unverified_count(&self) -> usize {
+        self.unverified
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.leaked.is_empty() && self.unverified == 0
+    }
+}
+
+impl Display for CleanupReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_clean() {
+            return write!(f, "{} resources checked, all removed", self.checked);
+        }
+        write!(
+            f,
+            "{} of {} resources still present",
+            self.leaked.len(),
+            self.checked
+        )?;
+        if !self.leaked.is_empty() {
+            let shown = &self.leaked[..self.leaked.len().min(LEAKED_SHOWN)];
+            write!(f, " ({}", shown.join(", "))?;
+            if self.leaked.len() > LEAKED_SHOWN {
+                write!(f, ", ...")?;
+            }
+            write!(f, ")")?;
+        }
+        if self.unverified > 0 {
+            write!(f, ", {} could not be checked", self.unverified)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct Seeded {
+    pub(crate) created: usize,
+    pub(crate) failed: usize,
+    pub(crate) elapsed: Duration,
+    pub(crate) ids: Option<Feed>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cleanup_reports_list_a_few_leaks_and_unchecked_ids() {
+        let clean = CleanupReport {
+            checked: 3,
+            ..CleanupReport::default()
+        };
+        assert!(clean.is_clean());
+        assert_eq!(clean.to_string(), "3 resources checked, all removed");
+
+        let leaky = CleanupReport {
+            checked: 10,
+            leaked: (1..=6).map(|id| id.to_string()).collect(),
+            unverified: 2,
+        };
+        assert!(!leaky.is_clean());
+        assert_eq!(
+            leaky.to_string(),
+            "6 of 10 resources still present (1, 2, 3, 4, 5, ...), 2 could not be checked"
+        );
+
+        let unchecked = CleanupReport {
+            checked: 1,
+            unverified: 1,
+            ..CleanupReport::default()
+        };
+        assert_eq!(
+            unchecked.to_string(),
+            "0 of 1 resources still present, 1 could not be checked"
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct Counter(Arc<AtomicU64>);
+
+impl Counter {
+    pub fn increment(&self) -> u64 {
+        self.add(1)
+    }
+
+    pub fn add(&self, n: u64) -> u64 {
+        self.0.fetch_add(n, Ordering::Relaxed) + n
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Gauge(Arc<AtomicI64>);
+
+impl Gauge {
+    pub fn set(&self, value: i64) {
+        self.0.store(value, Ordering::Relaxed);
+    }
+
+    pub fn add(&self, n: i64) -> i64 {
+        self.0.fetch_add(n, Ordering::Relaxed) + n
+    }
+
+    pub fn sub(&self, n: i64) -> i64 {
+        self.0.fetch_sub(n, Ordering::Relaxed) - n
+    }
+
+    pub fn get(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    counters: Mutex<HashMap<String, Counter>>,
+    gauges: Mutex<HashMap<String, Gauge>>,
+    ids: Mutex<HashMap<String, Counter>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Shared(Arc<Inner>);
+
+impl Shared {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn counter(&self, name: &str) -> Counter {
+        let mut counters = self.0.counters.lock().unwrap();
+        counters.entry(name.to_string()).or_default().clone()
+    }
+
+    pub fn gauge(&self, name: &str) -> Gauge {
+        let mut gauges = self.0.gauges.lock().unwrap();
+        gauges.entry(name.to_string()).or_default().clone()
+    }
+
+    pub fn next_id(&self, name: &str) -> u64 {
+        let mut ids = self.0.ids.lock().unwrap();
+        ids.entry(name.to_string()).or_default().increment() - 1
+    }
+
+    pub(crate) fn counters(&self) -> BTreeMap<String, u64> {
+        let counters = self.0.counters.lock().unwrap();
+        counters
+            .iter()
+            .map(|(name, counter)| (name.clone(), counter.get()))
+            .collect()
+    }
+
+    pub(crate) fn gauges(&self) -> BTreeMap<String, i64> {
+        let gauges = self.0.gauges.lock().unwrap();
+        gauges
+            .iter()
+            .map(|(name, gauge)| (name.clone(), gauge.get()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_state_is_shared_between_clones() {
+        let shared = Shared::new();
+        let other = shared.clone();
+        assert_eq!(shared.counter("logins").increment(), 1);
+        assert_eq!(other.counter("logins").add(2), 3);
+        other.gauge("open").set(5);
+        assert_eq!(shared.gauge("open").sub(2), 3);
+        assert_eq!(shared.gauge("open").add(-4), -1);
+
+        assert_eq!(
+            shared.counters(),
+            BTreeMap::from([("logins".to_string(), 3)])
+        );
+        assert_eq!(other.gauges(), BTreeMap::from([("open".to_string(), -1)]));
+    }
+
+    #[test]
+    fn ids_count_from_zero_per_name() {
+        let shared = Shared::new();
+        assert_eq!(shared.next_id("order"), 0);
+        assert_eq!(shared.next_id("order"), 1);
+        assert_eq!(shared.next_id("cart"), 0);
+        assert!(shared.counters().is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+use std::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Instant,
+};
+
+use hyper::{body::HttpBody, header::CONTENT_TYPE, Body, Response, Uri};
+use tokio::time::timeout_at;
+
+#[cfg(any(feature = "lua", feature = "rhai"))]
+use crate::script;
+use crate::{
+    clock::Clock,
+    metrics::Recorder,
+    resolve::HttpClient,
+    result::Outcome,
+    user::{UserContext, VirtualUser},
+};
+
+const FIRST_EVENT_LABEL: &str = "sse first event";
+
+pub(crate) struct Session {
+    pub(crate) http: HttpClient,
+    pub(crate) epoch: Instant,
+    pub(crate) deadline: Instant,
+    pub(crate) clock: Clock,
+    pub(crate) recorder: Recorder,
+}
+
+impl Session {
+    pub(crate) async fn run(
+        &self,
+        uri: &Uri,
+        user: &mut dyn VirtualUser,
+        ctx: &mut UserContext,
+        running: &AtomicBool,
+        sequence: &AtomicU64,
+    ) {
+        while running.load(Ordering::Relaxed) {
+            ctx.sequence = sequence.fetch_add(1, Ordering::Relaxed);
+            let Some(req) = user.next_request(uri, ctx) else {
+                break;
+            };
+            #[cfg(any(feature = "lua", feature = "rhai"))]
+            if script::failed(&req) {
+                self.recorder.record_failed(self.epoch.elapsed());
+                ctx.iteration += 1;
+                tokio::task::yield_now().await;
+                continue;
+            }
+
+            let start = Instant::now();
+            let timer = self.clock.now();
+            let res = match timeout_at(self.deadline.into(), self.http.request(req)).await {
+                Ok(Ok(res)) if is_event_stream(&res) => res,
+                Ok(Ok(_)) => {
+                    self.first_event(start, timer, Outcome::HttpError);
+                    continue;
+                }
+                Ok(Err(err)) => {
+                    self.first_event(start, timer, Outcome::from_error(&err));
+                    continue;
+                }
+                Err(_) => break,
+            };
+
+            let mut body = res.into_body();
+            let mut events = Events::default();
+            let mut last: Option<(Instant, u64)> = None;
+            let dropped = loop {
+                let chunk = match timeout_at(self.deadline.into(), body.data()).await {
+                    Ok(Some(Ok(chunk))) => chunk,
+                    Ok(Some(Err(_)) | None) => break true,
+                    Err(_) => break false,
+                };
+                for _ in 0..events.feed(&chunk) {
+                    match last {
+                        None => self.first_event(start, timer, Outcome::Success),
+                        Some((at, timer)) => self.recorder.record(
+                            at.saturating_duration_since(self.epoch),
+                            self.clock.elapsed(timer),
+                            Outcome::Success,
+                        ),
+                    }
+                    last = Some((Instant::now(), self.clock.now()));
+                }
+                if !running.load(Ordering::Relaxed) {
+                    break false;
+                }
+            };
+
+            if dropped {
+                if last.is_none() {
+                    self.first_event(start, timer, Outcome::TcpError);
+                }
+                self.recorder
+                    .record_drop(Instant::now().saturating_duration_since(self.epoch));
+            }
+            ctx.iteration += 1;
+        }
+    }
+
+    fn first_event(&self, start: Instant, timer: u64, outcome: Outcome) {
+        self.recorder.record_connect(
+            FIRST_EVENT_LABEL,
+            start.saturating_duration_since(self.epoch),
+            self.clock.elapsed(timer),
+            outcome,
+        );
+    }
+}
+
+fn is_event_stream(res: &Response<Body>) -> bool {
+    res.status().is_success()
+        && res
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.trim_start().starts_with("text/event-stream"))
+}
+
+#[derive(Default)]
+struct Events {
+    line: Vec<u8>,
+    data: bool,
+}
+
+impl Events {
+    fn feed(&mut self, chunk: &[u8]) -> usize {
+        let mut dispatched = 0;
+        for &byte in chunk {
+            if byte != b'\n' {
+                self.line.push(byte);
+                continue;
+            }
+            if self.line.last() == Some(&b'\r') {
+                self.line.pop();
+            }
+            if self.line.is_empty() {
+                if self.data {
+                    dispatched += 1;
+                }
+                self.data = false;
+            } else if self.line == b"data" || self.line.starts_with(b"data:") {
+                self.data = true;
+            }
+            self.line.clear();
+        }
+        dispatched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_dispatch_on_blank_lines_after_data() {
+        let mut events = Events::default();
+        assert_eq!(events.feed(b"id: 1\ndata: a\n"), 0);
+        assert_eq!(events.feed(b"\n: comment\n\ndata\r\n\r\n"), 2);
+        assert_eq!(events.feed(b"data: split"), 0);
+        assert_eq!(events.feed(b"\ndata: more\n\nevent: ping\n\n"), 1);
+    }
+
+    #[test]
+    fn only_successful_event_streams_are_consumed() {
+        let stream = |status: u16, content_type: &str| {
+            let res = Response::builder()
+                .status(status)
+                .header(CONTENT_TYPE, content_type)
+                .body(Body::empty())
+                .unwrap();
+            is_event_stream(&res)
+        };
+        assert!(stream(200, "text/event-stream; charset=utf-8"));
+        assert!(!stream(200, "application/json"));
+        assert!(!stream(503, "text/event-stream"));
+    }
+}
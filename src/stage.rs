@@ -0,0 +1,258 @@
+use std::{error::Error, fmt::Display, mem, time::Duration};
+
+use crate::{result::BenchmarkResult, SwarmBuilder};
+
+#[derive(Default)]
+pub struct Stages {
+    stages: Vec<(String, SwarmBuilder)>,
+    max_error_rate: Option<f64>,
+    max_latency: Vec<(f64, Duration)>,
+}
+
+impl Stages {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stage(mut self, name: impl Into<String>, swarm: SwarmBuilder) -> Self {
+        self.stages.push((name.into(), swarm));
+        self
+    }
+
+    pub fn max_error_rate(self, rate: f64) -> Self {
+        Self {
+            max_error_rate: Some(rate),
+            ..self
+        }
+    }
+
+    pub fn max_latency(mut self, quantile: f64, limit: Duration) -> Self {
+        self.max_latency.push((quantile, limit));
+        self
+    }
+
+    pub fn zerg(mut self) -> Result<StagesResult, Box<dyn Error + Send + Sync>> {
+        if self.stages.is_empty() {
+            return Err("no stages configured".into());
+        }
+        if self
+            .max_latency
+            .iter()
+            .any(|(quantile, _)| !(0.0..=1.0).contains(quantile))
+        {
+            return Err("latency thresholds need a quantile between 0 and 1".into());
+        }
+        let mut swarms = Vec::with_capacity(self.stages.len());
+        for (name, swarm) in mem::take(&mut self.stages) {
+            if swarms.iter().any(|(existing, _)| *existing == name) {
+                return Err(format!("duplicate stage name: {name}").into());
+            }
+            let swarm = swarm
+                .build()
+                .map_err(|err| format!("stage {name}: {err}"))?;
+            swarms.push((name, swarm));
+        }
+
+        let mut stages = Vec::with_capacity(swarms.len());
+        for (name, swarm) in swarms {
+            let result = swarm.zerg();
+            let failures = self.check(&result);
+            let passed = failures.is_empty();
+            stages.push(StageResult {
+                name,
+                result,
+                failures,
+            });
+            if !passed {
+                break;
+            }
+        }
+        Ok(StagesResult { stages })
+    }
+
+    fn check(&self, result: &BenchmarkResult) -> Vec<String> {
+        let mut failures = Vec::new();
+        if let Some(max) = self.max_error_rate {
+            let rate = error_rate(result);
+            if rate > max {
+                failures.push(format!(
+                    "error rate {:.2}% above {:.2}%",
+                    rate * 100.0,
+                    max * 100.0
+                ));
+            }
+        }
+        if !self.max_latency.is_empty() {
+            let percentiles = result.percentiles();
+            for (quantile, limit) in &self.max_latency {
+                let latency = percentiles.percentile(*quantile);
+                if latency > *limit {
+                    failures.push(format!(
+                        "P{} {:.2}ms above {:.2}ms",
+                        quantile * 100.0,
+                        latency.as_secs_f64() * 1000.0,
+                        limit.as_secs_f64() * 1000.0
+                    ));
+                }
+            }
+        }
+        failures
+    }
+}
+
+fn error_rate(result: &BenchmarkResult) -> f64 {
+    let failed = result.http_error_count() + result.tcp_error_count();
+    match result.success_count() + failed {
+        0 => 0.0,
+        total => failed as f64 / total as f64,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StageResult {
+    name: String,
+    result: BenchmarkResult,
+    failures: Vec<String>,
+}
+
+impl StageResult {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn result(&self) -> &BenchmarkResult {
+        &self.result
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    pub fn failures(&self) -> &[String] {
+        &self.failures
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StagesResult {
+    stages: Vec<StageResult>,
+}
+
+impl StagesResult {
+    pub fn stages(&self) -> &[StageResult] {
+        &self.stages
+    }
+
+    pub fn capacity(&self) -> Option<&StageResult> {
+        self.stages.iter().take_while(|stage| stage.passed()).last()
+    }
+
+    pub fn failed(&self) -> Option<&StageResult> {
+        self.stages.iter().find(|stage| !stage.passed())
+    }
+}
+
+impl Display for StagesResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for stage in &self.stages {
+            let result = &stage.result;
+            let percentiles = result.percentiles();
+            write!(
+                f,
+                "Stage {}: {:.2} reqs/sec, {:.2}% errors, P50 {:.2}ms, P99 {:.2}ms, ",
+                stage.name,
+                result.requests_per_second(),
+                error_rate(result) * 100.0,
+                percentiles.percentile(0.50).as_millis() as f64,
+                percentiles.percentile(0.99).as_millis() as f64,
+            )?;
+            match stage.passed() {
+                true => writeln!(f, "passed")?,
+                false => writeln!(f, "failed ({})", stage.failures.join(", "))?,
+            }
+        }
+        match self.capacity() {
+            Some(stage) => writeln!(
+                f,
+                "Capacity:  {} at {:.2} reqs/sec",
+                stage.name,
+                stage.result.requests_per_second()
+            ),
+            None => writeln!(f, "Capacity:  no stage met the thresholds"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{result::Outcome, swarm};
+
+    fn result(success: usize, failed: usize, ms: u64) -> BenchmarkResult {
+        let mut result = BenchmarkResult::default();
+        for _ in 0..success {
+            result.record(Duration::ZERO, Duration::from_millis(ms), Outcome::Success);
+        }
+        for _ in 0..failed {
+            result.record(Duration::ZERO, Duration::from_millis(ms), Outcome::TcpError);
+        }
+        result
+    }
+
+    #[test]
+    fn thresholds_report_every_breach() {
+        let stages = Stages::new()
+            .max_error_rate(0.1)
+            .max_latency(0.99, Duration::from_millis(50));
+        assert!(stages.check(&result(10, 0, 20)).is_empty());
+        assert_eq!(
+            stages.check(&result(8, 2, 100)),
+            [
+                "error rate 20.00% above 10.00%".to_string(),
+                "P99 100.00ms above 50.00ms".to_string(),
+            ]
+        );
+        assert_eq!(error_rate(&BenchmarkResult::default()), 0.0);
+    }
+
+    #[test]
+    fn capacity_is_the_last_stage_before_the_first_failure() {
+        let stage = |name: &str, failures: &[&str]| StageResult {
+            name: name.into(),
+            result: BenchmarkResult::default(),
+            failures: failures.iter().map(|f| f.to_string()).collect(),
+        };
+        let result = StagesResult {
+            stages: vec![stage("10", &[]), stage("20", &[]), stage("40", &["slow"])],
+        };
+        assert_eq!(result.capacity().map(StageResult::name), Some("20"));
+        assert_eq!(result.failed().map(StageResult::name), Some("40"));
+
+        let none = StagesResult {
+            stages: vec![stage("10", &["slow"])],
+        };
+        assert!(none.capacity().is_none());
+        assert!(none
+            .to_string()
+            .ends_with("Capacity:  no stage met the thresholds\n"));
+    }
+
+    #[test]
+    fn stages_are_checked_before_anything_runs() {
+        let error = |stages: Stages| stages.zerg().err().unwrap().to_string();
+        let target = || swarm("http://127.0.0.1:9/");
+        assert_eq!(error(Stages::new()), "no stages configured");
+        assert_eq!(
+            error(
+                Stages::new()
+                    .stage("a", target())
+                    .max_latency(99.0, Duration::ZERO)
+            ),
+            "latency thresholds need a quantile between 0 and 1"
+        );
+        assert_eq!(
+            error(Stages::new().stage("a", target()).stage("a", target())),
+            "duplicate stage name: a"
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use std::{fmt::Display, ops::AddAssign, time::Duration};
+
+pub(crate) const RESOLUTION: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Http,
+    Tcp,
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorKind::Http => write!(f, "http"),
+            ErrorKind::Tcp => write!(f, "tcp"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bucket {
+    pub(crate) success: usize,
+    pub(crate) http_error: usize,
+    pub(crate) tcp_error: usize,
+    pub(crate) queue_depth: u64,
+}
+
+impl Bucket {
+    pub fn success_count(&self) -> usize {
+        self.success
+    }
+
+    pub fn http_error_count(&self) -> usize {
+        self.http_error
+    }
+
+    pub fn tcp_error_count(&self) -> usize {
+        self.tcp_error
+    }
+
+    pub fn queue_depth(&self) -> u64 {
+        self.queue_depth
+    }
+
+    pub fn total_count(&self) -> usize {
+        self.success + self.http_error + self.tcp_error
+    }
+
+    pub fn error_rate(&self) -> f64 {
+        match self.total_count() {
+            0 => 0.0,
+            n => (self.http_error + self.tcp_error) as f64 / n as f64,
+        }
+    }
+}
+
+impl AddAssign<Bucket> for Bucket {
+    fn add_assign(&mut self, rhs: Bucket) {
+        self.success += rhs.success;
+        self.http_error += rhs.http_error;
+        self.tcp_error += rhs.tcp_error;
+        self.queue_depth = self.queue_depth.max(rhs.queue_depth);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    buckets: Vec<Bucket>,
+}
+
+impl Timeline {
+    pub(crate) fn from_buckets(buckets: Vec<Bucket>) -> Self {
+        Self { buckets }
+    }
+
+    pub(crate) fn bucket_mut(&mut self, offset: Duration) -> &mut Bucket {
+        let index = (offset.as_nanos() / RESOLUTION.as_nanos()) as usize;
+        if index >= self.buckets.len() {
+            self.buckets.resize(index + 1, Bucket::default());
+        }
+        &mut self.buckets[index]
+    }
+
+    pub(crate) fn shift(&mut self, by: Duration) {
+        let buckets = (by.as_secs_f64() / RESOLUTION.as_secs_f64()).round() as usize;
+        if buckets > 0 && !self.buckets.is_empty() {
+            self.buckets
+                .splice(0..0, std::iter::repeat_n(Bucket::default(), buckets));
+        }
+    }
+
+    pub fn resolution(&self) -> Duration {
+        RESOLUTION
+    }
+
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, Bucket)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(i, bucket)| (RESOLUTION * i as u32, *bucket))
+    }
+
+    pub fn error_bursts(&self, threshold: f64) -> Vec<ErrorBurst> {
+        let mut bursts = Vec::new();
+        let mut current: Option<(usize, Bucket)> = None;
+
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            if bucket.total_count() > 0 && bucket.error_rate() > threshold {
+                match current.as_mut() {
+                    Some((_, total)) => *total += *bucket,
+                    None => current = Some((i, *bucket)),
+                }
+            } else if let Some((start, total)) = current.take() {
+                bursts.push(ErrorBurst::new(start, i, total));
+            }
+        }
+
+        if let Some((start, total)) = current {
+            bursts.push(ErrorBurst::new(start, self.buckets.len(), total));
+        }
+
+        bursts
+    }
+}
+
+impl AddAssign<Timeline> for Timeline {
+    fn add_assign(&mut self, rhs: Timeline) {
+        if self.buckets.len() < rhs.buckets.len() {
+            self.buckets.resize(rhs.buckets.len(), Bucket::default());
+        }
+        for (bucket, other) in self.buckets.iter_mut().zip(rhs.buckets) {
+            *bucket += other;
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ErrorBurst {
+    start: Duration,
+    end: Duration,
+    kind: ErrorKind,
+    errors: usize,
+}
+
+impl ErrorBurst {
+    fn new(start: usize, end: usize, total: Bucket) -> Self {
+        let kind = if total.tcp_error > total.http_error {
+            ErrorKind::Tcp
+        } else {
+            ErrorKind::Http
+        };
+        Self {
+            start: RESOLUTION * start as u32,
+            end: RESOLUTION * end as u32,
+            kind,
+            errors: total.http_error + total.tcp_error,
+        }
+    }
+
+    pub fn start(&self) -> Duration {
+        self.start
+    }
+
+    pub fn end(&self) -> Duration {
+        self.end
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+
+    pub fn dominant_error(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.errors
+    }
+}
+
+impl Display for ErrorBurst {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} errors burst for {}s at t={}s ({} errors)",
+            self.kind,
+            self.duration().as_secs(),
+            self.start.as_secs(),
+            self.errors
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bucket(success: usize, http_error: usize, tcp_error: usize) -> Bucket {
+        Bucket {
+            success,
+            http_error,
+            tcp_error,
+            queue_depth: 0,
+        }
+    }
+
+    #[test]
+    fn bursts_span_consecutive_buckets_above_the_threshold() {
+        let timeline = Timeline::from_buckets(vec![
+            bucket(10, 0, 0),
+            bucket(5, 5, 0),
+            bucket(2, 0, 8),
+            bucket(0, 0, 0),
+            bucket(1, 9, 0),
+        ]);
+        let bursts = timeline.error_bursts(0.2);
+        assert_eq!(bursts.len(), 2);
+        assert_eq!(
+            (bursts[0].start(), bursts[0].end()),
+            (RESOLUTION, RESOLUTION * 3)
+        );
+        assert_eq!(bursts[0].dominant_error(), ErrorKind::Tcp);
+        assert_eq!(bursts[0].error_count(), 13);
+        assert_eq!(
+            bursts[0].to_string(),
+            "tcp errors burst for 2s at t=1s (13 errors)"
+        );
+        assert_eq!(bursts[1].duration(), RESOLUTION);
+        assert_eq!(bursts[1].dominant_error(), ErrorKind::Http);
+    }
+
+    #[test]
+    fn timelines_grow_shift_and_add_bucketwise() {
+        let mut timeline = Timeline::default();
+        timeline.bucket_mut(Duration::from_millis(2500)).success += 1;
+        assert_eq!(timeline.buckets().count(), 3);
+
+        timeline.shift(Duration::from_secs(2));
+        let (offset, last) = timeline.buckets().last().unwrap();
+        assert_eq!((offset, last.success_count()), (RESOLUTION * 4, 1));
+
+        let mut other = Timeline::from_buckets(vec![Bucket {
+            queue_depth: 7,
+            ..bucket(1, 1, 0)
+        }]);
+        other += timeline;
+        let buckets: Vec<_> = other.buckets().map(|(_, bucket)| bucket).collect();
+        assert_eq!(buckets.len(), 5);
+        assert_eq!((buckets[0].total_count(), buckets[0].queue_depth()), (2, 7));
+        assert_eq!(buckets[0].error_rate(), 0.5);
+        assert_eq!(Bucket::default().error_rate(), 0.0);
+    }
+}
@@ -0,0 +1,461 @@
+use std::{error::Error, net::SocketAddr, sync::Arc, time::Duration};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use ::ring::digest::{digest, SHA256};
+use hyper::Uri;
+use rustls::{
+    client::{
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        Resumption, WebPkiServerVerifier,
+    },
+    crypto::{ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider},
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+    version, ClientConfig, ClientConnection, DigitallySignedStruct, HandshakeKind, ProtocolVersion,
+    RootCertStore, SignatureScheme,
+};
+use tokio::{io::AsyncReadExt, net::TcpStream, time::timeout};
+use tokio_rustls::{client::TlsStream, TlsConnector};
+use webpki::EndEntityCert;
+
+use crate::{
+    identity::TlsFingerprint,
+    operation::Operation,
+    proxy::ProxyHeader,
+    result::Outcome,
+    session::{TlsOptions, TlsSession, TlsStats},
+    transport::Protocol,
+};
+
+const TICKET_WAIT: Duration = Duration::from_millis(100);
+
+pub(crate) fn client_config(
+    options: &TlsOptions,
+    protocol: Protocol,
+) -> Result<Arc<ClientConfig>, Box<dyn Error + Send + Sync>> {
+    if let Some(config) = &options.config {
+        if !options.pins.is_empty() {
+            return Err("certificate pinning cannot be combined with a custom TLS config".into());
+        }
+        if options.fingerprint.is_some() {
+            return Err(
+                "TLS fingerprint options cannot be combined with a custom TLS config".into(),
+            );
+        }
+        let mut config = ClientConfig::clone(config);
+        if config.alpn_protocols.is_empty() {
+            config.alpn_protocols = alpn(protocol);
+        }
+        return Ok(Arc::new(config));
+    }
+
+    let mut provider = ring::default_provider();
+    let builder = match &options.fingerprint {
+        Some(fingerprint) => {
+            fingerprint.apply(&mut provider)?;
+            let provider = Arc::new(provider.clone());
+            match fingerprint.tls13_only {
+                true => ClientConfig::builder_with_provider(provider)
+                    .with_protocol_versions(&[&version::TLS13])?,
+                false => ClientConfig::builder_with_provider(provider)
+                    .with_safe_default_protocol_versions()?,
+            }
+        }
+        None => ClientConfig::builder_with_provider(Arc::new(provider.clone()))
+            .with_safe_default_protocol_versions()?,
+    };
+    let provider = Arc::new(provider);
+
+    let verifier: Arc<dyn ServerCertVerifier> = if options.accept_invalid_certs {
+        Arc::new(AcceptAnyCert(provider.clone()))
+    } else {
+        let mut roots = RootCertStore::empty();
+        match &options.ca_bundle {
+            Some(path) => {
+                for cert in CertificateDer::pem_file_iter(path)? {
+                    roots.add(cert?)?;
+                }
+            }
+            None => {
+                roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+            }
+        }
+        WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone()).build()?
+    };
+
+    let verifier: Arc<dyn ServerCertVerifier> = match options.pins.is_empty() {
+        true => verifier,
+        false => Arc::new(PinnedCert {
+            inner: verifier,
+            pins: options
+                .pins
+                .iter()
+                .map(|pin| STANDARD.decode(pin))
+                .collect::<Result<_, _>>()?,
+        }),
+    };
+
+    let builder = builder
+        .dangerous()
+        .with_custom_certificate_verifier(verifier);
+    let mut config = match &options.identity {
+        Some(identity) => {
+            let (cert, key) = identity.pem()?;
+            builder.with_client_auth_cert(
+                CertificateDer::pem_slice_iter(&cert).collect::<Result<_, _>>()?,
+                PrivateKeyDer::from_pem_slice(&key)?,
+            )?
+        }
+        None => builder.with_no_client_auth(),
+    };
+    config.resumption = match options.resumption {
+        true => Resumption::default(),
+        false => Resumption::disabled(),
+    };
+    config.alpn_protocols = alpn(protocol);
+    Ok(Arc::new(config))
+}
+
+impl TlsFingerprint {
+    fn apply(&self, provider: &mut CryptoProvider) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if !self.cipher_suites.is_empty() {
+            provider.cipher_suites = self
+                .cipher_suites
+                .iter()
+                .map(|name| {
+                    provider
+                        .cipher_suites
+                        .iter()
+                        .find(|suite| format!("{:?}", suite.suite()) == *name)
+                        .copied()
+                        .ok_or_else(|| format!("unsupported cipher suite {name}"))
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        if !self.key_exchange_groups.is_empty() {
+            provider.kx_groups = self
+                .key_exchange_groups
+                .iter()
+                .map(|name| {
+                    provider
+                        .kx_groups
+                        .iter()
+                        .find(|group| format!("{:?}", group.name()) == *name)
+                        .copied()
+                        .ok_or_else(|| format!("unsupported key exchange group {name}"))
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        Ok(())
+    }
+}
+
+fn alpn(protocol: Protocol) -> Vec<Vec<u8>> {
+    vec![protocol.alpn().as_bytes().to_vec()]
+}
+
+#[derive(Clone)]
+pub(crate) struct Https {
+    connector: TlsConnector,
+    server_name: ServerName<'static>,
+    stats: TlsStats,
+}
+
+impl Https {
+    pub(crate) fn new(
+        uri: &Uri,
+        options: &TlsOptions,
+        protocol: Protocol,
+        stats: TlsStats,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(Self {
+            connector: TlsConnector::from(client_config(options, protocol)?),
+            server_name: server_name(uri)?,
+            stats,
+        })
+    }
+
+    pub(crate) async fn connect(
+        &self,
+        stream: TcpStream,
+    ) -> Result<TlsStream<TcpStream>, Box<dyn Error + Send + Sync>> {
+        let stream = self
+            .connector
+            .connect(self.server_name.clone(), stream)
+            .await?;
+        let connection = stream.get_ref().1;
+        record(&self.stats, connection);
+        self.stats.negotiated(connection.alpn_protocol());
+        Ok(stream)
+    }
+}
+
+pub(crate) fn server_name(uri: &Uri) -> Result<ServerName<'static>, Box<dyn Error + Send + Sync>> {
+    let host = uri.host().ok_or("missing host")?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    Ok(ServerName::try_from(host.to_string())?)
+}
+
+pub(crate) fn handshake(
+    addr: SocketAddr,
+    server_name: ServerName<'static>,
+    config: Arc<ClientConfig>,
+    resumption: bool,
+    proxy: Option<ProxyHeader>,
+    hold: Duration,
+    stats: TlsStats,
+) -> Operation {
+    let hold = match resumption {
+        true => hold.max(TICKET_WAIT),
+        false => hold,
+    };
+    let connector = TlsConnector::from(config);
+    Arc::new(move |_| {
+        let connector = connector.clone();
+        let server_name = server_name.clone();
+        let proxy = proxy.clone();
+        let stats = stats.clone();
+        Box::pin(async move {
+            let mut stream = match TcpStream::connect(addr).await {
+                Ok(stream) => stream,
+                Err(err) => return Outcome::from_error(&err),
+            };
+            stream.set_nodelay(true).ok();
+            if let Some(proxy) = proxy {
+                if proxy.write_to(&mut stream).await.is_err() {
+                    return Outcome::TcpError;
+                }
+            }
+            match connector.connect(server_name, stream).await {
+                Ok(mut stream) => {
+                    record(&stats, stream.get_ref().1);
+                    if !hold.is_zero() {
+                        tokio::spawn(async move {
+                            timeout(hold, stream.read(&mut [0; 1])).await.ok();
+                        });
+                    }
+                    Outcome::Success
+                }
+                Err(_) => Outcome::TlsHandshake,
+            }
+        })
+    })
+}
+
+pub(crate) fn record(stats: &TlsStats, connection: &ClientConnection) {
+    let version = match connection.protocol_version() {
+        Some(ProtocolVersion::TLSv1_2) => "TLSv1.2".to_string(),
+        Some(ProtocolVersion::TLSv1_3) => "TLSv1.3".to_string(),
+        Some(version) => format!("{version:?}"),
+        None => return,
+    };
+    let cipher = connection
+        .negotiated_cipher_suite()
+        .map(|suite| format!("{:?}", suite.suite()))
+        .unwrap_or_default();
+    let alpn = connection
+        .alpn_protocol()
+        .map(|alpn| String::from_utf8_lossy(alpn).into_owned());
+    let resumed = connection.handshake_kind() == Some(HandshakeKind::Resumed);
+
+    stats.record(
+        TlsSession {
+            version,
+            cipher,
+            alpn,
+        },
+        resumed,
+    );
+}
+
+#[derive(Debug)]
+struct PinnedCert {
+    inner: Arc<dyn ServerCertVerifier>,
+    pins: Vec<Vec<u8>>,
+}
+
+impl ServerCertVerifier for PinnedCert {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+
+        let cert = EndEntityCert::try_from(end_entity)
+            .map_err(|err| rustls::Error::General(err.to_string()))?;
+        let spki = digest(&SHA256, &cert.subject_public_key_info());
+        match self.pins.iter().any(|pin| pin == spki.as_ref()) {
+            true => Ok(verified),
+            false => Err(rustls::Error::General("certificate pin mismatch".into())),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+#[derive(Debug)]
+struct AcceptAnyCert(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insecure() -> TlsOptions {
+        TlsOptions {
+            accept_invalid_certs: true,
+            ..TlsOptions::default()
+        }
+    }
+
+    #[test]
+    fn server_names_accept_dns_names_and_bracketed_ips() {
+        let name = |uri: &'static str| server_name(&Uri::from_static(uri));
+        assert!(matches!(
+            name("https://[::1]:8443/").unwrap(),
+            ServerName::IpAddress(_)
+        ));
+        assert_eq!(
+            name("https://example.com/").unwrap(),
+            ServerName::try_from("example.com").unwrap()
+        );
+        assert!(name("/only-a-path").is_err());
+    }
+
+    #[test]
+    fn configs_advertise_the_requested_protocol() {
+        let config = client_config(&insecure(), Protocol::Http2).unwrap();
+        assert_eq!(config.alpn_protocols, [b"h2".to_vec()]);
+
+        let resuming = TlsOptions {
+            resumption: true,
+            ..insecure()
+        };
+        let config = client_config(&resuming, Protocol::Http1).unwrap();
+        assert_eq!(config.alpn_protocols, [b"http/1.1".to_vec()]);
+    }
+
+    #[test]
+    fn custom_configs_keep_their_alpn_and_refuse_pins() {
+        let mut custom = ClientConfig::clone(&client_config(&insecure(), Protocol::Http1).unwrap());
+        custom.alpn_protocols.clear();
+        let options = TlsOptions {
+            config: Some(Arc::new(custom)),
+            ..TlsOptions::default()
+        };
+        let config = client_config(&options, Protocol::Http2).unwrap();
+        assert_eq!(config.alpn_protocols, [b"h2".to_vec()]);
+
+        let pinned = TlsOptions {
+            pins: vec!["AAAA".into()],
+            ..options
+        };
+        assert!(client_config(&pinned, Protocol::Http1).is_err());
+    }
+
+    #[test]
+    fn pins_must_be_base64() {
+        let options = TlsOptions {
+            pins: vec!["not base64!".into()],
+            ..insecure()
+        };
+        assert!(client_config(&options, Protocol::Http1).is_err());
+    }
+
+    #[test]
+    fn fingerprints_narrow_the_provider_by_name() {
+        let fingerprint = TlsFingerprint {
+            cipher_suites: vec!["TLS13_AES_128_GCM_SHA256".into()],
+            key_exchange_groups: vec!["X25519".into()],
+            tls13_only: true,
+        };
+        let mut provider = ring::default_provider();
+        fingerprint.apply(&mut provider).unwrap();
+        assert_eq!(provider.cipher_suites.len(), 1);
+        assert_eq!(provider.kx_groups.len(), 1);
+
+        let unknown = TlsFingerprint {
+            cipher_suites: vec!["TLS_NULL_WITH_NULL_NULL".into()],
+            ..TlsFingerprint::default()
+        };
+        let err = unknown.apply(&mut ring::default_provider()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unsupported cipher suite TLS_NULL_WITH_NULL_NULL"
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use std::{error::Error, future::Future};
+
+use hyper::{body::HttpBody, Body, Request, Response};
+
+#[cfg(feature = "http3")]
+use crate::quic::QuicClient;
+#[cfg(feature = "io-uring")]
+use crate::uring::UringClient;
+use crate::{expect::ContinueClient, resolve::HttpClient};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    #[default]
+    Tokio,
+    #[cfg(feature = "io-uring")]
+    IoUring,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TlsBackend {
+    #[cfg_attr(any(feature = "tls", not(feature = "native-tls")), default)]
+    Rustls,
+    #[cfg_attr(all(feature = "native-tls", not(feature = "tls")), default)]
+    NativeTls,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Protocol {
+    #[default]
+    Http1,
+    Http2,
+    #[cfg(feature = "http3")]
+    Http3,
+}
+
+impl Protocol {
+    #[cfg(feature = "http3")]
+    pub const H3: Self = Self::Http3;
+
+    pub(crate) fn alpn(&self) -> &'static str {
+        match self {
+            Self::Http1 => "http/1.1",
+            Self::Http2 => "h2",
+            #[cfg(feature = "http3")]
+            Self::Http3 => "h3",
+        }
+    }
+}
+
+pub(crate) enum Transport {
+    Hyper(HttpClient),
+    #[cfg(feature = "io-uring")]
+    Uring(UringClient),
+    #[cfg(feature = "http3")]
+    Quic(QuicClient),
+    Buffered(HttpClient),
+    Continue(ContinueClient),
+}
+
+impl Transport {
+    pub(crate) async fn request(
+        &mut self,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        match self {
+            Self::Hyper(http) => Ok(http.request(req).await?),
+            #[cfg(feature = "io-uring")]
+            Self::Uring(uring) => uring.request(req).await,
+            #[cfg(feature = "http3")]
+            Self::Quic(quic) => quic.request(req).await,
+            Self::Buffered(http) => Ok(buffered(http.request(req).await?).await?),
+            Self::Continue(client) => client.request(req).await,
+        }
+    }
+
+    pub(crate) fn reconnect(&mut self, connect: impl Fn() -> HttpClient) {
+        match self {
+            Self::Hyper(http) | Self::Buffered(http) => *http = connect(),
+            #[cfg(feature = "io-uring")]
+            Self::Uring(_) => {}
+            #[cfg(feature = "http3")]
+            Self::Quic(_) => {}
+            Self::Continue(_) => {}
+        }
+    }
+}
+
+async fn buffered(res: Response<Body>) -> Result<Response<Body>, hyper::Error> {
+    let (mut parts, mut body) = res.into_parts();
+    let mut data = Vec::new();
+    while let Some(chunk) = body.data().await {
+        data.extend_from_slice(&chunk?);
+    }
+    if let Some(trailers) = body.trailers().await? {
+        parts.headers.extend(trailers);
+    }
+    Ok(Response::from_parts(parts, Body::from(data)))
+}
+
+pub(crate) enum Runtime {
+    Tokio(tokio::runtime::Runtime),
+    #[cfg(feature = "io-uring")]
+    Uring(tokio_uring::Runtime),
+}
+
+impl Runtime {
+    pub(crate) fn new(backend: Backend) -> Self {
+        match backend {
+            Backend::Tokio => Self::Tokio(
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap(),
+            ),
+            #[cfg(feature = "io-uring")]
+            Backend::IoUring => {
+                Self::Uring(tokio_uring::Runtime::new(&tokio_uring::builder()).unwrap())
+            }
+        }
+    }
+
+    pub(crate) fn block_on<F: Future>(&self, future: F) -> F::Output {
+        match self {
+            Self::Tokio(runtime) => runtime.block_on(future),
+            #[cfg(feature = "io-uring")]
+            Self::Uring(runtime) => runtime.block_on(future),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protocols_advertise_their_alpn_ids() {
+        assert_eq!(Protocol::default().alpn(), "http/1.1");
+        assert_eq!(Protocol::Http2.alpn(), "h2");
+        #[cfg(feature = "http3")]
+        assert_eq!(Protocol::H3.alpn(), "h3");
+    }
+
+    #[test]
+    fn buffered_responses_keep_the_body_and_fold_in_trailers() {
+        let runtime = Runtime::new(Backend::Tokio);
+        let res = runtime.block_on(async {
+            let (mut sender, body) = Body::channel();
+            tokio::spawn(async move {
+                sender.send_data("hello ".into()).await.unwrap();
+                sender.send_data("world".into()).await.unwrap();
+                let mut trailers = hyper::HeaderMap::new();
+                trailers.insert("grpc-status", "0".parse().unwrap());
+                sender.send_trailers(trailers).await.unwrap();
+            });
+            let res = buffered(Response::new(body)).await.unwrap();
+            let status = res.headers()["grpc-status"].clone();
+            (
+                status,
+                hyper::body::to_bytes(res.into_body()).await.unwrap(),
+            )
+        });
+        assert_eq!(res.0, "0");
+        assert_eq!(&res.1[..], b"hello world");
+    }
+}
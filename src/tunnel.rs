@@ -0,0 +1,348 @@
+use std::{
+    error::Error,
+    io,
+    net::{IpAddr, SocketAddr},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hyper::Uri;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+const MAX_RESPONSE: usize = 8192;
+const SOCKS_VERSION: u8 = 5;
+const NO_AUTH: u8 = 0;
+const PASSWORD_AUTH: u8 = 2;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Tunnel {
+    pub(crate) proxy: Uri,
+    kind: Kind,
+}
+
+#[derive(Debug, Clone)]
+enum Kind {
+    Http {
+        authorization: Option<String>,
+    },
+    Socks5 {
+        credentials: Option<(Vec<u8>, Vec<u8>)>,
+    },
+}
+
+impl Tunnel {
+    pub(crate) fn new(proxy: &Uri, addr: SocketAddr) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let authority = proxy.authority().ok_or("missing proxy host")?;
+        let credentials = authority
+            .as_str()
+            .rsplit_once('@')
+            .map(|(credentials, _)| decode(credentials));
+        let kind = match proxy.scheme_str() {
+            Some("http") => Kind::Http {
+                authorization: credentials
+                    .map(|credentials| format!("Basic {}", STANDARD.encode(credentials))),
+            },
+            Some("socks5" | "socks5h") => Kind::Socks5 {
+                credentials: credentials.map(|credentials| {
+                    match credentials.iter().position(|byte| *byte == b':') {
+                        Some(colon) => (
+                            credentials[..colon].to_vec(),
+                            credentials[colon + 1..].to_vec(),
+                        ),
+                        None => (credentials, Vec::new()),
+                    }
+                }),
+            },
+            _ => return Err("forward proxies must use an http:// or socks5:// uri".into()),
+        };
+        if let Kind::Socks5 {
+            credentials: Some((user, password)),
+        } = &kind
+        {
+            if user.len() > 255 || password.len() > 255 {
+                return Err("SOCKS5 credentials are limited to 255 bytes".into());
+            }
+        }
+        Ok(Self {
+            proxy: format!("http://{addr}/").parse()?,
+            kind,
+        })
+    }
+
+    pub(crate) fn default_port(proxy: &Uri) -> u16 {
+        match proxy.scheme_str() {
+            Some("socks5" | "socks5h") => 1080,
+            _ => 80,
+        }
+    }
+
+    pub(crate) async fn open(&self, stream: &mut TcpStream, target: &Uri) -> io::Result<()> {
+        let host = target
+            .host()
+            .unwrap_or_default()
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        let port = target.port_u16().unwrap_or(match target.scheme_str() {
+            Some("https") => 443,
+            _ => 80,
+        });
+        match &self.kind {
+            Kind::Http { authorization } => connect(stream, host, port, authorization).await,
+            Kind::Socks5 { credentials } => socks5(stream, host, port, credentials).await,
+        }
+    }
+}
+
+async fn connect(
+    stream: &mut TcpStream,
+    host: &str,
+    port: u16,
+    authorization: &Option<String>,
+) -> io::Result<()> {
+    let host = match host.contains(':') {
+        true => format!("[{host}]"),
+        false => host.to_string(),
+    };
+    let mut connect = format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n");
+    if let Some(authorization) = authorization {
+        connect.push_str(&format!("Proxy-Authorization: {authorization}\r\n"));
+    }
+    connect.push_str("\r\n");
+    stream.write_all(connect.as_bytes()).await?;
+
+    let mut response = Vec::new();
+    let mut buf = [0; 1024];
+    loop {
+        let read = stream.read(&mut buf).await?;
+        if read == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "proxy closed the tunnel",
+            ));
+        }
+        response.extend_from_slice(&buf[..read]);
+
+        let mut headers = [httparse::EMPTY_HEADER; 32];
+        let mut parsed = httparse::Response::new(&mut headers);
+        match parsed.parse(&response) {
+            Ok(httparse::Status::Complete(len)) if len == response.len() => {
+                return match parsed.code {
+                    Some(200..=299) => Ok(()),
+                    code => Err(io::Error::new(
+                        io::ErrorKind::ConnectionRefused,
+                        format!("proxy refused tunnel: {}", code.unwrap_or_default()),
+                    )),
+                };
+            }
+            Ok(httparse::Status::Complete(_)) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "proxy sent data before the tunnel was used",
+                ));
+            }
+            Ok(httparse::Status::Partial) if response.len() < MAX_RESPONSE => {}
+            Ok(httparse::Status::Partial) | Err(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid proxy response",
+                ));
+            }
+        }
+    }
+}
+
+async fn socks5(
+    stream: &mut TcpStream,
+    host: &str,
+    port: u16,
+    credentials: &Option<(Vec<u8>, Vec<u8>)>,
+) -> io::Result<()> {
+    let methods: &[u8] = match credentials {
+        Some(_) => &[SOCKS_VERSION, 2, NO_AUTH, PASSWORD_AUTH],
+        None => &[SOCKS_VERSION, 1, NO_AUTH],
+    };
+    stream.write_all(methods).await?;
+    let mut reply = [0; 2];
+    stream.read_exact(&mut reply).await?;
+    match (reply, credentials) {
+        ([SOCKS_VERSION, NO_AUTH], _) => {}
+        ([SOCKS_VERSION, PASSWORD_AUTH], Some((user, password))) => {
+            let mut auth = vec![1, user.len() as u8];
+            auth.extend_from_slice(user);
+            auth.push(password.len() as u8);
+            auth.extend_from_slice(password);
+            stream.write_all(&auth).await?;
+            stream.read_exact(&mut reply).await?;
+            if reply[1] != 0 {
+                return Err(refused("SOCKS5 proxy rejected the credentials"));
+            }
+        }
+        _ => return Err(refused("SOCKS5 proxy offered no acceptable authentication")),
+    }
+
+    let mut request = vec![SOCKS_VERSION, 1, 0];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) if host.len() <= 255 => {
+            request.extend_from_slice(&[3, host.len() as u8]);
+            request.extend_from_slice(host.as_bytes());
+        }
+        Err(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "hostname too long for SOCKS5",
+            ))
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut head = [0; 4];
+    stream.read_exact(&mut head).await?;
+    if head[0] != SOCKS_VERSION || head[1] != 0 {
+        return Err(refused(&format!(
+            "SOCKS5 proxy refused tunnel: {}",
+            head[1]
+        )));
+    }
+    let len = match head[3] {
+        1 => 4,
+        4 => 16,
+        3 => stream.read_u8().await? as usize,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid SOCKS5 reply",
+            ))
+        }
+    };
+    let mut bound = vec![0; len + 2];
+    stream.read_exact(&mut bound).await?;
+    Ok(())
+}
+
+fn refused(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionRefused, message.to_string())
+}
+
+fn decode(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+
+    /// Plays a proxy that reads each exchange's byte count and answers with its reply, then
+    /// returns everything it read.
+    fn proxy(exchanges: Vec<(usize, Vec<u8>)>) -> (SocketAddr, thread::JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut seen = Vec::new();
+            for (len, reply) in exchanges {
+                let mut buf = vec![0; len];
+                stream.read_exact(&mut buf).unwrap();
+                seen.extend(buf);
+                stream.write_all(&reply).unwrap();
+            }
+            seen
+        });
+        (addr, handle)
+    }
+
+    fn open(tunnel: Tunnel, addr: SocketAddr, target: &str) -> io::Result<()> {
+        let target: Uri = target.parse().unwrap();
+        tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()
+            .unwrap()
+            .block_on(async move {
+                let mut stream = TcpStream::connect(addr).await?;
+                tunnel.open(&mut stream, &target).await
+            })
+    }
+
+    #[test]
+    fn proxy_credentials_are_percent_decoded() {
+        assert_eq!(decode("us%65r:p%3Ass"), b"user:p:ss");
+        assert_eq!(decode("100%"), b"100%");
+        assert_eq!(decode("%zz"), b"%zz");
+    }
+
+    #[test]
+    fn socks5_sends_credentials_and_domain_targets() {
+        let (addr, handle) = proxy(vec![
+            (4, vec![5, 2]),
+            (10, vec![1, 0]),
+            (18, vec![5, 0, 0, 1, 127, 0, 0, 1, 0, 80]),
+        ]);
+        let tunnel = Tunnel::new(&"socks5://al%69ce:pw@proxy".parse().unwrap(), addr).unwrap();
+        open(tunnel, addr, "http://example.com:8080/").unwrap();
+
+        let mut expected = vec![5, 2, 0, 2, 1, 5];
+        expected.extend(b"alice");
+        expected.extend([2]);
+        expected.extend(b"pw");
+        expected.extend([5, 1, 0, 3, 11]);
+        expected.extend(b"example.com");
+        expected.extend(8080u16.to_be_bytes());
+        assert_eq!(handle.join().unwrap(), expected);
+    }
+
+    #[test]
+    fn socks5_sends_ip_targets_and_surfaces_refusals() {
+        let (addr, handle) = proxy(vec![(3, vec![5, 0]), (22, vec![5, 5, 0, 1])]);
+        let tunnel = Tunnel::new(&"socks5://proxy".parse().unwrap(), addr).unwrap();
+        let err = open(tunnel, addr, "https://[::1]/").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+
+        let mut expected = vec![5, 1, 0, 5, 1, 0, 4];
+        expected.extend(std::net::Ipv6Addr::LOCALHOST.octets());
+        expected.extend(443u16.to_be_bytes());
+        assert_eq!(handle.join().unwrap(), expected);
+    }
+
+    #[test]
+    fn oversized_socks5_credentials_are_rejected() {
+        let user = "u".repeat(256);
+        let proxy: Uri = format!("socks5://{user}@proxy").parse().unwrap();
+        assert!(Tunnel::new(&proxy, "127.0.0.1:1080".parse().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use std::{
+    error::Error,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt, TryStreamExt,
+};
+use hyper::{body::Bytes, Body};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::user::UserContext;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+pub(crate) type BodyStream = BoxStream<'static, Result<Bytes, Box<dyn Error + Send + Sync>>>;
+pub(crate) type MakeBody = Arc<dyn Fn(&UserContext) -> BodyStream + Send + Sync + 'static>;
+
+#[derive(Default)]
+pub(crate) struct UploadTracker {
+    bytes: AtomicU64,
+}
+
+impl UploadTracker {
+    pub(crate) fn body(self: &Arc<Self>, stream: BodyStream) -> Body {
+        let tracker = self.clone();
+        Body::wrap_stream(stream.inspect_ok(move |chunk| {
+            tracker
+                .bytes
+                .fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        }))
+    }
+
+    pub(crate) fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+}
+
+pub(crate) fn read<R>(reader: R) -> BodyStream
+where
+    R: AsyncRead + Send + Unpin + 'static,
+{
+    stream::try_unfold(reader, |mut reader| async move {
+        let mut chunk = vec![0; CHUNK_SIZE];
+        match reader.read(&mut chunk).await? {
+            0 => Ok(None),
+            read => {
+                chunk.truncate(read);
+                Ok(Some((Bytes::from(chunk), reader)))
+            }
+        }
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uploads_stream_the_reader_and_count_bytes() {
+        let data = vec![7u8; CHUNK_SIZE + 10];
+        let tracker = Arc::new(UploadTracker::default());
+        let body = tracker.body(read(std::io::Cursor::new(data.clone())));
+        let sent = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(hyper::body::to_bytes(body))
+            .unwrap();
+        assert_eq!(sent, data);
+        assert_eq!(tracker.bytes(), data.len() as u64);
+    }
+}
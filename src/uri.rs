@@ -1,4 +1,4 @@
-use hyper::Uri;
+use hyper::{http, Uri};
 
 pub trait UriExt {
     fn with_path(&self, path: impl Into<String>) -> Self;
@@ -15,3 +15,49 @@ impl UriExt for Uri {
         format!("{self}{path}").try_into().unwrap()
     }
 }
+
+pub(crate) fn rebase(uri: &Uri, path_and_query: &str) -> Uri {
+    try_rebase(uri, path_and_query).unwrap()
+}
+
+pub(crate) fn try_rebase(uri: &Uri, path_and_query: &str) -> Result<Uri, http::Error> {
+    let mut target = if path_and_query.starts_with('/') {
+        Uri::builder().path_and_query(path_and_query)
+    } else {
+        Uri::builder().path_and_query(format!("/{path_and_query}"))
+    };
+    if let Some(scheme) = uri.scheme() {
+        target = target.scheme(scheme.clone());
+    }
+    if let Some(authority) = uri.authority() {
+        target = target.authority(authority.clone());
+    }
+    target.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebasing_keeps_the_origin_and_replaces_the_path() {
+        let base: Uri = "https://example.com:8443/old?x=1".parse().unwrap();
+        assert_eq!(
+            rebase(&base, "/new?y=2"),
+            "https://example.com:8443/new?y=2"
+        );
+        assert_eq!(
+            rebase(&base, "relative"),
+            "https://example.com:8443/relative"
+        );
+        assert_eq!(rebase(&Uri::default(), "/only"), "/only");
+        assert!(try_rebase(&base, "/not a path").is_err());
+    }
+
+    #[test]
+    fn with_path_appends_to_the_base() {
+        let base: Uri = "http://example.com/".parse().unwrap();
+        assert_eq!(base.with_path("/a/b"), "http://example.com/a/b");
+        assert_eq!(base.with_path("c"), "http://example.com/c");
+    }
+}
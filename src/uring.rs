@@ -0,0 +1,120 @@
+use std::{error::Error, mem, net::SocketAddr};
+
+use hyper::{body, Body, Method, Request, Response};
+use tokio_uring::{buf::BoundedBuf, net::TcpStream};
+
+use crate::wire;
+
+const READ_SIZE: usize = 16 * 1024;
+
+pub(crate) struct UringClient {
+    addr: SocketAddr,
+    stream: Option<TcpStream>,
+    write: Vec<u8>,
+    read: Vec<u8>,
+}
+
+impl UringClient {
+    pub(crate) fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            stream: None,
+            write: Vec::new(),
+            read: Vec::new(),
+        }
+    }
+
+    pub(crate) async fn request(
+        &mut self,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let head_only = req.method() == Method::HEAD;
+        let (parts, body) = req.into_parts();
+        let body = body::to_bytes(body).await?;
+
+        let mut write = mem::take(&mut self.write);
+        write.clear();
+        wire::encode_request(&parts, &body, &mut write);
+
+        let stream = match self.stream.take() {
+            Some(stream) => stream,
+            None => {
+                let stream = TcpStream::connect(self.addr).await?;
+                stream.set_nodelay(true)?;
+                stream
+            }
+        };
+
+        let (written, write) = stream.write_all(write).await;
+        self.write = write;
+        written?;
+
+        self.read.clear();
+        let mut eof = false;
+        loop {
+            if let Some((res, _)) = wire::parse_response(&self.read, head_only, eof)? {
+                if wire::keep_alive(&res) {
+                    self.stream = Some(stream);
+                }
+                return Ok(res);
+            }
+            if eof {
+                return Err("connection closed".into());
+            }
+
+            self.read.reserve(READ_SIZE);
+            let len = self.read.len();
+            let (read, buf) = stream.read(mem::take(&mut self.read).slice(len..)).await;
+            self.read = buf.into_inner();
+            eof = read? == 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+
+    #[test]
+    fn keep_alive_connections_are_reused_and_closed_ones_are_not() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut head = Vec::new();
+            for res in [
+                &b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok"[..],
+                b"HTTP/1.1 201 Created\r\nconnection: close\r\ncontent-length: 0\r\n\r\n",
+            ] {
+                head.clear();
+                while !head.ends_with(b"\r\n\r\n") {
+                    let mut byte = [0];
+                    stream.read_exact(&mut byte).unwrap();
+                    head.push(byte[0]);
+                }
+                stream.write_all(res).unwrap();
+            }
+        });
+
+        tokio_uring::start(async move {
+            let mut client = UringClient::new(addr);
+            let get = || Request::get("/").body(Body::empty()).unwrap();
+
+            let res = client.request(get()).await.unwrap();
+            assert_eq!(res.status(), 200);
+            assert_eq!(&body::to_bytes(res.into_body()).await.unwrap()[..], b"ok");
+            assert!(client.stream.is_some());
+
+            let res = client.request(get()).await.unwrap();
+            assert_eq!(res.status(), 201);
+            assert!(client.stream.is_none());
+        });
+        server.join().unwrap();
+    }
+}
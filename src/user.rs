@@ -0,0 +1,166 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use hyper::{Body, Request, Response, Uri};
+
+use crate::{budget::RequestBudget, feed::Feed, shared::Shared, Expectation, MakeRequest};
+
+pub(crate) type UserFactory = Arc<dyn Fn() -> Box<dyn VirtualUser> + Send + Sync + 'static>;
+
+pub trait VirtualUser: 'static {
+    fn on_start(&mut self, _ctx: &UserContext) {}
+
+    fn next_request(&mut self, uri: &Uri, ctx: &UserContext) -> Option<Request<Body>>;
+
+    fn on_response(&mut self, res: Response<Body>, _ctx: &UserContext) -> bool {
+        res.status().is_success()
+    }
+
+    fn on_stop(&mut self, _ctx: &UserContext) {}
+}
+
+#[derive(Debug, Clone)]
+pub struct UserContext {
+    pub(crate) id: usize,
+    pub(crate) sequence: u64,
+    pub(crate) iteration: u64,
+    pub(crate) epoch: Instant,
+    pub(crate) duration: Option<Duration>,
+    pub(crate) budget: Option<Arc<RequestBudget>>,
+    pub(crate) shared: Shared,
+    pub(crate) tenant: Option<Arc<str>>,
+    pub(crate) feed_value: Option<Arc<str>>,
+    pub(crate) seeded: Option<Feed>,
+    pub(crate) class: Option<Arc<str>>,
+}
+
+impl UserContext {
+    pub(crate) fn new(
+        id: usize,
+        epoch: Instant,
+        duration: Option<Duration>,
+        budget: Option<Arc<RequestBudget>>,
+        shared: Shared,
+    ) -> Self {
+        Self {
+            id,
+            sequence: 0,
+            iteration: 0,
+            epoch,
+            duration,
+            budget,
+            shared,
+            tenant: None,
+            feed_value: None,
+            seeded: None,
+            class: None,
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn iteration(&self) -> u64 {
+        self.iteration
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.epoch.elapsed()
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    pub fn shared(&self) -> &Shared {
+        &self.shared
+    }
+
+    pub fn tenant(&self) -> Option<&str> {
+        self.tenant.as_deref()
+    }
+
+    pub fn feed_value(&self) -> Option<&str> {
+        self.feed_value.as_deref()
+    }
+
+    pub fn seeded(&self) -> Option<&Feed> {
+        self.seeded.as_ref()
+    }
+
+    pub fn class(&self) -> Option<&str> {
+        self.class.as_deref()
+    }
+
+    pub fn progress(&self) -> f64 {
+        let elapsed = self.duration.map(|duration| match duration.is_zero() {
+            true => 1.0,
+            false => (self.elapsed().as_secs_f64() / duration.as_secs_f64()).min(1.0),
+        });
+        let spent = self.budget.as_ref().map(|budget| budget.progress());
+        elapsed.into_iter().chain(spent).fold(0.0, f64::max)
+    }
+}
+
+pub(crate) struct ClosureUser {
+    pub(crate) make_request: MakeRequest,
+    pub(crate) expectation_matcher: Expectation,
+}
+
+impl VirtualUser for ClosureUser {
+    fn next_request(&mut self, uri: &Uri, ctx: &UserContext) -> Option<Request<Body>> {
+        Some((self.make_request)(uri, ctx))
+    }
+
+    fn on_response(&mut self, res: Response<Body>, _ctx: &UserContext) -> bool {
+        (self.expectation_matcher)(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_follows_whichever_limit_is_further_along() {
+        let ctx = |duration, budget| {
+            UserContext::new(0, Instant::now(), duration, budget, Shared::default())
+        };
+        assert_eq!(ctx(None, None).progress(), 0.0);
+        assert_eq!(ctx(Some(Duration::ZERO), None).progress(), 1.0);
+
+        let budget = Arc::new(RequestBudget::new(4));
+        budget.take();
+        budget.take();
+        let both = ctx(Some(Duration::from_secs(3600)), Some(budget));
+        assert!((both.progress() - 0.5).abs() < 1e-3, "{}", both.progress());
+    }
+
+    #[test]
+    fn closure_users_never_run_out_and_judge_by_the_expectation() {
+        let mut user = ClosureUser {
+            make_request: Arc::new(|uri, ctx| {
+                Request::get(format!("{uri}{}", ctx.id()))
+                    .body(Body::empty())
+                    .unwrap()
+            }),
+            expectation_matcher: Arc::new(|res| res.status() == 404),
+        };
+        let ctx = UserContext::new(7, Instant::now(), None, None, Shared::default());
+        let uri = Uri::from_static("http://localhost/");
+
+        let req = user.next_request(&uri, &ctx).unwrap();
+        assert_eq!(req.uri(), "http://localhost/7");
+        let mut res = Response::new(Body::empty());
+        *res.status_mut() = hyper::StatusCode::NOT_FOUND;
+        assert!(user.on_response(res, &ctx));
+        assert!(!user.on_response(Response::new(Body::empty()), &ctx));
+    }
+}
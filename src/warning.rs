@@ -0,0 +1,110 @@
+use std::{fmt::Display, time::Duration};
+
+use crate::result::{BenchmarkResult, STARVATION_THRESHOLD};
+
+const CPU_SATURATION: f64 = 0.9;
+const TRANSPORT_ERROR_RATE: f64 = 0.01;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    ClientSaturated { cpu: f64 },
+    ScheduleLag { late: usize, average: Duration },
+    TransportErrors { rate: f64 },
+    StarvedUsers { users: usize },
+}
+
+impl Warning {
+    pub(crate) fn detect(result: &BenchmarkResult) -> Vec<Self> {
+        let mut warnings = Vec::new();
+        if let Some(cpu) = result.client_cpu().filter(|cpu| *cpu > CPU_SATURATION) {
+            warnings.push(Self::ClientSaturated { cpu });
+        }
+        if let Some(average) = result.average_schedule_lag() {
+            warnings.push(Self::ScheduleLag {
+                late: result.late_request_count(),
+                average,
+            });
+        }
+        let attempted =
+            result.success_count() + result.http_error_count() + result.tcp_error_count();
+        if attempted > 0 {
+            let rate = result.tcp_error_count() as f64 / attempted as f64;
+            if rate > TRANSPORT_ERROR_RATE {
+                warnings.push(Self::TransportErrors { rate });
+            }
+        }
+        if let Some(fairness) = result.fairness(STARVATION_THRESHOLD) {
+            if fairness.user_count() > 1 && !fairness.starved_users().is_empty() {
+                warnings.push(Self::StarvedUsers {
+                    users: fairness.starved_users().len(),
+                });
+            }
+        }
+        warnings
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ClientSaturated { cpu } => write!(
+                f,
+                "client CPU at {:.0}% of the worker threads, results may measure the load generator",
+                cpu * 100.0
+            ),
+            Self::ScheduleLag { late, average } => write!(
+                f,
+                "{late} requests left behind schedule (avg {:.2}ms), the target rate was not sustained",
+                average.as_secs_f64() * 1000.0
+            ),
+            Self::TransportErrors { rate } => write!(
+                f,
+                "{:.2}% of requests failed at the transport level, latency covers only the survivors",
+                rate * 100.0
+            ),
+            Self::StarvedUsers { users } => write!(
+                f,
+                "{users} users were starved, worker threads could not serve every user evenly"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::result::Outcome;
+
+    #[test]
+    fn transport_errors_above_one_percent_are_flagged() {
+        let mut result = BenchmarkResult::default();
+        for _ in 0..98 {
+            result.record(Duration::ZERO, Duration::from_millis(1), Outcome::Success);
+        }
+        assert!(Warning::detect(&result).is_empty());
+
+        result.record(Duration::ZERO, Duration::from_millis(1), Outcome::TcpError);
+        result.record(Duration::ZERO, Duration::from_millis(1), Outcome::TcpError);
+        assert_eq!(
+            Warning::detect(&result),
+            [Warning::TransportErrors { rate: 0.02 }]
+        );
+    }
+
+    #[test]
+    fn warnings_explain_what_they_mean() {
+        assert_eq!(
+            Warning::StarvedUsers { users: 3 }.to_string(),
+            "3 users were starved, worker threads could not serve every user evenly"
+        );
+        assert!(Warning::ScheduleLag {
+            late: 4,
+            average: Duration::from_micros(1500),
+        }
+        .to_string()
+        .starts_with("4 requests left behind schedule (avg 1.50ms)"));
+        assert!(Warning::ClientSaturated { cpu: 0.95 }
+            .to_string()
+            .starts_with("client CPU at 95%"));
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use futures::{SinkExt, StreamExt};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::broadcast,
+};
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+use crate::{
+    config::RunConfig,
+    report::{Reporter, Snapshot},
+    result::BenchmarkResult,
+};
+
+const BACKLOG: usize = 64;
+
+pub struct WebSocketReporter {
+    sender: broadcast::Sender<Frame>,
+    config: Arc<Mutex<Option<String>>>,
+    local_addr: SocketAddr,
+}
+
+#[derive(Debug, Clone)]
+enum Frame {
+    Text(String),
+    Close,
+}
+
+impl WebSocketReporter {
+    pub fn bind(addr: impl Into<SocketAddr>) -> io::Result<Self> {
+        let listener = std::net::TcpListener::bind(addr.into())?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        let (sender, _) = broadcast::channel(BACKLOG);
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let frames = sender.clone();
+        let config = Arc::new(Mutex::new(None));
+        let greeting = config.clone();
+        thread::spawn(move || {
+            runtime.block_on(async move {
+                let listener = match TcpListener::from_std(listener) {
+                    Ok(listener) => listener,
+                    Err(_) => return,
+                };
+                while let Ok((stream, _)) = listener.accept().await {
+                    let greeting = greeting.lock().unwrap().clone();
+                    tokio::spawn(serve(stream, greeting, frames.subscribe()));
+                }
+            })
+        });
+
+        Ok(Self {
+            sender,
+            config,
+            local_addr,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+async fn serve(
+    stream: TcpStream,
+    greeting: Option<String>,
+    mut frames: broadcast::Receiver<Frame>,
+) {
+    let Ok(socket) = accept_async(stream).await else {
+        return;
+    };
+    let (mut sink, mut incoming) = socket.split();
+    if let Some(greeting) = greeting {
+        if sink.send(Message::text(greeting)).await.is_err() {
+            return;
+        }
+    }
+    loop {
+        tokio::select! {
+            frame = frames.recv() => match frame {
+                Ok(Frame::Text(json)) => {
+                    if sink.send(Message::text(json)).await.is_err() {
+                        return;
+                    }
+                }
+                Ok(Frame::Close) | Err(broadcast::error::RecvError::Closed) => {
+                    let _ = sink.send(Message::Close(None)).await;
+                    return;
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+            },
+            message = incoming.next() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+impl Reporter for WebSocketReporter {
+    fn start(&mut self, config: &RunConfig) {
+        let frame = serde_json::json!({
+            "config": config,
+            "fingerprint": config.fingerprint(),
+        });
+        if let Ok(json) = serde_json::to_string(&frame) {
+            *self.config.lock().unwrap() = Some(json.clone());
+            let _ = self.sender.send(Frame::Text(json));
+        }
+    }
+
+    fn report(&mut self, snapshot: &Snapshot) {
+        if let Ok(json) = serde_json::to_string(snapshot) {
+            let _ = self.sender.send(Frame::Text(json));
+        }
+    }
+
+    fn finish(&mut self, _result: &BenchmarkResult) {
+        let _ = self.sender.send(Frame::Close);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio_tungstenite::client_async;
+
+    use super::*;
+
+    fn config() -> RunConfig {
+        RunConfig {
+            version: "0.0.0".into(),
+            target: "http://localhost/".into(),
+            method: None,
+            headers: Vec::new(),
+            mode: "http".into(),
+            protocol: "http1".into(),
+            backend: "hyper".into(),
+            threads: 1,
+            concurrency: 1,
+            duration_ms: None,
+            requests: Some(1),
+            profile: "closed".into(),
+            pipeline: None,
+            streams_per_connection: 1,
+            region: None,
+        }
+    }
+
+    #[test]
+    fn late_joiners_get_the_config_then_snapshots_then_close() {
+        let mut reporter = WebSocketReporter::bind(([127, 0, 0, 1], 0)).unwrap();
+        reporter.start(&config());
+        let addr = reporter.local_addr();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async move {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let (mut socket, _) = client_async(format!("ws://{addr}/"), stream).await.unwrap();
+
+            let Some(Ok(Message::Text(greeting))) = socket.next().await else {
+                panic!("no greeting");
+            };
+            assert!(greeting.contains("\"fingerprint\""), "{greeting}");
+
+            let snapshot = Snapshot::new(
+                Duration::ZERO,
+                Duration::from_secs(1),
+                &Default::default(),
+                1,
+            );
+            reporter.report(&snapshot);
+            reporter.finish(&BenchmarkResult::default());
+
+            let Some(Ok(Message::Text(json))) = socket.next().await else {
+                panic!("no snapshot");
+            };
+            assert!(json.contains("\"interval_ms\":1000"), "{json}");
+            assert!(matches!(socket.next().await, Some(Ok(Message::Close(_)))));
+        });
+    }
+}
@@ -0,0 +1,211 @@
+use std::{error::Error, str};
+
+use hyper::{
+    body::Bytes,
+    header::{CONNECTION, CONTENT_LENGTH, HOST},
+    http::request::Parts,
+    Body, Response, Version,
+};
+
+type WireError = Box<dyn Error + Send + Sync>;
+
+const MAX_HEADERS: usize = 64;
+
+#[derive(Debug, Clone, Copy)]
+struct CloseDelimited;
+
+pub(crate) fn encode_request(parts: &Parts, body: &[u8], buf: &mut Vec<u8>) {
+    let target = parts
+        .uri
+        .path_and_query()
+        .map(|target| target.as_str())
+        .unwrap_or("/");
+
+    buf.extend_from_slice(parts.method.as_str().as_bytes());
+    buf.push(b' ');
+    buf.extend_from_slice(target.as_bytes());
+    buf.extend_from_slice(b" HTTP/1.1\r\n");
+
+    if !parts.headers.contains_key(HOST) {
+        if let Some(authority) = parts.uri.authority() {
+            header(buf, HOST.as_str(), authority.as_str().as_bytes());
+        }
+    }
+    for (name, value) in &parts.headers {
+        header(buf, name.as_str(), value.as_bytes());
+    }
+    if !body.is_empty() && !parts.headers.contains_key(CONTENT_LENGTH) {
+        header(buf, "content-length", body.len().to_string().as_bytes());
+    }
+
+    buf.extend_from_slice(b"\r\n");
+    buf.extend_from_slice(body);
+}
+
+fn header(buf: &mut Vec<u8>, name: &str, value: &[u8]) {
+    buf.extend_from_slice(name.as_bytes());
+    buf.extend_from_slice(b": ");
+    buf.extend_from_slice(value);
+    buf.extend_from_slice(b"\r\n");
+}
+
+pub(crate) fn parse_response(
+    buf: &[u8],
+    head_only: bool,
+    eof: bool,
+) -> Result<Option<(Response<Body>, usize)>, WireError> {
+    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut parsed = httparse::Response::new(&mut headers);
+    let head = match parsed.parse(buf)? {
+        httparse::Status::Complete(len) => len,
+        httparse::Status::Partial => return Ok(None),
+    };
+
+    let status = parsed.code.unwrap_or_default();
+    let version = match parsed.version {
+        Some(0) => Version::HTTP_10,
+        _ => Version::HTTP_11,
+    };
+    let mut res = Response::builder().status(status).version(version);
+    let mut length = None;
+    let mut chunked = false;
+    for header in parsed.headers.iter() {
+        if header.name.eq_ignore_ascii_case("content-length") {
+            length = Some(str::from_utf8(header.value)?.trim().parse()?);
+        } else if header.name.eq_ignore_ascii_case("transfer-encoding") {
+            chunked = header.value.to_ascii_lowercase().ends_with(b"chunked");
+        }
+        res = res.header(header.name, header.value);
+    }
+
+    if head_only || status / 100 == 1 || status == 204 || status == 304 {
+        return Ok(Some((res.body(Body::empty())?, head)));
+    }
+
+    let rest = &buf[head..];
+    let (body, len) = match length {
+        _ if chunked => match decode_chunked(rest)? {
+            Some(decoded) => decoded,
+            None => return Ok(None),
+        },
+        Some(length) if rest.len() >= length => (Bytes::copy_from_slice(&rest[..length]), length),
+        Some(_) => return Ok(None),
+        None if eof => {
+            res = res.extension(CloseDelimited);
+            (Bytes::copy_from_slice(rest), rest.len())
+        }
+        None => return Ok(None),
+    };
+
+    Ok(Some((res.body(Body::from(body))?, head + len)))
+}
+
+fn decode_chunked(buf: &[u8]) -> Result<Option<(Bytes, usize)>, WireError> {
+    let mut body = Vec::new();
+    let mut pos = 0;
+    loop {
+        let (start, size) =
+            match httparse::parse_chunk_size(&buf[pos..]).map_err(|_| "invalid chunk size")? {
+                httparse::Status::Complete(chunk) => chunk,
+                httparse::Status::Partial => return Ok(None),
+            };
+        pos += start;
+
+        if size == 0 {
+            let trailers = &buf[pos..];
+            if trailers.starts_with(b"\r\n") {
+                return Ok(Some((body.into(), pos + 2)));
+            }
+            return Ok(trailers
+                .windows(4)
+                .position(|end| end == b"\r\n\r\n")
+                .map(|end| (body.into(), pos + end + 4)));
+        }
+
+        let size = size as usize;
+        if buf.len() < pos + size + 2 {
+            return Ok(None);
+        }
+        body.extend_from_slice(&buf[pos..pos + size]);
+        pos += size + 2;
+    }
+}
+
+pub(crate) fn keep_alive(res: &Response<Body>) -> bool {
+    let connection = |token: &[u8]| {
+        res.headers()
+            .get(CONNECTION)
+            .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(token))
+    };
+    match res.version() {
+        _ if res.extensions().get::<CloseDelimited>().is_some() => false,
+        Version::HTTP_10 => connection(b"keep-alive"),
+        _ => !connection(b"close"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(res: Response<Body>) -> Bytes {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(hyper::body::to_bytes(res.into_body()))
+            .unwrap()
+    }
+
+    #[test]
+    fn content_length_bodies_wait_for_every_byte() {
+        let buf = b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\nhello";
+        assert!(parse_response(&buf[..buf.len() - 1], false, false)
+            .unwrap()
+            .is_none());
+
+        let (res, len) = parse_response(buf, false, false).unwrap().unwrap();
+        assert_eq!(len, buf.len());
+        assert!(keep_alive(&res));
+        assert_eq!(body(res), "hello");
+    }
+
+    #[test]
+    fn chunked_bodies_are_decoded_through_trailers() {
+        let buf = b"HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n\
+            3\r\nabc\r\n2\r\nde\r\n0\r\nx-sum: 1\r\n\r\nHTTP/1.1";
+        let (res, len) = parse_response(buf, false, false).unwrap().unwrap();
+        assert_eq!(len, buf.len() - b"HTTP/1.1".len());
+        assert_eq!(body(res), "abcde");
+
+        let partial = b"HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n3\r\nab";
+        assert!(parse_response(partial, false, false).unwrap().is_none());
+    }
+
+    #[test]
+    fn close_delimited_bodies_end_at_eof_and_close_the_connection() {
+        let buf = b"HTTP/1.1 200 OK\r\n\r\nuntil close";
+        assert!(parse_response(buf, false, false).unwrap().is_none());
+
+        let (res, len) = parse_response(buf, false, true).unwrap().unwrap();
+        assert_eq!(len, buf.len());
+        assert!(!keep_alive(&res));
+        assert_eq!(body(res), "until close");
+    }
+
+    #[test]
+    fn bodiless_statuses_and_head_responses_stop_at_the_head() {
+        for status in ["204 No Content", "304 Not Modified"] {
+            let buf = format!("HTTP/1.1 {status}\r\ncontent-length: 10\r\n\r\n");
+            let (res, len) = parse_response(buf.as_bytes(), false, false)
+                .unwrap()
+                .unwrap();
+            assert_eq!(len, buf.len());
+            assert!(body(res).is_empty());
+        }
+
+        let head = b"HTTP/1.0 200 OK\r\ncontent-length: 10\r\n\r\n";
+        let (res, len) = parse_response(head, true, false).unwrap().unwrap();
+        assert_eq!(len, head.len());
+        assert!(!keep_alive(&res));
+    }
+}
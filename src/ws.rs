@@ -0,0 +1,271 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use futures::{SinkExt, StreamExt};
+use hyper::Uri;
+use tokio::{
+    net::TcpStream,
+    time::{interval_at, timeout_at, MissedTickBehavior},
+};
+use tokio_tungstenite::{client_async, tungstenite::Message, WebSocketStream};
+
+use crate::{clock::Clock, metrics::Recorder, result::Outcome, user::UserContext};
+
+const CONNECT_LABEL: &str = "ws connect";
+
+type MakeMessage = Arc<dyn Fn(&UserContext) -> Message + Send + Sync + 'static>;
+type FrameExpectation = Arc<dyn Fn(&[u8]) -> bool + Send + Sync + 'static>;
+
+#[derive(Clone)]
+pub struct WebSocket {
+    message: MakeMessage,
+    expectation: FrameExpectation,
+    rate: Option<f64>,
+    active: f64,
+}
+
+impl WebSocket {
+    pub fn text(message: impl Fn(&UserContext) -> String + Send + Sync + 'static) -> Self {
+        Self::new(Arc::new(move |ctx| Message::Text(message(ctx))))
+    }
+
+    pub fn binary(message: impl Fn(&UserContext) -> Vec<u8> + Send + Sync + 'static) -> Self {
+        Self::new(Arc::new(move |ctx| Message::Binary(message(ctx))))
+    }
+
+    fn new(message: MakeMessage) -> Self {
+        Self {
+            message,
+            expectation: Arc::new(|_| true),
+            rate: None,
+            active: 1.0,
+        }
+    }
+
+    pub fn expecting(self, f: impl Fn(&[u8]) -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            expectation: Arc::new(f),
+            ..self
+        }
+    }
+
+    pub fn rate(self, messages_per_second: f64) -> Self {
+        Self {
+            rate: Some(messages_per_second).filter(|rate| *rate > 0.0),
+            ..self
+        }
+    }
+
+    pub fn active_fraction(self, fraction: f64) -> Self {
+        Self {
+            active: fraction.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    fn is_active(&self, user: usize) -> bool {
+        ((user + 1) as f64 * self.active).floor() > (user as f64 * self.active).floor()
+    }
+}
+
+pub(crate) struct Session {
+    pub(crate) addr: SocketAddr,
+    pub(crate) epoch: Instant,
+    pub(crate) deadline: Instant,
+    pub(crate) clock: Clock,
+    pub(crate) recorder: Recorder,
+}
+
+impl Session {
+    pub(crate) async fn run(
+        &self,
+        uri: &Uri,
+        websocket: &WebSocket,
+        ctx: &mut UserContext,
+        running: &AtomicBool,
+        sequence: &AtomicU64,
+    ) {
+        while running.load(Ordering::Relaxed) {
+            let Some(mut stream) = self.connect(uri).await else {
+                continue;
+            };
+
+            if !websocket.is_active(ctx.id()) {
+                self.recorder
+                    .record_idle(Instant::now().saturating_duration_since(self.epoch));
+                match self.hold(&mut stream, running).await {
+                    true => self
+                        .recorder
+                        .record_drop(Instant::now().saturating_duration_since(self.epoch)),
+                    false => {
+                        stream.close(None).await.ok();
+                        break;
+                    }
+                }
+                continue;
+            }
+
+            let mut ticks = websocket.rate.map(|rate| {
+                let period = Duration::from_secs_f64(1.0 / rate);
+                let mut ticks = interval_at((Instant::now() + period).into(), period);
+                ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                ticks
+            });
+
+            let dropped = loop {
+                if let Some(ticks) = &mut ticks {
+                    if timeout_at(self.deadline.into(), ticks.tick())
+                        .await
+                        .is_err()
+                    {
+                        break false;
+                    }
+                }
+                if !running.load(Ordering::Relaxed) {
+                    break false;
+                }
+
+                ctx.sequence = sequence.fetch_add(1, Ordering::Relaxed);
+                let start = Instant::now();
+                let timer = self.clock.now();
+                if stream.send((websocket.message)(ctx)).await.is_err() {
+                    self.fail(start, timer);
+                    break true;
+                }
+
+                let payload = match timeout_at(self.deadline.into(), next_frame(&mut stream)).await
+                {
+                    Ok(Some(payload)) => payload,
+                    Ok(None) => {
+                        self.fail(start, timer);
+                        break true;
+                    }
+                    Err(_) => break false,
+                };
+                let outcome = match (websocket.expectation)(&payload) {
+                    true => Outcome::Success,
+                    false => Outcome::HttpError,
+                };
+                self.recorder.record(
+                    start.saturating_duration_since(self.epoch),
+                    self.clock.elapsed(timer),
+                    outcome,
+                );
+                ctx.iteration += 1;
+            };
+
+            match dropped {
+                true => self
+                    .recorder
+                    .record_drop(Instant::now().saturating_duration_since(self.epoch)),
+                false => {
+                    stream.close(None).await.ok();
+                }
+            }
+        }
+    }
+
+    async fn hold(&self, stream: &mut WebSocketStream<TcpStream>, running: &AtomicBool) -> bool {
+        loop {
+            match timeout_at(self.deadline.into(), next_frame(stream)).await {
+                Ok(Some(_)) => self
+                    .recorder
+                    .record_push(Instant::now().saturating_duration_since(self.epoch)),
+                Ok(None) => return true,
+                Err(_) => return false,
+            }
+            if !running.load(Ordering::Relaxed) {
+                return false;
+            }
+        }
+    }
+
+    async fn connect(&self, uri: &Uri) -> Option<WebSocketStream<TcpStream>> {
+        let start = Instant::now();
+        let timer = self.clock.now();
+        let connected = match TcpStream::connect(self.addr).await {
+            Ok(stream) => {
+                stream.set_nodelay(true).ok();
+                client_async(uri.to_string(), stream)
+                    .await
+                    .map(|(stream, _)| stream)
+                    .map_err(|_| Outcome::TcpError)
+            }
+            Err(err) => Err(Outcome::from_error(&err)),
+        };
+        let outcome = match &connected {
+            Ok(_) => Outcome::Success,
+            Err(outcome) => *outcome,
+        };
+        self.recorder.record_connect(
+            CONNECT_LABEL,
+            start.saturating_duration_since(self.epoch),
+            self.clock.elapsed(timer),
+            outcome,
+        );
+        connected.ok()
+    }
+
+    fn fail(&self, start: Instant, timer: u64) {
+        self.recorder.record(
+            start.saturating_duration_since(self.epoch),
+            self.clock.elapsed(timer),
+            Outcome::TcpError,
+        );
+    }
+}
+
+async fn next_frame(stream: &mut WebSocketStream<TcpStream>) -> Option<Vec<u8>> {
+    while let Some(message) = stream.next().await {
+        match message.ok()? {
+            Message::Text(text) => return Some(text.into_bytes()),
+            Message::Binary(data) => return Some(data),
+            Message::Close(_) => return None,
+            Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn echo() -> WebSocket {
+        WebSocket::text(|ctx| format!("hello {}", ctx.id()))
+    }
+
+    #[test]
+    fn active_fractions_spread_over_users() {
+        let half = echo().active_fraction(0.5);
+        let active: Vec<_> = (0..6).filter(|user| half.is_active(*user)).collect();
+        assert_eq!(active, [1, 3, 5]);
+
+        assert!((0..6).all(|user| echo().is_active(user)));
+        assert!((0..6).all(|user| !echo().active_fraction(-1.0).is_active(user)));
+    }
+
+    #[test]
+    fn non_positive_rates_mean_unpaced() {
+        assert_eq!(echo().rate(20.0).rate, Some(20.0));
+        assert_eq!(echo().rate(0.0).rate, None);
+        assert_eq!(echo().rate(-3.0).rate, None);
+    }
+
+    #[test]
+    fn messages_and_expectations_come_from_the_builder() {
+        let socket =
+            WebSocket::binary(|ctx| vec![ctx.id() as u8]).expecting(|frame| frame == b"ok");
+        let ctx = UserContext::new(4, Instant::now(), None, None, Default::default());
+        assert_eq!((socket.message)(&ctx), Message::Binary(vec![4]));
+        assert!((socket.expectation)(b"ok"));
+        assert!(!(socket.expectation)(b"no"));
+        assert_eq!((echo().message)(&ctx), Message::Text("hello 4".into()));
+    }
+}
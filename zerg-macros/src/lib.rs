@@ -0,0 +1,47 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, ItemFn, LitStr, Visibility};
+
+/// Turns an async function taking a `zerg::Client` into a constructor returning a
+/// `zerg::Scenario`, named after the function unless a name is given. Nothing is registered
+/// globally: pass the result to `SwarmBuilder::scenario`.
+///
+/// ```ignore
+/// #[zerg::scenario("checkout")]
+/// async fn checkout(client: zerg::Client) {
+///     client.step("cart").get("/cart").await.ok();
+/// }
+///
+/// zerg::swarm("http://localhost:8080").scenario(checkout());
+/// ```
+#[proc_macro_attribute]
+pub fn scenario(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let name = if attr.is_empty() {
+        None
+    } else {
+        Some(parse_macro_input!(attr as LitStr))
+    };
+    let mut func = parse_macro_input!(item as ItemFn);
+
+    if func.sig.asyncness.is_none() {
+        return syn::Error::new_spanned(func.sig.fn_token, "scenarios must be async functions")
+            .to_compile_error()
+            .into();
+    }
+
+    let attrs = std::mem::take(&mut func.attrs);
+    let vis = std::mem::replace(&mut func.vis, Visibility::Inherited);
+    let ident = func.sig.ident.clone();
+    let name = name
+        .map(|name| name.value())
+        .unwrap_or_else(|| ident.to_string());
+
+    quote! {
+        #(#attrs)*
+        #vis fn #ident() -> ::zerg::Scenario {
+            #func
+            ::zerg::Scenario::new(#name, #ident)
+        }
+    }
+    .into()
+}